@@ -0,0 +1,35 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use fu_vv_cal::{fast, Course};
+use select::document::Document;
+
+/// Course page in the markup of the VV with `sessions` weekly sessions.
+fn course_page(sessions: usize) -> String {
+    let mut html = String::from(
+        "<html><body><div class=\"subc\"><h1>Organische Chemie 1</h1></div><div class=\"dates\">",
+    );
+    for i in 0..sessions {
+        html.push_str(&format!(
+            "<div id=\"link_to_details_{id}\" class=\"link_to_details\">\
+             <span class=\"course_date_time\">Mo, {day:02}.10.2019 10:00 - 12:00</span>\
+             <span class=\"course_room\">Hörsaal A</span></div>",
+            id = 500_000 + i,
+            day = 1 + i % 28
+        ));
+    }
+    html.push_str("</div></body></html>");
+    html
+}
+
+fn parse(c: &mut Criterion) {
+    let html = course_page(30);
+
+    c.bench_function("dom parser", |b| {
+        b.iter(|| Course::from_document(&Document::from(black_box(html.as_str())), false).unwrap())
+    });
+    c.bench_function("fast parser", |b| {
+        b.iter(|| fast::course_from_html(black_box(&html), false).unwrap())
+    });
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,24 @@
+#ifndef FU_VV_CAL_H
+#define FU_VV_CAL_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Fetch a course from the VV and return its calendar as ICS.
+ *
+ * Returns NULL on failure, see fu_vv_last_error. The result must be released with
+ * fu_vv_string_free. */
+char *fu_vv_fetch_course_ics(const char *id, const char *semester);
+
+/* Message of the last error on the calling thread, or NULL. Owned by the library. */
+const char *fu_vv_last_error(void);
+
+/* Release a string returned by fu_vv_fetch_course_ics. */
+void fu_vv_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
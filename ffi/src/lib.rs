@@ -0,0 +1,80 @@
+//! C-compatible interface to fu-vv-cal, for embedding the scraper in apps that can't run the CLI.
+//!
+//! See `fu_vv_cal.h` for the declarations.
+
+use fu_vv_cal::RequestedCourse;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', ""))
+        .expect("NUL bytes have been removed from the message");
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// Fetch a course from the VV and return its calendar as a NUL-terminated ICS string.
+///
+/// Returns NULL on failure, in which case `fu_vv_last_error` describes the problem. The returned
+/// string must be released with `fu_vv_string_free`.
+///
+/// # Safety
+///
+/// `id` and `semester` must be valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn fu_vv_fetch_course_ics(
+    id: *const c_char,
+    semester: *const c_char,
+) -> *mut c_char {
+    if id.is_null() || semester.is_null() {
+        set_last_error("id and semester must not be NULL".to_owned());
+        return ptr::null_mut();
+    }
+    let id = CStr::from_ptr(id).to_string_lossy().into_owned();
+    let semester = CStr::from_ptr(semester).to_string_lossy().into_owned();
+
+    match fetch_course_ics(RequestedCourse::new(id, semester)) {
+        Ok(calendar) => calendar.into_raw(),
+        Err(message) => {
+            set_last_error(message);
+            ptr::null_mut()
+        }
+    }
+}
+
+fn fetch_course_ics(course: RequestedCourse) -> Result<CString, String> {
+    let mut runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+    let calendar = runtime
+        .block_on(course.get_as_ical())
+        .map_err(|err| err.to_string())?;
+
+    CString::new(calendar.to_string()).map_err(|err| err.to_string())
+}
+
+/// Message of the last error on the calling thread, or NULL if there was none.
+///
+/// The string is owned by the library and stays valid until the next call on the same thread.
+#[no_mangle]
+pub extern "C" fn fu_vv_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| match &*last_error.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Release a string returned by this library.
+///
+/// # Safety
+///
+/// `string` must have been returned by `fu_vv_fetch_course_ics` and not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn fu_vv_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
@@ -0,0 +1,230 @@
+//! Python bindings to the parser and calendar generator of fu-vv-cal, e.g. to load schedules into
+//! pandas:
+//!
+//! ```python
+//! import fu_vv_cal, pandas, requests
+//!
+//! html = requests.get("https://www.fu-berlin.de/vv/de/lv/548214?sm=499474").text
+//! course = fu_vv_cal.parse_course(html, id="548214")
+//! sessions = pandas.DataFrame(course.records())
+//! ```
+//!
+//! Fetching is left to Python, like in `wasm/`. Build with `maturin build` (or `pyo3-pack`).
+
+use cal::options::CalendarOptions;
+use cal::sanitize;
+use chrono::{Datelike, Timelike};
+use pyo3::exceptions::ValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDateTime, PyDict, PyList};
+use pyo3::wrap_pyfunction;
+
+/// A course parsed from its page in the VV.
+#[pyclass]
+pub struct Course {
+    inner: cal::Course,
+}
+
+#[pymethods]
+impl Course {
+    #[getter]
+    fn id(&self) -> PyResult<Option<String>> {
+        Ok(self.inner.id.clone())
+    }
+
+    #[getter]
+    fn name(&self) -> PyResult<String> {
+        Ok(self.inner.name.clone())
+    }
+
+    #[getter]
+    fn kind(&self) -> PyResult<Option<String>> {
+        Ok(self.inner.kind.clone())
+    }
+
+    #[getter]
+    fn url(&self) -> PyResult<Option<String>> {
+        Ok(self.inner.url.clone())
+    }
+
+    #[getter]
+    fn sws(&self) -> PyResult<Option<f32>> {
+        Ok(self.inner.sws)
+    }
+
+    #[getter]
+    fn ects(&self) -> PyResult<Option<f32>> {
+        Ok(self.inner.ects)
+    }
+
+    #[getter]
+    fn modules(&self) -> PyResult<Vec<String>> {
+        Ok(self.inner.modules.clone())
+    }
+
+    #[getter]
+    fn language(&self) -> PyResult<Option<String>> {
+        Ok(self.inner.language.clone())
+    }
+
+    #[getter]
+    fn events(&self) -> PyResult<Vec<Event>> {
+        Ok(self
+            .inner
+            .events
+            .iter()
+            .cloned()
+            .map(|inner| Event { inner })
+            .collect())
+    }
+
+    /// One dict per session, with the course columns repeated, for `pandas.DataFrame`.
+    fn records(&self) -> PyResult<PyObject> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        let records = PyList::empty(py);
+        for event in self.inner.events.iter() {
+            let record = event_dict(py, event)?;
+            record.set_item("course_id", &self.inner.id)?;
+            record.set_item("course_name", &self.inner.name)?;
+            record.set_item("course_kind", &self.inner.kind)?;
+            records.append(record)?;
+        }
+        Ok(records.to_object(py))
+    }
+
+    /// Calendar of the course as ICS text, as exported by the CLI.
+    fn to_ics(&self) -> PyResult<String> {
+        let calendar = self
+            .inner
+            .clone()
+            .to_ical(&CalendarOptions::default())
+            .map_err(value_error)?;
+        Ok(sanitize::fold(&calendar.to_string()))
+    }
+}
+
+/// A session of a course.
+///
+/// `start` and `end` are naive datetimes in the local time of Berlin.
+#[pyclass]
+pub struct Event {
+    inner: cal::CourseEvent,
+}
+
+#[pymethods]
+impl Event {
+    #[getter]
+    fn id(&self) -> PyResult<String> {
+        Ok(self.inner.id.clone())
+    }
+
+    #[getter]
+    fn start(&self) -> PyResult<PyObject> {
+        let gil = Python::acquire_gil();
+        local_datetime(gil.python(), &self.inner.timespan.start)
+    }
+
+    #[getter]
+    fn end(&self) -> PyResult<PyObject> {
+        let gil = Python::acquire_gil();
+        local_datetime(gil.python(), &self.inner.timespan.end)
+    }
+
+    #[getter]
+    fn room(&self) -> PyResult<Option<String>> {
+        Ok(self.inner.room.clone())
+    }
+
+    #[getter]
+    fn lecturers(&self) -> PyResult<Vec<String>> {
+        Ok(self.inner.lecturers.clone())
+    }
+
+    #[getter]
+    fn part(&self) -> PyResult<Option<String>> {
+        Ok(self.inner.part.clone())
+    }
+
+    #[getter]
+    fn group(&self) -> PyResult<Option<String>> {
+        Ok(self.inner.group.clone())
+    }
+
+    #[getter]
+    fn remark(&self) -> PyResult<Option<String>> {
+        Ok(self.inner.remark.clone())
+    }
+
+    #[getter]
+    fn week(&self) -> PyResult<Option<u32>> {
+        Ok(self.inner.week)
+    }
+
+    #[getter]
+    fn exam(&self) -> PyResult<bool> {
+        Ok(self.inner.exam)
+    }
+
+    /// The session as a dict, see `Course.records`.
+    fn to_dict(&self) -> PyResult<PyObject> {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        Ok(event_dict(py, &self.inner)?.to_object(py))
+    }
+}
+
+fn event_dict<'p>(py: Python<'p>, event: &cal::CourseEvent) -> PyResult<&'p PyDict> {
+    let dict = PyDict::new(py);
+    dict.set_item("id", &event.id)?;
+    dict.set_item("start", local_datetime(py, &event.timespan.start)?)?;
+    dict.set_item("end", local_datetime(py, &event.timespan.end)?)?;
+    dict.set_item("room", &event.room)?;
+    dict.set_item("lecturers", &event.lecturers)?;
+    dict.set_item("part", &event.part)?;
+    dict.set_item("group", &event.group)?;
+    dict.set_item("remark", &event.remark)?;
+    dict.set_item("week", event.week)?;
+    dict.set_item("exam", event.exam)?;
+    Ok(dict)
+}
+
+fn local_datetime(py: Python, time: &chrono::DateTime<chrono_tz::Tz>) -> PyResult<PyObject> {
+    let time = time.naive_local();
+    let datetime = PyDateTime::new(
+        py,
+        time.year(),
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+        0,
+        None,
+    )?;
+    Ok(datetime.to_object(py))
+}
+
+fn value_error(err: cal::StdError) -> PyErr {
+    ValueError::py_err(err.to_string())
+}
+
+/// Parse the HTML of a course page of the VV.
+///
+/// `id` is the course id from the URL of the page (the segment after `/lv/`), it keeps the UIDs of
+/// `to_ics` the same as in calendars exported by the CLI. `url` is linked in the descriptions.
+#[pyfunction(id = "None", url = "None")]
+fn parse_course(html: &str, id: Option<String>, url: Option<String>) -> PyResult<Course> {
+    let mut inner = cal::Course::from_html(html).map_err(value_error)?;
+    inner.id = id;
+    inner.url = url;
+    Ok(Course { inner })
+}
+
+#[pymodule]
+fn fu_vv_cal(_py: Python, module: &PyModule) -> PyResult<()> {
+    module.add_class::<Course>()?;
+    module.add_class::<Event>()?;
+    module.add_wrapped(wrap_pyfunction!(parse_course))?;
+    Ok(())
+}
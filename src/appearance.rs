@@ -0,0 +1,99 @@
+//! How generated events appear in the calendars of subscribers.
+
+use crate::Error;
+use ics::properties::{Class, Transp};
+use ics::Event;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// Access classification of events (`CLASS`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Public,
+    Private,
+    Confidential,
+}
+
+impl FromStr for Visibility {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_lowercase().as_str() {
+            "public" => Ok(Visibility::Public),
+            "private" => Ok(Visibility::Private),
+            "confidential" => Ok(Visibility::Confidential),
+            _ => Err(Error::InvalidVisibility {
+                text: text.to_owned(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Appearance {
+    /// Don't block time in free/busy lookups (`TRANSP:TRANSPARENT`)
+    #[serde(default)]
+    pub transparent: bool,
+    /// Access classification, unset by default which clients treat as public
+    pub visibility: Option<Visibility>,
+    /// Give courses without a configured color one of the palette of `colors`
+    #[serde(default)]
+    pub auto_colors: bool,
+}
+
+impl Appearance {
+    /// Add `TRANSP` and `CLASS` to an iCalendar event.
+    pub fn apply_ical(&self, event: &mut Event) {
+        if self.transparent {
+            event.push(Transp::transparent());
+        }
+        match self.visibility {
+            Some(Visibility::Public) => event.push(Class::public()),
+            Some(Visibility::Private) => event.push(Class::private()),
+            Some(Visibility::Confidential) => event.push(Class::confidential()),
+            None => {}
+        }
+    }
+
+    /// Add `freeBusyStatus` and `privacy` to a JSCalendar event.
+    pub fn apply_jscalendar(&self, event: &mut Value) {
+        if self.transparent {
+            event["freeBusyStatus"] = "free".into();
+        }
+        match self.visibility {
+            Some(Visibility::Public) => event["privacy"] = "public".into(),
+            Some(Visibility::Private) => event["privacy"] = "private".into(),
+            // JSCalendar has no direct equivalent, "secret" hides the details as well
+            Some(Visibility::Confidential) => event["privacy"] = "secret".into(),
+            None => {}
+        }
+    }
+
+    /// Add `transparency` and `visibility` to a Google Calendar event.
+    pub fn apply_google(&self, event: &mut Value) {
+        if self.transparent {
+            event["transparency"] = "transparent".into();
+        }
+        match self.visibility {
+            Some(Visibility::Public) => event["visibility"] = "public".into(),
+            Some(Visibility::Private) => event["visibility"] = "private".into(),
+            Some(Visibility::Confidential) => event["visibility"] = "confidential".into(),
+            None => {}
+        }
+    }
+
+    /// Add `showAs` and `sensitivity` to a Microsoft Graph event.
+    pub fn apply_outlook(&self, event: &mut Value) {
+        if self.transparent {
+            event["showAs"] = "free".into();
+        }
+        match self.visibility {
+            Some(Visibility::Public) => event["sensitivity"] = "normal".into(),
+            Some(Visibility::Private) => event["sensitivity"] = "private".into(),
+            Some(Visibility::Confidential) => event["sensitivity"] = "confidential".into(),
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::changes::Change;
+use crate::digest::escape_html;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// Atom feed with one entry per change, paired with the time it was detected.
+///
+/// `feed_id` has to be a stable URI identifying the feed, entry ids are derived from it.
+pub fn changes_feed(title: &str, feed_id: &str, changes: &[(DateTime<Utc>, Change)]) -> String {
+    let updated = changes
+        .iter()
+        .map(|(detected, _)| *detected)
+        .max()
+        .unwrap_or_else(Utc::now);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <title>{}</title>\n", escape_html(title)));
+    xml.push_str(&format!("  <id>{}</id>\n", escape_html(feed_id)));
+    xml.push_str(&format!("  <updated>{}</updated>\n", timestamp(updated)));
+    xml.push_str("  <author><name>fu-vv-cal</name></author>\n");
+
+    // Newest first
+    for (detected, change) in changes.iter().rev() {
+        let entry_id = format!("{}:{}:{}", feed_id, change.event.id, detected.timestamp());
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <id>{}</id>\n", escape_html(&entry_id)));
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape_html(&change.to_string())
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            timestamp(*detected)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
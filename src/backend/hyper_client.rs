@@ -0,0 +1,71 @@
+use super::transport_error;
+use crate::fetch::{self, HttpRequest};
+use crate::{Error, StdError};
+use http::Response;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Request};
+use hyper_tls::HttpsConnector;
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+
+type HttpsClient = Client<HttpsConnector<HttpConnector>>;
+
+lazy_static! {
+    /// Client shared by all requests, so connections to the same host are pooled and reused.
+    /// Created on first use and recreated when the connect timeout changes.
+    static ref CLIENT: Mutex<Option<HttpsClient>> = Mutex::new(None);
+}
+
+/// Drop the shared client, so the next request creates one with the current timeouts.
+pub(crate) fn reset() {
+    *CLIENT.lock().unwrap() = None;
+}
+
+pub(crate) async fn execute(req: HttpRequest) -> Result<Response<Vec<u8>>, StdError> {
+    let HttpRequest {
+        method,
+        url,
+        headers,
+        body,
+    } = req;
+    let mut builder = Request::builder();
+    builder.method(method).uri(url.as_str());
+    let mut req = builder
+        .body(Body::from(body))
+        .map_err(|_| Error::InvalidUrl { url: url.clone() })?;
+    *req.headers_mut() = headers;
+
+    let res = client()?
+        .request(req)
+        .await
+        .map_err(|err| transport_error(&url, err))?;
+    let (parts, mut body) = res.into_parts();
+    let mut bytes = Vec::new();
+    while let Some(next) = body.next().await {
+        let chunk = next.map_err(|err| Error::InvalidBody {
+            url: url.clone(),
+            message: err.to_string(),
+        })?;
+        bytes.extend(chunk);
+    }
+    Ok(Response::from_parts(parts, bytes))
+}
+
+/// The shared client, see `CLIENT`.
+fn client() -> Result<HttpsClient, StdError> {
+    let mut client = CLIENT.lock().unwrap();
+    if let Some(client) = &*client {
+        return Ok(client.clone());
+    }
+
+    let (connect_timeout, _) = fetch::timeouts();
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_connect_timeout(Some(connect_timeout));
+    let tls = native_tls::TlsConnector::new()?;
+    let https = HttpsConnector::from((http, tls.into()));
+    let new_client = Client::builder().build::<_, hyper::Body>(https);
+    *client = Some(new_client.clone());
+
+    Ok(new_client)
+}
@@ -0,0 +1,48 @@
+//! HTTP client behind `fetch`, chosen at compile time with exactly one of the features
+//! `hyper-backend` (hyper with native-tls), `reqwest-backend` or `ureq-backend` (blocking, run on
+//! a thread of its own). `hyper-backend` is a default feature, so the others are enabled with
+//! e.g. `--no-default-features --features server,reqwest-backend`.
+//!
+//! A backend sends a `HttpRequest` once and returns the response with its whole body. Retries,
+//! redirects and compression are left to `fetch`.
+
+#[cfg(not(any(
+    feature = "hyper-backend",
+    feature = "reqwest-backend",
+    feature = "ureq-backend"
+)))]
+compile_error!("enable one of the features hyper-backend, reqwest-backend or ureq-backend");
+
+#[cfg(any(
+    all(feature = "hyper-backend", feature = "reqwest-backend"),
+    all(feature = "hyper-backend", feature = "ureq-backend"),
+    all(feature = "reqwest-backend", feature = "ureq-backend")
+))]
+compile_error!(
+    "enable only one of the features hyper-backend, reqwest-backend or ureq-backend \
+     (hyper-backend is a default feature, see --no-default-features)"
+);
+
+#[cfg(feature = "hyper-backend")]
+mod hyper_client;
+#[cfg(feature = "hyper-backend")]
+pub(crate) use hyper_client::{execute, reset};
+
+#[cfg(feature = "reqwest-backend")]
+mod reqwest_client;
+#[cfg(feature = "reqwest-backend")]
+pub(crate) use reqwest_client::{execute, reset};
+
+#[cfg(feature = "ureq-backend")]
+mod ureq_client;
+#[cfg(feature = "ureq-backend")]
+pub(crate) use ureq_client::{execute, reset};
+
+use crate::Error;
+
+fn transport_error<E: std::fmt::Display>(url: &str, err: E) -> Error {
+    Error::TransportError {
+        url: url.to_owned(),
+        message: err.to_string(),
+    }
+}
@@ -0,0 +1,66 @@
+use super::transport_error;
+use crate::fetch::{self, HttpRequest};
+use crate::StdError;
+use http::Response;
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+
+lazy_static! {
+    /// Client shared by all requests, see `hyper_client::CLIENT`.
+    static ref CLIENT: Mutex<Option<reqwest::Client>> = Mutex::new(None);
+}
+
+/// Drop the shared client, so the next request creates one with the current timeouts.
+pub(crate) fn reset() {
+    *CLIENT.lock().unwrap() = None;
+}
+
+pub(crate) async fn execute(req: HttpRequest) -> Result<Response<Vec<u8>>, StdError> {
+    let HttpRequest {
+        method,
+        url,
+        headers,
+        body,
+    } = req;
+    let mut res = client(&url)?
+        .request(method, url.as_str())
+        .headers(headers)
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| transport_error(&url, err))?;
+
+    let status = res.status();
+    let headers = res.headers().clone();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = res
+        .chunk()
+        .await
+        .map_err(|err| transport_error(&url, err))?
+    {
+        bytes.extend_from_slice(&chunk);
+    }
+    let mut response = Response::new(bytes);
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+    Ok(response)
+}
+
+/// The shared client, see `CLIENT`.
+fn client(url: &str) -> Result<reqwest::Client, StdError> {
+    let mut client = CLIENT.lock().unwrap();
+    if let Some(client) = &*client {
+        return Ok(client.clone());
+    }
+
+    let (connect_timeout, _) = fetch::timeouts();
+    let new_client = reqwest::Client::builder()
+        .connect_timeout(connect_timeout)
+        // `fetch` decides which redirects to follow
+        .redirect(reqwest::RedirectPolicy::none())
+        .build()
+        .map_err(|err| transport_error(url, err))?;
+    *client = Some(new_client.clone());
+
+    Ok(new_client)
+}
@@ -0,0 +1,68 @@
+use super::transport_error;
+use crate::fetch::{self, HttpRequest};
+use crate::{Error, StdError};
+use http::header::{HeaderName, HeaderValue};
+use http::{Response, StatusCode};
+use std::io::Read;
+use std::thread;
+use tokio::sync::oneshot;
+
+/// ureq keeps no state between requests.
+pub(crate) fn reset() {}
+
+/// Send `req` with ureq.
+///
+/// ureq blocks until the response is read, so every request runs on a thread of its own instead
+/// of stalling the runtime and the other requests on it.
+pub(crate) async fn execute(req: HttpRequest) -> Result<Response<Vec<u8>>, StdError> {
+    let url = req.url.clone();
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        // The receiver is only gone if the request timed out in the meantime
+        let _ = sender.send(execute_blocking(req));
+    });
+    let response = receiver.await.map_err(|err| transport_error(&url, err))??;
+    Ok(response)
+}
+
+fn execute_blocking(req: HttpRequest) -> Result<Response<Vec<u8>>, Error> {
+    let url = req.url.as_str();
+    let (connect_timeout, request_timeout) = fetch::timeouts();
+    let mut request = ureq::request(req.method.as_str(), url);
+    request
+        .timeout_connect(connect_timeout.as_millis() as u64)
+        .timeout_read(request_timeout.as_millis() as u64)
+        // `fetch` decides which redirects to follow
+        .redirects(0);
+    for (name, value) in req.headers.iter() {
+        request.set(
+            name.as_str(),
+            value.to_str().map_err(|err| transport_error(url, err))?,
+        );
+    }
+
+    let res = if req.body.is_empty() {
+        request.call()
+    } else {
+        request.send_bytes(&req.body)
+    };
+    if let Some(err) = res.synthetic_error() {
+        return Err(transport_error(url, err));
+    }
+
+    let mut response = Response::new(vec![]);
+    *response.status_mut() =
+        StatusCode::from_u16(res.status()).map_err(|err| transport_error(url, err))?;
+    for name in res.headers_names() {
+        let header =
+            HeaderName::from_bytes(name.as_bytes()).map_err(|err| transport_error(url, err))?;
+        for value in res.all(&name) {
+            let value = HeaderValue::from_str(value).map_err(|err| transport_error(url, err))?;
+            response.headers_mut().append(header.clone(), value);
+        }
+    }
+    res.into_reader()
+        .read_to_end(response.body_mut())
+        .map_err(|err| transport_error(url, err))?;
+    Ok(response)
+}
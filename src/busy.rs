@@ -0,0 +1,55 @@
+//! Busy-only export: every session becomes an event titled just "Busy", without course names,
+//! rooms, lecturers or links, so students can share their availability without revealing what
+//! they study.
+//!
+//! Event ids are replaced by hashes of course and session id, and UIDs are made of those alone
+//! (see `uid::for_event`), so not even the VV ids give the courses away.
+
+use crate::options::CalendarOptions;
+use crate::{stable_hash, Course, CourseEvent};
+
+/// Summary of every event in busy-only mode
+pub const BUSY_SUMMARY: &str = "Busy";
+
+/// Prefix of the ids of anonymized sessions
+const ID_PREFIX: &str = "busy-";
+
+/// Anonymize `course` if `options` ask for busy-only calendars.
+pub fn apply(course: &mut Course, options: &CalendarOptions) {
+    if options.busy_only {
+        anonymize(course);
+    }
+}
+
+/// Strip `course` down to the times of its sessions.
+pub fn anonymize(course: &mut Course) {
+    let course_id = course.id.clone().unwrap_or_default();
+    course.name = BUSY_SUMMARY.to_owned();
+    course.kind = None;
+    course.url = None;
+    course.color = None;
+    course.sws = None;
+    course.ects = None;
+    course.modules = vec![];
+    course.language = None;
+    course.registration = Default::default();
+    for event in course.events.iter_mut().chain(course.cancelled.iter_mut()) {
+        anonymize_event(&course_id, event);
+    }
+}
+
+fn anonymize_event(course_id: &str, event: &mut CourseEvent) {
+    if !event.id.starts_with(ID_PREFIX) {
+        let key = format!("{}|{}", course_id, event.id);
+        event.id = format!("{}{:016x}", ID_PREFIX, stable_hash(&key));
+    }
+    event.summary = Some(BUSY_SUMMARY.to_owned());
+    event.week = None;
+    event.part = None;
+    event.remark = None;
+    event.room = None;
+    event.lecturers = vec![];
+    event.detail_url = None;
+    event.exam = false;
+    event.group = None;
+}
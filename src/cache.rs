@@ -0,0 +1,61 @@
+//! On-disk cache of fetched course pages, mostly to speed up repeated runs during development.
+//!
+//! Every page is stored in its own file, named after the URL-encoded URL, together with the time
+//! it was fetched.
+
+use crate::StdError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct PageCache {
+    pub dir: PathBuf,
+    /// Age up to which a cached page is used instead of fetching it again
+    pub max_age: Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedPage {
+    url: String,
+    fetched_at: DateTime<Utc>,
+    body: String,
+}
+
+impl PageCache {
+    pub fn new<P: Into<PathBuf>>(dir: P, max_age: Duration) -> Self {
+        Self {
+            dir: dir.into(),
+            max_age,
+        }
+    }
+
+    /// The cached body of `url`, if it was fetched within `max_age`.
+    pub fn get(&self, url: &str) -> Option<String> {
+        let contents = std::fs::read_to_string(self.path(url)).ok()?;
+        let page: CachedPage = serde_json::from_str(&contents).ok()?;
+        let age = (Utc::now() - page.fetched_at).to_std().ok()?;
+        if page.url != url || age > self.max_age {
+            return None;
+        }
+
+        Some(page.body)
+    }
+
+    pub fn put(&self, url: &str, body: &str) -> Result<(), StdError> {
+        std::fs::create_dir_all(&self.dir)?;
+        let page = CachedPage {
+            url: url.to_owned(),
+            fetched_at: Utc::now(),
+            body: body.to_owned(),
+        };
+        std::fs::write(self.path(url), serde_json::to_string(&page)?)?;
+        Ok(())
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        let name = url::form_urlencoded::byte_serialize(url.as_bytes()).collect::<String>();
+        self.dir.join(format!("{}.json", name))
+    }
+}
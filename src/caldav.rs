@@ -0,0 +1,210 @@
+use crate::config::CalDavConfig;
+use crate::fetch::{self, HttpRequest};
+use crate::merge::UidRegistry;
+use crate::sanitize;
+use crate::uid;
+use crate::{new_calendar, Error, RequestedCourse, StdError};
+use http::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE, IF_MATCH, IF_NONE_MATCH};
+use http::{Method, StatusCode};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+/// Prefix of the resources created by fu-vv-cal, so other events in the collection are left alone.
+const RESOURCE_PREFIX: &str = "fu-vv-cal-";
+
+/// Upload the events of `courses` into the CalDAV collection configured in `caldav`.
+///
+/// Every event is stored as its own resource named after its UID. Existing resources are only
+/// overwritten if they still have the ETag seen at the start of the sync, and resources of events
+/// that disappeared from the VV are deleted.
+pub async fn sync(caldav: &CalDavConfig, courses: &[RequestedCourse]) -> Result<(), StdError> {
+    let collection = Collection::new(caldav);
+
+    let mut existing = collection.etags().await?;
+    let mut registry = UidRegistry::new();
+    for requested in courses.iter() {
+        let course = requested.get_course().await?;
+        let name = course.name.clone();
+        let count = course.events.len();
+
+        let mut uids = vec![];
+        let options = &requested.options;
+        let cal_events = course.into_ical_events(options, |event| {
+            let (uid, rewritten) = registry.claim_checked(
+                &uid::for_event(options, Some(&requested.id), &event.id),
+                &requested.id,
+            );
+            uids.push(uid.clone());
+            (uid, rewritten)
+        });
+        for (uid, cal_event) in uids.into_iter().zip(cal_events) {
+            let mut calendar = new_calendar();
+            calendar.add_event(cal_event);
+
+            let resource = resource_name(&uid);
+            let etag = existing.remove(&resource);
+            collection
+                .put(
+                    &resource,
+                    etag.as_ref(),
+                    sanitize::fold(&calendar.to_string()),
+                )
+                .await?;
+        }
+        info!(
+            events = count as u64,
+            course = name.as_str(),
+            "uploaded to CalDAV"
+        );
+    }
+
+    // Whatever is left over belongs to events that no longer exist
+    for (resource, etag) in existing.iter() {
+        collection.delete(resource, etag).await?;
+    }
+
+    Ok(())
+}
+
+/// Name of the resource holding the event with `uid` within a collection.
+pub fn resource_name(uid: &str) -> String {
+    let uid = url::form_urlencoded::byte_serialize(uid.as_bytes()).collect::<String>();
+    format!("{}{}.ics", RESOURCE_PREFIX, uid)
+}
+
+struct Collection {
+    url: String,
+    authorization: Option<String>,
+}
+
+impl Collection {
+    fn new(caldav: &CalDavConfig) -> Self {
+        let mut url = caldav.url.clone();
+        if !url.ends_with('/') {
+            url.push('/');
+        }
+        let authorization = caldav.username.as_ref().map(|username| {
+            let password = caldav.password.as_ref().map(String::as_str).unwrap_or("");
+            format!(
+                "Basic {}",
+                base64::encode(&format!("{}:{}", username, password))
+            )
+        });
+
+        Self { url, authorization }
+    }
+
+    /// ETags of the fu-vv-cal resources in the collection, keyed by resource name.
+    async fn etags(&self) -> Result<HashMap<String, String>, StdError> {
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:getetag/></d:prop></d:propfind>"#;
+        let mut req = self.request(Method::from_bytes(b"PROPFIND").unwrap(), &self.url, body)?;
+        req.headers.insert("Depth", HeaderValue::from_static("1"));
+        req.headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/xml; charset=utf-8"),
+        );
+
+        let (status, body) = fetch::send(req).await?;
+        check_response(status, &self.url)?;
+
+        let document = roxmltree::Document::parse(&body)?;
+        let mut etags = HashMap::new();
+        for response in document
+            .descendants()
+            .filter(|node| node.has_tag_name(("DAV:", "response")))
+        {
+            let href = response
+                .descendants()
+                .find(|node| node.has_tag_name(("DAV:", "href")))
+                .and_then(|node| node.text());
+            let etag = response
+                .descendants()
+                .find(|node| node.has_tag_name(("DAV:", "getetag")))
+                .and_then(|node| node.text());
+            if let (Some(href), Some(etag)) = (href, etag) {
+                let resource = href.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+                if resource.starts_with(RESOURCE_PREFIX) {
+                    etags.insert(resource.to_owned(), etag.trim().to_owned());
+                }
+            }
+        }
+
+        Ok(etags)
+    }
+
+    /// Create or update a resource.
+    ///
+    /// With an `etag` the resource is only replaced if it wasn't modified in the meantime,
+    /// without one it is only created if it doesn't exist yet.
+    async fn put(
+        &self,
+        resource: &str,
+        etag: Option<&String>,
+        calendar: String,
+    ) -> Result<(), StdError> {
+        let url = format!("{}{}", self.url, resource);
+        let mut req = self.request(Method::PUT, &url, calendar)?;
+        req.headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/calendar; charset=utf-8"),
+        );
+        match etag {
+            Some(etag) => req.headers.insert(IF_MATCH, HeaderValue::from_str(etag)?),
+            None => req
+                .headers
+                .insert(IF_NONE_MATCH, HeaderValue::from_static("*")),
+        };
+
+        let (status, _) = fetch::send(req).await?;
+        if status == StatusCode::PRECONDITION_FAILED {
+            warn!(url = url.as_str(), "modified on the server, skipping it");
+            return Ok(());
+        }
+        check_response(status, &url)
+    }
+
+    async fn delete(&self, resource: &str, etag: &str) -> Result<(), StdError> {
+        let url = format!("{}{}", self.url, resource);
+        let mut req = self.request(Method::DELETE, &url, "")?;
+        req.headers.insert(IF_MATCH, HeaderValue::from_str(etag)?);
+
+        let (status, _) = fetch::send(req).await?;
+        match status {
+            StatusCode::NOT_FOUND => Ok(()),
+            StatusCode::PRECONDITION_FAILED => {
+                warn!(
+                    url = url.as_str(),
+                    "modified on the server, not deleting it"
+                );
+                Ok(())
+            }
+            status => check_response(status, &url),
+        }
+    }
+
+    fn request<B: Into<Vec<u8>>>(
+        &self,
+        method: Method,
+        url: &str,
+        body: B,
+    ) -> Result<HttpRequest, StdError> {
+        let mut req = HttpRequest::new(method, url).with_body(body);
+        if let Some(authorization) = &self.authorization {
+            req = req.header(AUTHORIZATION, authorization)?;
+        }
+
+        Ok(req)
+    }
+}
+
+fn check_response(status: StatusCode, url: &str) -> Result<(), StdError> {
+    if !status.is_success() {
+        return Err(Error::CalDavError {
+            status: status.as_u16(),
+            url: url.to_owned(),
+        }
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,229 @@
+//! Buildings of the FU campus, to locate the rooms given in the VV.
+//!
+//! A built-in table covers the Dahlem campus, further buildings can be configured with
+//! `[[building]]` (see `CalendarOptions::buildings`) and take precedence.
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+/// A building rooms can be located in.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Building {
+    /// Street and house number as written in room names, e.g. "Takustr. 9"
+    pub street: String,
+    /// Abbreviations used in room names instead of the street, e.g. "HFB"
+    #[serde(default)]
+    pub codes: Vec<String>,
+    /// Name of the building or the institute in it, e.g. "Institut für Informatik"
+    pub name: Option<String>,
+    #[serde(default = "default_postcode")]
+    pub postcode: String,
+    /// Latitude and longitude of the entrance
+    pub coordinates: Option<(f64, f64)>,
+}
+
+fn default_postcode() -> String {
+    "14195".to_owned()
+}
+
+impl Building {
+    fn new(street: &str, codes: &[&str], name: Option<&str>, coordinates: (f64, f64)) -> Self {
+        Self {
+            street: street.to_owned(),
+            codes: codes.iter().map(|code| (*code).to_owned()).collect(),
+            name: name.map(str::to_owned),
+            postcode: default_postcode(),
+            coordinates: Some(coordinates),
+        }
+    }
+
+    /// Value of the iCalendar `GEO` property.
+    pub fn geo(&self) -> Option<String> {
+        let (latitude, longitude) = self.coordinates?;
+        Some(format!("{:.6};{:.6}", latitude, longitude))
+    }
+
+    /// `geo:` URI of the coordinates, as used by JSCalendar.
+    pub fn geo_uri(&self) -> Option<String> {
+        let (latitude, longitude) = self.coordinates?;
+        Some(format!("geo:{:.6},{:.6}", latitude, longitude))
+    }
+
+    /// Postal address of `room` in this building, e.g. "SR 006, Institut für Informatik,
+    /// Takustr. 9, 14195 Berlin".
+    pub fn address(&self, room: &str) -> String {
+        let postcode = format!("{} Berlin", self.postcode);
+        Some(room)
+            .into_iter()
+            .chain(self.name.as_ref().map(String::as_str))
+            .chain(Some(self.street.as_str()))
+            .chain(Some(postcode.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn matches(&self, room: &str) -> bool {
+        let room = normalize(room);
+        // "Arnimallee 1" must not match "Arnimallee 14"
+        (!self.street.is_empty() && contains_word(&room, &normalize(&self.street)))
+            || self
+                .codes
+                .iter()
+                .any(|code| contains_word(&room, &normalize(code)))
+    }
+}
+
+lazy_static! {
+    /// Buildings of the Dahlem campus most courses take place in
+    static ref BUILDINGS: Vec<Building> = vec![
+        Building::new(
+            "Takustr. 9",
+            &["T9"],
+            Some("Institut für Informatik"),
+            (52.457_08, 13.297_60),
+        ),
+        Building::new(
+            "Takustr. 3",
+            &["T3"],
+            Some("Institut für Chemie und Biochemie"),
+            (52.456_61, 13.296_58),
+        ),
+        Building::new(
+            "Arnimallee 3",
+            &["A3"],
+            Some("Institut für Mathematik"),
+            (52.453_52, 13.287_74),
+        ),
+        Building::new(
+            "Arnimallee 6",
+            &["A6"],
+            Some("Institut für Mathematik"),
+            (52.452_94, 13.288_29),
+        ),
+        Building::new("Arnimallee 7", &["A7"], None, (52.452_73, 13.287_39)),
+        Building::new(
+            "Arnimallee 14",
+            &["A14"],
+            Some("Fachbereich Physik"),
+            (52.449_91, 13.287_43),
+        ),
+        Building::new("Arnimallee 22", &["A22"], None, (52.451_19, 13.285_34)),
+        Building::new(
+            "Fabeckstr. 34-36",
+            &[],
+            Some("Institut für Chemie und Biochemie"),
+            (52.456_21, 13.289_71),
+        ),
+        Building::new(
+            "Thielallee 63",
+            &[],
+            Some("Institut für Chemie und Biochemie"),
+            (52.455_59, 13.290_45),
+        ),
+        Building::new(
+            "Königin-Luise-Str. 12-16",
+            &[],
+            Some("Institut für Biologie"),
+            (52.457_89, 13.300_22),
+        ),
+        Building::new(
+            "Habelschwerdter Allee 45",
+            &["Rostlaube", "Silberlaube"],
+            Some("Rost- und Silberlaube"),
+            (52.452_66, 13.289_27),
+        ),
+        Building::new(
+            "Garystr. 35",
+            &["HFB", "Henry-Ford-Bau"],
+            Some("Henry-Ford-Bau"),
+            (52.448_96, 13.281_86),
+        ),
+        Building::new(
+            "Garystr. 21",
+            &[],
+            Some("Fachbereich Wirtschaftswissenschaft"),
+            (52.448_76, 13.284_64),
+        ),
+        Building::new(
+            "Ihnestr. 21",
+            &["OSI"],
+            Some("Otto-Suhr-Institut"),
+            (52.452_57, 13.283_99),
+        ),
+        Building::new("Otto-von-Simson-Str. 26", &[], None, (52.452_29, 13.291_83)),
+        Building::new("Van't-Hoff-Str. 8", &[], None, (52.449_70, 13.283_01)),
+    ];
+}
+
+/// The building the room `room` is in, if it is known, looking in the `configured` buildings
+/// before the built-in ones.
+pub fn building_of(room: &str, configured: &[Building]) -> Option<Building> {
+    configured
+        .iter()
+        .chain(BUILDINGS.iter())
+        .find(|building| building.matches(room))
+        .cloned()
+}
+
+/// Lowercase `text` with the spellings of "Straße" unified.
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .replace("straße", "str.")
+        .replace("strasse", "str.")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether `word` occurs in `text` without being part of a longer word or number.
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_word_char = |c: char| c.is_alphanumeric();
+    text.match_indices(word).any(|(index, _)| {
+        !text[..index].ends_with(is_word_char)
+            && !text[index + word.len()..].starts_with(is_word_char)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn street(room: &str, configured: &[Building]) -> Option<String> {
+        building_of(room, configured).map(|building| building.street)
+    }
+
+    #[test]
+    fn built_in_buildings() {
+        assert_eq!(street("SR 006, Takustraße 9", &[]).unwrap(), "Takustr. 9");
+        assert_eq!(street("HFB, Hörsaal A", &[]).unwrap(), "Garystr. 35");
+        assert_eq!(
+            street("Arnimallee 14, Hörsaal B", &[]).unwrap(),
+            "Arnimallee 14"
+        );
+        assert!(street("Arnimallee 1, Raum 2", &[]).is_none());
+        assert!(street("Online", &[]).is_none());
+    }
+
+    #[test]
+    fn configured_buildings_take_precedence() {
+        let configured = vec![
+            Building::new(
+                "Takustr. 9",
+                &["Informatik"],
+                Some("Informatik"),
+                (52.0, 13.0),
+            ),
+            Building::new("Arnimallee 1", &[], None, (52.1, 13.1)),
+        ];
+        let building = building_of("Takustr. 9, SR 005", &configured).unwrap();
+        assert_eq!(
+            building.name.as_ref().map(String::as_str),
+            Some("Informatik")
+        );
+        assert_eq!(
+            street("Arnimallee 1, Raum 2", &configured).unwrap(),
+            "Arnimallee 1"
+        );
+        assert_eq!(street("HFB", &configured).unwrap(), "Garystr. 35");
+    }
+}
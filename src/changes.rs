@@ -0,0 +1,651 @@
+use crate::registration::Registration;
+#[cfg(feature = "native")]
+use crate::RequestedCourse;
+use crate::{Course, CourseEvent, Error, StdError};
+use chrono::{DateTime, Datelike, Utc, Weekday};
+use chrono_tz::Europe::Berlin;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::Path;
+use timespan::DateTimeSpan;
+
+/// Version of the persisted snapshot format, see `migrate`.
+pub const SNAPSHOT_VERSION: u64 = 2;
+
+/// Prefix of the summary of a session whose room changed, since that's easily missed.
+pub const ROOM_CHANGE_PREFIX: &str = "⚠ Raumänderung: ";
+
+/// Events of a set of courses at one point in time, persisted to detect changes between runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Courses by course id
+    pub courses: BTreeMap<String, CourseSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CourseSnapshot {
+    pub name: String,
+    pub events: Vec<EventSnapshot>,
+    /// Sessions that disappeared from the VV since they were first exported
+    #[serde(default)]
+    pub cancelled: Vec<EventSnapshot>,
+    /// Fingerprint of the course as last exported, see `fingerprint`
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// Removed from the config, so all of its sessions are exported as cancelled
+    #[serde(default)]
+    pub dropped: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventSnapshot {
+    pub id: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// iCal `SEQUENCE` the session was last exported with
+    #[serde(default)]
+    pub sequence: u32,
+    /// Room, if known (see `details`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+    /// Room the session was in before it was moved to `room`, see `mark_room_changes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_room: Option<String>,
+}
+
+impl Snapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, course_id: &str, course: &Course) {
+        let events = course
+            .events
+            .iter()
+            .map(|event| EventSnapshot {
+                id: event.id.clone(),
+                start: event.timespan.start.with_timezone(&Utc),
+                end: event.timespan.end.with_timezone(&Utc),
+                sequence: event.sequence,
+                room: event.room.clone(),
+                previous_room: None,
+            })
+            .collect();
+
+        self.courses.insert(
+            course_id.to_owned(),
+            CourseSnapshot {
+                name: course.name.clone(),
+                events,
+                cancelled: vec![],
+                fingerprint: None,
+                dropped: false,
+            },
+        );
+    }
+
+    /// Keep the courses of `previous` that are missing from this snapshot as dropped, with all of
+    /// their sessions cancelled, and return how many were dropped just now.
+    pub fn drop_missing(&mut self, previous: &Snapshot) -> usize {
+        let mut newly_dropped = 0;
+        for (course_id, previous_course) in previous.courses.iter() {
+            if self.courses.contains_key(course_id) {
+                continue;
+            }
+            let mut course = previous_course.clone();
+            if !course.dropped {
+                let events = std::mem::replace(&mut course.events, vec![]);
+                course.cancelled.extend(events.into_iter().map(|mut event| {
+                    event.sequence += 1;
+                    event
+                }));
+                course.dropped = true;
+                course.fingerprint = None;
+                newly_dropped += 1;
+            }
+            self.courses.insert(course_id.clone(), course);
+        }
+        newly_dropped
+    }
+
+    /// Courses removed from the config, with their sessions as cancelled ones so clients remove
+    /// the whole series.
+    pub fn dropped_courses(&self) -> Result<Vec<(String, Course)>, StdError> {
+        self.courses
+            .iter()
+            .filter(|(_, course)| course.dropped)
+            .map(|(course_id, course)| {
+                Ok((
+                    course_id.clone(),
+                    Course {
+                        id: Some(course_id.clone()),
+                        name: course.name.clone(),
+                        kind: None,
+                        events: vec![],
+                        cancelled: course
+                            .cancelled
+                            .iter()
+                            .map(cancelled_event)
+                            .collect::<Result<_, StdError>>()?,
+                        url: None,
+                        color: None,
+                        sws: None,
+                        ects: None,
+                        modules: vec![],
+                        language: None,
+                        registration: Registration::default(),
+                        warnings: vec![],
+                    },
+                ))
+            })
+            .collect()
+    }
+
+    /// Whether `course` is exported exactly as last time, judged by the recorded fingerprint.
+    pub fn unchanged(&self, course_id: &str, previous: &Snapshot) -> bool {
+        match (self.courses.get(course_id), previous.courses.get(course_id)) {
+            (Some(course), Some(previous_course)) => {
+                course.fingerprint.is_some() && course.fingerprint == previous_course.fingerprint
+            }
+            _ => false,
+        }
+    }
+
+    /// Record the fingerprint of `course` as exported with `settings`, see `fingerprint`.
+    pub fn set_fingerprint(
+        &mut self,
+        course_id: &str,
+        course: &Course,
+        settings: &str,
+    ) -> Result<(), StdError> {
+        if let Some(snapshot) = self.courses.get_mut(course_id) {
+            snapshot.fingerprint = Some(fingerprint(course, settings)?);
+        }
+        Ok(())
+    }
+
+    /// Carry the state of `previous` over to this snapshot.
+    ///
+    /// The sequence of every session that moved since `previous` is incremented, and sessions
+    /// that disappeared are kept as cancelled (with an incremented sequence as well), so they
+    /// can still be exported as cancellations.
+    pub fn track(&mut self, previous: &Snapshot) {
+        for (course_id, course) in self.courses.iter_mut() {
+            let previous_course = match previous.courses.get(course_id) {
+                Some(previous_course) => previous_course,
+                None => continue,
+            };
+
+            for event in course.events.iter_mut() {
+                let prev = previous_course
+                    .events
+                    .iter()
+                    .chain(previous_course.cancelled.iter())
+                    .find(|prev| prev.id == event.id);
+                if let Some(prev) = prev {
+                    let cancelled = previous_course.cancelled.contains(prev);
+                    let room_changed = room_changed(prev, event);
+                    event.sequence = if cancelled
+                        || room_changed
+                        || prev.start != event.start
+                        || prev.end != event.end
+                    {
+                        prev.sequence + 1
+                    } else {
+                        prev.sequence
+                    };
+                    if room_changed {
+                        event.previous_room = prev.room.clone();
+                    } else {
+                        // Rooms are only known when details were fetched
+                        if event.room.is_none() {
+                            event.room = prev.room.clone();
+                        }
+                        event.previous_room = prev.previous_room.clone();
+                    }
+                }
+            }
+
+            let mut cancelled = vec![];
+            for prev in previous_course.events.iter() {
+                if !course.events.iter().any(|event| event.id == prev.id) {
+                    let mut prev = prev.clone();
+                    prev.sequence += 1;
+                    cancelled.push(prev);
+                }
+            }
+            for prev in previous_course.cancelled.iter() {
+                if !course.events.iter().any(|event| event.id == prev.id) {
+                    cancelled.push(prev.clone());
+                }
+            }
+            course.cancelled = cancelled;
+        }
+    }
+
+    /// Set the sequences and cancelled sessions of `course` from this snapshot.
+    pub fn annotate(&self, course_id: &str, course: &mut Course) -> Result<(), StdError> {
+        let snapshot = match self.courses.get(course_id) {
+            Some(snapshot) => snapshot,
+            None => return Ok(()),
+        };
+
+        for event in course.events.iter_mut() {
+            if let Some(tracked) = snapshot
+                .events
+                .iter()
+                .find(|tracked| tracked.id == event.id)
+            {
+                event.sequence = tracked.sequence;
+            }
+        }
+        course.cancelled = snapshot
+            .cancelled
+            .iter()
+            .map(cancelled_event)
+            .collect::<Result<_, StdError>>()?;
+
+        Ok(())
+    }
+
+    /// Prefix the summaries of the sessions of `course` whose room changed (see `track`) with
+    /// `ROOM_CHANGE_PREFIX` and note the previous room in their remark.
+    ///
+    /// Runs after the summaries are rendered, so templates don't drop the mark.
+    pub fn mark_room_changes(&self, course_id: &str, course: &mut Course) {
+        let snapshot = match self.courses.get(course_id) {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        let name = course.name.clone();
+        for event in course.events.iter_mut() {
+            let previous_room = match snapshot
+                .events
+                .iter()
+                .find(|tracked| tracked.id == event.id)
+                .and_then(|tracked| tracked.previous_room.as_ref())
+            {
+                Some(previous_room) => previous_room,
+                None => continue,
+            };
+            let summary = event.summary.take().unwrap_or_else(|| name.clone());
+            event.summary = Some(format!("{}{}", ROOM_CHANGE_PREFIX, summary));
+            let note = format!(
+                "Raumänderung: {} → {}",
+                previous_room,
+                event.room.as_ref().map(String::as_str).unwrap_or("?")
+            );
+            event.remark = Some(match event.remark.take() {
+                Some(remark) => format!("{}\n{}", note, remark),
+                None => note,
+            });
+        }
+    }
+
+    /// Fetch the current state of `courses` from the VV.
+    #[cfg(feature = "native")]
+    pub async fn fetch(courses: &[RequestedCourse]) -> Result<Self, StdError> {
+        let mut snapshot = Self::new();
+        for requested in courses.iter() {
+            let course = requested.get_course().await?;
+            snapshot.insert(&requested.id, &course);
+        }
+        Ok(snapshot)
+    }
+
+    /// Load a snapshot, or an empty one if the file doesn't exist yet.
+    ///
+    /// Files written by older versions are migrated to the current format.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, StdError> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(Self::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut value: Value = serde_json::from_str(&contents)?;
+        migrate(&mut value)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StdError> {
+        let mut value = serde_json::to_value(self)?;
+        value["version"] = SNAPSHOT_VERSION.into();
+        std::fs::write(path, serde_json::to_string_pretty(&value)?)?;
+        Ok(())
+    }
+
+    /// Sessions that were added, cancelled or moved since `previous`.
+    ///
+    /// Only courses contained in both snapshots are compared, so adding a course to the config
+    /// doesn't report all of its sessions as new.
+    pub fn changes_since(&self, previous: &Snapshot) -> Vec<Change> {
+        let mut changes = vec![];
+        for (course_id, course) in self.courses.iter() {
+            let previous_course = match previous.courses.get(course_id) {
+                Some(previous_course) => previous_course,
+                None => continue,
+            };
+            let change = |event: &EventSnapshot, kind| Change {
+                course_id: course_id.clone(),
+                course_name: course.name.clone(),
+                event: event.clone(),
+                kind,
+            };
+
+            for event in course.events.iter() {
+                match previous_course
+                    .events
+                    .iter()
+                    .find(|prev| prev.id == event.id)
+                {
+                    None => changes.push(change(event, ChangeKind::Added)),
+                    Some(prev) if prev.start != event.start || prev.end != event.end => changes
+                        .push(change(
+                            event,
+                            ChangeKind::Moved {
+                                previous_start: prev.start,
+                                previous_end: prev.end,
+                            },
+                        )),
+                    Some(_) => {}
+                }
+                if let Some(prev) = previous_course
+                    .events
+                    .iter()
+                    .find(|prev| prev.id == event.id && room_changed(prev, event))
+                {
+                    changes.push(change(
+                        event,
+                        ChangeKind::RoomChanged {
+                            previous_room: prev.room.clone().unwrap_or_default(),
+                        },
+                    ));
+                }
+            }
+            for prev in previous_course.events.iter() {
+                if !course.events.iter().any(|event| event.id == prev.id) {
+                    changes.push(change(prev, ChangeKind::Cancelled));
+                }
+            }
+        }
+
+        changes.sort_by_key(|change| change.event.start);
+        changes
+    }
+}
+
+/// Session of a course, known only from a snapshot since it was cancelled.
+/// Whether the session moved from the room of `prev` to the one of `event`, both known.
+fn room_changed(prev: &EventSnapshot, event: &EventSnapshot) -> bool {
+    match (&prev.room, &event.room) {
+        (Some(previous), Some(current)) => previous != current,
+        _ => false,
+    }
+}
+
+fn cancelled_event(cancelled: &EventSnapshot) -> Result<CourseEvent, StdError> {
+    Ok(CourseEvent {
+        id: cancelled.id.clone(),
+        timespan: DateTimeSpan::new(
+            cancelled.start.with_timezone(&Berlin),
+            cancelled.end.with_timezone(&Berlin),
+        )?,
+        sequence: cancelled.sequence,
+        week: None,
+        exam: false,
+        part: None,
+        remark: None,
+        room: None,
+        lecturers: vec![],
+        detail_url: None,
+        group: None,
+        summary: None,
+        all_day: false,
+    })
+}
+
+/// Hash of everything of `course` that ends up in its calendar, to skip rewriting unchanged ones.
+///
+/// `settings` describes everything else the calendar is rendered with (output format, options
+/// and the like), so changing them rewrites the calendars as well. The hash is stable across
+/// Rust versions and runs, as it is persisted in the state file.
+pub fn fingerprint(course: &Course, settings: &str) -> Result<String, StdError> {
+    // The serialized course covers all of its fields, including ones added later
+    let content = serde_json::to_string(course)?;
+    let text = format!("{}\n{}", settings, content);
+    Ok(format!("{:016x}", crate::stable_hash(&text)))
+}
+
+/// Migrate a persisted snapshot to the current `SNAPSHOT_VERSION`, one version at a time.
+///
+/// Files without a version were written before versioning was introduced and are version 1.
+fn migrate(value: &mut Value) -> Result<(), StdError> {
+    let mut version = value.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version > SNAPSHOT_VERSION {
+        return Err(Error::UnsupportedStateVersion { version }.into());
+    }
+
+    while version < SNAPSHOT_VERSION {
+        match version {
+            1 => migrate_v1_to_v2(value),
+            _ => unreachable!(),
+        }
+        version += 1;
+    }
+
+    if let Some(object) = value.as_object_mut() {
+        object.remove("version");
+    }
+    Ok(())
+}
+
+/// Sequences and cancelled sessions were added for change tracking.
+fn migrate_v1_to_v2(value: &mut Value) {
+    let courses = match value.get_mut("courses").and_then(Value::as_object_mut) {
+        Some(courses) => courses,
+        None => return,
+    };
+    for course in courses.values_mut() {
+        if let Some(events) = course.get_mut("events").and_then(Value::as_array_mut) {
+            for event in events.iter_mut() {
+                event["sequence"] = 0.into();
+            }
+        }
+        course["cancelled"] = Value::Array(vec![]);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Change {
+    pub course_id: String,
+    pub course_name: String,
+    /// The session after the change, or before it was cancelled
+    pub event: EventSnapshot,
+    #[serde(flatten)]
+    pub kind: ChangeKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Cancelled,
+    Moved {
+        previous_start: DateTime<Utc>,
+        previous_end: DateTime<Utc>,
+    },
+    /// The session takes place in another room, `event.room`
+    #[serde(rename = "room")]
+    RoomChanged {
+        previous_room: String,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ChangeKind::Added => write!(
+                f,
+                "{}: new session on {}",
+                self.course_name,
+                format_session(self.event.start, self.event.end)
+            ),
+            ChangeKind::Cancelled => write!(
+                f,
+                "{}: session on {} cancelled",
+                self.course_name,
+                format_session(self.event.start, self.event.end)
+            ),
+            ChangeKind::Moved {
+                previous_start,
+                previous_end,
+            } => {
+                let previous = previous_start.with_timezone(&Berlin);
+                let current = self.event.start.with_timezone(&Berlin);
+                if previous.date() == current.date() {
+                    // Only the time changed
+                    write!(
+                        f,
+                        "{}: session on {} {} moved {} → {}",
+                        self.course_name,
+                        weekday_abbreviation(current.weekday()),
+                        current.format("%d.%m."),
+                        format_time_range(*previous_start, *previous_end),
+                        format_time_range(self.event.start, self.event.end)
+                    )
+                } else {
+                    write!(
+                        f,
+                        "{}: session on {} moved to {}",
+                        self.course_name,
+                        format_session(*previous_start, *previous_end),
+                        format_session(self.event.start, self.event.end)
+                    )
+                }
+            }
+            ChangeKind::RoomChanged { previous_room } => write!(
+                f,
+                "{}: session on {} moved from room {} to {}",
+                self.course_name,
+                format_session(self.event.start, self.event.end),
+                previous_room,
+                self.event.room.as_ref().map(String::as_str).unwrap_or("?")
+            ),
+        }
+    }
+}
+
+/// Human readable local time of a session, e.g. "Mo 21.10. 10:00–13:00".
+pub fn format_session(start: DateTime<Utc>, end: DateTime<Utc>) -> String {
+    let local_start = start.with_timezone(&Berlin);
+    format!(
+        "{} {} {}",
+        weekday_abbreviation(local_start.weekday()),
+        local_start.format("%d.%m."),
+        format_time_range(start, end)
+    )
+}
+
+/// Local start and end time of a session, e.g. "10:00–13:00".
+pub fn format_time_range(start: DateTime<Utc>, end: DateTime<Utc>) -> String {
+    format!(
+        "{}–{}",
+        start.with_timezone(&Berlin).format("%H:%M"),
+        end.with_timezone(&Berlin).format("%H:%M")
+    )
+}
+
+/// German weekday abbreviation as used by the VV.
+pub fn weekday_abbreviation(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Mo",
+        Weekday::Tue => "Di",
+        Weekday::Wed => "Mi",
+        Weekday::Thu => "Do",
+        Weekday::Fri => "Fr",
+        Weekday::Sat => "Sa",
+        Weekday::Sun => "So",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::CalendarOptions;
+    use crate::timespan_from_parts;
+
+    fn course() -> Course {
+        Course {
+            id: Some("10101".to_owned()),
+            name: "Algorithmen und Datenstrukturen".to_owned(),
+            kind: Some("Vorlesung".to_owned()),
+            events: vec![CourseEvent {
+                id: "500001".to_owned(),
+                timespan: timespan_from_parts("14.10.2019", "10:00", "12:00").unwrap(),
+                sequence: 0,
+                week: None,
+                part: None,
+                remark: None,
+                room: None,
+                lecturers: vec![],
+                detail_url: None,
+                exam: false,
+                group: None,
+                summary: None,
+                all_day: false,
+            }],
+            cancelled: vec![],
+            url: None,
+            color: None,
+            sws: None,
+            ects: None,
+            modules: vec![],
+            language: None,
+            registration: Registration::default(),
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn fingerprint_is_stable() {
+        let first = fingerprint(&course(), "ics").unwrap();
+        assert_eq!(first.len(), 16);
+        assert_eq!(fingerprint(&course(), "ics").unwrap(), first);
+    }
+
+    #[test]
+    fn fingerprint_covers_the_course() {
+        let original = fingerprint(&course(), "ics").unwrap();
+        let mut all_day = course();
+        all_day.events[0].make_all_day().unwrap();
+        assert_ne!(fingerprint(&all_day, "ics").unwrap(), original);
+        let mut described = course();
+        described.modules.push("21601".to_owned());
+        assert_ne!(fingerprint(&described, "ics").unwrap(), original);
+        let mut credited = course();
+        credited.ects = Some(5.0);
+        assert_ne!(fingerprint(&credited, "ics").unwrap(), original);
+    }
+
+    #[test]
+    fn fingerprint_covers_the_settings() {
+        let options = CalendarOptions::default();
+        let busy = CalendarOptions {
+            busy_only: true,
+            ..CalendarOptions::default()
+        };
+        assert_ne!(
+            fingerprint(&course(), &options.fingerprint()).unwrap(),
+            fingerprint(&course(), &busy.fingerprint()).unwrap()
+        );
+        assert_ne!(
+            fingerprint(&course(), "json").unwrap(),
+            fingerprint(&course(), "jscalendar").unwrap()
+        );
+    }
+}
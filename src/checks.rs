@@ -0,0 +1,148 @@
+use crate::changes::{format_session, format_time_range};
+use crate::{Course, CourseEvent};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use std::fmt;
+
+/// Bounds for sanity checks of parsed sessions.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Checks {
+    /// Sessions shorter than this are reported
+    #[serde(default = "default_min_duration_minutes")]
+    pub min_duration_minutes: i64,
+    /// Sessions longer than this are reported
+    #[serde(default = "default_max_duration_hours")]
+    pub max_duration_hours: i64,
+    /// Fail the export if sessions of different courses overlap
+    #[serde(default)]
+    pub fail_on_clash: bool,
+}
+
+impl Default for Checks {
+    fn default() -> Self {
+        Self {
+            min_duration_minutes: default_min_duration_minutes(),
+            max_duration_hours: default_max_duration_hours(),
+            fail_on_clash: false,
+        }
+    }
+}
+
+fn default_min_duration_minutes() -> i64 {
+    15
+}
+
+fn default_max_duration_hours() -> i64 {
+    12
+}
+
+/// A session whose duration is outside of the configured bounds, which usually means that its
+/// time was parsed incorrectly.
+#[derive(Debug, Clone)]
+pub struct ImplausibleDuration {
+    pub course_name: String,
+    pub event: CourseEvent,
+    pub duration: Duration,
+}
+
+impl fmt::Display for ImplausibleDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: session {} on {} lasts {} minutes, check the parsed time",
+            self.course_name,
+            self.event.id,
+            format_session(
+                self.event.timespan.start.with_timezone(&Utc),
+                self.event.timespan.end.with_timezone(&Utc)
+            ),
+            self.duration.num_minutes()
+        )
+    }
+}
+
+/// Two sessions of different courses that take place at the same time.
+#[derive(Debug, Clone)]
+pub struct Clash {
+    pub course_name: String,
+    pub event: CourseEvent,
+    pub other_course_name: String,
+    pub other_event: CourseEvent,
+}
+
+impl fmt::Display for Clash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let start = self
+            .event
+            .timespan
+            .start
+            .max(self.other_event.timespan.start);
+        let end = self.event.timespan.end.min(self.other_event.timespan.end);
+        write!(
+            f,
+            "{} ({}) clashes with {} ({}), overlapping {}",
+            self.course_name,
+            format_session(
+                self.event.timespan.start.with_timezone(&Utc),
+                self.event.timespan.end.with_timezone(&Utc)
+            ),
+            self.other_course_name,
+            format_session(
+                self.other_event.timespan.start.with_timezone(&Utc),
+                self.other_event.timespan.end.with_timezone(&Utc)
+            ),
+            format_time_range(start.with_timezone(&Utc), end.with_timezone(&Utc))
+        )
+    }
+}
+
+/// All pairs of overlapping sessions of different `courses`.
+pub fn clashes<'a, I>(courses: I) -> Vec<Clash>
+where
+    I: IntoIterator<Item = &'a Course>,
+{
+    let courses = courses.into_iter().collect::<Vec<_>>();
+    let mut clashes = vec![];
+    for (i, course) in courses.iter().enumerate() {
+        for other_course in courses[i + 1..].iter() {
+            for event in course.events.iter() {
+                for other_event in other_course.events.iter() {
+                    if event.overlaps(other_event) {
+                        clashes.push(Clash {
+                            course_name: course.name.clone(),
+                            event: event.clone(),
+                            other_course_name: other_course.name.clone(),
+                            other_event: other_event.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    clashes.sort_by_key(|clash| clash.event.timespan.start);
+
+    clashes
+}
+
+impl Checks {
+    pub fn implausible_durations(&self, course: &Course) -> Vec<ImplausibleDuration> {
+        let min = Duration::minutes(self.min_duration_minutes);
+        let max = Duration::hours(self.max_duration_hours);
+
+        course
+            .events
+            .iter()
+            .filter_map(|event| {
+                let duration = event.duration();
+                if duration >= min && duration <= max {
+                    return None;
+                }
+                Some(ImplausibleDuration {
+                    course_name: course.name.clone(),
+                    event: event.clone(),
+                    duration,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,181 @@
+//! Coalescing of back-to-back sessions: some courses list one long block as consecutive slots
+//! (e.g. 10:00–12:00 and 12:00–14:00), which clutters the calendar with separate events.
+//!
+//! Sessions are only joined if they are on the same day, adjacent or overlapping, and agree in
+//! everything else that ends up in the calendar (part, group, room, exam), so distinct sessions
+//! that happen to follow each other stay apart. All-day sessions (e.g. block courses) are left as
+//! they are, their spans cover whole days and aren't slots of a longer block.
+
+use crate::{Course, CourseEvent, StdError};
+use timespan::DateTimeSpan;
+
+/// Join the adjacent and overlapping sessions of `course`, returning how many were merged into
+/// the session before them.
+///
+/// The joined session keeps the id of the earliest one, so its UID stays stable.
+pub fn coalesce(course: &mut Course) -> Result<usize, StdError> {
+    let mut events = std::mem::replace(&mut course.events, vec![]);
+    events.sort_by_key(|event| event.timespan.start);
+
+    let mut merged = 0;
+    for event in events {
+        if let Some(previous) = course.events.last_mut() {
+            if joinable(previous, &event) {
+                join(previous, event)?;
+                merged += 1;
+                continue;
+            }
+        }
+        course.events.push(event);
+    }
+    Ok(merged)
+}
+
+fn joinable(previous: &CourseEvent, next: &CourseEvent) -> bool {
+    !previous.all_day
+        && !next.all_day
+        && previous.timespan.start.date() == next.timespan.start.date()
+        && next.timespan.start <= previous.timespan.end
+        && previous.part == next.part
+        && previous.group == next.group
+        && previous.room == next.room
+        && previous.exam == next.exam
+}
+
+fn join(previous: &mut CourseEvent, next: CourseEvent) -> Result<(), StdError> {
+    if next.timespan.end > previous.timespan.end {
+        previous.timespan = DateTimeSpan::new(previous.timespan.start, next.timespan.end)?;
+    }
+    previous.sequence = previous.sequence.max(next.sequence);
+    for lecturer in next.lecturers {
+        if !previous.lecturers.contains(&lecturer) {
+            previous.lecturers.push(lecturer);
+        }
+    }
+    previous.remark = match (previous.remark.take(), next.remark) {
+        (Some(remark), Some(other)) if remark != other => Some(format!("{}; {}", remark, other)),
+        (remark, other) => remark.or(other),
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registration::Registration;
+    use crate::timespan_from_parts;
+
+    fn session(id: &str, day: &str, start: &str, end: &str) -> CourseEvent {
+        CourseEvent {
+            id: id.to_owned(),
+            timespan: timespan_from_parts(day, start, end).unwrap(),
+            sequence: 0,
+            week: None,
+            part: None,
+            remark: None,
+            room: Some("Hörsaal 1a".to_owned()),
+            lecturers: vec![],
+            detail_url: None,
+            exam: false,
+            group: None,
+            summary: None,
+            all_day: false,
+        }
+    }
+
+    fn course(events: Vec<CourseEvent>) -> Course {
+        Course {
+            id: Some("10101".to_owned()),
+            name: "Blockseminar".to_owned(),
+            kind: Some("Seminar".to_owned()),
+            events,
+            cancelled: vec![],
+            url: None,
+            color: None,
+            sws: None,
+            ects: None,
+            modules: vec![],
+            language: None,
+            registration: Registration::default(),
+            warnings: vec![],
+        }
+    }
+
+    fn spans(course: &Course) -> Vec<(String, String)> {
+        course
+            .events
+            .iter()
+            .map(|event| {
+                (
+                    event.timespan.start.format("%d.%m. %H:%M").to_string(),
+                    event.timespan.end.format("%d.%m. %H:%M").to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn span(start: &str, end: &str) -> (String, String) {
+        (start.to_owned(), end.to_owned())
+    }
+
+    #[test]
+    fn joins_adjacent_and_overlapping_sessions() {
+        let mut first = session("500001", "14.10.2019", "10:00", "12:00");
+        first.lecturers.push("Prof. Dr. Müller".to_owned());
+        first.remark = Some("Teil 1".to_owned());
+        let mut second = session("500002", "14.10.2019", "12:00", "14:00");
+        second.lecturers.push("Dr. Schmidt".to_owned());
+        second.remark = Some("Teil 2".to_owned());
+        second.sequence = 2;
+        let third = session("500003", "14.10.2019", "13:00", "15:00");
+        // Listed out of order
+        let mut course = course(vec![third, second, first]);
+
+        assert_eq!(coalesce(&mut course).unwrap(), 2);
+        assert_eq!(spans(&course), vec![span("14.10. 10:00", "14.10. 15:00")]);
+        let joined = &course.events[0];
+        assert_eq!(joined.id, "500001");
+        assert_eq!(joined.sequence, 2);
+        assert_eq!(joined.lecturers, vec!["Prof. Dr. Müller", "Dr. Schmidt"]);
+        assert_eq!(
+            joined.remark.as_ref().map(String::as_str),
+            Some("Teil 1; Teil 2")
+        );
+    }
+
+    #[test]
+    fn keeps_distinct_sessions_apart() {
+        let gap = session("500002", "14.10.2019", "12:15", "14:00");
+        let next_day = session("500003", "15.10.2019", "10:00", "12:00");
+        let mut other_room = session("500004", "15.10.2019", "12:00", "14:00");
+        other_room.room = Some("Seminarraum 2".to_owned());
+        let mut exam = session("500005", "15.10.2019", "14:00", "16:00");
+        exam.room = Some("Seminarraum 2".to_owned());
+        exam.exam = true;
+        let mut course = course(vec![
+            session("500001", "14.10.2019", "10:00", "12:00"),
+            gap,
+            next_day,
+            other_room,
+            exam,
+        ]);
+
+        assert_eq!(coalesce(&mut course).unwrap(), 0);
+        assert_eq!(course.events.len(), 5);
+    }
+
+    #[test]
+    fn leaves_all_day_sessions_alone() {
+        let mut first = session("500001", "14.10.2019", "10:00", "12:00");
+        first.make_all_day().unwrap();
+        let mut second = session("500002", "15.10.2019", "10:00", "12:00");
+        second.make_all_day().unwrap();
+        let timed = session("500003", "14.10.2019", "10:00", "12:00");
+        let mut course = course(vec![first, second, timed]);
+
+        assert_eq!(coalesce(&mut course).unwrap(), 0);
+        assert_eq!(course.events.len(), 3);
+        let all_day = course.events.iter().filter(|event| event.all_day);
+        assert_eq!(all_day.count(), 2);
+    }
+}
@@ -0,0 +1,70 @@
+//! Colors of courses in calendars.
+//!
+//! Courses carry their color as `#rrggbb`. Apple clients read that from
+//! `X-APPLE-CALENDAR-COLOR`, while the `COLOR` property of RFC 7986 takes a CSS3 color name, for
+//! which the closest name of the palette is used.
+
+use crate::Error;
+use std::ops::Range;
+
+/// Colors assigned to courses without a configured color, as CSS3 name and `#rrggbb`
+const PALETTE: &[(&str, &str)] = &[
+    ("steelblue", "#4682b4"),
+    ("seagreen", "#2e8b57"),
+    ("darkorange", "#ff8c00"),
+    ("mediumpurple", "#9370db"),
+    ("crimson", "#dc143c"),
+    ("teal", "#008080"),
+    ("goldenrod", "#daa520"),
+    ("hotpink", "#ff69b4"),
+    ("sienna", "#a0522d"),
+    ("slategray", "#708090"),
+];
+
+/// Color of the course at `index` in the config, cycling through the palette.
+pub fn auto(index: usize) -> String {
+    PALETTE[index % PALETTE.len()].1.to_owned()
+}
+
+/// `color` as `#rrggbb`, given either like that, as `#rgb` or as a name of the palette.
+pub fn parse(color: &str) -> Result<String, Error> {
+    let invalid = || Error::InvalidColor {
+        color: color.to_owned(),
+    };
+
+    let color = color.trim().to_lowercase();
+    if let Some((_, hex)) = PALETTE.iter().find(|(name, _)| *name == color) {
+        return Ok((*hex).to_owned());
+    }
+    if !color.starts_with('#') || !color[1..].chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    match color.len() {
+        7 => Ok(color),
+        4 => Ok(color[1..].chars().fold("#".to_owned(), |mut hex, c| {
+            hex.push(c);
+            hex.push(c);
+            hex
+        })),
+        _ => Err(invalid()),
+    }
+}
+
+/// The palette's CSS3 color name closest to the `#rrggbb` color `hex`.
+pub fn css_name(hex: &str) -> &'static str {
+    let color = rgb(hex).unwrap_or((0, 0, 0));
+    PALETTE
+        .iter()
+        .min_by_key(|(_, palette_hex)| {
+            let other = rgb(palette_hex).unwrap_or((0, 0, 0));
+            let distance = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+            distance(color.0, other.0) + distance(color.1, other.1) + distance(color.2, other.2)
+        })
+        .map(|(name, _)| *name)
+        .unwrap()
+}
+
+fn rgb(hex: &str) -> Option<(u8, u8, u8)> {
+    let channel = |range: Range<usize>| u8::from_str_radix(hex.get(range)?, 16).ok();
+    Some((channel(1..3)?, channel(3..5)?, channel(5..7)?))
+}
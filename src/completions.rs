@@ -0,0 +1,69 @@
+//! Completion of configured courses in the shell completion scripts generated by clap.
+//!
+//! The scripts call the hidden `complete-courses` subcommand when completing `--course`, which
+//! lists the ids of the configured courses with their aliases as descriptions.
+
+use crate::config::Config;
+
+/// Hidden subcommand listing the configured courses
+pub const COURSES_SUBCOMMAND: &str = "complete-courses";
+
+/// The configured courses as `{id}\t{alias}` lines, or only ids for courses without alias.
+pub fn course_lines(config: &Config) -> Vec<String> {
+    config
+        .courses
+        .iter()
+        .map(|course| match &course.alias {
+            Some(alias) => format!("{}\t{}", course.id, alias),
+            None => course.id.clone(),
+        })
+        .collect()
+}
+
+/// `script` generated by clap for `shell`, extended to complete `--course` with the configured
+/// courses.
+pub fn with_courses(shell: &str, script: &str) -> String {
+    match shell {
+        "bash" => format!("{}\n{}", script, BASH),
+        // The script ends by calling the completion function, which may be autoloaded from
+        // `fpath`, so the wrapper has to be called there instead
+        "zsh" => {
+            let call = "_fu-vv-cal \"$@\"";
+            match script.rfind(call) {
+                Some(index) => format!("{}{}", &script[..index], ZSH),
+                None => format!("{}\n{}", script, ZSH),
+            }
+        }
+        "fish" => format!("{}\n{}", script, FISH),
+        _ => script.to_owned(),
+    }
+}
+
+const BASH: &str = r#"_fu_vv_cal_with_courses() {
+    if [[ "${COMP_WORDS[COMP_CWORD-1]}" == "--course" ]]; then
+        local courses
+        courses="$(fu-vv-cal complete-courses 2>/dev/null | cut -f1)"
+        COMPREPLY=($(compgen -W "${courses}" -- "${COMP_WORDS[COMP_CWORD]}"))
+    else
+        _fu-vv-cal "$@"
+    fi
+}
+complete -F _fu_vv_cal_with_courses -o bashdefault -o default fu-vv-cal
+"#;
+
+const ZSH: &str = r#"_fu_vv_cal_with_courses() {
+    if [[ "${words[CURRENT-1]}" == "--course" ]]; then
+        local -a courses
+        courses=(${(f)"$(fu-vv-cal complete-courses 2>/dev/null | tr '\t' ':')"})
+        _describe 'course' courses
+    else
+        _fu-vv-cal "$@"
+    fi
+}
+
+_fu_vv_cal_with_courses "$@"
+"#;
+
+const FISH: &str = r#"complete -c fu-vv-cal -l course -x \
+    -a "(fu-vv-cal complete-courses 2>/dev/null)"
+"#;
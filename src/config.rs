@@ -0,0 +1,844 @@
+use crate::appearance::Appearance;
+use crate::campus::Building;
+use crate::checks::Checks;
+use crate::fetch::{self, Locale};
+use crate::hierarchy::{self, ChildMode};
+use crate::holidays::LectureFreeMode;
+use crate::hooks::Hooks;
+use crate::lv;
+use crate::options::CalendarOptions;
+use crate::refresh;
+use crate::robots;
+use crate::rules::{Rule, Rules};
+use crate::semester::Semester;
+use crate::source;
+use crate::timezone;
+use crate::uid::UidScheme;
+use crate::{Error, RequestedCourse, StdError};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Name of merged calendars if the config doesn't set `merged_name`
+pub const DEFAULT_MERGED_NAME: &str = "fu-vv-cal";
+
+/// Contents of the `fu-vv-cal.toml` configuration file.
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    /// Default semester (`sm` parameter of the VV) for courses that don't specify one
+    pub semester: Option<String>,
+    /// File that all courses are merged into, one per semester if courses of several semesters
+    /// are configured
+    pub merged_output: Option<String>,
+    /// Name of the merged calendar in calendar clients
+    #[serde(default = "default_merged_name")]
+    pub merged_name: String,
+    /// JSON feed of all sessions for web frontends, see `feed`
+    pub json_output: Option<String>,
+    /// File the exported sessions are tracked in, so changed sessions get a new `SEQUENCE` and
+    /// removed ones are exported as cancelled
+    pub state: Option<String>,
+    /// SQLite database every fetched course is recorded in, see `store`
+    pub store: Option<String>,
+    /// First day of the lecture period, from which the week numbers of sessions are counted.
+    /// Defaults to the lecture start of the semester if it is known (see `semester`), or the week
+    /// of the earliest session.
+    pub lecture_start: Option<NaiveDate>,
+    #[serde(default, rename = "course")]
+    pub courses: Vec<CourseConfig>,
+    /// Language version of the VV to scrape, `de` or `en`
+    #[serde(default)]
+    pub locale: Locale,
+    /// Minimum time between two requests to the VV in milliseconds
+    #[serde(default = "default_request_delay_ms")]
+    pub request_delay_ms: u64,
+    /// How to reach whoever runs the exports, e.g. an email address, sent in the `User-Agent` of
+    /// all requests so the admins of the VV can get in touch
+    pub contact: Option<String>,
+    /// Request pages of the VV even if its robots.txt disallows them, see `robots`
+    #[serde(default)]
+    pub ignore_robots: bool,
+    /// Skip sessions that can't be parsed with a warning instead of failing, see `lenient`
+    #[serde(default)]
+    pub lenient: bool,
+    /// Export every session as just "Busy", without anything about the course, see `busy`
+    #[serde(default)]
+    pub busy_only: bool,
+    /// Time allowed for connecting to the VV in seconds
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// Time allowed for a whole request to the VV in seconds, after which it is retried
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// Time between exports with `--watch`, e.g. "6h", unless `--interval` is given
+    pub watch_interval: Option<String>,
+    /// How often subscribed clients should reload the calendars in minutes, 0 to leave it to them
+    #[serde(default = "default_refresh_interval_mins")]
+    pub refresh_interval_mins: u64,
+    /// Export every course for all semesters with known VV ids (see `semester`), in addition to
+    /// its configured ones
+    #[serde(default)]
+    pub all_semesters: bool,
+    /// Cookie sent along with the requests to the VV, e.g. `JSESSIONID=…` copied from a browser,
+    /// for course pages only shown to members of the FU. See `fetch::set_session_cookie`.
+    pub session_cookie: Option<String>,
+    /// Add the registration deadline of every course as an all-day event, see
+    /// `CalendarOptions::registration_reminders`
+    #[serde(default)]
+    pub registration_reminders: bool,
+    /// Mark the first and the last session of every course in its summary, see `first_last`
+    #[serde(default)]
+    pub mark_first_last: bool,
+    /// Join back-to-back sessions of a course into one event, see `coalesce`
+    #[serde(default)]
+    pub coalesce_sessions: bool,
+    /// Add the semester dates as all-day events to combined calendars, see `milestones`
+    #[serde(default)]
+    pub milestones: bool,
+    /// Minutes of the buffer events for the way between buildings, 0 for none, see `travel`
+    #[serde(default)]
+    pub travel_buffer_mins: u32,
+    /// Update existing calendar files instead of overwriting them, see `update`
+    #[serde(default)]
+    pub update_in_place: bool,
+    /// IANA name of the zone the exported times are given in, see `timezone`
+    pub timezone: Option<String>,
+    /// Write the times of iCalendar files as floating local times, see `timezone`
+    #[serde(default)]
+    pub floating_times: bool,
+    /// What happens to sessions on public holidays and in lecture breaks, see `holidays`
+    #[serde(default)]
+    pub lecture_free: LectureFreeMode,
+    /// Rules selecting the sessions of all courses
+    #[serde(default)]
+    pub rules: Rules,
+    /// How the generated events appear in calendars
+    #[serde(default)]
+    pub appearance: Appearance,
+    /// UIDs of the generated events, `stable` or `legacy` for subscriptions of older versions
+    #[serde(default)]
+    pub uid_scheme: UidScheme,
+    /// Also export events under their legacy UID as cancelled, when switching to `stable`
+    #[serde(default)]
+    pub migrate_uids: bool,
+    /// Buildings rooms are located in, in addition to the built-in ones of `campus`
+    #[serde(default, rename = "building")]
+    pub buildings: Vec<Building>,
+    /// Bounds of the sanity checks of parsed sessions
+    #[serde(default)]
+    pub checks: Checks,
+    /// External commands run after exporting
+    #[serde(default)]
+    pub hooks: Hooks,
+    pub enrollment: Option<EnrollmentConfig>,
+    pub google: Option<GoogleConfig>,
+    pub caldav: Option<CalDavConfig>,
+    pub outlook: Option<OutlookConfig>,
+    /// Webhooks notified about schedule changes, requires `state`
+    #[serde(default, rename = "webhook")]
+    pub webhooks: Vec<WebhookConfig>,
+    pub telemetry: Option<TelemetryConfig>,
+    /// Access control of `serve`
+    pub server: Option<ServerConfig>,
+    /// Options of the exported calendars, built from the settings above by `load`
+    #[serde(skip)]
+    pub calendar: CalendarOptions,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CourseConfig {
+    /// ID of the course, or the URL of its VV page which may include the semester, see
+    /// `fetch::parse_course_reference`. Replaced by the plain id when the config is loaded.
+    /// LV numbers like "21720a" are replaced by `resolve_lv_numbers`.
+    pub id: String,
+    /// Course catalogue the course is listed in, `fu` by default
+    #[serde(default = "default_source")]
+    pub source: String,
+    pub semester: Option<String>,
+    /// Further semesters the course is exported for, e.g. to archive past teaching schedules
+    #[serde(default)]
+    pub semesters: Vec<String>,
+    /// File the calendar of this course is written to, `{alias}.ics` by default if there is an
+    /// alias
+    pub output: Option<String>,
+    /// Short name used instead of the course name from the VV, e.g. "OC1"
+    pub alias: Option<String>,
+    /// Exercise group to export on pages listing several groups, e.g. "Gruppe B"
+    pub group: Option<String>,
+    /// Color of the course in calendars, `#rrggbb` or a name of the palette of `colors`
+    pub color: Option<String>,
+    /// Days whose sessions of this course are skipped
+    #[serde(default)]
+    pub exclude_dates: Vec<NaiveDate>,
+    /// Minutes the sessions start later than the VV states, negative for earlier, see `offsets`
+    #[serde(default)]
+    pub start_offset_mins: i64,
+    /// Minutes the sessions end later than the VV states, negative for earlier
+    #[serde(default)]
+    pub end_offset_mins: i64,
+    /// Export all sessions as all-day events, e.g. for block courses (Blockseminare)
+    #[serde(default)]
+    pub all_day: bool,
+    /// Export the courses linked from this course's page instead, e.g. its Vorlesung and Übung,
+    /// see `hierarchy`
+    pub children: Option<ChildMode>,
+}
+
+impl CourseConfig {
+    /// File the calendar of this course is written to, with `extension` for the default name.
+    pub fn output(&self, extension: &str) -> Option<String> {
+        self.output.clone().or_else(|| {
+            let alias = self.alias.as_ref()?;
+            Some(format!("{}.{}", alias.replace('/', "-"), extension))
+        })
+    }
+
+    /// Config of the part `id` of this course, with `label` appended to its output and alias.
+    fn child(&self, id: String, label: &str) -> CourseConfig {
+        let slug = label
+            .to_lowercase()
+            .replace(|c: char| !c.is_alphanumeric(), "-");
+        let output = self.output.as_ref().map(|output| match output.rfind('.') {
+            Some(dot) if !output[dot..].contains('/') => {
+                format!("{}-{}{}", &output[..dot], slug, &output[dot..])
+            }
+            _ => format!("{}-{}", output, slug),
+        });
+        CourseConfig {
+            id,
+            output,
+            alias: self
+                .alias
+                .as_ref()
+                .map(|alias| format!("{} {}", alias, label)),
+            children: None,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GoogleConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default = "default_google_calendar")]
+    pub calendar: String,
+    /// File the OAuth tokens are persisted in between runs
+    #[serde(default = "default_google_token_file")]
+    pub token_file: String,
+}
+
+/// Sign-in to read the enrolled courses and restricted course pages, see `enrollment`.
+#[derive(Debug, Deserialize)]
+pub struct EnrollmentConfig {
+    /// Page linking the enrolled courses, e.g. the timetable (Stundenplan) in Campus Management
+    pub timetable_url: Option<String>,
+    /// Page of the VV that asks to sign in, visited to get a session for course pages only shown
+    /// to members of the FU. Not needed if `session_cookie` is set.
+    pub vv_login_url: Option<String>,
+    /// Name of the FU account, `FU_VV_CAL_CM_USERNAME` takes precedence
+    pub username: String,
+}
+
+/// Who may use `serve`, see `server::auth`. Without tokens and signing key it is open to anyone.
+#[derive(Debug, Deserialize)]
+pub struct ServerConfig {
+    /// Tokens granting access, sent as bearer token, CalDAV password or `token` query parameter
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    /// Secret for URLs signed with `sign-url`, which grant access without a token
+    pub signing_key: Option<String>,
+    /// PEM certificate chain and private key to serve HTTPS with, see `server::tls`
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CalDavConfig {
+    /// URL of the calendar collection, e.g.
+    /// `https://cloud.example.org/remote.php/dav/calendars/me/lectures/`
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OutlookConfig {
+    /// Application (client) id of an app registration allowing public client flows
+    pub client_id: String,
+    /// Azure AD tenant, `common` for both personal and work or school accounts
+    #[serde(default = "default_outlook_tenant")]
+    pub tenant: String,
+    /// Calendar id, defaults to the user's default calendar
+    pub calendar: Option<String>,
+    /// File the OAuth tokens are persisted in between runs
+    #[serde(default = "default_outlook_token_file")]
+    pub token_file: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+}
+
+/// Anonymous reporting of parse success rates, only active with `opt_in = true`.
+#[derive(Debug, Deserialize)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub opt_in: bool,
+    pub endpoint: String,
+}
+
+/// Prefix of the environment variables overriding the config file
+const ENV_PREFIX: &str = "FU_VV_CAL_";
+
+/// Top-level settings that can be overridden by `FU_VV_CAL_{name}`, with whether they are numbers
+const ENV_SETTINGS: &[(&str, &str, bool)] = &[
+    ("SEMESTER", "semester", false),
+    ("OUTPUT", "merged_output", false),
+    ("MERGED_NAME", "merged_name", false),
+    ("JSON_OUTPUT", "json_output", false),
+    ("STATE", "state", false),
+    ("STORE", "store", false),
+    ("LECTURE_START", "lecture_start", false),
+    ("LOCALE", "locale", false),
+    ("REQUEST_DELAY_MS", "request_delay_ms", true),
+    ("CONNECT_TIMEOUT_SECS", "connect_timeout_secs", true),
+    ("REQUEST_TIMEOUT_SECS", "request_timeout_secs", true),
+    ("REFRESH_INTERVAL_MINS", "refresh_interval_mins", true),
+    ("SESSION_COOKIE", "session_cookie", false),
+    ("CONTACT", "contact", false),
+];
+
+fn default_merged_name() -> String {
+    DEFAULT_MERGED_NAME.to_owned()
+}
+
+fn default_request_delay_ms() -> u64 {
+    fetch::DEFAULT_REQUEST_DELAY.as_millis() as u64
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    fetch::DEFAULT_CONNECT_TIMEOUT.as_secs()
+}
+
+fn default_request_timeout_secs() -> u64 {
+    fetch::DEFAULT_REQUEST_TIMEOUT.as_secs()
+}
+
+fn default_refresh_interval_mins() -> u64 {
+    refresh::DEFAULT_REFRESH_INTERVAL.as_secs() / 60
+}
+
+fn default_source() -> String {
+    "fu".to_owned()
+}
+
+fn default_google_calendar() -> String {
+    "primary".to_owned()
+}
+
+fn default_google_token_file() -> String {
+    "google_token.json".to_owned()
+}
+
+fn default_outlook_tenant() -> String {
+    "common".to_owned()
+}
+
+fn default_outlook_token_file() -> String {
+    "outlook_token.json".to_owned()
+}
+
+impl Config {
+    /// Load the config file and apply its process-wide settings.
+    ///
+    /// `FU_VV_CAL_*` environment variables override the file, which may be missing if they are set.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, StdError> {
+        Self::load_with_course_list(path, None)
+    }
+
+    /// Like `load`, with the courses replaced by `course_list` if given, see
+    /// `parse_course_list`. The file may be missing then.
+    pub fn load_with_course_list<P: AsRef<Path>>(
+        path: P,
+        course_list: Option<&str>,
+    ) -> Result<Self, StdError> {
+        let path = path.as_ref();
+        let mut overrides = env_overrides()?;
+        if let Some(course_list) = course_list {
+            overrides.push(("course".to_owned(), parse_course_list(course_list)?));
+        }
+        let contents = if overrides.is_empty() || path.exists() {
+            std::fs::read_to_string(path)?
+        } else {
+            String::new()
+        };
+        let mut value: toml::Value = toml::from_str(&contents)?;
+        if let toml::Value::Table(table) = &mut value {
+            for (key, value) in overrides {
+                table.insert(key, value);
+            }
+        }
+        let mut config: Self = value.try_into()?;
+        for course in config.courses.iter_mut() {
+            let (id, semester) = fetch::parse_course_reference(&course.id)?;
+            course.id = id;
+            if course.semester.is_none() {
+                course.semester = semester;
+            }
+        }
+        fetch::set_request_delay(Duration::from_millis(config.request_delay_ms));
+        robots::set_respected(!config.ignore_robots);
+        fetch::set_contact(config.contact.clone());
+        fetch::set_session_cookie(config.session_cookie.clone());
+        config.calendar = CalendarOptions {
+            uid_scheme: config.uid_scheme,
+            migrate_uids: config.migrate_uids,
+            appearance: config.appearance.clone(),
+            busy_only: config.busy_only,
+            timezone: config
+                .timezone
+                .as_ref()
+                .map(|name| timezone::parse(name))
+                .transpose()?,
+            floating: config.floating_times,
+            registration_reminders: config.registration_reminders,
+            refresh_interval: Duration::from_secs(config.refresh_interval_mins * 60),
+            buildings: config.buildings.clone(),
+        };
+        fetch::set_timeouts(
+            Duration::from_secs(config.connect_timeout_secs),
+            Duration::from_secs(config.request_timeout_secs),
+        );
+        Ok(config)
+    }
+
+    /// Add the courses with `ids` that aren't configured yet, for the default semester.
+    pub fn add_courses(&mut self, ids: Vec<String>) {
+        for id in ids {
+            if self.courses.iter().any(|course| course.id == id) {
+                continue;
+            }
+            self.courses.push(CourseConfig {
+                id,
+                source: default_source(),
+                semester: None,
+                semesters: vec![],
+                output: None,
+                alias: None,
+                group: None,
+                color: None,
+                exclude_dates: vec![],
+                start_offset_mins: 0,
+                end_offset_mins: 0,
+                all_day: false,
+                children: None,
+            });
+        }
+    }
+
+    /// Replace the LV numbers given as course ids by the VV ids of the courses, see `lv`.
+    pub async fn resolve_lv_numbers(&mut self) -> Result<(), StdError> {
+        let first_semesters = self
+            .courses
+            .iter()
+            .map(|course| self.semesters_of(course).into_iter().next())
+            .collect::<Vec<_>>();
+        for (course, semester) in self.courses.iter_mut().zip(first_semesters) {
+            if lv::lv_number(&course.id).is_none() {
+                continue;
+            }
+            let semester = semester.ok_or_else(|| Error::MissingSemester {
+                id: course.id.clone(),
+            })?;
+            let id = lv::resolve_id(&course.id, &semester, self.locale).await?;
+            info!(
+                number = course.id.as_str(),
+                id = id.as_str(),
+                "resolved LV number"
+            );
+            course.id = id;
+        }
+        Ok(())
+    }
+
+    /// Replace the courses with `children = "separate"` by the courses linked from their pages,
+    /// labelled with their type, e.g. the alias "OC1 Übung" for the Übung of "OC1".
+    pub async fn expand_children(&mut self) -> Result<(), StdError> {
+        let courses = std::mem::replace(&mut self.courses, vec![]);
+        for course in courses {
+            if course.children != Some(ChildMode::Separate) {
+                self.courses.push(course);
+                continue;
+            }
+            let semester = self
+                .semesters_of(&course)
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::MissingSemester {
+                    id: course.id.clone(),
+                })?;
+            let source = source::by_name(&course.source, self.locale, self.lenient)?;
+            let mut parts = vec![];
+            for id in hierarchy::child_ids(&*source, &course.id, &semester).await? {
+                let child = source.get_course(&id, &semester).await?;
+                parts.push((id, child.kind.unwrap_or(child.name)));
+            }
+            if parts.is_empty() {
+                warn!(
+                    course = course.id.as_str(),
+                    "page links no other courses, exporting the course itself"
+                );
+                self.courses.push(course);
+                continue;
+            }
+            for (id, part) in parts.iter() {
+                // Several parts of one type, e.g. exercise groups, are told apart by their ids
+                let label = if parts.iter().filter(|(_, other)| other == part).count() > 1 {
+                    format!("{} {}", part, id)
+                } else {
+                    part.clone()
+                };
+                info!(
+                    parent = course.id.as_str(),
+                    id = id.as_str(),
+                    part = label.as_str(),
+                    "found child course"
+                );
+                self.courses.push(course.child(id.clone(), &label));
+            }
+        }
+        Ok(())
+    }
+
+    /// Semesters `course` is exported for, its own or the default one first.
+    pub fn semesters_of(&self, course: &CourseConfig) -> Vec<String> {
+        let mut semesters = course
+            .semester
+            .as_ref()
+            .or_else(|| self.semester.as_ref())
+            .into_iter()
+            .chain(course.semesters.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        if self.all_semesters {
+            semesters.extend(
+                Semester::all()
+                    .into_iter()
+                    .filter_map(|semester| semester.vv_id)
+                    .map(str::to_owned),
+            );
+        }
+        let mut unique = vec![];
+        for semester in semesters {
+            if !unique.contains(&semester) {
+                unique.push(semester);
+            }
+        }
+        unique
+    }
+
+    /// Key of a course in the state file: its id, followed by the semester for the further
+    /// semesters of `semesters_of`, so these are tracked separately.
+    pub fn state_key(&self, id: &str, semester: &str, course: &CourseConfig) -> String {
+        match self.semesters_of(course).first() {
+            Some(first) if first != semester => format!("{}@{}", id, semester),
+            _ => id.to_owned(),
+        }
+    }
+
+    /// All configured courses, once per semester they are exported for, see `semesters_of`.
+    pub fn requested_courses(&self) -> Result<Vec<(RequestedCourse, &CourseConfig)>, StdError> {
+        let mut requested_courses = vec![];
+        for course in self.courses.iter() {
+            let semesters = self.semesters_of(course);
+            if semesters.is_empty() {
+                return Err(Error::MissingSemester {
+                    id: course.id.clone(),
+                }
+                .into());
+            }
+            for semester in semesters {
+                let mut rules = self.rules.clone();
+                rules
+                    .exclude
+                    .extend(course.exclude_dates.iter().map(|date| Rule {
+                        from: Some(*date),
+                        until: Some(*date),
+                        ..Rule::default()
+                    }));
+                let mut source = source::by_name(&course.source, self.locale, self.lenient)?;
+                if course.children == Some(ChildMode::Merged) {
+                    source = Arc::new(hierarchy::Merged::new(source));
+                }
+                let requested = RequestedCourse::new(course.id.clone(), semester)
+                    .with_rules(rules)
+                    .with_source(source)
+                    .with_options(self.calendar.clone());
+                requested_courses.push((requested, course));
+            }
+        }
+        Ok(requested_courses)
+    }
+}
+
+/// Whether any `FU_VV_CAL_*` variable configures the export, so no config file is needed.
+pub fn configured_by_env() -> bool {
+    std::env::vars().any(|(name, _)| {
+        name == format!("{}COURSES", ENV_PREFIX)
+            || ENV_SETTINGS
+                .iter()
+                .any(|(suffix, _, _)| name == format!("{}{}", ENV_PREFIX, suffix))
+    })
+}
+
+/// Courses given one per line as `id[,semester][,alias]`, e.g. for `--ids-file`.
+///
+/// Empty lines and lines starting with `#` are skipped. Courses without semester get the default
+/// one of the config.
+fn parse_course_list(list: &str) -> Result<toml::Value, Error> {
+    let mut courses = vec![];
+    for (index, line) in list.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.splitn(3, ',').map(str::trim);
+        let id = fields.next().unwrap_or_default();
+        if id.is_empty() {
+            return Err(Error::InvalidCourseList {
+                line: index + 1,
+                text: line.to_owned(),
+            });
+        }
+        let mut course = toml::value::Table::new();
+        course.insert("id".to_owned(), toml::Value::String(id.to_owned()));
+        for key in &["semester", "alias"] {
+            match fields.next() {
+                Some(value) if !value.is_empty() => {
+                    course.insert((*key).to_owned(), toml::Value::String(value.to_owned()));
+                }
+                _ => {}
+            }
+        }
+        courses.push(toml::Value::Table(course));
+    }
+    Ok(toml::Value::Array(courses))
+}
+
+/// Settings of the config file overridden by environment variables.
+///
+/// `FU_VV_CAL_COURSES` replaces the configured courses by a comma separated list of ids or VV
+/// URLs, each optionally followed by an alias, e.g. `524870:OC1,524871:OC1 Übung`.
+fn env_overrides() -> Result<Vec<(String, toml::Value)>, StdError> {
+    overrides_from(|name| std::env::var(name).ok())
+}
+
+/// Like `env_overrides`, with the variables looked up by `var`.
+fn overrides_from<F: Fn(&str) -> Option<String>>(
+    var: F,
+) -> Result<Vec<(String, toml::Value)>, StdError> {
+    let mut overrides = vec![];
+    for (suffix, key, is_number) in ENV_SETTINGS.iter() {
+        let name = format!("{}{}", ENV_PREFIX, suffix);
+        let value = match var(&name) {
+            Some(value) => value,
+            None => continue,
+        };
+        let value = if *is_number {
+            let number = value
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| Error::InvalidEnvVar {
+                    name: name.clone(),
+                    value: value.clone(),
+                })?;
+            toml::Value::Integer(number)
+        } else {
+            toml::Value::String(value)
+        };
+        overrides.push(((*key).to_owned(), value));
+    }
+
+    if let Some(list) = var(&format!("{}COURSES", ENV_PREFIX)) {
+        let courses = list
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                // The alias follows the first colon that isn't part of a URL scheme or `lv:`
+                let after_scheme = if entry.starts_with(lv::LV_PREFIX) {
+                    lv::LV_PREFIX.len()
+                } else {
+                    entry.find("://").map_or(0, |index| index + 3)
+                };
+                let (id, alias) = match entry[after_scheme..].find(':') {
+                    Some(index) => {
+                        let (id, alias) = entry.split_at(after_scheme + index);
+                        (id, Some(&alias[1..]))
+                    }
+                    None => (entry, None),
+                };
+                let mut course = toml::value::Table::new();
+                course.insert("id".to_owned(), toml::Value::String(id.trim().to_owned()));
+                if let Some(alias) = alias {
+                    let alias = alias.trim().to_owned();
+                    course.insert("alias".to_owned(), toml::Value::String(alias));
+                }
+                toml::Value::Table(course)
+            })
+            .collect();
+        overrides.push(("course".to_owned(), toml::Value::Array(courses)));
+    }
+
+    Ok(overrides)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Overrides of the variables `vars`, given as (suffix, value) pairs.
+    fn overrides(vars: &[(&str, &str)]) -> Result<Vec<(String, toml::Value)>, StdError> {
+        let vars = vars
+            .iter()
+            .map(|(suffix, value)| (format!("{}{}", ENV_PREFIX, suffix), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        overrides_from(|name| vars.get(name).cloned())
+    }
+
+    /// The config file `contents` with `overrides` applied, as `Config::load` does.
+    fn config(contents: &str, overrides: Vec<(String, toml::Value)>) -> Config {
+        let mut value: toml::Value = toml::from_str(contents).unwrap();
+        if let toml::Value::Table(table) = &mut value {
+            for (key, value) in overrides {
+                table.insert(key, value);
+            }
+        }
+        value.try_into().unwrap()
+    }
+
+    const FILE: &str = "semester = \"498562\"\n\
+                        request_delay_ms = 2000\n\
+                        [[course]]\n\
+                        id = \"524870\"\n";
+
+    #[test]
+    fn no_overrides_without_variables() {
+        assert!(overrides(&[]).unwrap().is_empty());
+        let config = config(FILE, vec![]);
+        assert_eq!(config.semester.as_ref().map(String::as_str), Some("498562"));
+        assert_eq!(config.request_delay_ms, 2000);
+    }
+
+    #[test]
+    fn variables_override_the_file() {
+        let overrides = overrides(&[
+            ("SEMESTER", "518000"),
+            ("REQUEST_DELAY_MS", " 500 "),
+            ("OUTPUT", "out/merged.ics"),
+        ])
+        .unwrap();
+        let config = config(FILE, overrides);
+        assert_eq!(config.semester.as_ref().map(String::as_str), Some("518000"));
+        assert_eq!(config.request_delay_ms, 500);
+        assert_eq!(
+            config.merged_output.as_ref().map(String::as_str),
+            Some("out/merged.ics")
+        );
+        assert_eq!(config.courses[0].id, "524870");
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        match overrides(&[("REQUEST_TIMEOUT_SECS", "30s")]) {
+            Err(err) => assert_eq!(
+                err.downcast_ref::<Error>().map(ToString::to_string),
+                Some(
+                    Error::InvalidEnvVar {
+                        name: "FU_VV_CAL_REQUEST_TIMEOUT_SECS".to_owned(),
+                        value: "30s".to_owned(),
+                    }
+                    .to_string()
+                )
+            ),
+            Ok(_) => panic!("30s accepted as a number of seconds"),
+        }
+    }
+
+    #[test]
+    fn courses_variable_replaces_the_courses() {
+        let overrides = overrides(&[(
+            "COURSES",
+            "525101, 524870:OC1,https://www.fu-berlin.de/vv/de/lv/524871?sm=498562:OC1 Übung,,",
+        )])
+        .unwrap();
+        let config = config(FILE, overrides);
+        let courses = config
+            .courses
+            .iter()
+            .map(|course| {
+                (
+                    course.id.as_str(),
+                    course.alias.as_ref().map(String::as_str),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            courses,
+            vec![
+                ("525101", None),
+                ("524870", Some("OC1")),
+                (
+                    "https://www.fu-berlin.de/vv/de/lv/524871?sm=498562",
+                    Some("OC1 Übung")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_course_lists() {
+        let list = "# Wintersemester\n\
+                    524870\n\
+                    \n\
+                    524871, 498562\n\
+                    524872,,OC1 Übung\n\
+                    524873,498562,OC2\n";
+        let courses = match parse_course_list(list).unwrap() {
+            toml::Value::Array(courses) => courses,
+            other => panic!("not an array: {}", other),
+        };
+        let courses = courses
+            .into_iter()
+            .map(|course| course.try_into::<CourseConfig>().unwrap())
+            .map(|course| (course.id, course.semester, course.alias))
+            .collect::<Vec<_>>();
+        let owned = |text: &str| Some(text.to_owned());
+        assert_eq!(
+            courses,
+            vec![
+                ("524870".to_owned(), None, None),
+                ("524871".to_owned(), owned("498562"), None),
+                ("524872".to_owned(), None, owned("OC1 Übung")),
+                ("524873".to_owned(), owned("498562"), owned("OC2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_course_lists_without_ids() {
+        match parse_course_list("524870\n , 498562\n") {
+            Err(Error::InvalidCourseList { line, text }) => {
+                assert_eq!(line, 2);
+                assert_eq!(text, ", 498562");
+            }
+            other => panic!("expected InvalidCourseList, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Detail pages of single sessions, which list the room, lecturers and comments that the course
+//! page leaves out.
+//!
+//! Fetching them costs one request per session, so it's opt-in (`--details`).
+
+use crate::fetch;
+use crate::{Course, Error, StdError};
+use select::document::Document;
+use select::predicate::Name;
+
+/// Information from the detail page of a session.
+#[derive(Debug, Clone, Default)]
+pub struct SessionDetails {
+    pub room: Option<String>,
+    pub lecturers: Vec<String>,
+    pub comment: Option<String>,
+}
+
+/// Fetch the detail page of every session of `course` that links one, and add its information.
+pub async fn enrich(course: &mut Course) -> Result<(), StdError> {
+    let base = course.url.clone();
+    for event in course.events.iter_mut() {
+        let detail_url = match &event.detail_url {
+            Some(detail_url) => detail_url,
+            None => continue,
+        };
+        let url = match &base {
+            Some(base) => fetch::resolve_url(base, detail_url)?,
+            None => detail_url.clone(),
+        };
+
+        let (status, html) = fetch::get(&url).await?;
+        if !status.is_success() {
+            return Err(Error::HttpRequestError {
+                url,
+                status: status.as_u16(),
+            }
+            .into());
+        }
+        let details = from_document(&Document::from(html.as_str()));
+
+        if details.room.is_some() {
+            event.room = details.room;
+        }
+        if !details.lecturers.is_empty() {
+            event.lecturers = details.lecturers;
+        }
+        if event.remark.is_none() {
+            event.remark = details.comment;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a detail page, which lists its information as labelled table rows or definitions.
+pub fn from_document(document: &Document) -> SessionDetails {
+    let mut details = SessionDetails::default();
+    for (label, value) in labelled_values(document) {
+        let label = label.to_lowercase();
+        if value.is_empty() {
+            continue;
+        }
+        if label.starts_with("raum") || label.starts_with("room") || label.starts_with("ort") {
+            details.room = Some(value);
+        } else if label.starts_with("dozent")
+            || label.starts_with("lehrende")
+            || label.starts_with("lecturer")
+            || label.starts_with("instructor")
+        {
+            details.lecturers = value
+                .split(|c| c == ',' || c == ';')
+                .map(|lecturer| lecturer.trim().to_owned())
+                .filter(|lecturer| !lecturer.is_empty())
+                .collect();
+        } else if label.starts_with("bemerkung")
+            || label.starts_with("kommentar")
+            || label.starts_with("comment")
+            || label.starts_with("remark")
+        {
+            details.comment = Some(value);
+        }
+    }
+
+    details
+}
+
+/// `th`/`td` pairs of table rows and `dt`/`dd` pairs of definition lists.
+fn labelled_values(document: &Document) -> Vec<(String, String)> {
+    let normalize = |text: String| {
+        text.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .trim_end_matches(':')
+            .to_owned()
+    };
+
+    let mut values = vec![];
+    for row in document.find(Name("tr")) {
+        let label = row.find(Name("th")).next();
+        let value = row.find(Name("td")).next();
+        if let (Some(label), Some(value)) = (label, value) {
+            values.push((normalize(label.text()), normalize(value.text())));
+        }
+    }
+    for term in document.find(Name("dt")) {
+        let mut sibling = term.next();
+        while let Some(node) = sibling {
+            if node.name() == Some("dd") {
+                values.push((normalize(term.text()), normalize(node.text())));
+                break;
+            }
+            if node.name().is_some() {
+                break;
+            }
+            sibling = node.next();
+        }
+    }
+
+    values
+}
@@ -0,0 +1,122 @@
+use crate::changes::{format_session, Change, EventSnapshot, Snapshot};
+use chrono::{DateTime, Duration, Utc};
+
+/// Upcoming sessions together with the changes since the previous digest, e.g. for a weekly mail.
+#[derive(Debug, Clone)]
+pub struct Digest {
+    pub from: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+    /// Sessions within the period, paired with the name of their course
+    pub sessions: Vec<(String, EventSnapshot)>,
+    pub changes: Vec<Change>,
+}
+
+impl Digest {
+    /// Digest of the `days` starting at `from`, with the changes from `previous` to `current`.
+    pub fn new(current: &Snapshot, previous: &Snapshot, from: DateTime<Utc>, days: i64) -> Self {
+        let until = from + Duration::days(days);
+
+        let mut sessions = current
+            .courses
+            .values()
+            .flat_map(|course| {
+                course
+                    .events
+                    .iter()
+                    .filter(|event| event.end > from && event.start < until)
+                    .map(move |event| (course.name.clone(), event.clone()))
+            })
+            .collect::<Vec<_>>();
+        sessions.sort_by_key(|(_, event)| event.start);
+
+        Self {
+            from,
+            until,
+            sessions,
+            changes: current.changes_since(previous),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "Sessions from {} to {}\n\n",
+            self.from.format("%d.%m.%Y"),
+            self.until.format("%d.%m.%Y")
+        );
+        if self.sessions.is_empty() {
+            text.push_str("No sessions.\n");
+        }
+        for (course_name, event) in self.sessions.iter() {
+            text.push_str(&format!(
+                "- {}: {}\n",
+                format_session(event.start, event.end),
+                course_name
+            ));
+        }
+
+        text.push_str("\nChanges since the last digest\n\n");
+        if self.changes.is_empty() {
+            text.push_str("No changes.\n");
+        }
+        for change in self.changes.iter() {
+            text.push_str(&format!("- {}\n", change));
+        }
+
+        text
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>Digest</title></head>\n<body>\n",
+        );
+        html.push_str(&format!(
+            "<h2>Sessions from {} to {}</h2>\n",
+            self.from.format("%d.%m.%Y"),
+            self.until.format("%d.%m.%Y")
+        ));
+        push_html_list(
+            &mut html,
+            self.sessions.iter().map(|(course_name, event)| {
+                format!(
+                    "<strong>{}</strong>: {}",
+                    escape_html(&format_session(event.start, event.end)),
+                    escape_html(course_name)
+                )
+            }),
+            "No sessions.",
+        );
+
+        html.push_str("<h2>Changes since the last digest</h2>\n");
+        push_html_list(
+            &mut html,
+            self.changes
+                .iter()
+                .map(|change| escape_html(&change.to_string())),
+            "No changes.",
+        );
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+fn push_html_list<I: Iterator<Item = String>>(html: &mut String, items: I, empty: &str) {
+    let items = items.collect::<Vec<_>>();
+    if items.is_empty() {
+        html.push_str(&format!("<p>{}</p>\n", empty));
+        return;
+    }
+
+    html.push_str("<ul>\n");
+    for item in items {
+        html.push_str(&format!("<li>{}</li>\n", item));
+    }
+    html.push_str("</ul>\n");
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
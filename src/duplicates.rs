@@ -0,0 +1,72 @@
+//! Sessions listed on several course pages, e.g. a Vorlesung and its Übung sharing dates, which
+//! would otherwise show up twice in a combined calendar.
+
+use crate::{Course, CourseEvent, COURSE_KINDS};
+use std::collections::HashMap;
+
+/// What makes two sessions the same: start, end, room and normalized title.
+type Key = (i64, i64, Option<String>, String);
+
+/// Merge sessions of later courses into equal sessions of earlier ones, returning how many were
+/// merged.
+///
+/// The kept session gets the lecturers of the merged one, and a remark naming its course.
+pub fn merge_duplicates(courses: &mut [(String, Course)]) -> usize {
+    let mut merged = 0;
+    // Key -> index of the course and of the session in it
+    let mut seen = HashMap::<Key, (usize, usize)>::new();
+    for course_index in 0..courses.len() {
+        let events = std::mem::replace(&mut courses[course_index].1.events, vec![]);
+        let mut kept = vec![];
+        for event in events {
+            let key = key(&courses[course_index].1, &event);
+            match seen.get(&key) {
+                Some(&(kept_course, kept_event)) if kept_course != course_index => {
+                    let name = courses[course_index].1.name.clone();
+                    merge(&mut courses[kept_course].1.events[kept_event], event, &name);
+                    merged += 1;
+                }
+                _ => {
+                    seen.insert(key, (course_index, kept.len()));
+                    kept.push(event);
+                }
+            }
+        }
+        courses[course_index].1.events = kept;
+    }
+    merged
+}
+
+fn key(course: &Course, event: &CourseEvent) -> Key {
+    (
+        event.timespan.start.timestamp(),
+        event.timespan.end.timestamp(),
+        event.room.clone(),
+        normalized_title(event.summary.as_ref().unwrap_or(&course.name)),
+    )
+}
+
+/// Lowercase words of `title` without course types, so "OC 1 Vorlesung" equals "OC 1 (Übung)".
+fn normalized_title(title: &str) -> String {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .filter(|word| !COURSE_KINDS.iter().any(|kind| kind.to_lowercase() == *word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn merge(kept: &mut CourseEvent, duplicate: CourseEvent, course_name: &str) {
+    for lecturer in duplicate.lecturers {
+        if !kept.lecturers.contains(&lecturer) {
+            kept.lecturers.push(lecturer);
+        }
+    }
+    kept.sequence = kept.sequence.max(duplicate.sequence);
+    let note = format!("Auch: {}", course_name);
+    kept.remark = Some(match kept.remark.take() {
+        Some(remark) => format!("{}\n{}", remark, note),
+        None => note,
+    });
+}
@@ -0,0 +1,272 @@
+//! Courses the user is enrolled in, read from their timetable in Campus Management after signing
+//! in through the single sign-on of the FU, and sessions for course pages of the VV that are only
+//! shown to members of the FU.
+//!
+//! Opt-in via the `[enrollment]` section of the config. The password never goes into the config
+//! file: it's taken from `PASSWORD_ENV` or the system keyring (service `KEYRING_SERVICE`, the
+//! account name as user).
+
+use crate::config::EnrollmentConfig;
+use crate::fetch::{self, HttpRequest};
+use crate::lecturer::course_id_from_link;
+use crate::{Error, StdError};
+use http::header::{HeaderMap, CONTENT_TYPE, COOKIE, LOCATION, SET_COOKIE};
+use http::Method;
+use select::document::Document;
+use select::node::Node;
+use select::predicate::{Attr, Name};
+use tracing::debug;
+
+/// Environment variable with the password of the FU account
+pub const PASSWORD_ENV: &str = "FU_VV_CAL_CM_PASSWORD";
+/// Environment variable with the FU account name, overriding the configured one
+pub const USERNAME_ENV: &str = "FU_VV_CAL_CM_USERNAME";
+/// Keyring service the password is looked up under
+pub const KEYRING_SERVICE: &str = "fu-vv-cal";
+/// Number of redirects and forms followed for a single page, e.g. through the SAML round trip.
+const MAX_HOPS: usize = 10;
+
+/// Sign in and return the VV ids of the courses linked on the timetable page.
+pub async fn enrolled_course_ids(config: &EnrollmentConfig) -> Result<Vec<String>, StdError> {
+    let timetable_url = config
+        .timetable_url
+        .as_ref()
+        .ok_or(Error::MissingEnrollmentSetting {
+            key: "timetable_url",
+        })?;
+    let (_, url, html) = signed_in(config, timetable_url).await?;
+
+    let document = Document::from(html.as_str());
+    let mut ids = vec![];
+    for href in document
+        .find(Name("a"))
+        .filter_map(|link| link.attr("href"))
+    {
+        if let Some(id) = course_id_from_link(href) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+    if ids.is_empty() {
+        return Err(Error::NoEnrolledCourses { url }.into());
+    }
+    Ok(ids)
+}
+
+/// Sign in on the way to `vv_login_url` and return the cookies of the VV, to be sent along with
+/// the requests for course pages (see `fetch::set_session_cookie`).
+pub async fn vv_session_cookie(config: &EnrollmentConfig) -> Result<String, StdError> {
+    let login_url = config
+        .vv_login_url
+        .as_ref()
+        .ok_or(Error::MissingEnrollmentSetting {
+            key: "vv_login_url",
+        })?;
+    let (session, url, _) = signed_in(config, login_url).await?;
+    let cookie = session.cookie_header(fetch::VV_HOST);
+    if cookie.is_empty() {
+        return Err(Error::LoginFailed { url }.into());
+    }
+    Ok(cookie)
+}
+
+/// Visit `url`, signing in when asked to. Returns the session with its cookies, and the URL and
+/// HTML of the page finally reached.
+async fn signed_in(
+    config: &EnrollmentConfig,
+    url: &str,
+) -> Result<(Session, String, String), StdError> {
+    let username = std::env::var(USERNAME_ENV).unwrap_or_else(|_| config.username.clone());
+    let password = password(&username)?;
+
+    let mut session = Session::default();
+    let (mut url, mut html) = session.request(Method::GET, url, None).await?;
+    let mut signed_in = false;
+    for _ in 0..MAX_HOPS {
+        let form = match next_form(&Document::from(html.as_str())) {
+            Some(form) => form,
+            None => break,
+        };
+        if form.has_password {
+            // The login form again means the credentials were rejected
+            if signed_in {
+                return Err(Error::LoginFailed { url }.into());
+            }
+            signed_in = true;
+        }
+        let fields = form
+            .fields
+            .into_iter()
+            .map(|(name, kind, value)| match kind.as_str() {
+                "password" => (name, password.clone()),
+                "text" | "email" => (name, username.clone()),
+                _ => (name, value),
+            })
+            .collect::<Vec<_>>();
+        let action = fetch::resolve_url(&url, &form.action)?;
+        let (next_url, next_html) = session.request(Method::POST, &action, Some(fields)).await?;
+        url = next_url;
+        html = next_html;
+    }
+    Ok((session, url, html))
+}
+
+/// The password of `username`, see the module documentation.
+fn password(username: &str) -> Result<String, Error> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(password);
+    }
+    keyring::Keyring::new(KEYRING_SERVICE, username)
+        .get_password()
+        .map_err(|_| Error::MissingCredentials {
+            username: username.to_owned(),
+            env: PASSWORD_ENV,
+        })
+}
+
+/// Form that has to be submitted to get on: the login form or one of the auto-submitting forms of
+/// the SAML round trip.
+struct Form {
+    action: String,
+    /// Name, type and value of every input
+    fields: Vec<(String, String, String)>,
+    has_password: bool,
+}
+
+fn next_form(document: &Document) -> Option<Form> {
+    document.find(Name("form")).find_map(|form: Node| {
+        let fields = form
+            .find(Name("input"))
+            .filter_map(|input| {
+                let name = input.attr("name")?.to_owned();
+                let kind = input.attr("type").unwrap_or("text").to_lowercase();
+                if kind == "submit" || kind == "button" {
+                    return None;
+                }
+                Some((name, kind, input.attr("value").unwrap_or("").to_owned()))
+            })
+            .collect::<Vec<_>>();
+        let has_password = fields.iter().any(|(_, kind, _)| kind == "password");
+        let is_saml = form.find(Attr("name", "SAMLResponse")).next().is_some()
+            || form.find(Attr("name", "SAMLRequest")).next().is_some();
+        if !has_password && !is_saml {
+            return None;
+        }
+        Some(Form {
+            action: form.attr("action").unwrap_or("").to_owned(),
+            fields,
+            has_password,
+        })
+    })
+}
+
+/// Requests sharing cookies, like a browser visiting the pages one after another.
+#[derive(Debug, Default)]
+struct Session {
+    /// Domain, name and value of every cookie set so far
+    cookies: Vec<(String, String, String)>,
+}
+
+impl Session {
+    /// Send a request, posting `form` if given, and follow the redirects. Returns the final URL
+    /// and body.
+    async fn request(
+        &mut self,
+        method: Method,
+        url: &str,
+        form: Option<Vec<(String, String)>>,
+    ) -> Result<(String, String), StdError> {
+        let (mut method, mut url, mut form) = (method, url.to_owned(), form);
+        for _ in 0..MAX_HOPS {
+            let host = host(&url)?;
+            let mut req = HttpRequest::new(method.clone(), url.as_str());
+            let cookies = self.cookie_header(&host);
+            if !cookies.is_empty() {
+                req = req.header(COOKIE, cookies)?;
+            }
+            if let Some(fields) = form.take() {
+                req = req
+                    .header(CONTENT_TYPE, "application/x-www-form-urlencoded")?
+                    .with_body(
+                        url::form_urlencoded::Serializer::new(String::new())
+                            .extend_pairs(fields)
+                            .finish(),
+                    );
+            }
+            let (status, headers, text) = fetch::send_with_headers(req).await?;
+            self.store_cookies(&host, &headers);
+            debug!(url = url.as_str(), status = status.as_u16(), "sign-in step");
+
+            if !status.is_redirection() {
+                if !status.is_success() {
+                    return Err(Error::HttpRequestError {
+                        url,
+                        status: status.as_u16(),
+                    }
+                    .into());
+                }
+                return Ok((url, text));
+            }
+            let location = headers
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .ok_or(Error::LoginFailed { url: url.clone() })?;
+            url = fetch::resolve_url(&url, location)?;
+            method = Method::GET;
+        }
+        Err(Error::LoginFailed { url }.into())
+    }
+
+    fn cookie_header(&self, host: &str) -> String {
+        self.cookies
+            .iter()
+            .filter(|(domain, _, _)| {
+                host == domain.as_str() || host.ends_with(&format!(".{}", domain))
+            })
+            .map(|(_, name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    fn store_cookies(&mut self, host: &str, headers: &HeaderMap) {
+        for header in headers.get_all(SET_COOKIE).iter() {
+            let header = match header.to_str() {
+                Ok(header) => header,
+                Err(_) => continue,
+            };
+            let mut parts = header.split(';').map(str::trim);
+            let (name, value) = match parts.next().map(|pair| pair.splitn(2, '=')) {
+                Some(mut pair) => match (pair.next(), pair.next()) {
+                    (Some(name), Some(value)) => (name.to_owned(), value.to_owned()),
+                    _ => continue,
+                },
+                None => continue,
+            };
+            let domain = parts
+                .filter_map(|attribute| {
+                    let mut pair = attribute.splitn(2, '=');
+                    let key = pair.next()?;
+                    if !key.eq_ignore_ascii_case("domain") {
+                        return None;
+                    }
+                    Some(pair.next()?.trim_start_matches('.').to_lowercase())
+                })
+                .next()
+                .unwrap_or_else(|| host.to_owned());
+            self.cookies.retain(|(known_domain, known_name, _)| {
+                (known_domain, known_name) != (&domain, &name)
+            });
+            self.cookies.push((domain, name, value));
+        }
+    }
+}
+
+fn host(url: &str) -> Result<String, Error> {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+        .ok_or_else(|| Error::InvalidUrl {
+            url: url.to_owned(),
+        })
+}
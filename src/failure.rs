@@ -0,0 +1,162 @@
+//! Errors as wrapper scripts see them: the phase that failed, a distinct exit code per kind of
+//! failure and, with `--error-format json`, one JSON object per line on stderr.
+
+use crate::{Error, StdError};
+use serde_json::json;
+use std::fmt;
+
+/// Exit code of invalid arguments or config, as clap uses for usage errors
+pub const EXIT_CONFIG: i32 = 2;
+/// Exit code of failures to reach the VV
+pub const EXIT_NETWORK: i32 = 3;
+/// Exit code of course pages that could not be parsed
+pub const EXIT_PARSE: i32 = 4;
+/// Exit code of exports that skipped some courses, see `--keep-going`
+pub const EXIT_PARTIAL: i32 = 5;
+
+/// Where an error happened.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Config,
+    Fetch,
+    Parse,
+    Export,
+}
+
+impl Phase {
+    /// The phase `err` comes from, judged by its type.
+    pub fn of(err: &(dyn std::error::Error + 'static)) -> Self {
+        if let Some(failure) = err.downcast_ref::<CourseFailure>() {
+            return Self::of(&*failure.error);
+        }
+        if err.is::<toml::de::Error>() {
+            return Phase::Config;
+        }
+        match err.downcast_ref::<Error>() {
+            Some(Error::HttpRequestError { .. })
+            | Some(Error::RequestTimeout { .. })
+            | Some(Error::DisallowedByRobots { .. })
+            | Some(Error::TransportError { .. })
+            | Some(Error::Interstitial { .. })
+            | Some(Error::InvalidBody { .. })
+            | Some(Error::MissingFixture { .. }) => Phase::Fetch,
+            Some(Error::MissingElement { .. })
+            | Some(Error::PageStructureChanged { .. })
+            | Some(Error::InvalidDate { .. })
+            | Some(Error::NoEnrolledCourses { .. })
+            | Some(Error::UnsupportedModelVersion { .. })
+            | Some(Error::CourseParseError { .. }) => Phase::Parse,
+            Some(Error::InvalidLocale { .. })
+            | Some(Error::UnknownSource { .. })
+            | Some(Error::InvalidVisibility { .. })
+            | Some(Error::InvalidColor { .. })
+            | Some(Error::MissingSemester { .. })
+            | Some(Error::MissingConfigSection { .. })
+            | Some(Error::MissingStateFile)
+            | Some(Error::MissingCredentials { .. })
+            | Some(Error::MissingEnrollmentSetting { .. })
+            | Some(Error::LoginFailed { .. })
+            | Some(Error::MissingDefaultSemester)
+            | Some(Error::InvalidCourseList { .. })
+            | Some(Error::InvalidRule { .. })
+            | Some(Error::InvalidInterval { .. })
+            | Some(Error::InvalidTimeOffsets { .. })
+            | Some(Error::InvalidEnvVar { .. })
+            | Some(Error::InvalidUidScheme { .. })
+            | Some(Error::InvalidTimezone { .. })
+            | Some(Error::InvalidTlsFile { .. })
+            | Some(Error::IncompleteTlsConfig)
+            | Some(Error::InvalidLectureFreeMode { .. })
+            | Some(Error::InvalidCourseUrl { .. })
+            | Some(Error::UnknownLvNumber { .. })
+            | Some(Error::AmbiguousLvNumber { .. })
+            | Some(Error::InvalidSelection { .. }) => Phase::Config,
+            _ => Phase::Export,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::Config => "config",
+            Phase::Fetch => "fetch",
+            Phase::Parse => "parse",
+            Phase::Export => "export",
+        }
+    }
+}
+
+/// Error of a single course, so the course can be named in the report.
+#[derive(Debug)]
+pub struct CourseFailure {
+    pub id: String,
+    pub error: StdError,
+}
+
+impl fmt::Display for CourseFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Course {}: {}", self.id, self.error)
+    }
+}
+
+impl std::error::Error for CourseFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+/// An error as reported to the user.
+#[derive(Debug)]
+pub struct Failure {
+    /// ID of the course the error belongs to, if any
+    pub course: Option<String>,
+    pub phase: Phase,
+    pub message: String,
+    /// Exit code of the process
+    pub exit_code: i32,
+}
+
+impl Failure {
+    pub fn new(err: &(dyn std::error::Error + 'static)) -> Self {
+        let (course, message) = match err.downcast_ref::<CourseFailure>() {
+            Some(failure) => (Some(failure.id.clone()), failure.error.to_string()),
+            None => match err.downcast_ref::<Error>() {
+                Some(Error::CourseParseError { id, message }) => {
+                    (Some(id.clone()), message.clone())
+                }
+                _ => (None, err.to_string()),
+            },
+        };
+        let phase = Phase::of(err);
+        let exit_code = match (err.downcast_ref::<Error>(), phase) {
+            (Some(Error::PartialExport { .. }), _) => EXIT_PARTIAL,
+            (_, Phase::Config) => EXIT_CONFIG,
+            (_, Phase::Fetch) => EXIT_NETWORK,
+            (_, Phase::Parse) => EXIT_PARSE,
+            (_, Phase::Export) => 1,
+        };
+        Self {
+            course,
+            phase,
+            message,
+            exit_code,
+        }
+    }
+
+    /// Print the failure to stderr, as text or with `format` "json" as a JSON line.
+    pub fn report(&self, format: &str) {
+        match format {
+            "json" => eprintln!(
+                "{}",
+                json!({
+                    "course": self.course,
+                    "phase": self.phase.as_str(),
+                    "message": self.message,
+                })
+            ),
+            _ => match &self.course {
+                Some(course) => eprintln!("Error: course {}: {}", course, self.message),
+                None => eprintln!("Error: {}", self.message),
+            },
+        }
+    }
+}
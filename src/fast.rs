@@ -0,0 +1,543 @@
+//! Fast-path parser for bulk crawls.
+//!
+//! Scans the raw HTML for the few elements the calendar needs instead of building a DOM tree.
+//! It relies on the markup more strictly than `Course::from_document`, which should be preferred
+//! whenever only a handful of pages are parsed.
+
+use crate::lenient;
+use crate::registration::Registration;
+use crate::{
+    course_kind, credits, extra_session, fallback_session_id, is_exam, is_extra_sessions_label,
+    is_group_label, language, modules, parse_timespan, Course, CourseEvent, Error, StdError,
+};
+
+/// Class of the rows of sessions
+const ROW_CLASS: &str = "link_to_details";
+/// Prefix of the `id` of session rows, followed by the id of the session
+const ROW_ID_PREFIX: &str = "link_to_details_";
+const DATE_TIME_MARKER: &str = "course_date_time";
+const TYPE_MARKER: &str = "course_type";
+const REMARK_MARKER: &str = "course_remark";
+/// Classes of the pagination element of a date list
+pub(crate) const PAGINATION_CLASSES: &[&str] = &["pagination", "pager"];
+/// Texts of links to the next page of a date list
+pub(crate) const NEXT_PAGE_LABELS: &[&str] = &[
+    "»",
+    "›",
+    ">",
+    "weiter",
+    "nächste seite",
+    "next",
+    "next page",
+];
+/// Elements the pagination of a date list can be
+const PAGINATION_ELEMENTS: &[&str] = &["nav", "div", "ul"];
+
+/// Parse a course page like `Course::from_document`.
+pub fn course_from_html(html: &str, lenient: bool) -> Result<Course, StdError> {
+    let page = decode_entities(&strip_tags(html));
+    let (sws, ects) = credits(&page);
+    let mut warnings = vec![];
+    Ok(Course {
+        id: None,
+        name: name_from_html(html).ok_or(Error::MissingElement {
+            element: "course name",
+        })?,
+        kind: kind_from_html(html),
+        events: events_from_html(html, lenient, &mut warnings)?,
+        cancelled: vec![],
+        url: None,
+        color: None,
+        sws,
+        ects,
+        modules: modules(&page),
+        language: language(&page),
+        registration: Registration::parse(&page),
+        warnings,
+    })
+}
+
+/// Text of the first `<h1>` after the `subc` container.
+fn name_from_html(html: &str) -> Option<String> {
+    let subc = html.find("\"subc")?;
+    let h1 = subc + html[subc..].find("<h1")?;
+    let content = h1 + html[h1..].find('>')? + 1;
+    let end = content + html[content..].find("</h1>")?;
+
+    Some(decode_entities(strip_tags(&html[content..end]).trim()))
+}
+
+/// Course type from the page header, between the `subc` container and the first session.
+fn kind_from_html(html: &str) -> Option<String> {
+    let subc = html.find("\"subc")?;
+    let end = row_starts(html)
+        .into_iter()
+        .find(|start| *start > subc)
+        .unwrap_or_else(|| html.len());
+    let header = &html[subc..end];
+    // Skip the course name
+    let header = match header.find("</h1>") {
+        Some(h1_end) => &header[h1_end..],
+        None => header,
+    };
+
+    course_kind(&decode_entities(&strip_tags(header)))
+}
+
+fn events_from_html(
+    html: &str,
+    lenient: bool,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<CourseEvent>, StdError> {
+    let starts = row_starts(html);
+    let headings = headings(html);
+
+    let mut events = vec![];
+    for (i, start) in starts.iter().enumerate() {
+        // Everything up to the next event belongs to this one
+        let end = starts.get(i + 1).cloned().unwrap_or_else(|| html.len());
+        let section = &html[*start..end];
+        // Only the tag of the row itself, the section contains other attributes as well
+        let row_tag = &section[..section.find('>').unwrap_or(0)];
+
+        let date_text = match element_text(section, DATE_TIME_MARKER) {
+            Some(date_text) => date_text,
+            None => {
+                let row = decode_entities(&strip_tags(section));
+                let row = row.split_whitespace().collect::<Vec<_>>().join(" ");
+                let err = Error::MissingElement {
+                    element: "session date",
+                };
+                lenient::skip_session(&row, err.into(), lenient, warnings)?;
+                continue;
+            }
+        };
+        let (timespan, all_day) = match parse_timespan(date_text.clone()) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                lenient::skip_session(&date_text, err, lenient, warnings)?;
+                continue;
+            }
+        };
+
+        let id = match attribute(row_tag, "id") {
+            Some(id) if id.starts_with(ROW_ID_PREFIX) => id[ROW_ID_PREFIX.len()..].to_owned(),
+            _ => fallback_session_id("", &timespan, &events),
+        };
+        let part = element_text(section, TYPE_MARKER).filter(|part| !part.is_empty());
+
+        events.push(CourseEvent {
+            id,
+            timespan,
+            sequence: 0,
+            week: None,
+            exam: is_exam(
+                part.as_ref(),
+                preceding_heading(&headings, *start, |_| true).as_ref(),
+            ),
+            part,
+            remark: element_text(section, REMARK_MARKER).filter(|remark| !remark.is_empty()),
+            room: None,
+            lecturers: vec![],
+            detail_url: attribute(row_tag, "href"),
+            group: preceding_heading(&headings, *start, is_group_label),
+            summary: None,
+            all_day,
+        });
+    }
+
+    // Extra sessions under a heading like "Zusätzliche Termine", see
+    // `CourseEvent::all_from_document`
+    for (i, heading) in headings.iter().enumerate() {
+        if !is_extra_sessions_label(&heading.text) {
+            continue;
+        }
+        let end = headings.get(i + 1).map_or(html.len(), |next| next.start);
+        for row in rows(&html[heading.end..end]) {
+            if row.contains("link_to_details") {
+                continue;
+            }
+            let text = decode_entities(&strip_tags(row));
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            if let Some(event) = extra_session(&text, &heading.text, &events) {
+                events.push(event);
+            }
+        }
+    }
+
+    Ok(events)
+}
+
+/// Offsets of the opening tags of all session rows, i.e. elements with the class `ROW_CLASS`.
+fn row_starts(html: &str) -> Vec<usize> {
+    let mut starts: Vec<usize> = vec![];
+    for (index, _) in html.match_indices(ROW_CLASS) {
+        let start = match html[..index].rfind('<') {
+            Some(start) => start,
+            None => continue,
+        };
+        // The id of a row contains the class name as well
+        if starts.last() == Some(&start) {
+            continue;
+        }
+        let tag = &html[start..index + html[index..].find('>').unwrap_or(0)];
+        let is_row = attribute(tag, "class")
+            .map(|classes| classes.split_whitespace().any(|class| class == ROW_CLASS))
+            .unwrap_or(false);
+        if is_row {
+            starts.push(start);
+        }
+    }
+    starts
+}
+
+/// A `<h1>` to `<h6>` heading of a page.
+struct Heading {
+    /// Offset of the opening tag
+    start: usize,
+    /// Offset after the closing tag
+    end: usize,
+    text: String,
+}
+
+/// All headings of `html`, in the order of the page.
+fn headings(html: &str) -> Vec<Heading> {
+    let mut headings = vec![];
+    let mut offset = 0;
+    while let Some(found) = html[offset..].find("<h") {
+        let start = offset + found;
+        offset = start + "<h".len();
+        // Skip `<head>`, `<hr>` and the like
+        let level = match html[offset..].chars().next() {
+            Some(level) if level.is_ascii_digit() => level,
+            _ => continue,
+        };
+        let content = match html[offset..].find('>') {
+            Some(tag_end) => offset + tag_end + 1,
+            None => break,
+        };
+        let close = match html[content..].find(&format!("</h{}", level)) {
+            Some(close) => content + close,
+            None => continue,
+        };
+        let end = close
+            + html[close..]
+                .find('>')
+                .map_or(html.len() - close, |i| i + 1);
+        let text = decode_entities(&strip_tags(&html[content..close]));
+        headings.push(Heading {
+            start,
+            end,
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+        });
+        offset = end;
+    }
+    headings
+}
+
+/// The table rows and list items of `section`, each up to its end or the next nested one.
+fn rows(section: &str) -> Vec<&str> {
+    let is_row_start = |index: usize| {
+        let tag = &section[index + 1..];
+        (tag.starts_with("tr") || tag.starts_with("li"))
+            && tag[2..].starts_with(|c: char| c == '>' || c.is_whitespace())
+    };
+    let starts = section
+        .match_indices('<')
+        .map(|(index, _)| index)
+        .filter(|index| is_row_start(*index))
+        .collect::<Vec<_>>();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let row = &section[*start..starts.get(i + 1).cloned().unwrap_or(section.len())];
+            let row_end = ["</tr", "</li"]
+                .iter()
+                .filter_map(|close| row.find(close))
+                .min();
+            &row[..row_end.unwrap_or(row.len())]
+        })
+        .collect()
+}
+
+/// Text of the last of `headings` before `offset` for which `matches` holds.
+fn preceding_heading(
+    headings: &[Heading],
+    offset: usize,
+    matches: fn(&str) -> bool,
+) -> Option<String> {
+    // Headings are in the order of the page, so the ones before `offset` are a prefix
+    let before = match headings.binary_search_by_key(&offset, |heading| heading.end) {
+        Ok(index) => index + 1,
+        Err(index) => index,
+    };
+    headings[..before]
+        .iter()
+        .rev()
+        .find(|heading| matches(&heading.text))
+        .map(|heading| heading.text.clone())
+}
+
+/// Link to the next page of a paginated date list, like `source::next_page`.
+///
+/// Only a pagination element after the first session is considered, so "next" links of the
+/// navigation or a search before the sessions aren't mistaken for further pages of sessions.
+pub fn next_page(html: &str) -> Option<String> {
+    let first_row = *row_starts(html).first()?;
+    let pagination = pagination(html, first_row)?;
+    let mut offset = 0;
+    while let Some(found) = pagination[offset..].find("<a") {
+        let start = offset + found;
+        offset = start + "<a".len();
+        // Skip `<abbr>` and the like
+        if !pagination[offset..].starts_with(|c: char| c.is_whitespace() || c == '>') {
+            continue;
+        }
+        let content = start + pagination[start..].find('>')? + 1;
+        let content_end = pagination[content..]
+            .find("</a")
+            .map(|end| content + end)
+            .unwrap_or_else(|| pagination.len());
+        let tag = &pagination[start..content];
+        let label = decode_entities(strip_tags(&pagination[content..content_end]).trim());
+        let is_next = has_token(attribute(tag, "rel").as_ref().map(String::as_str), "next")
+            || has_token(attribute(tag, "class").as_ref().map(String::as_str), "next")
+            || NEXT_PAGE_LABELS.contains(&label.to_lowercase().as_str());
+        if is_next {
+            return attribute(tag, "href");
+        }
+    }
+    None
+}
+
+/// Content of the first pagination element after `offset`, up to its first closing tag.
+fn pagination(html: &str, offset: usize) -> Option<&str> {
+    let mut offset = offset;
+    while let Some(found) = html[offset..].find('<') {
+        let start = offset + found;
+        offset = start + 1;
+        let name = PAGINATION_ELEMENTS.iter().find(|name| {
+            let rest = &html[offset..];
+            rest.starts_with(*name)
+                && rest[name.len()..].starts_with(|c: char| c.is_whitespace() || c == '>')
+        });
+        let name = match name {
+            Some(name) => name,
+            None => continue,
+        };
+        let content = start + html[start..].find('>')? + 1;
+        let is_pagination = attribute(&html[start..content], "class")
+            .map(|classes| {
+                PAGINATION_CLASSES
+                    .iter()
+                    .any(|class| has_token(Some(&classes), class))
+            })
+            .unwrap_or(false);
+        if is_pagination {
+            let end = html[content..]
+                .find(&format!("</{}", name))
+                .map(|end| content + end)
+                .unwrap_or_else(|| html.len());
+            return Some(&html[content..end]);
+        }
+    }
+    None
+}
+
+/// Whether the space-separated attribute `value` contains `token`.
+pub(crate) fn has_token(value: Option<&str>, token: &str) -> bool {
+    value
+        .map(|value| value.split_whitespace().any(|part| part == token))
+        .unwrap_or(false)
+}
+
+/// Text of the element with the class `marker` in the section of an event.
+fn element_text(section: &str, marker: &str) -> Option<String> {
+    let marker = section.find(marker)?;
+    let content = marker + section[marker..].find('>')? + 1;
+    let content_end = content + section[content..].find('<')?;
+
+    Some(decode_entities(section[content..content_end].trim()))
+}
+
+/// Value of the first `name` attribute in `section`.
+fn attribute(section: &str, name: &str) -> Option<String> {
+    let marker = format!("{}=\"", name);
+    let start = section.find(&marker)? + marker.len();
+    let end = start + section[start..].find('"')?;
+
+    Some(decode_entities(&section[start..end]))
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&raquo;", "»")
+        .replace("&rsaquo;", "›")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use select::document::Document;
+
+    /// Course page with grouped sessions, an exam, a row without id and extra sessions
+    const PAGE: &str = "<html><body>\
+        <div class=\"subc\"><h1>Organische Chemie 1</h1><p>Vorlesung</p></div>\
+        <div class=\"dates\">\
+        <h3>Gruppe A</h3>\
+        <div id=\"link_to_details_500001\" class=\"link_to_details\" href=\"/vv/lv/1/500001\">\
+        <span class=\"course_date_time\">Mo, 14.10.2019 10:00 - 12:00</span>\
+        <span class=\"course_type\">Vorlesung</span>\
+        <span class=\"course_remark\">Hörsaal A</span></div>\
+        <h3>Klausur</h3>\
+        <div class=\"link_to_details\">\
+        <span class=\"course_date_time\">Mo, 10.02.2020 10:00 - 12:00</span></div>\
+        </div>\
+        <h3>Zusätzliche Termine</h3>\
+        <ul><li>Fr, 18.10.2019 14:00 - 16:00 Fragestunde</li></ul>\
+        </body></html>";
+
+    /// Course page with `sessions` sessions, ten under each group heading.
+    fn long_page(sessions: usize) -> String {
+        let mut html = String::from(
+            "<html><body><div class=\"subc\"><h1>Analysis 1</h1></div><div class=\"dates\">",
+        );
+        for i in 0..sessions {
+            if i % 10 == 0 {
+                html.push_str(&format!("<h3>Gruppe {}</h3>", i / 10 + 1));
+            }
+            html.push_str(&format!(
+                "<div id=\"link_to_details_{id}\" class=\"link_to_details\">\
+                 <span class=\"course_date_time\">Mo, {day:02}.10.2019 \
+                 {hour:02}:00 - {end:02}:00</span>\
+                 </div>",
+                id = 500_000 + i,
+                day = 1 + i % 28,
+                hour = 8 + i / 28,
+                end = 9 + i / 28
+            ));
+        }
+        html.push_str("</div></body></html>");
+        html
+    }
+
+    fn assert_same_as_dom(html: &str) {
+        let fast = course_from_html(html, false).unwrap();
+        let dom = Course::from_document(&Document::from(html), false).unwrap();
+        assert_eq!(fast.name, dom.name);
+        assert_eq!(fast.kind, dom.kind);
+        assert_eq!(format!("{:?}", fast.events), format!("{:?}", dom.events));
+    }
+
+    #[test]
+    fn matches_dom_parser() {
+        assert_same_as_dom(PAGE);
+    }
+
+    #[test]
+    fn matches_dom_parser_on_long_pages() {
+        assert_same_as_dom(&long_page(200));
+    }
+
+    #[test]
+    fn takes_headings_before_each_session() {
+        let course = course_from_html(PAGE, false).unwrap();
+        assert_eq!(course.events.len(), 3);
+
+        let lecture = &course.events[0];
+        assert_eq!(lecture.id, "500001");
+        assert_eq!(lecture.group.as_ref().map(String::as_str), Some("Gruppe A"));
+        assert!(!lecture.exam);
+
+        let exam = &course.events[1];
+        assert!(exam.id.starts_with("row_"));
+        assert_eq!(exam.group.as_ref().map(String::as_str), Some("Gruppe A"));
+        assert!(exam.exam);
+
+        let extra = &course.events[2];
+        assert!(extra.id.starts_with("extra_"));
+        assert_eq!(
+            extra.remark.as_ref().map(String::as_str),
+            Some("Fragestunde")
+        );
+
+        let course = course_from_html(&long_page(30), false).unwrap();
+        assert_eq!(
+            course.events[9].group.as_ref().map(String::as_str),
+            Some("Gruppe 1")
+        );
+        assert_eq!(
+            course.events[10].group.as_ref().map(String::as_str),
+            Some("Gruppe 2")
+        );
+    }
+
+    #[test]
+    fn skips_broken_sessions_only_if_lenient() {
+        let html = PAGE.replace(
+            "Mo, 10.02.2020 10:00 - 12:00",
+            "Mo, 31.02.2020 10:00 - 12:00",
+        );
+        assert!(course_from_html(&html, false).is_err());
+        assert!(Course::from_document(&Document::from(html.as_str()), false).is_err());
+
+        let course = course_from_html(&html, true).unwrap();
+        assert_eq!(course.events.len(), 2);
+        assert_eq!(course.warnings.len(), 1);
+        let dom = Course::from_document(&Document::from(html.as_str()), true).unwrap();
+        assert_eq!(dom.events.len(), 2);
+        assert_eq!(dom.warnings, course.warnings);
+    }
+
+    /// `PAGE` with `pagination` after the sessions and a pagination of a search before them
+    fn paginated(pagination: &str) -> String {
+        PAGE.replace(
+            "<div class=\"subc\">",
+            "<nav class=\"pagination\"><a href=\"/suche?page=2\">weiter</a></nav>\
+             <div class=\"subc\">",
+        )
+        .replace("<h3>Zusätzliche", &format!("{}<h3>Zusätzliche", pagination))
+    }
+
+    #[test]
+    fn finds_the_next_page() {
+        assert_eq!(next_page(PAGE), None);
+        let pages = [
+            "<ul class=\"pagination\"><li><a href=\"?page=1\">1</a></li>\
+             <li><a href=\"?sm=1&amp;page=2\">weiter</a></li></ul>",
+            "<div class=\"pager small\"><a class=\"next\" href=\"?sm=1&amp;page=2\">2</a></div>",
+            "<nav class=\"pagination\"><abbr>1</abbr><a rel=\"next\" href=\"?sm=1&amp;page=2\">\
+             <span>Nächste Seite</span></a></nav>",
+            "<ul class=\"pager\"><li><a href=\"?sm=1&amp;page=2\"> &raquo; </a></li></ul>",
+        ];
+        for pagination in pages.iter() {
+            let html = paginated(pagination);
+            assert_eq!(next_page(&html).unwrap(), "?sm=1&page=2", "{}", pagination);
+        }
+        // No pagination after the sessions
+        assert_eq!(
+            next_page(&paginated("<ul class=\"menu\"><a>weiter</a></ul>")),
+            None
+        );
+    }
+}
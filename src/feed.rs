@@ -0,0 +1,170 @@
+//! Combined JSON feed of all exported sessions, meant as a stable contract for web frontends.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "generated_at": "2019-10-20T08:00:00Z",
+//!   "events": [
+//!     {
+//!       "id": "5551234",
+//!       "course_id": "524870",
+//!       "course_name": "Organische Chemie 1",
+//!       "course_sws": 4,
+//!       "course_ects": 5,
+//!       "course_modules": ["21601"],
+//!       "course_language": "Englisch",
+//!       "start": "2019-10-21T08:00:00Z",
+//!       "end": "2019-10-21T11:00:00Z",
+//!       "week": 2,
+//!       "sequence": 1,
+//!       "status": "confirmed",
+//!       "change": { "kind": "moved", "previous_start": "…", "previous_end": "…" }
+//!     }
+//!   ],
+//!   "warnings": [{ "course": "524870", "message": "skipped session \"…\": …" }]
+//! }
+//! ```
+//!
+//! - `id` is the VV id of the session and only unique together with `course_id`
+//! - Timestamps are RFC 3339 in UTC
+//! - `course_sws` and `course_ects` are the weekly hours and credits of the course, `null` if
+//!   its page doesn't state them
+//! - `course_modules` are the codes of the modules the course page references, possibly empty
+//! - `course_language` is the language of instruction as stated on the page, `null` if unknown
+//! - `week` is the lecture week (see `weeks`), `null` if unknown
+//! - `status` is `confirmed` or `cancelled`; cancelled sessions stay in the feed
+//! - `change` marks sessions that were `added`, `moved` or changed `room` (with `previous_room`)
+//!   since the previous export, and is `null` otherwise or if no `state` file is configured
+//! - `warnings` lists the sessions skipped with `lenient` parsing, usually empty
+//!
+//! Fields are only ever added within a version; removing or changing fields bumps `version`.
+
+use crate::changes::{Change, ChangeKind};
+use crate::lenient::ParseWarning;
+use crate::{Course, CourseEvent, StdError};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+
+/// Version of the feed format, see the module documentation.
+pub const FEED_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Feed {
+    pub version: u32,
+    pub generated_at: DateTime<Utc>,
+    pub events: Vec<FeedEvent>,
+    /// Problems of the course pages that were skipped, see `lenient`
+    pub warnings: Vec<ParseWarning>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedEvent {
+    pub id: String,
+    pub course_id: String,
+    pub course_name: String,
+    pub course_sws: Option<f32>,
+    pub course_ects: Option<f32>,
+    pub course_modules: Vec<String>,
+    pub course_language: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub week: Option<u32>,
+    pub sequence: u32,
+    pub status: FeedStatus,
+    pub change: Option<FeedChange>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedStatus {
+    Confirmed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum FeedChange {
+    Added,
+    Moved {
+        previous_start: DateTime<Utc>,
+        previous_end: DateTime<Utc>,
+    },
+    #[serde(rename = "room")]
+    RoomChanged {
+        previous_room: String,
+    },
+}
+
+impl Feed {
+    /// Feed of `courses` (paired with their course id), marking the sessions in `changes`.
+    pub fn new<'a, I>(courses: I, changes: &[Change], generated_at: DateTime<Utc>) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a Course)>,
+    {
+        let mut events = vec![];
+        for (course_id, course) in courses {
+            let event = |event: &CourseEvent, status| FeedEvent {
+                id: event.id.clone(),
+                course_id: course_id.to_owned(),
+                course_name: course.name.clone(),
+                course_sws: course.sws,
+                course_ects: course.ects,
+                course_modules: course.modules.clone(),
+                course_language: course.language.clone(),
+                start: event.timespan.start.with_timezone(&Utc),
+                end: event.timespan.end.with_timezone(&Utc),
+                week: event.week,
+                sequence: event.sequence,
+                status,
+                change: feed_change(changes, course_id, &event.id),
+            };
+            events.extend(
+                course
+                    .events
+                    .iter()
+                    .map(|e| event(e, FeedStatus::Confirmed)),
+            );
+            events.extend(
+                course
+                    .cancelled
+                    .iter()
+                    .map(|e| event(e, FeedStatus::Cancelled)),
+            );
+        }
+        events.sort_by_key(|event| event.start);
+
+        Self {
+            version: FEED_VERSION,
+            generated_at,
+            events,
+            warnings: vec![],
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StdError> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn feed_change(changes: &[Change], course_id: &str, event_id: &str) -> Option<FeedChange> {
+    let change = changes
+        .iter()
+        .find(|change| change.course_id == course_id && change.event.id == event_id)?;
+    match &change.kind {
+        ChangeKind::Added => Some(FeedChange::Added),
+        ChangeKind::Moved {
+            previous_start,
+            previous_end,
+        } => Some(FeedChange::Moved {
+            previous_start: *previous_start,
+            previous_end: *previous_end,
+        }),
+        ChangeKind::RoomChanged { previous_room } => Some(FeedChange::RoomChanged {
+            previous_room: previous_room.clone(),
+        }),
+        // Expressed by the status
+        ChangeKind::Cancelled => None,
+    }
+}
@@ -0,0 +1,896 @@
+use crate::backend;
+use crate::cache::PageCache;
+use crate::{metrics, robots, usage, Error, StdError};
+use chrono::{DateTime, Utc};
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use http::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, COOKIE,
+    RETRY_AFTER, USER_AGENT,
+};
+use http::{Method, Response, StatusCode};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::io::Read;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::timer::{delay, Timeout};
+use tracing::{debug, warn};
+
+/// Number of attempts for a request before its last response or error is returned.
+const MAX_ATTEMPTS: u32 = 4;
+/// Delay before the first retry, doubled for every further attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+/// Upper bound for delays requested via `Retry-After`, so a bogus header can't stall a run.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(600);
+/// Number of interstitial redirects followed before giving up.
+const MAX_REDIRECTS: u32 = 3;
+/// Default minimum time between two upstream requests.
+pub const DEFAULT_REQUEST_DELAY: Duration = Duration::from_millis(500);
+/// Default time allowed for establishing a connection.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Compressions of response bodies `read_body` undoes.
+const ACCEPTED_ENCODINGS: &str = "gzip, deflate";
+/// Host of the VV.
+pub const VV_HOST: &str = "www.fu-berlin.de";
+/// Where the admins of the VV can read up on what fetches their pages, sent in the `User-Agent`
+pub const REPOSITORY_URL: &str = "https://github.com/hobofan/fu-vv-cal";
+/// Default time allowed for a whole request, including reading the response body.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    /// Point in time until which the upstream server asked us to back off.
+    ///
+    /// Shared by all requests, so concurrent fetches slow down together instead of each one
+    /// running into the rate limit on its own.
+    static ref THROTTLED_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+    /// Minimum time between the starts of two upstream requests, see `set_request_delay`.
+    static ref REQUEST_DELAY: Mutex<Duration> = Mutex::new(DEFAULT_REQUEST_DELAY);
+
+    /// Earliest point in time at which the next upstream request may start.
+    static ref NEXT_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+
+    /// Connect and request timeouts, see `set_timeouts`.
+    static ref TIMEOUTS: Mutex<(Duration, Duration)> =
+        Mutex::new((DEFAULT_CONNECT_TIMEOUT, DEFAULT_REQUEST_TIMEOUT));
+
+    /// Cookie sent with the requests to the VV, see `set_session_cookie`.
+    static ref SESSION_COOKIE: Mutex<Option<String>> = Mutex::new(None);
+
+    /// How to reach the operator, sent in the `User-Agent`, see `set_contact`.
+    static ref CONTACT: Mutex<Option<String>> = Mutex::new(None);
+
+    /// Cache of course pages, see `set_page_cache`.
+    static ref PAGE_CACHE: Mutex<Option<PageCache>> = Mutex::new(None);
+}
+
+/// Language version of the VV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    De,
+    En,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale::De
+    }
+}
+
+impl FromStr for Locale {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_lowercase().as_str() {
+            "de" => Ok(Locale::De),
+            "en" => Ok(Locale::En),
+            _ => Err(Error::InvalidLocale {
+                locale: text.to_owned(),
+            }),
+        }
+    }
+}
+
+impl Locale {
+    /// Path segment of the VV URLs, e.g. `de` in `/vv/de/lv/…`.
+    pub fn path_segment(self) -> &'static str {
+        match self {
+            Locale::De => "de",
+            Locale::En => "en",
+        }
+    }
+}
+
+/// Space out all upstream requests by at least `delay`, so bulk exports don't hammer the VV.
+pub fn set_request_delay(delay: Duration) {
+    *REQUEST_DELAY.lock().unwrap() = delay;
+}
+
+/// Give up on connections that aren't established within `connect` and on requests that don't
+/// complete within `request`, so a slow upstream fails the request (and lets `get` retry it)
+/// instead of stalling forever.
+pub fn set_timeouts(connect: Duration, request: Duration) {
+    let previous = std::mem::replace(&mut *TIMEOUTS.lock().unwrap(), (connect, request));
+    if previous.0 != connect {
+        backend::reset();
+    }
+}
+
+/// Keep fetched course pages in `cache`, and reuse them instead of requesting them again while
+/// they are fresh enough.
+pub fn set_page_cache(cache: PageCache) {
+    *PAGE_CACHE.lock().unwrap() = Some(cache);
+}
+
+/// Send `cookie` (e.g. "JSESSIONID=…") along with every request to the VV, for course pages that
+/// only show their dates to signed-in members of the FU. Other hosts never get to see it.
+pub fn set_session_cookie(cookie: Option<String>) {
+    *SESSION_COOKIE.lock().unwrap() = cookie.filter(|cookie| !cookie.trim().is_empty());
+}
+
+/// Name `contact` (e.g. an email address) in the `User-Agent` of all requests, so admins can
+/// reach whoever runs automated exports.
+pub fn set_contact(contact: Option<String>) {
+    *CONTACT.lock().unwrap() = contact.filter(|contact| !contact.trim().is_empty());
+}
+
+/// `User-Agent` of all requests, e.g.
+/// `fu-vv-cal/0.1.0 (+https://github.com/hobofan/fu-vv-cal; admin@example.org)`.
+pub fn user_agent() -> String {
+    let contact = CONTACT.lock().unwrap().clone();
+    let comment = match contact {
+        Some(contact) => format!("+{}; {}", REPOSITORY_URL, contact.trim()),
+        None => format!("+{}", REPOSITORY_URL),
+    };
+    format!("fu-vv-cal/{} ({})", env!("CARGO_PKG_VERSION"), comment)
+}
+
+/// Current connect and request timeouts.
+pub fn timeouts() -> (Duration, Duration) {
+    *TIMEOUTS.lock().unwrap()
+}
+
+/// Future returned by `CoursePageFetcher::fetch_page`.
+pub type PageFuture<'a> = Pin<Box<dyn Future<Output = Result<String, StdError>> + Send + 'a>>;
+
+/// Source of the HTML of VV course pages.
+pub trait CoursePageFetcher: fmt::Debug + Send + Sync {
+    /// Fetch the page of course `id` in `semester`, in the language `locale`.
+    fn fetch_page<'a>(&'a self, id: &'a str, semester: &'a str, locale: Locale) -> PageFuture<'a>;
+
+    /// Fetch another page belonging to a course, e.g. further pages of a paginated date list.
+    fn fetch_url<'a>(&'a self, url: &'a str) -> PageFuture<'a>;
+}
+
+/// Id and, if given, semester of a course referenced by its id or by the URL of its VV page, e.g.
+/// `https://www.fu-berlin.de/vv/de/lv/524870?sm=498562`.
+pub fn parse_course_reference(reference: &str) -> Result<(String, Option<String>), Error> {
+    let reference = reference.trim();
+    if !reference.contains("://") {
+        return Ok((reference.to_owned(), None));
+    }
+
+    let invalid = || Error::InvalidCourseUrl {
+        url: reference.to_owned(),
+    };
+    let url = url::Url::parse(reference).map_err(|_| invalid())?;
+    let segments = url.path_segments().ok_or_else(invalid)?.collect::<Vec<_>>();
+    let id = segments
+        .windows(2)
+        .find(|pair| pair[0] == "lv" && !pair[1].is_empty())
+        .map(|pair| pair[1].to_owned())
+        .ok_or_else(invalid)?;
+    let semester = url
+        .query_pairs()
+        .find(|(key, _)| key == "sm")
+        .map(|(_, value)| value.into_owned());
+    Ok((id, semester))
+}
+
+/// URL of the page of a course in the VV, in the language `locale`.
+pub fn course_url(id: &str, semester: &str, locale: Locale) -> String {
+    format!(
+        "https://www.fu-berlin.de/vv/{locale}/lv/{id}?sm={semester}",
+        locale = locale.path_segment(),
+        id = id,
+        semester = semester
+    )
+}
+
+/// URL of the VV search for `query` in `semester`, in the language `locale`.
+pub fn search_url(query: &str, semester: &str, locale: Locale) -> String {
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("query", query)
+        .append_pair("sm", semester)
+        .finish();
+    format!(
+        "https://www.fu-berlin.de/vv/{}/search?{}",
+        locale.path_segment(),
+        query
+    )
+}
+
+/// Whether the VV answers at all, for readiness checks.
+///
+/// Any response short of a server error counts, the request is sent once without retries.
+pub async fn check_reachable() -> Result<(), StdError> {
+    let url = vv_url();
+    let (status, _) = send(HttpRequest::new(Method::HEAD, url.as_str())).await?;
+    if status.is_server_error() {
+        return Err(Error::HttpRequestError {
+            url,
+            status: status.as_u16(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// URL of the start page of the VV.
+fn vv_url() -> String {
+    format!(
+        "https://www.fu-berlin.de/vv/{}/",
+        Locale::default().path_segment()
+    )
+}
+
+/// Fetches course pages from the live VV.
+#[derive(Debug, Default)]
+pub struct LiveFetcher;
+
+impl CoursePageFetcher for LiveFetcher {
+    fn fetch_page<'a>(&'a self, id: &'a str, semester: &'a str, locale: Locale) -> PageFuture<'a> {
+        Box::pin(async move {
+            let url = course_url(id, semester, locale);
+            self.fetch_url(&url).await
+        })
+    }
+
+    fn fetch_url<'a>(&'a self, url: &'a str) -> PageFuture<'a> {
+        Box::pin(async move {
+            let cache = PAGE_CACHE.lock().unwrap().clone();
+            if let Some(cache) = &cache {
+                let cached = cache.get(url);
+                metrics::record_cache_lookup(cached.is_some());
+                if let Some(body_str) = cached {
+                    debug!(url, "using cached page");
+                    usage::record_cache_hit();
+                    return Ok(body_str);
+                }
+            }
+
+            let mut page_url = url.to_owned();
+            let mut redirects = 0;
+            let body_str = loop {
+                let (status, body_str) = get(&page_url).await?;
+
+                if !status.is_success() {
+                    return Err(Error::HttpRequestError {
+                        url: page_url,
+                        status: status.as_u16(),
+                    }
+                    .into());
+                }
+
+                match interstitial(&body_str) {
+                    None => {
+                        usage::record_page();
+                        break body_str;
+                    }
+                    Some(Interstitial::Redirect(target)) if redirects < MAX_REDIRECTS => {
+                        page_url = resolve_url(&page_url, &target)?;
+                        debug!(url = page_url.as_str(), "following interstitial redirect");
+                        redirects += 1;
+                    }
+                    Some(Interstitial::Redirect(_)) => {
+                        return Err(Error::Interstitial {
+                            url: page_url,
+                            kind: "redirect loop",
+                        }
+                        .into())
+                    }
+                    Some(Interstitial::Consent) => {
+                        return Err(Error::Interstitial {
+                            url: page_url,
+                            kind: "consent page",
+                        }
+                        .into())
+                    }
+                }
+            };
+
+            if let Some(cache) = cache {
+                if let Err(err) = cache.put(url, &body_str) {
+                    warn!(url, %err, "failed to cache page");
+                }
+            }
+
+            Ok(body_str)
+        })
+    }
+}
+
+/// Page served instead of the course page.
+#[derive(Debug, PartialEq)]
+enum Interstitial {
+    /// Meta refresh or JavaScript redirect stub pointing to the real page
+    Redirect(String),
+    /// Cookie consent banner or similar, without course content
+    Consent,
+}
+
+/// Detect pages that stand in for the course page, which would otherwise only fail while parsing.
+fn interstitial(html: &str) -> Option<Interstitial> {
+    // Every course page has the `subc` container, see `Course::from_document`
+    if html.contains("\"subc") {
+        return None;
+    }
+
+    let lowercase = html.to_ascii_lowercase();
+    if let Some(target) = meta_refresh_target(&lowercase, html).or_else(|| script_redirect(html)) {
+        return Some(Interstitial::Redirect(target));
+    }
+    if lowercase.contains("cookie") || lowercase.contains("consent") {
+        return Some(Interstitial::Consent);
+    }
+    None
+}
+
+/// Target of `<meta http-equiv="refresh" content="0; url=…">`.
+///
+/// `lowercase` is `html` in ASCII lowercase (so byte offsets match), used for finding the tag
+/// while the URL is taken from `html`.
+fn meta_refresh_target(lowercase: &str, html: &str) -> Option<String> {
+    let meta = lowercase.find("http-equiv=\"refresh\"")?;
+    let url = meta + lowercase[meta..].find("url=")? + "url=".len();
+    let end = url + html[url..].find(|c| c == '"' || c == '\'' || c == '>')?;
+
+    Some(html[url..end].trim().to_owned()).filter(|target| !target.is_empty())
+}
+
+/// Target of a script redirect like `window.location.href = "…"` or `location.replace('…')`.
+fn script_redirect(html: &str) -> Option<String> {
+    ["location.href", "location.replace", "window.location"]
+        .iter()
+        .filter_map(|marker| {
+            let start = html.find(marker)? + marker.len();
+            let rest = &html[start..];
+            let quote_start = rest.find(|c| c == '"' || c == '\'')?;
+            // Only an assignment or call may come between the marker and the quoted URL
+            if !rest[..quote_start]
+                .chars()
+                .all(|c| c == '=' || c == '(' || c.is_whitespace())
+            {
+                return None;
+            }
+            let quote = rest[quote_start..].chars().next()?;
+            let url = &rest[quote_start + 1..];
+            let end = url.find(quote)?;
+            Some(url[..end].to_owned())
+        })
+        .next()
+}
+
+/// Resolve the link `target` on the page at `base`.
+pub fn resolve_url(base: &str, target: &str) -> Result<String, StdError> {
+    let base = url::Url::parse(base).map_err(|_| Error::InvalidUrl {
+        url: base.to_owned(),
+    })?;
+    let resolved = base.join(target).map_err(|_| Error::InvalidUrl {
+        url: target.to_owned(),
+    })?;
+    Ok(resolved.into_string())
+}
+
+/// Serves course pages from memory, e.g. saved pages in tests.
+#[derive(Debug, Default)]
+pub struct FixtureFetcher {
+    /// HTML by course id, or by URL for further pages of a course
+    pages: HashMap<String, String>,
+}
+
+impl FixtureFetcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_page<S1: Into<String>, S2: Into<String>>(mut self, id: S1, html: S2) -> Self {
+        self.pages.insert(id.into(), html.into());
+        self
+    }
+}
+
+impl CoursePageFetcher for FixtureFetcher {
+    fn fetch_page<'a>(&'a self, id: &'a str, _semester: &'a str, _: Locale) -> PageFuture<'a> {
+        Box::pin(async move {
+            self.pages
+                .get(id)
+                .cloned()
+                .ok_or_else(|| Error::MissingFixture { id: id.to_owned() }.into())
+        })
+    }
+    fn fetch_url<'a>(&'a self, url: &'a str) -> PageFuture<'a> {
+        self.fetch_page(url, "", Locale::default())
+    }
+}
+
+/// A request for `send`, independent of the HTTP client behind `backend`.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn new<S: Into<String>>(method: Method, url: S) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            headers: HeaderMap::new(),
+            body: vec![],
+        }
+    }
+
+    /// Set the header `name`, failing on values that can't be sent in a header.
+    pub fn header<V: AsRef<str>>(mut self, name: HeaderName, value: V) -> Result<Self, StdError> {
+        self.headers
+            .insert(name, HeaderValue::from_str(value.as_ref())?);
+        Ok(self)
+    }
+
+    pub fn with_body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+/// GET `url` and return the final status and body.
+///
+/// Network errors and `5xx` responses are retried with exponential backoff. `429 Too Many
+/// Requests` and `503 Service Unavailable` honor the `Retry-After` header, which throttles all
+/// requests of the process until it has passed.
+///
+/// Pages of the VV that its robots.txt disallows fail with `DisallowedByRobots`, see `robots`.
+pub async fn get(url: &str) -> Result<(StatusCode, String), StdError> {
+    robots::check(url).await?;
+    get_unchecked(url).await
+}
+
+/// GET `url` like `get`, regardless of robots.txt.
+pub(crate) async fn get_unchecked(url: &str) -> Result<(StatusCode, String), StdError> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        wait_for_throttle().await;
+        wait_for_turn().await;
+
+        let last_attempt = attempt >= MAX_ATTEMPTS;
+        let started = Instant::now();
+        let result = get_once(url).await;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        metrics::record_upstream_request(match &result {
+            Ok((status, _, _)) => status.is_success(),
+            Err(_) => false,
+        });
+        match result {
+            Ok((status, _, body)) if status.is_success() || last_attempt => {
+                debug!(
+                    url,
+                    status = status.as_u16(),
+                    elapsed_ms,
+                    bytes = body.len(),
+                    "GET"
+                );
+                return Ok((status, body));
+            }
+            Ok((status, retry_after, _))
+                if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() =>
+            {
+                warn!(url, status = status.as_u16(), attempt, "retrying request");
+                match retry_after {
+                    Some(retry_after) => throttle(retry_after),
+                    None => delay(Instant::now() + backoff).await,
+                }
+            }
+            Ok((status, _, body)) => {
+                debug!(url, status = status.as_u16(), elapsed_ms, "GET");
+                return Ok((status, body));
+            }
+            Err(err) => {
+                if last_attempt {
+                    return Err(err);
+                }
+                warn!(url, %err, attempt, "retrying request");
+                delay(Instant::now() + backoff).await;
+            }
+        }
+
+        attempt += 1;
+        backoff *= 2;
+    }
+}
+
+async fn get_once(url: &str) -> Result<(StatusCode, Option<Duration>, String), StdError> {
+    url::Url::parse(url).map_err(|_| Error::InvalidUrl {
+        url: url.to_owned(),
+    })?;
+    let mut req = HttpRequest::new(Method::GET, url)
+        .header(ACCEPT_ENCODING, ACCEPTED_ENCODINGS)?
+        .header(USER_AGENT, user_agent())?;
+    if let Some(cookie) = session_cookie(url) {
+        req = req.header(COOKIE, cookie)?;
+    }
+    with_timeout(url, async {
+        let res = backend::execute(req).await?;
+        let status = res.status();
+        let retry_after = retry_after(&res);
+        let body_str = read_body(res, url)?;
+
+        Ok((status, retry_after, body_str))
+    })
+    .await
+}
+
+/// Send `req` once, without any retries, and return the status and body.
+pub async fn send(req: HttpRequest) -> Result<(StatusCode, String), StdError> {
+    let (status, _, body) = send_with_headers(req).await?;
+    Ok((status, body))
+}
+
+/// Send `req` once like `send`, also returning the response headers, e.g. for cookies and
+/// redirects.
+pub async fn send_with_headers(
+    mut req: HttpRequest,
+) -> Result<(StatusCode, HeaderMap, String), StdError> {
+    if !req.headers.contains_key(ACCEPT_ENCODING) {
+        req.headers.insert(
+            ACCEPT_ENCODING,
+            HeaderValue::from_static(ACCEPTED_ENCODINGS),
+        );
+    }
+    if !req.headers.contains_key(USER_AGENT) {
+        req.headers
+            .insert(USER_AGENT, HeaderValue::from_str(&user_agent())?);
+    }
+
+    let url = req.url.clone();
+    with_timeout(&url, async {
+        let res = backend::execute(req).await?;
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body_str = read_body(res, &url)?;
+
+        Ok((status, headers, body_str))
+    })
+    .await
+}
+
+/// The session cookie if `url` points to the VV, see `set_session_cookie`.
+fn session_cookie(url: &str) -> Option<String> {
+    let cookie = SESSION_COOKIE.lock().unwrap().clone()?;
+    let url = url::Url::parse(url).ok()?;
+    if url.scheme() != "https" || url.host_str() != Some(VV_HOST) {
+        return None;
+    }
+    Some(cookie)
+}
+
+/// Run `request`, failing with `RequestTimeout` if it takes longer than the request timeout.
+async fn with_timeout<T, F>(url: &str, request: F) -> Result<T, StdError>
+where
+    F: Future<Output = Result<T, StdError>>,
+{
+    let (_, request_timeout) = timeouts();
+    match Timeout::new(request, request_timeout).await {
+        Ok(result) => result,
+        Err(_) => Err(Error::RequestTimeout {
+            url: url.to_owned(),
+            timeout_secs: request_timeout.as_secs(),
+        }
+        .into()),
+    }
+}
+
+/// Read the body of `response`, decompressing it according to `Content-Encoding` and decoding it
+/// according to the declared charset.
+fn read_body(response: Response<Vec<u8>>, url: &str) -> Result<String, StdError> {
+    let invalid_body = |message: String| Error::InvalidBody {
+        url: url.to_owned(),
+        message,
+    };
+    let header = |name: HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_lowercase)
+    };
+    let encoding = header(CONTENT_ENCODING);
+    let content_type = header(CONTENT_TYPE);
+
+    let bytes = response.into_body();
+    usage::record_download(bytes.len());
+
+    let bytes = decompress(bytes, encoding.as_ref().map(String::as_str))
+        .map_err(|err| invalid_body(err.to_string()))?;
+    let charset = content_type
+        .as_ref()
+        .and_then(|content_type| charset_parameter(content_type))
+        .or_else(|| meta_charset(&bytes));
+    Ok(decode(bytes, charset.as_ref().map(String::as_str)).map_err(invalid_body)?)
+}
+
+/// Undo the `Content-Encoding` of a response body.
+fn decompress(bytes: Vec<u8>, encoding: Option<&str>) -> std::io::Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    match encoding.map(str::trim) {
+        Some("gzip") | Some("x-gzip") => {
+            GzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+        }
+        // Should be zlib-wrapped, but some servers send raw deflate data
+        Some("deflate") => {
+            if ZlibDecoder::new(bytes.as_slice())
+                .read_to_end(&mut decoded)
+                .is_err()
+            {
+                decoded.clear();
+                DeflateDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+            }
+        }
+        _ => return Ok(bytes),
+    }
+    Ok(decoded)
+}
+
+/// The `charset` parameter of a `Content-Type` value, e.g. "iso-8859-1".
+fn charset_parameter(content_type: &str) -> Option<String> {
+    content_type
+        .split(';')
+        .filter_map(|parameter| {
+            let mut pair = parameter.splitn(2, '=');
+            if pair.next()?.trim() != "charset" {
+                return None;
+            }
+            Some(pair.next()?.trim().trim_matches('"').to_lowercase())
+        })
+        .next()
+}
+
+/// The charset an HTML page declares in a `<meta>` tag near its start.
+fn meta_charset(bytes: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).to_lowercase();
+    let start = head.find("charset=")? + "charset=".len();
+    let charset = head[start..]
+        .trim_start_matches(|c: char| c == '"' || c == '\'')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect::<String>();
+    Some(charset).filter(|charset| !charset.is_empty())
+}
+
+/// Decode a body in `charset`, UTF-8 if none is declared.
+///
+/// Undeclared bodies that aren't valid UTF-8 are taken as Windows-1252 like browsers do, which
+/// the VV occasionally serves without saying so.
+fn decode(bytes: Vec<u8>, charset: Option<&str>) -> Result<String, String> {
+    let single_byte = |bytes: &[u8], to_char: fn(u8) -> char| {
+        bytes.iter().map(|byte| to_char(*byte)).collect::<String>()
+    };
+    match charset {
+        None => Ok(String::from_utf8(bytes)
+            .unwrap_or_else(|err| single_byte(err.as_bytes(), windows_1252))),
+        Some("utf-8") | Some("utf8") | Some("us-ascii") => {
+            String::from_utf8(bytes).map_err(|err| err.to_string())
+        }
+        Some("iso-8859-1") | Some("latin1") => Ok(single_byte(&bytes, char::from)),
+        Some("iso-8859-15") | Some("latin9") => Ok(single_byte(&bytes, iso_8859_15)),
+        Some("windows-1252") | Some("cp1252") => Ok(single_byte(&bytes, windows_1252)),
+        Some(other) => Err(format!("unsupported charset {}", other)),
+    }
+}
+
+/// Characters of Windows-1252 at 0x80 to 0x9F, where ISO-8859-1 has control characters. The five
+/// unassigned bytes are kept as those.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+/// A byte in Windows-1252, which is ISO-8859-1 with printable characters at 0x80 to 0x9F.
+fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9f => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
+
+/// A byte in ISO-8859-15, which replaces eight characters of ISO-8859-1, e.g. "¤" by "€".
+fn iso_8859_15(byte: u8) -> char {
+    match byte {
+        0xa4 => '€',
+        0xa6 => 'Š',
+        0xa8 => 'š',
+        0xb4 => 'Ž',
+        0xb8 => 'ž',
+        0xbc => 'Œ',
+        0xbd => 'œ',
+        0xbe => 'Ÿ',
+        _ => char::from(byte),
+    }
+}
+
+/// Delay requested by the `Retry-After` header, given either in seconds or as an HTTP date.
+fn retry_after(response: &Response<Vec<u8>>) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    let wait = match value.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => {
+            let date = DateTime::parse_from_rfc2822(value).ok()?;
+            (date.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .unwrap_or_else(|_| Duration::from_secs(0))
+        }
+    };
+
+    Some(wait.min(MAX_RETRY_AFTER))
+}
+
+/// Hold back all requests for `duration`, unless they are already held back for longer.
+fn throttle(duration: Duration) {
+    let until = Instant::now() + duration;
+    let mut throttled_until = THROTTLED_UNTIL.lock().unwrap();
+    if throttled_until
+        .map(|current| current < until)
+        .unwrap_or(true)
+    {
+        *throttled_until = Some(until);
+    }
+}
+
+/// Wait until the request delay since the previous request has passed, and reserve the next slot.
+async fn wait_for_turn() {
+    let now = Instant::now();
+    let start = {
+        let mut next_request = NEXT_REQUEST.lock().unwrap();
+        let start = next_request.map(|next| next.max(now)).unwrap_or(now);
+        let request_delay = *REQUEST_DELAY.lock().unwrap();
+        let request_delay = robots::crawl_delay()
+            .map_or(request_delay, |crawl_delay| crawl_delay.max(request_delay));
+        *next_request = Some(start + request_delay);
+        start
+    };
+    if start > now {
+        delay(start).await;
+    }
+}
+
+async fn wait_for_throttle() {
+    loop {
+        let throttled_until = *THROTTLED_UNTIL.lock().unwrap();
+        match throttled_until {
+            Some(until) if until > Instant::now() => delay(until).await,
+            _ => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::Write;
+
+    const PAGE: &str = "<h1>Übung zur Vorlesung</h1>";
+
+    #[test]
+    fn decompresses_bodies() {
+        let mut gzip = GzEncoder::new(vec![], Compression::default());
+        gzip.write_all(PAGE.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        assert_eq!(
+            decompress(gzip.clone(), Some("gzip")).unwrap(),
+            PAGE.as_bytes()
+        );
+        assert_eq!(decompress(gzip, Some(" x-gzip")).unwrap(), PAGE.as_bytes());
+
+        let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+        zlib.write_all(PAGE.as_bytes()).unwrap();
+        let zlib = zlib.finish().unwrap();
+        assert_eq!(decompress(zlib, Some("deflate")).unwrap(), PAGE.as_bytes());
+
+        // Raw deflate data as sent by some servers for "deflate"
+        let mut raw = DeflateEncoder::new(vec![], Compression::default());
+        raw.write_all(PAGE.as_bytes()).unwrap();
+        let raw = raw.finish().unwrap();
+        assert_eq!(decompress(raw, Some("deflate")).unwrap(), PAGE.as_bytes());
+
+        let plain = PAGE.as_bytes().to_vec();
+        assert_eq!(decompress(plain.clone(), None).unwrap(), plain);
+        assert_eq!(decompress(plain.clone(), Some("identity")).unwrap(), plain);
+        assert!(decompress(plain, Some("gzip")).is_err());
+    }
+
+    #[test]
+    fn charset_parameters() {
+        let charset = |content_type| charset_parameter(content_type);
+        assert_eq!(charset("text/html; charset=UTF-8").unwrap(), "utf-8");
+        assert_eq!(
+            charset("text/html;charset=\"ISO-8859-1\"").unwrap(),
+            "iso-8859-1"
+        );
+        assert_eq!(
+            charset("text/html; boundary=x; charset=windows-1252").unwrap(),
+            "windows-1252"
+        );
+        assert!(charset("text/html").is_none());
+        assert!(charset("text/html; charsets=utf-8").is_none());
+    }
+
+    #[test]
+    fn meta_charsets() {
+        let charset = |html: &str| meta_charset(html.as_bytes());
+        assert_eq!(charset("<meta charset=\"utf-8\">").unwrap(), "utf-8");
+        assert_eq!(
+            charset(
+                "<meta http-equiv=\"Content-Type\" content=\"text/html; charset=ISO-8859-15\">"
+            )
+            .unwrap(),
+            "iso-8859-15"
+        );
+        assert_eq!(
+            charset("<meta charset='windows-1252'/>").unwrap(),
+            "windows-1252"
+        );
+        assert!(charset("<meta charset=\"\">").is_none());
+        assert!(charset("<html><body>no declaration</body></html>").is_none());
+        // Only the start of the page is looked at
+        let late = format!("{}<meta charset=\"utf-8\">", " ".repeat(2000));
+        assert!(charset(&late).is_none());
+    }
+
+    #[test]
+    fn decodes_charsets() {
+        let utf8 = "„Übung“ – 5 €".as_bytes().to_vec();
+        assert_eq!(decode(utf8.clone(), None).unwrap(), "„Übung“ – 5 €");
+        assert_eq!(decode(utf8, Some("utf-8")).unwrap(), "„Übung“ – 5 €");
+        assert!(decode(vec![0xdc], Some("utf-8")).is_err());
+
+        // „Übung“ – — 5 €
+        let windows_1252 = vec![
+            0x84, 0xdc, 0x62, 0x75, 0x6e, 0x67, 0x93, 0x20, 0x96, 0x20, 0x97, 0x20, 0x35, 0x20,
+            0x80,
+        ];
+        let text = "„Übung“ – — 5 €";
+        assert_eq!(
+            decode(windows_1252.clone(), Some("windows-1252")).unwrap(),
+            text
+        );
+        // Undeclared and not UTF-8
+        assert_eq!(decode(windows_1252.clone(), None).unwrap(), text);
+        assert_eq!(
+            decode(windows_1252, Some("iso-8859-1")).unwrap(),
+            "\u{84}Übung\u{93} \u{96} \u{97} 5 \u{80}"
+        );
+
+        assert_eq!(
+            decode(vec![0x35, 0x20, 0xa4], Some("iso-8859-15")).unwrap(),
+            "5 €"
+        );
+        assert_eq!(
+            decode(vec![0x35, 0x20, 0xa4], Some("iso-8859-1")).unwrap(),
+            "5 ¤"
+        );
+        assert_eq!(decode(vec![0xa6, 0xbe], Some("iso-8859-15")).unwrap(), "ŠŸ");
+        assert!(decode(vec![0x35], Some("koi8-r")).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Marks for the first and the last regular session of a course, so it's visible in the calendar
+//! when a course kicks off and when to expect wrap-up and exam information.
+
+use crate::{Course, CourseEvent};
+
+/// Suffix of the summary of the first session.
+pub const FIRST_SUFFIX: &str = " (erster Termin)";
+/// Suffix of the summary of the last session.
+pub const LAST_SUFFIX: &str = " (letzter Termin)";
+
+/// Append `FIRST_SUFFIX` and `LAST_SUFFIX` to the summaries of the earliest and the latest
+/// session of `course`.
+///
+/// Exams don't count as sessions here. Courses with a single session stay unmarked.
+pub fn mark(course: &mut Course) {
+    let regular = || course.events.iter().filter(|event| !event.exam);
+    if regular().count() < 2 {
+        return;
+    }
+    let first = regular()
+        .min_by_key(|event| event.timespan.start)
+        .map(|event| event.id.clone());
+    let last = regular()
+        .max_by_key(|event| event.timespan.start)
+        .map(|event| event.id.clone());
+
+    let name = course.name.clone();
+    for event in course.events.iter_mut() {
+        if event.exam {
+            continue;
+        }
+        if first.as_ref() == Some(&event.id) {
+            append(event, &name, FIRST_SUFFIX);
+        } else if last.as_ref() == Some(&event.id) {
+            append(event, &name, LAST_SUFFIX);
+        }
+    }
+}
+
+fn append(event: &mut CourseEvent, name: &str, suffix: &str) {
+    let summary = event.summary.take().unwrap_or_else(|| name.to_owned());
+    event.summary = Some(format!("{}{}", summary, suffix));
+}
@@ -0,0 +1,194 @@
+use crate::appearance::Appearance;
+use crate::config::GoogleConfig;
+use crate::fetch::{self, HttpRequest};
+use crate::oauth::DeviceFlow;
+use crate::{Course, CourseEvent, Error, RequestedCourse, StdError};
+use chrono::Utc;
+use http::header::{AUTHORIZATION, CONTENT_TYPE};
+use http::{Method, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use tracing::info;
+
+const DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const CALENDAR_API: &str = "https://www.googleapis.com/calendar/v3";
+const SCOPE: &str = "https://www.googleapis.com/auth/calendar";
+
+/// Sync the events of `courses` into the Google Calendar configured in `google`.
+///
+/// Google events get their id from the VV event id, so repeated syncs update events in place.
+/// Events created by an earlier sync that are no longer part of any course are deleted.
+pub async fn sync(google: &GoogleConfig, courses: &[RequestedCourse]) -> Result<(), StdError> {
+    let flow = DeviceFlow {
+        service: "your Google Calendar",
+        device_code_url: DEVICE_CODE_URL,
+        token_url: TOKEN_URL,
+        client_id: &google.client_id,
+        client_secret: Some(&google.client_secret),
+        scope: SCOPE,
+        token_file: &google.token_file,
+    };
+    let api = CalendarApi {
+        token: flow.access_token().await?,
+        calendar: google.calendar.clone(),
+    };
+
+    let existing = api.list_managed_events().await?;
+    let mut synced = HashSet::new();
+    for requested in courses.iter() {
+        let course = requested.get_course().await?;
+        for event in course.events.iter() {
+            let id = google_event_id(event);
+            let body = event_json(&id, &course, event, &requested.options.appearance);
+            if existing.contains(&id) {
+                api.update(&id, &body).await?;
+            } else {
+                api.insert(&id, &body).await?;
+            }
+            synced.insert(id);
+        }
+        info!(
+            events = course.events.len() as u64,
+            course = course.name.as_str(),
+            "synced to Google Calendar"
+        );
+    }
+
+    for id in existing.difference(&synced) {
+        api.delete(id).await?;
+    }
+
+    Ok(())
+}
+
+/// Google Calendar event ids only allow the characters of base32hex, so the VV id is hex-encoded.
+fn google_event_id(event: &CourseEvent) -> String {
+    let hex = event
+        .id
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!("fuvv{}", hex)
+}
+
+fn event_json(id: &str, course: &Course, event: &CourseEvent, appearance: &Appearance) -> Value {
+    let mut value = json!({
+        "id": id,
+        "summary": course.name,
+        "start": { "dateTime": event.timespan.start.with_timezone(&Utc).to_rfc3339() },
+        "end": { "dateTime": event.timespan.end.with_timezone(&Utc).to_rfc3339() },
+        "status": "confirmed",
+        "extendedProperties": {
+            // Marks events managed by fu-vv-cal, see `CalendarApi::list_managed_events`
+            "private": { "fuVvCal": "true", "fuVvCalEvent": event.id },
+        },
+    });
+    appearance.apply_google(&mut value);
+    value
+}
+
+struct CalendarApi {
+    token: String,
+    calendar: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventList {
+    #[serde(default)]
+    items: Vec<EventListItem>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventListItem {
+    id: String,
+}
+
+impl CalendarApi {
+    /// Ids of all events in the calendar that were created by fu-vv-cal.
+    async fn list_managed_events(&self) -> Result<HashSet<String>, StdError> {
+        let mut ids = HashSet::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut path =
+                "/events?privateExtendedProperty=fuVvCal%3Dtrue&maxResults=2500".to_owned();
+            if let Some(page_token) = &page_token {
+                path.push_str(&format!("&pageToken={}", encode(page_token)));
+            }
+
+            let (status, body) = self.call(Method::GET, &path, None).await?;
+            check_response(status, &body)?;
+            let list: EventList = serde_json::from_str(&body)?;
+            ids.extend(list.items.into_iter().map(|item| item.id));
+
+            match list.next_page_token {
+                Some(next_page_token) => page_token = Some(next_page_token),
+                None => return Ok(ids),
+            }
+        }
+    }
+
+    async fn insert(&self, id: &str, event: &Value) -> Result<(), StdError> {
+        let (status, body) = self.call(Method::POST, "/events", Some(event)).await?;
+        // The id is still taken by an event that was deleted from the calendar
+        if status == StatusCode::CONFLICT {
+            return self.update(id, event).await;
+        }
+        check_response(status, &body)
+    }
+
+    async fn update(&self, id: &str, event: &Value) -> Result<(), StdError> {
+        let path = format!("/events/{}", id);
+        let (status, body) = self.call(Method::PUT, &path, Some(event)).await?;
+        check_response(status, &body)
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), StdError> {
+        let path = format!("/events/{}", id);
+        let (status, body) = self.call(Method::DELETE, &path, None).await?;
+        // Already gone
+        if status == StatusCode::GONE {
+            return Ok(());
+        }
+        check_response(status, &body)
+    }
+
+    async fn call(
+        &self,
+        method: Method,
+        path: &str,
+        event: Option<&Value>,
+    ) -> Result<(StatusCode, String), StdError> {
+        let url = format!(
+            "{}/calendars/{}{}",
+            CALENDAR_API,
+            encode(&self.calendar),
+            path
+        );
+        let body = event.map(Value::to_string).unwrap_or_default();
+        let req = HttpRequest::new(method, url)
+            .header(AUTHORIZATION, format!("Bearer {}", self.token))?
+            .header(CONTENT_TYPE, "application/json")?
+            .with_body(body);
+
+        fetch::send(req).await
+    }
+}
+
+fn check_response(status: StatusCode, body: &str) -> Result<(), StdError> {
+    if !status.is_success() {
+        return Err(Error::GoogleApiError {
+            status: status.as_u16(),
+            message: body.to_owned(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
@@ -0,0 +1,104 @@
+//! Courses whose VV page only links their parts, e.g. a module entry with a Vorlesung and an
+//! Übung listed as courses of their own.
+//!
+//! Instead of configuring the id of every part, such a parent course is configured with
+//! `children`: `separate` replaces it by its parts, each with its own calendar (see
+//! `Config::expand_children`), `merged` exports the sessions of all parts in one calendar, with
+//! the type of each part as its category (see `Merged`).
+
+use crate::lecturer::course_id_from_link;
+use crate::source::{CourseFuture, CourseSource};
+use crate::{fetch, Error, StdError};
+use select::document::Document;
+use select::predicate::Name;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::debug;
+
+/// How the parts of a parent course are exported.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChildMode {
+    /// A calendar per part
+    Separate,
+    /// One calendar with the sessions of all parts
+    Merged,
+}
+
+/// Ids of the courses linked from the page of course `id` of `source`, in the order of the page.
+pub async fn child_ids(
+    source: &dyn CourseSource,
+    id: &str,
+    semester: &str,
+) -> Result<Vec<String>, StdError> {
+    let url = source.course_url(id, semester);
+    let (status, body) = fetch::get(&url).await?;
+    if !status.is_success() {
+        return Err(Error::HttpRequestError {
+            url,
+            status: status.as_u16(),
+        }
+        .into());
+    }
+
+    let document = Document::from(body.as_str());
+    let mut ids: Vec<String> = vec![];
+    for href in document
+        .find(Name("a"))
+        .filter_map(|node| node.attr("href"))
+    {
+        if let Some(child) = course_id_from_link(href) {
+            if child != id && !ids.contains(&child) {
+                ids.push(child);
+            }
+        }
+    }
+    debug!(course = id, children = ids.len(), "found child courses");
+    Ok(ids)
+}
+
+/// Source of a parent course with the sessions of its parts, each labelled with the type of its
+/// part (e.g. "Übung") unless the VV gives one.
+#[derive(Debug)]
+pub struct Merged {
+    inner: Arc<dyn CourseSource>,
+}
+
+impl Merged {
+    pub fn new(inner: Arc<dyn CourseSource>) -> Self {
+        Self { inner }
+    }
+}
+
+impl CourseSource for Merged {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn course_url(&self, id: &str, semester: &str) -> String {
+        self.inner.course_url(id, semester)
+    }
+
+    fn get_course<'a>(&'a self, id: &'a str, semester: &'a str) -> CourseFuture<'a> {
+        Box::pin(async move {
+            let mut course = self.inner.get_course(id, semester).await?;
+            for child_id in child_ids(&*self.inner, id, semester).await? {
+                let child = self.inner.get_course(&child_id, semester).await?;
+                let part = child.kind.clone().unwrap_or_else(|| child.name.clone());
+                for mut event in child.events {
+                    if course.events.iter().any(|known| known.id == event.id) {
+                        continue;
+                    }
+                    if event.part.is_none() {
+                        event.part = Some(part.clone());
+                    }
+                    course.events.push(event);
+                }
+            }
+            course
+                .events
+                .sort_by(|a, b| a.timespan.start.cmp(&b.timespan.start));
+            Ok(course)
+        })
+    }
+}
@@ -0,0 +1,284 @@
+//! Days without lectures: the breaks of the FU academic calendar (see `semester`) and the public
+//! holidays of Berlin, so sessions the VV lists on them (e.g. weekly dates running through the
+//! Christmas break) can be dropped or marked.
+
+use crate::semester::Semester;
+use crate::{Course, Error};
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// What happens to sessions on lecture-free days.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LectureFreeMode {
+    /// Export them unchanged
+    Keep,
+    /// Export them with a remark naming the holiday or break
+    Annotate,
+    /// Leave them out, exams excepted
+    Drop,
+}
+
+impl Default for LectureFreeMode {
+    fn default() -> Self {
+        LectureFreeMode::Keep
+    }
+}
+
+impl FromStr for LectureFreeMode {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "keep" => Ok(LectureFreeMode::Keep),
+            "annotate" => Ok(LectureFreeMode::Annotate),
+            "drop" => Ok(LectureFreeMode::Drop),
+            _ => Err(Error::InvalidLectureFreeMode {
+                text: text.to_owned(),
+            }),
+        }
+    }
+}
+
+/// Name of the public holiday in Berlin on `date`, if any.
+pub fn public_holiday(date: NaiveDate) -> Option<&'static str> {
+    let year = date.year();
+    let easter = easter_sunday(year);
+    let fixed = [
+        (NaiveDate::from_ymd(year, 1, 1), "Neujahr"),
+        (NaiveDate::from_ymd(year, 5, 1), "Tag der Arbeit"),
+        (
+            NaiveDate::from_ymd(year, 10, 3),
+            "Tag der Deutschen Einheit",
+        ),
+        (NaiveDate::from_ymd(year, 12, 25), "1. Weihnachtsfeiertag"),
+        (NaiveDate::from_ymd(year, 12, 26), "2. Weihnachtsfeiertag"),
+        (easter - Duration::days(2), "Karfreitag"),
+        (easter + Duration::days(1), "Ostermontag"),
+        (easter + Duration::days(39), "Christi Himmelfahrt"),
+        (easter + Duration::days(50), "Pfingstmontag"),
+    ];
+    if let Some((_, name)) = fixed.iter().find(|(day, _)| *day == date) {
+        return Some(name);
+    }
+    // Berlin holiday since 2019
+    if year >= 2019 && date == NaiveDate::from_ymd(year, 3, 8) {
+        return Some("Internationaler Frauentag");
+    }
+    None
+}
+
+/// Why there are no lectures on `date`: a public holiday or a break of its semester.
+pub fn lecture_free_reason(date: NaiveDate) -> Option<String> {
+    if let Some(holiday) = public_holiday(date) {
+        return Some(format!("Feiertag: {}", holiday));
+    }
+    let semester = Semester::containing(date)?;
+    let in_break = semester
+        .breaks
+        .iter()
+        .any(|(from, until)| date >= *from && date <= *until);
+    if in_break {
+        Some("Vorlesungsfreie Zeit".to_owned())
+    } else {
+        None
+    }
+}
+
+/// Drop or annotate the sessions of `course` on lecture-free days, returning how many there were.
+pub fn apply(course: &mut Course, mode: LectureFreeMode) -> usize {
+    if mode == LectureFreeMode::Keep {
+        return 0;
+    }
+
+    let mut affected = 0;
+    let events = std::mem::replace(&mut course.events, vec![]);
+    for mut event in events {
+        let reason = match lecture_free_reason(event.timespan.start.naive_local().date()) {
+            Some(reason) => reason,
+            None => {
+                course.events.push(event);
+                continue;
+            }
+        };
+        affected += 1;
+        if mode == LectureFreeMode::Drop && !event.exam {
+            continue;
+        }
+        event.remark = Some(match event.remark.take() {
+            Some(remark) => format!("{}\n{}", reason, remark),
+            None => reason,
+        });
+        course.events.push(event);
+    }
+    affected
+}
+
+/// Easter Sunday of `year` in the Gregorian calendar (anonymous Gregorian algorithm).
+fn easter_sunday(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd(year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registration::Registration;
+    use crate::{timespan_from_parts, CourseEvent};
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    fn session(id: &str, day: &str, exam: bool) -> CourseEvent {
+        CourseEvent {
+            id: id.to_owned(),
+            timespan: timespan_from_parts(day, "10:00", "12:00").unwrap(),
+            sequence: 0,
+            week: None,
+            part: None,
+            remark: Some("Hörsaal 1a".to_owned()),
+            room: None,
+            lecturers: vec![],
+            detail_url: None,
+            exam,
+            group: None,
+            summary: None,
+            all_day: false,
+        }
+    }
+
+    /// A course with sessions on a lecture day, a holiday, in the Christmas break and an exam on
+    /// a holiday.
+    fn course() -> Course {
+        Course {
+            id: Some("10101".to_owned()),
+            name: "Algorithmen und Datenstrukturen".to_owned(),
+            kind: Some("Vorlesung".to_owned()),
+            events: vec![
+                session("500001", "17.10.2019", false),
+                session("500002", "03.10.2019", false),
+                session("500003", "30.12.2019", false),
+                session("500004", "26.12.2019", true),
+            ],
+            cancelled: vec![],
+            url: None,
+            color: None,
+            sws: None,
+            ects: None,
+            modules: vec![],
+            language: None,
+            registration: Registration::default(),
+            warnings: vec![],
+        }
+    }
+
+    fn ids(course: &Course) -> Vec<&str> {
+        course
+            .events
+            .iter()
+            .map(|event| event.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn computes_easter() {
+        assert_eq!(easter_sunday(2019), date(2019, 4, 21));
+        assert_eq!(easter_sunday(2020), date(2020, 4, 12));
+        assert_eq!(public_holiday(date(2019, 4, 19)), Some("Karfreitag"));
+        assert_eq!(public_holiday(date(2020, 4, 13)), Some("Ostermontag"));
+        assert_eq!(
+            public_holiday(date(2020, 5, 21)),
+            Some("Christi Himmelfahrt")
+        );
+        assert_eq!(public_holiday(date(2020, 6, 1)), Some("Pfingstmontag"));
+        assert_eq!(public_holiday(date(2020, 4, 12)), None);
+    }
+
+    #[test]
+    fn frauentag_since_2019() {
+        assert_eq!(public_holiday(date(2018, 3, 8)), None);
+        assert_eq!(
+            public_holiday(date(2019, 3, 8)),
+            Some("Internationaler Frauentag")
+        );
+        assert_eq!(
+            public_holiday(date(2020, 3, 8)),
+            Some("Internationaler Frauentag")
+        );
+    }
+
+    #[test]
+    fn reasons_for_lecture_free_days() {
+        assert_eq!(
+            lecture_free_reason(date(2019, 10, 3))
+                .as_ref()
+                .map(String::as_str),
+            Some("Feiertag: Tag der Deutschen Einheit")
+        );
+        assert_eq!(
+            lecture_free_reason(date(2019, 12, 30))
+                .as_ref()
+                .map(String::as_str),
+            Some("Vorlesungsfreie Zeit")
+        );
+        assert_eq!(lecture_free_reason(date(2019, 10, 17)), None);
+    }
+
+    #[test]
+    fn keeps_sessions() {
+        let mut course = course();
+        assert_eq!(apply(&mut course, LectureFreeMode::Keep), 0);
+        assert_eq!(ids(&course), vec!["500001", "500002", "500003", "500004"]);
+        assert!(course
+            .events
+            .iter()
+            .all(|event| event.remark.as_ref().map(String::as_str) == Some("Hörsaal 1a")));
+    }
+
+    #[test]
+    fn drops_sessions_but_not_exams() {
+        let mut course = course();
+        assert_eq!(apply(&mut course, LectureFreeMode::Drop), 3);
+        assert_eq!(ids(&course), vec!["500001", "500004"]);
+        assert_eq!(
+            course.events[1].remark.as_ref().map(String::as_str),
+            Some("Feiertag: 2. Weihnachtsfeiertag\nHörsaal 1a")
+        );
+    }
+
+    #[test]
+    fn annotates_sessions() {
+        let mut course = course();
+        assert_eq!(apply(&mut course, LectureFreeMode::Annotate), 3);
+        assert_eq!(ids(&course), vec!["500001", "500002", "500003", "500004"]);
+        let remarks = course
+            .events
+            .iter()
+            .map(|event| event.remark.as_ref().map(String::as_str).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            remarks,
+            vec![
+                "Hörsaal 1a",
+                "Feiertag: Tag der Deutschen Einheit\nHörsaal 1a",
+                "Vorlesungsfreie Zeit\nHörsaal 1a",
+                "Feiertag: 2. Weihnachtsfeiertag\nHörsaal 1a",
+            ]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use crate::changes::Change;
+use serde::Deserialize;
+use std::process::Command;
+use tracing::warn;
+
+/// External commands run after exporting, to integrate with other systems.
+///
+/// Commands are run with `sh -c`, after replacing the placeholders with shell-quoted values.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Hooks {
+    /// Run for every written file, with `{file}`
+    pub post_generate: Option<String>,
+    /// Run for every course with changes, with `{course}` (id), `{course_name}` and `{changes}`
+    /// (the changes, one per line)
+    pub on_change: Option<String>,
+}
+
+impl Hooks {
+    pub fn post_generate(&self, file: &str) {
+        if let Some(command) = &self.post_generate {
+            run(command, &[("file", file)]);
+        }
+    }
+
+    pub fn on_change(&self, changes: &[Change]) {
+        let command = match &self.on_change {
+            Some(command) => command,
+            None => return,
+        };
+
+        let mut course_ids = changes
+            .iter()
+            .map(|change| change.course_id.as_str())
+            .collect::<Vec<_>>();
+        course_ids.sort();
+        course_ids.dedup();
+        for course_id in course_ids {
+            let course_changes = changes
+                .iter()
+                .filter(|change| change.course_id == course_id)
+                .collect::<Vec<_>>();
+            let text = course_changes
+                .iter()
+                .map(|change| change.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            run(
+                command,
+                &[
+                    ("course", course_id),
+                    ("course_name", &course_changes[0].course_name),
+                    ("changes", &text),
+                ],
+            );
+        }
+    }
+}
+
+/// Run a hook, only warning if it fails so the export itself still succeeds.
+fn run(template: &str, values: &[(&str, &str)]) {
+    let mut command = template.to_owned();
+    for (key, value) in values.iter() {
+        command = command.replace(&format!("{{{}}}", key), &shell_quote(value));
+    }
+
+    match Command::new("sh").arg("-c").arg(&command).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!(command = command.as_str(), %status, "hook failed"),
+        Err(err) => warn!(command = command.as_str(), %err, "hook could not be run"),
+    }
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
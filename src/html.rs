@@ -0,0 +1,92 @@
+//! Standalone HTML page with a week view of the courses, e.g. for a student council website.
+
+use crate::digest::escape_html;
+use crate::weeks::{self, GridEntry, WEEKDAYS};
+use crate::{Course, StdError};
+use std::path::Path;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; width: 100%; table-layout: fixed; }
+th, td { border: 1px solid #ccc; padding: 0.4em; vertical-align: top; }
+th { background: #004659; color: #fff; }
+td.time { white-space: nowrap; font-weight: bold; width: 7em; }
+.entry { margin-bottom: 0.4em; }
+.entry a { color: #004659; }
+.room { color: #666; font-size: 0.9em; }
+footer { margin-top: 1em; color: #666; font-size: 0.8em; }
+";
+
+/// Page titled `title` with the sessions of `courses` as a typical week, see
+/// `weeks::week_grid`, linking every course to its VV page.
+pub fn document(title: &str, courses: &[Course]) -> String {
+    let slots = weeks::week_grid(courses);
+    // Saturday only if anything takes place on it
+    let days = WEEKDAYS
+        .iter()
+        .enumerate()
+        .filter(|(day, _)| *day < 5 || slots.iter().any(|slot| !slot.days[*day].is_empty()))
+        .collect::<Vec<_>>();
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title>\n\
+         <style>{}</style></head>\n<body>\n<h1>{}</h1>\n",
+        escape_html(title),
+        STYLE,
+        escape_html(title)
+    );
+    if slots.is_empty() {
+        html.push_str("<p>Keine Termine.</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Zeit</th>");
+        for (_, (_, name)) in days.iter() {
+            html.push_str(&format!("<th>{}</th>", name));
+        }
+        html.push_str("</tr>\n");
+        for slot in slots.iter() {
+            html.push_str(&format!(
+                "<tr><td class=\"time\">{}–{}</td>",
+                slot.start.format("%H:%M"),
+                slot.end.format("%H:%M")
+            ));
+            for (day, _) in days.iter() {
+                html.push_str("<td>");
+                for entry in slot.days[*day].iter() {
+                    html.push_str(&entry_html(entry));
+                }
+                html.push_str("</td>");
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str(&format!(
+        "<footer>Erstellt mit fu-vv-cal {}</footer>\n</body>\n</html>\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    html
+}
+
+pub fn save<P: AsRef<Path>>(content: &str, path: P) -> Result<(), StdError> {
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+fn entry_html(entry: &GridEntry) -> String {
+    let title = match &entry.url {
+        Some(url) => format!(
+            "<a href=\"{}\">{}</a>",
+            escape_html(url),
+            escape_html(&entry.title)
+        ),
+        None => escape_html(&entry.title),
+    };
+    match &entry.room {
+        Some(room) => format!(
+            "<div class=\"entry\">{}<br><span class=\"room\">{}</span></div>",
+            title,
+            escape_html(room)
+        ),
+        None => format!("<div class=\"entry\">{}</div>", title),
+    }
+}
@@ -0,0 +1,177 @@
+use crate::campus;
+use crate::merge::{UidCollision, UidRegistry};
+use crate::options::CalendarOptions;
+use crate::uid;
+use crate::{Course, CourseEvent, StdError};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+/// JSCalendar (RFC 8984) `Group` with the events of `course`, the counterpart of
+/// `Course::to_ical`.
+pub fn course_group(course: Course, updated: DateTime<Utc>, options: &CalendarOptions) -> Value {
+    let name = course.name.clone();
+    let course_id = course.id.clone();
+    let entries = events(course, updated, options, |event| {
+        uid::for_event(options, course_id.as_ref().map(String::as_str), &event.id)
+    });
+    group(&name, entries, updated)
+}
+
+/// One `Group` named `name` with the events of all `courses`, rewriting colliding UIDs like
+/// `merge::merge_courses`.
+pub fn merged_group(
+    name: &str,
+    courses: Vec<(String, Course)>,
+    updated: DateTime<Utc>,
+    options: &CalendarOptions,
+) -> (Value, Vec<UidCollision>) {
+    let mut registry = UidRegistry::new();
+    let mut entries = vec![];
+    for (source, course) in courses {
+        entries.extend(events(course, updated, options, |event| {
+            registry.claim(&uid::for_event(options, Some(&source), &event.id), &source)
+        }));
+    }
+
+    (group(name, entries, updated), registry.into_collisions())
+}
+
+pub fn save<P: AsRef<Path>>(group: &Value, path: P) -> Result<(), StdError> {
+    std::fs::write(path, to_string(group)?)?;
+    Ok(())
+}
+
+pub fn to_string(group: &Value) -> Result<String, StdError> {
+    Ok(serde_json::to_string_pretty(group)?)
+}
+
+fn group(title: &str, entries: Vec<Value>, updated: DateTime<Utc>) -> Value {
+    json!({
+        "@type": "Group",
+        "uid": format!("fu-vv-cal-{}", title),
+        "updated": timestamp(updated),
+        "title": title,
+        "entries": entries,
+    })
+}
+
+fn events<F>(
+    course: Course,
+    updated: DateTime<Utc>,
+    options: &CalendarOptions,
+    mut uid_for: F,
+) -> Vec<Value>
+where
+    F: FnMut(&CourseEvent) -> String,
+{
+    let name = course.name;
+    let kind = course.kind;
+    let url = course.url;
+    let color = course.color;
+    let modules = course.modules;
+    let all_events = course
+        .events
+        .into_iter()
+        .map(|event| (event, false))
+        .chain(course.cancelled.into_iter().map(|event| (event, true)))
+        .collect::<Vec<_>>();
+    let uids = all_events
+        .iter()
+        .map(|(event, _)| uid_for(event))
+        .collect::<Vec<_>>();
+    let first_id = match uids.first() {
+        Some(first_id) => first_id.clone(),
+        None => return vec![],
+    };
+
+    all_events
+        .into_iter()
+        .zip(uids)
+        .map(|((event, cancelled), uid)| {
+            let start = event.timespan.start;
+            let mut related_to = Map::new();
+            related_to.insert(
+                first_id.clone(),
+                json!({ "@type": "Relation", "relation": { "parent": true } }),
+            );
+
+            let mut value = json!({
+                "@type": "Event",
+                "uid": uid,
+                "updated": timestamp(updated),
+                "title": event.summary.as_ref().unwrap_or(&name),
+                "start": start.naive_local().format("%Y-%m-%dT%H:%M:%S").to_string(),
+                "timeZone": start.timezone().name(),
+                "duration": duration(event.duration()),
+                "sequence": event.sequence,
+                "status": if cancelled { "cancelled" } else { "confirmed" },
+                "relatedTo": related_to,
+            });
+            let description = event
+                .week
+                .map(|week| format!("Woche {}", week))
+                .into_iter()
+                .chain(event.remark.clone())
+                .collect::<Vec<_>>();
+            if !description.is_empty() {
+                value["description"] = description.join("\n").into();
+            }
+            if event.all_day {
+                value["showWithoutTime"] = true.into();
+                value["duration"] = format!("P{}D", event.duration().num_days()).into();
+            }
+            if let Some(url) = event.session_url(url.as_ref().map(String::as_str)) {
+                value["links"] =
+                    json!({ "vv": { "@type": "Link", "href": url, "rel": "describedby" } });
+            }
+            if event.exam {
+                value["keywords"] = json!({ "EXAM": true });
+                value["alerts"] = json!({
+                    "week": { "@type": "Alert", "trigger": { "@type": "OffsetTrigger", "offset": "-P7D" } },
+                    "day": { "@type": "Alert", "trigger": { "@type": "OffsetTrigger", "offset": "-P1D" } },
+                });
+            } else if let Some(category) = event.part.as_ref().or_else(|| kind.as_ref()) {
+                let mut keywords = Map::new();
+                keywords.insert(category.clone(), true.into());
+                value["keywords"] = keywords.into();
+            }
+            for module in modules.iter() {
+                if value["keywords"].is_null() {
+                    value["keywords"] = Map::new().into();
+                }
+                value["keywords"][format!("Modul {}", module)] = true.into();
+            }
+            if let Some(room) = &event.room {
+                let mut location = json!({ "@type": "Location", "name": room });
+                if let Some(building) = campus::building_of(room, &options.buildings) {
+                    location["description"] = building.address(room).into();
+                    if let Some(geo_uri) = building.geo_uri() {
+                        location["coordinates"] = geo_uri.into();
+                    }
+                }
+                value["locations"] = json!({ "room": location });
+            }
+            if let Some(color) = &color {
+                value["color"] = color.clone().into();
+            }
+            options.appearance.apply_jscalendar(&mut value);
+            value
+        })
+        .collect()
+}
+
+/// ISO 8601 duration like "PT1H30M".
+fn duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    let (hours, minutes) = (minutes / 60, minutes % 60);
+    match (hours, minutes) {
+        (0, minutes) => format!("PT{}M", minutes),
+        (hours, 0) => format!("PT{}H", hours),
+        (hours, minutes) => format!("PT{}H{}M", hours, minutes),
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
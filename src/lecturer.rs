@@ -0,0 +1,62 @@
+use crate::fetch::{self, Locale};
+use crate::source::FuVv;
+use crate::{Error, RequestedCourse, StdError};
+
+use select::document::Document;
+use select::predicate::Name;
+use std::sync::Arc;
+
+/// All courses linked from a lecturer's VV page, e.g. to build their teaching calendar.
+///
+/// Courses are requested for `semester`, regardless of the semester the page links to, and parsed
+/// with the fast-path parser from the VV in `locale`, `lenient`ly if set.
+pub async fn courses_of(
+    url: &str,
+    semester: &str,
+    locale: Locale,
+    lenient: bool,
+) -> Result<Vec<RequestedCourse>, StdError> {
+    let (status, body) = fetch::get(url).await?;
+    if !status.is_success() {
+        return Err(Error::HttpRequestError {
+            url: url.to_owned(),
+            status: status.as_u16(),
+        }
+        .into());
+    }
+
+    let document = Document::from(body.as_str());
+    let mut ids: Vec<String> = vec![];
+    for href in document
+        .find(Name("a"))
+        .filter_map(|node| node.attr("href"))
+    {
+        if let Some(id) = course_id_from_link(href) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+
+    let source = FuVv::new()
+        .with_fast_parser()
+        .with_locale(locale)
+        .with_lenient(lenient);
+    let source = Arc::new(source);
+    Ok(ids
+        .into_iter()
+        .map(|id| RequestedCourse::new(id, semester).with_source(source.clone()))
+        .collect())
+}
+
+/// Course id of a link to a course page like `/vv/de/lv/524870?sm=498562`.
+pub(crate) fn course_id_from_link(href: &str) -> Option<String> {
+    let path = href.split(|c| c == '?' || c == '#').next().unwrap_or("");
+    let mut segments = path.trim_end_matches('/').rsplit('/');
+    let id = segments.next()?;
+    if segments.next()? != "lv" || id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(id.to_owned())
+}
@@ -0,0 +1,58 @@
+//! Lenient parsing: sessions whose rows can't be parsed (e.g. a malformed date) are skipped with a
+//! warning instead of failing their whole course, so one broken row doesn't cost the calendar.
+//!
+//! It is enabled per source (see `FuVv::lenient`), from the `lenient` setting of the config or
+//! `--lenient`.
+//!
+//! Warnings are returned with the parsed course (see `Course::warnings`), the export prints them
+//! and adds them to the JSON feed.
+
+use crate::StdError;
+use serde::Serialize;
+use serde_json::json;
+use tracing::warn;
+
+/// Fail with `err` about the session `row`, or add it to `warnings` if `lenient`.
+pub(crate) fn skip_session(
+    row: &str,
+    err: StdError,
+    lenient: bool,
+    warnings: &mut Vec<String>,
+) -> Result<(), StdError> {
+    if !lenient {
+        return Err(err);
+    }
+    let message = format!("skipped session \"{}\": {}", row, err);
+    warn!(%message, "lenient parsing");
+    warnings.push(message);
+    Ok(())
+}
+
+/// A problem of a course page that was skipped in lenient mode.
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseWarning {
+    pub course: Option<String>,
+    pub message: String,
+}
+
+impl ParseWarning {
+    /// Print the warning to stderr, as text or with `format` "json" as a JSON line like
+    /// `failure::Failure`.
+    pub fn report(&self, format: &str) {
+        match format {
+            "json" => eprintln!(
+                "{}",
+                json!({
+                    "course": self.course,
+                    "phase": "parse",
+                    "level": "warning",
+                    "message": self.message,
+                })
+            ),
+            _ => match &self.course {
+                Some(course) => eprintln!("Warning: course {}: {}", course, self.message),
+                None => eprintln!("Warning: {}", self.message),
+            },
+        }
+    }
+}
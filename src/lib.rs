@@ -0,0 +1,1512 @@
+use crate::registration::Registration;
+use crate::semester::Semester;
+use chrono::TimeZone;
+use chrono::{NaiveDate, NaiveTime};
+use chrono_tz::Europe::Berlin;
+use ics::components::{Alarm, Parameter, Property};
+use ics::properties::{Action, Trigger};
+use ics::properties::{
+    Categories, Description, DtEnd, DtStart, Location, Organizer, Sequence, Status, Summary,
+};
+use ics::{Event, ICalendar};
+use select::document::Document;
+use select::predicate::{Attr, Class, Name, Predicate};
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, Backtrace, ErrorCompat, ResultExt, Snafu};
+#[cfg(feature = "native")]
+use std::sync::Arc;
+use timespan::{DateTimeSpan, NaiveDateTimeSpan};
+
+pub mod appearance;
+pub mod atom;
+#[cfg(feature = "native")]
+mod backend;
+pub mod busy;
+pub mod cache;
+#[cfg(feature = "native")]
+pub mod caldav;
+pub mod campus;
+pub mod changes;
+pub mod checks;
+pub mod coalesce;
+pub mod colors;
+#[cfg(feature = "native")]
+pub mod completions;
+#[cfg(feature = "native")]
+pub mod config;
+#[cfg(feature = "native")]
+pub mod details;
+pub mod digest;
+pub mod duplicates;
+#[cfg(feature = "native")]
+pub mod enrollment;
+pub mod failure;
+pub mod fast;
+pub mod feed;
+#[cfg(feature = "native")]
+pub mod fetch;
+pub mod first_last;
+#[cfg(feature = "native")]
+pub mod google;
+#[cfg(feature = "native")]
+pub mod hierarchy;
+pub mod holidays;
+pub mod hooks;
+pub mod html;
+pub mod jscalendar;
+#[cfg(feature = "native")]
+pub mod lecturer;
+pub mod lenient;
+#[cfg(feature = "native")]
+pub mod lv;
+pub mod merge;
+pub mod metrics;
+pub mod milestones;
+pub mod model;
+#[cfg(feature = "native")]
+pub mod oauth;
+pub mod offsets;
+pub mod options;
+pub mod org;
+#[cfg(feature = "native")]
+pub mod outlook;
+#[cfg(feature = "native")]
+pub mod picker;
+pub mod progress;
+pub mod refresh;
+pub mod registration;
+#[cfg(feature = "native")]
+pub mod related;
+#[cfg(feature = "native")]
+pub mod reload;
+#[cfg(feature = "native")]
+pub mod robots;
+pub mod rules;
+pub mod sanitize;
+pub mod schema;
+#[cfg(feature = "native")]
+pub mod search;
+#[cfg(feature = "native")]
+pub mod selection;
+pub mod semester;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "native")]
+pub mod shutdown;
+#[cfg(feature = "native")]
+pub mod source;
+pub mod stats;
+#[cfg(feature = "native")]
+pub mod store;
+pub mod summary;
+#[cfg(feature = "native")]
+pub mod telemetry;
+pub mod timezone;
+pub mod travel;
+pub mod uid;
+pub mod update;
+pub mod usage;
+pub mod validate;
+#[cfg(feature = "native")]
+pub mod webhook;
+pub mod weeks;
+#[cfg(feature = "native")]
+pub mod xlsx;
+
+use merge::UidCollision;
+use options::CalendarOptions;
+#[cfg(feature = "native")]
+use progress::Progress;
+use rules::Rules;
+#[cfg(feature = "native")]
+use source::{CourseSource, FuVv};
+
+pub type StdError = Box<dyn std::error::Error>;
+
+/// Version of the VV markup the parser is written against, bumped whenever the parser is adapted
+/// to changed course pages.
+pub const LAYOUT_VERSION: &str = "2019-10";
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("The HTTP request to {} failed with status {}", url, status))]
+    HttpRequestError { url: String, status: u16 },
+    #[snafu(display("The request to {} timed out after {}s", url, timeout_secs))]
+    RequestTimeout { url: String, timeout_secs: u64 },
+    #[snafu(display(
+        "The robots.txt of the VV disallows {}, pass --ignore-robots to request it anyway",
+        url
+    ))]
+    DisallowedByRobots { url: String },
+    #[snafu(display("The request to {} failed: {}", url, message))]
+    TransportError { url: String, message: String },
+    #[snafu(display("{} served a {} instead of the course page", url, kind))]
+    Interstitial { url: String, kind: &'static str },
+    #[snafu(display("Unsupported locale \"{}\", use de or en", locale))]
+    InvalidLocale { locale: String },
+    #[snafu(display("Unknown course source \"{}\"", name))]
+    UnknownSource { name: String },
+    #[snafu(display("Unknown visibility \"{}\", use public, private or confidential", text))]
+    InvalidVisibility { text: String },
+    #[snafu(display("Invalid color \"{}\", use #rrggbb or a palette name", color))]
+    InvalidColor { color: String },
+    #[snafu(display("Invalid URL {}", url))]
+    InvalidUrl { url: String },
+    #[snafu(display(
+        "{} is no VV course page, like https://www.fu-berlin.de/vv/de/lv/524870",
+        url
+    ))]
+    InvalidCourseUrl { url: String },
+    #[snafu(display("No course with the LV number {} in semester {}", number, semester))]
+    UnknownLvNumber { number: String, semester: String },
+    #[snafu(display("The LV number {} matches several courses: {}", number, ids))]
+    AmbiguousLvNumber { number: String, ids: String },
+    #[snafu(display("The response from {} could not be read: {}", url, message))]
+    InvalidBody { url: String, message: String },
+    #[snafu(display("The course page has no {}", element))]
+    MissingElement { element: &'static str },
+    #[snafu(display(
+        "The page has no `{}` anymore, the VV layout probably changed. Page excerpt: {}",
+        selector,
+        snippet
+    ))]
+    PageStructureChanged {
+        selector: &'static str,
+        snippet: String,
+    },
+    #[snafu(display("Invalid session date \"{}\"", text))]
+    InvalidDate { text: String },
+    #[snafu(display("Failed to parse course {}: {}", id, message))]
+    CourseParseError { id: String, message: String },
+    #[snafu(display("No semester configured for course {}", id))]
+    MissingSemester { id: String },
+    #[snafu(display("No [{}] section in the config file", section))]
+    MissingConfigSection { section: String },
+    #[snafu(display("No state file configured, set `state` in the config file or pass --state"))]
+    MissingStateFile,
+    #[snafu(display("No semester given, pass --semester or set `semester` in the config file"))]
+    MissingDefaultSemester,
+    #[snafu(display("Invalid course in line {} of the course list: {}", line, text))]
+    InvalidCourseList { line: usize, text: String },
+    #[snafu(display("No fixture page for course {}", id))]
+    MissingFixture { id: String },
+    #[snafu(display(
+        "The state file has version {}, which is newer than this version of fu-vv-cal supports",
+        version
+    ))]
+    UnsupportedStateVersion { version: u64 },
+    #[snafu(display(
+        "Unsupported course data version {}, this version of fu-vv-cal reads up to {}",
+        version,
+        model::MODEL_VERSION
+    ))]
+    UnsupportedModelVersion { version: u64 },
+    #[snafu(display("Authorization failed: {}", message))]
+    AuthError { message: String },
+    #[snafu(display(
+        "No password for {}, set {} or store it in the system keyring",
+        username,
+        env
+    ))]
+    MissingCredentials { username: String, env: &'static str },
+    #[snafu(display("No `{}` in the [enrollment] section of the config file", key))]
+    MissingEnrollmentSetting { key: &'static str },
+    #[snafu(display("Signing in at {} failed, check the username and password", url))]
+    LoginFailed { url: String },
+    #[snafu(display("{} lists no courses of the VV", url))]
+    NoEnrolledCourses { url: String },
+    #[snafu(display(
+        "Google Calendar API request failed with status {}: {}",
+        status,
+        message
+    ))]
+    GoogleApiError { status: u16, message: String },
+    #[snafu(display(
+        "Microsoft Graph API request failed with status {}: {}",
+        status,
+        message
+    ))]
+    OutlookApiError { status: u16, message: String },
+    #[snafu(display("CalDAV request to {} failed with status {}", url, status))]
+    CalDavError { status: u16, url: String },
+    #[snafu(display("Invalid rule: {}", message))]
+    InvalidRule { message: String },
+    #[snafu(display("Invalid value \"{}\" of the environment variable {}", value, name))]
+    InvalidEnvVar { name: String, value: String },
+    #[snafu(display("Unknown UID scheme \"{}\", use stable or legacy", text))]
+    InvalidUidScheme { text: String },
+    #[snafu(display("Unknown time zone \"{}\", use an IANA name like Europe/London", name))]
+    InvalidTimezone { name: String },
+    #[snafu(display("Invalid TLS file {}: {}", path, message))]
+    InvalidTlsFile { path: String, message: String },
+    #[snafu(display("Set both tls_cert and tls_key in the [server] section to serve HTTPS"))]
+    IncompleteTlsConfig,
+    #[snafu(display("Invalid lecture-free mode \"{}\", use keep, annotate or drop", text))]
+    InvalidLectureFreeMode { text: String },
+    #[snafu(display("The time offsets of {} make sessions end before they start", course))]
+    InvalidTimeOffsets { course: String },
+    #[snafu(display("Invalid interval \"{}\", use e.g. 6h or 30m", text))]
+    InvalidInterval { text: String },
+    #[snafu(display("{} sessions clash with sessions of other courses", count))]
+    TimetableClash { count: usize },
+    #[snafu(display("{} of {} courses could not be exported", failed, total))]
+    PartialExport { failed: usize, total: usize },
+    #[snafu(display("Invalid course selection: {}", message))]
+    InvalidSelection { message: String },
+    #[snafu(display("The calendar to merge into has no END:VCALENDAR, it may be truncated"))]
+    IncompleteCalendar,
+}
+
+/// Date formats of the German and English VV.
+const DATE_FORMATS: &[&str] = &["%d.%m.%Y", "%m/%d/%Y", "%Y-%m-%d"];
+
+/// Parse timespan of "Mo, 21.10.2019 10:00 - 13:00" (or "Mon, 10/21/2019 10:00 - 13:00" on the
+/// English VV), with whether the session lasts whole days.
+///
+/// Rows of block courses may only give a day ("Mo, 21.10.2019"), a range of days ("Mo, 21.10.2019
+/// - Fr, 25.10.2019") or a whole day ("00:00 - 23:59"), which become all-day sessions from
+/// midnight to midnight.
+fn parse_timespan(date_text: String) -> Result<(DateTimeSpan<chrono_tz::Tz>, bool), StdError> {
+    let invalid = || Error::InvalidDate {
+        text: date_text.clone(),
+    };
+
+    // Skip the weekday, whose abbreviation depends on the language
+    let weekday_end = date_text.find(", ").ok_or_else(invalid)? + ", ".len();
+    let date_split = date_text[weekday_end..]
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    let is_dash = |word: &&str| *word == "-" || *word == "–";
+
+    if date_split.len() >= 4 && !date_split.get(1).map_or(false, is_dash) {
+        let span =
+            timespan_from_parts(date_split[0], date_split[1], date_split[3]).ok_or_else(invalid)?;
+        let midnight = NaiveTime::from_hms(0, 0, 0);
+        let is_whole_day =
+            span.start.time() == midnight && span.end.time() == NaiveTime::from_hms(23, 59, 0);
+        if is_whole_day {
+            let day = span.start.naive_local().date();
+            return Ok((all_day_span(day, day).ok_or_else(invalid)?, true));
+        }
+        return Ok((span, false));
+    }
+
+    let first = date_split.first().and_then(|day| parse_date(day));
+    let last = match date_split.get(1) {
+        None => first,
+        // The weekday of the last day comes before it again
+        Some(dash) if is_dash(dash) => date_split[2..]
+            .iter()
+            .filter_map(|day| parse_date(day))
+            .last(),
+        Some(_) => None,
+    };
+    match (first, last) {
+        (Some(first), Some(last)) => Ok((all_day_span(first, last).ok_or_else(invalid)?, true)),
+        _ => Err(invalid().into()),
+    }
+}
+
+fn parse_date(day: &str) -> Option<NaiveDate> {
+    DATE_FORMATS
+        .iter()
+        .filter_map(|format| NaiveDate::parse_from_str(day, format).ok())
+        .next()
+}
+
+/// Timespan of the days from `first` until `last` (inclusive), from midnight to midnight.
+fn all_day_span(first: NaiveDate, last: NaiveDate) -> Option<DateTimeSpan<chrono_tz::Tz>> {
+    let span =
+        NaiveDateTimeSpan::new(first.and_hms(0, 0, 0), last.succ_opt()?.and_hms(0, 0, 0)).ok()?;
+    DateTimeSpan::from_local_datetimespan(&span, &Berlin).ok()
+}
+
+/// Timespan of a day like "21.10.2019" from "10:00" until "13:00".
+fn timespan_from_parts(
+    day: &str,
+    start_time: &str,
+    end_time: &str,
+) -> Option<DateTimeSpan<chrono_tz::Tz>> {
+    let day = parse_date(day)?;
+    let start_time = NaiveTime::parse_from_str(start_time, "%R").ok()?;
+    let end_time = NaiveTime::parse_from_str(end_time, "%R").ok()?;
+
+    let span = NaiveDateTimeSpan::new(day.and_time(start_time), day.and_time(end_time)).ok()?;
+    DateTimeSpan::from_local_datetimespan(&span, &Berlin).ok()
+}
+
+/// The first timespan like "21.10.2019 10:00 - 13:00" in free text, with the text after it.
+pub(crate) fn find_timespan(text: &str) -> Option<(DateTimeSpan<chrono_tz::Tz>, String)> {
+    let words = text.split_whitespace().collect::<Vec<_>>();
+    words.windows(4).enumerate().find_map(|(index, window)| {
+        if window[2] != "-" && window[2] != "–" {
+            return None;
+        }
+        let span = timespan_from_parts(window[0], window[1], window[3])?;
+        Some((span, words[index + 4..].join(" ")))
+    })
+}
+
+/// Alarms of exams, a week and a day before.
+const EXAM_ALARMS: &[&str] = &["-P7D", "-P1D"];
+
+/// Course types of the VV, longer ones first so e.g. "Proseminar" isn't taken for "Seminar".
+pub(crate) const COURSE_KINDS: &[&str] = &[
+    "Vorlesung",
+    "Übung",
+    "Proseminar",
+    "Hauptseminar",
+    "Projektseminar",
+    "Seminar",
+    "Praktikum",
+    "Tutorium",
+    "Kolloquium",
+    // English VV
+    "Lecture",
+    "Exercise",
+    "Practical",
+    "Tutorial",
+    "Colloquium",
+];
+
+/// The first course type mentioned in `text`.
+pub(crate) fn course_kind(text: &str) -> Option<String> {
+    COURSE_KINDS
+        .iter()
+        .filter_map(|kind| text.find(kind).map(|index| (index, kind)))
+        .min_by_key(|(index, _)| *index)
+        .map(|(_, kind)| (*kind).to_owned())
+}
+
+/// Labels of the weekly hours (Semesterwochenstunden) on course pages
+const SWS_LABELS: &[&str] = &["SWS", "Semesterwochenstunden"];
+/// Labels of the credits on course pages
+const ECTS_LABELS: &[&str] = &["ECTS", "LP", "Leistungspunkte", "CP"];
+
+/// Semesterwochenstunden and ECTS credits mentioned in `text`, e.g. "2 SWS" or "ECTS: 5".
+pub(crate) fn credits(text: &str) -> (Option<f32>, Option<f32>) {
+    let words = text
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| ":;()[]/".contains(c)))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let number = |index: Option<usize>| {
+        let word = words.get(index?)?;
+        // German decimals like "2,5"
+        let word = word.trim_end_matches(',').replace(',', ".");
+        word.parse::<f32>().ok().filter(|number| *number > 0.0)
+    };
+    let find = |labels: &[&str]| {
+        words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| labels.contains(*word))
+            .filter_map(|(index, _)| {
+                number(index.checked_sub(1)).or_else(|| number(Some(index + 1)))
+            })
+            .next()
+    };
+
+    (find(SWS_LABELS), find(ECTS_LABELS))
+}
+
+/// Labels in front of module codes on course pages
+const MODULE_LABELS: &[&str] = &["Modul", "Modulnummer", "Module"];
+
+/// Module codes referenced in `text`, e.g. "21601" for "Modul 21601", without duplicates.
+pub(crate) fn modules(text: &str) -> Vec<String> {
+    let words = text
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| ":;,.()[]".contains(c)))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let mut modules = vec![];
+    for pair in words.windows(2) {
+        let (label, code) = (pair[0], pair[1]);
+        let is_code = code.chars().any(|c| c.is_ascii_digit())
+            && code
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if MODULE_LABELS.contains(&label) && is_code && !modules.iter().any(|known| known == code) {
+            modules.push(code.to_owned());
+        }
+    }
+    modules
+}
+
+/// Labels of the language of instruction on course pages
+const LANGUAGE_LABELS: &[&str] = &["Unterrichtssprache", "Lehrsprache", "Sprache"];
+
+/// ISO 639-1 code of a language named in German or English, or given as code, e.g. "en" for
+/// "Englisch".
+pub fn language_code(name: &str) -> Option<&'static str> {
+    let code = match name.trim().to_lowercase().as_str() {
+        "de" | "deutsch" | "german" => "de",
+        "en" | "englisch" | "english" => "en",
+        "fr" | "französisch" | "french" => "fr",
+        "es" | "spanisch" | "spanish" => "es",
+        "it" | "italienisch" | "italian" => "it",
+        "pl" | "polnisch" | "polish" => "pl",
+        "ru" | "russisch" | "russian" => "ru",
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Language of instruction as stated after one of `LANGUAGE_LABELS` in `text`, e.g.
+/// "Englisch" or "Deutsch/Englisch".
+pub(crate) fn language(text: &str) -> Option<String> {
+    let words = text
+        .split(|c: char| c.is_whitespace() || c == '/' || c == ',')
+        .map(|word| word.trim_matches(|c: char| ":;.()[]".contains(c)))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    for (index, word) in words.iter().enumerate() {
+        if !LANGUAGE_LABELS.contains(word) {
+            continue;
+        }
+        let languages = words[index + 1..]
+            .iter()
+            .filter(|word| !["und", "and", "oder", "or"].contains(*word))
+            .take_while(|word| language_code(word).is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        if !languages.is_empty() {
+            return Some(languages.join("/"));
+        }
+    }
+    None
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Course {
+    /// ID of the course in its catalogue, set when the course was requested from there
+    pub id: Option<String>,
+    pub name: String,
+    /// Type of the course (Veranstaltungsart), e.g. "Vorlesung" or "Seminar"
+    pub kind: Option<String>,
+    pub events: Vec<CourseEvent>,
+    /// Sessions that were exported before but disappeared from the VV, see
+    /// `changes::Snapshot::annotate`
+    pub cancelled: Vec<CourseEvent>,
+    /// Page of the course in the VV, set when the course was requested from there
+    pub url: Option<String>,
+    /// Color of the course in calendars as `#rrggbb`, see `colors`
+    pub color: Option<String>,
+    /// Weekly hours (Semesterwochenstunden), if the page states them
+    pub sws: Option<f32>,
+    /// ECTS credits, if the page states them
+    pub ects: Option<f32>,
+    /// Codes of the modules the course belongs to, e.g. "21601"
+    pub modules: Vec<String>,
+    /// Language of instruction (Unterrichtssprache) as stated on the page, e.g. "Englisch"
+    pub language: Option<String>,
+    /// Places and how to register, see `registration`
+    pub registration: Registration,
+    /// Sessions skipped while parsing in lenient mode, see `lenient`
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+}
+
+impl Course {
+    /// Parse a course page, skipping sessions that can't be parsed with a warning if `lenient`
+    /// (see `lenient`).
+    pub fn from_document(document: &Document, lenient: bool) -> Result<Self, StdError> {
+        let page = document
+            .find(Name("body"))
+            .next()
+            .or_else(|| document.nth(0))
+            .map(|node| node.text())
+            .unwrap_or_default();
+        let (sws, ects) = credits(&page);
+        let mut warnings = vec![];
+        Ok(Self {
+            id: None,
+            name: Self::name_from_document(&document)?,
+            kind: Self::kind_from_document(&document),
+            events: CourseEvent::all_from_document(document, lenient, &mut warnings)?,
+            cancelled: vec![],
+            url: None,
+            color: None,
+            sws,
+            ects,
+            modules: modules(&page),
+            language: language(&page),
+            registration: Registration::parse(&page),
+            warnings,
+        })
+    }
+
+    /// Parse the HTML of a course page.
+    pub fn from_html(html: &str, lenient: bool) -> Result<Self, StdError> {
+        Self::from_document(&Document::from(html), lenient)
+    }
+
+    /// Parse a saved course page, e.g. to debug the parser without network access.
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P, lenient: bool) -> Result<Self, StdError> {
+        Self::from_html(&std::fs::read_to_string(path)?, lenient)
+    }
+
+    /// Set the id of the course in its catalogue, deriving the ids of sessions without one from
+    /// it (see `fallback_session_id`).
+    pub fn set_id(&mut self, id: &str) {
+        let mut events: Vec<CourseEvent> = Vec::with_capacity(self.events.len());
+        for mut event in self.events.drain(..) {
+            if event.id.starts_with(FALLBACK_ID_PREFIX) {
+                event.id = fallback_session_id(id, &event.timespan, &events);
+            }
+            events.push(event);
+        }
+        self.events = events;
+        self.id = Some(id.to_owned());
+    }
+
+    fn name_from_document(document: &Document) -> Result<String, StdError> {
+        let node = document
+            .find(Class("subc").descendant(Name("h1")))
+            .next()
+            .ok_or(Error::MissingElement {
+                element: "course name",
+            })?;
+
+        Ok(node.text().trim().to_owned())
+    }
+
+    /// Course type from the page header, i.e. the `subc` container without the course name.
+    fn kind_from_document(document: &Document) -> Option<String> {
+        let header = document.find(Class("subc")).next()?;
+        let mut text = header.text();
+        if let Some(name) = header.find(Name("h1")).next() {
+            text = text.replace(&name.text(), "");
+        }
+
+        course_kind(&text)
+    }
+
+    /// ISO 639-1 codes of the languages of instruction, see `language_code`.
+    pub fn language_codes(&self) -> Vec<&'static str> {
+        self.language
+            .iter()
+            .flat_map(|language| language.split('/'))
+            .filter_map(language_code)
+            .collect()
+    }
+
+    /// Short description of the course for the info pane of calendar clients: its type,
+    /// workload, language, registration, lecturers, semester and VV page, as far as they are
+    /// known.
+    pub fn description(&self) -> String {
+        let mut lines = vec![];
+        if let Some(kind) = &self.kind {
+            lines.push(kind.clone());
+        }
+        let credits = self
+            .sws
+            .map(|sws| format!("{} SWS", sws))
+            .into_iter()
+            .chain(self.ects.map(|ects| format!("{} ECTS", ects)))
+            .collect::<Vec<_>>();
+        if !credits.is_empty() {
+            lines.push(credits.join(", "));
+        }
+        if let Some(language) = &self.language {
+            lines.push(format!("Unterrichtssprache: {}", language));
+        }
+        lines.extend(self.registration.description_lines());
+        let mut lecturers = Vec::<&str>::new();
+        for lecturer in self.events.iter().flat_map(|event| event.lecturers.iter()) {
+            if !lecturers.contains(&lecturer.as_str()) {
+                lecturers.push(lecturer);
+            }
+        }
+        if !lecturers.is_empty() {
+            lines.push(format!("Lehrende: {}", lecturers.join(", ")));
+        }
+        let semester = self
+            .events
+            .first()
+            .and_then(|event| Semester::containing(event.timespan.start.naive_local().date()));
+        if let Some(semester) = semester {
+            lines.push(semester.name.to_owned());
+        }
+        if let Some(url) = &self.url {
+            lines.push(url.clone());
+        }
+        lines.join("\n")
+    }
+
+    /// Calendar of this course, named after it.
+    pub fn to_ical(self, options: &CalendarOptions) -> Result<ICalendar<'static>, StdError> {
+        let name = self.name.clone();
+        self.to_ical_named(&name, options)
+    }
+
+    /// Calendar of this course, with `name` shown by calendar clients.
+    pub fn to_ical_named(
+        self,
+        name: &str,
+        options: &CalendarOptions,
+    ) -> Result<ICalendar<'static>, StdError> {
+        let mut calendar = published_calendar(name, options);
+        let description = self.description();
+        if !description.is_empty() {
+            calendar.push(Property::new("X-WR-CALDESC", sanitize::text(&description)));
+        }
+        if let Some(color) = &self.color {
+            calendar.push(ics::components::Property::new(
+                "X-APPLE-CALENDAR-COLOR",
+                color.clone(),
+            ));
+            calendar.push(ics::components::Property::new(
+                "COLOR",
+                colors::css_name(color),
+            ));
+        }
+
+        let course_id = self.id.clone();
+        let uid_for = |event: &CourseEvent| {
+            (
+                uid::for_event(options, course_id.as_ref().map(String::as_str), &event.id),
+                false,
+            )
+        };
+        for cal_event in self.into_ical_events(options, uid_for) {
+            calendar.add_event(cal_event);
+        }
+
+        Ok(calendar)
+    }
+
+    /// Build the iCal events of this course, taking the UID of every event and whether it was
+    /// rewritten by a `merge::UidRegistry` from `uid_for`.
+    ///
+    /// The VV id of events with a rewritten UID is kept in `X-FU-VV-CAL-ORIGINAL-UID`. Cancelled
+    /// sessions are included with `STATUS:CANCELLED`, so subscribed clients remove them.
+    ///
+    /// The sessions form a series whose parent is the first one, all others refer to it with
+    /// `RELATED-TO;RELTYPE=PARENT`. Courses removed from the config are exported with all of
+    /// their sessions cancelled (see `changes::Snapshot::drop_missing`).
+    pub fn into_ical_events<F>(
+        self,
+        options: &CalendarOptions,
+        mut uid_for: F,
+    ) -> Vec<Event<'static>>
+    where
+        F: FnMut(&CourseEvent) -> (String, bool),
+    {
+        let events = self
+            .events
+            .into_iter()
+            .map(|event| (event, false))
+            .chain(self.cancelled.into_iter().map(|event| (event, true)))
+            .collect::<Vec<_>>();
+        let uids = events
+            .iter()
+            .map(|(event, _)| uid_for(event))
+            .collect::<Vec<_>>();
+
+        // Courses without published dates (common early in the semester) have no events
+        let first_id = match uids.first() {
+            Some((first_id, _)) => first_id.clone(),
+            None => return vec![],
+        };
+        let mut cal_events = vec![];
+        for ((event, cancelled), (uid, rewritten)) in events.into_iter().zip(uids) {
+            if let Some(legacy_uid) = uid::migrated_from(options, &uid, &event.id) {
+                cal_events.push(legacy_cancellation(legacy_uid, &event, &self.name, options));
+            }
+            let mut cal_event = Event::new(uid.clone(), timezone::utc_time(&event.timespan.start));
+            push_times(&mut cal_event, &event, options);
+            cal_event.push(Summary::new(sanitize::text(
+                event.summary.as_ref().unwrap_or(&self.name),
+            )));
+            if event.exam {
+                cal_event.push(Categories::new("EXAM"));
+                for trigger in EXAM_ALARMS.iter() {
+                    let mut alarm = Alarm::new(Action::display(), Trigger::new(*trigger));
+                    alarm.push(Description::new(sanitize::text(&format!(
+                        "Klausur: {}",
+                        self.name
+                    ))));
+                    cal_event.add_alarm(alarm);
+                }
+            } else if let Some(category) = event.part.as_ref().or_else(|| self.kind.as_ref()) {
+                cal_event.push(Categories::new(sanitize::text(category)));
+            }
+            for module in self.modules.iter() {
+                cal_event.push(Categories::new(format!("Modul {}", sanitize::text(module))));
+            }
+            if uid != first_id {
+                let mut related_to = Property::new("RELATED-TO", first_id.clone());
+                related_to.add(Parameter::new("RELTYPE", "PARENT"));
+                cal_event.push(related_to);
+            }
+            if let Some(url) = event.session_url(self.url.as_ref().map(String::as_str)) {
+                cal_event.push(ics::components::Property::new("URL", url));
+            }
+            if let Some(room) = &event.room {
+                match campus::building_of(room, &options.buildings) {
+                    Some(building) => {
+                        cal_event.push(Location::new(sanitize::text(&building.address(room))));
+                        if let Some(geo) = building.geo() {
+                            cal_event.push(ics::components::Property::new("GEO", geo));
+                        }
+                    }
+                    None => cal_event.push(Location::new(sanitize::text(room))),
+                }
+            }
+            if let Some(color) = &self.color {
+                cal_event.push(ics::components::Property::new(
+                    "COLOR",
+                    colors::css_name(color),
+                ));
+            }
+            let lecturers = if event.lecturers.is_empty() {
+                None
+            } else {
+                Some(format!("Dozent: {}", event.lecturers.join(", ")))
+            };
+            let description = event
+                .week
+                .map(|week| format!("Woche {}", week))
+                .into_iter()
+                .chain(lecturers)
+                .chain(event.remark.clone())
+                .collect::<Vec<_>>();
+            if !description.is_empty() {
+                cal_event.push(Description::new(sanitize::text(&description.join("\n"))));
+            }
+            if event.sequence > 0 {
+                cal_event.push(Sequence::new(event.sequence.to_string()));
+            }
+            if cancelled {
+                cal_event.push(Status::cancelled());
+            }
+            options.appearance.apply_ical(&mut cal_event);
+            if rewritten {
+                cal_event.push(ics::components::Property::new(
+                    "X-FU-VV-CAL-ORIGINAL-UID",
+                    event.id,
+                ));
+            }
+
+            cal_events.push(cal_event);
+        }
+        if options.registration_reminders {
+            cal_events.extend(self.registration.reminder(&self.name, &first_id));
+        }
+
+        cal_events
+    }
+}
+
+/// `DTSTART` and `DTEND` of `event`, as dates for all-day sessions.
+fn push_times(cal_event: &mut Event<'static>, event: &CourseEvent, options: &CalendarOptions) {
+    if !event.all_day {
+        let span = &event.timespan;
+        cal_event.push(DtStart::new(timezone::ical_time(&span.start, options)));
+        cal_event.push(DtEnd::new(timezone::ical_time(&span.end, options)));
+        return;
+    }
+    // Days of the VV, even if times are converted to another zone (see `timezone`)
+    for (name, time) in &[
+        ("DTSTART", event.timespan.start),
+        ("DTEND", event.timespan.end),
+    ] {
+        let date = time.with_timezone(&Berlin).format("%Y%m%d").to_string();
+        let mut property = Property::new(*name, date);
+        property.add(Parameter::new("VALUE", "DATE"));
+        cal_event.push(property);
+    }
+}
+
+/// Cancellation of `event` under the UID older versions exported it with, see `uid`.
+fn legacy_cancellation(
+    uid: String,
+    event: &CourseEvent,
+    name: &str,
+    options: &CalendarOptions,
+) -> Event<'static> {
+    let mut cal_event = Event::new(uid, timezone::utc_time(&event.timespan.start));
+    push_times(&mut cal_event, event, options);
+    cal_event.push(Summary::new(sanitize::text(
+        event.summary.as_ref().map(String::as_str).unwrap_or(name),
+    )));
+    cal_event.push(Sequence::new((event.sequence + 1).to_string()));
+    cal_event.push(Status::cancelled());
+    cal_event
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CourseEvent {
+    pub id: String,
+    /// Serialized as `start` and `end`, see `model`
+    #[serde(flatten, with = "model::timespan")]
+    pub timespan: DateTimeSpan<chrono_tz::Tz>,
+    /// iCal `SEQUENCE`, incremented whenever the session changes
+    pub sequence: u32,
+    /// Week of the lecture period, see `weeks::assign_weeks`
+    pub week: Option<u32>,
+    /// Type label of the session, e.g. "Vorlesung" or "Übung", on pages that combine several
+    /// parts of a course
+    pub part: Option<String>,
+    /// Note on the session, e.g. "Exkursion" or "online via Webex"
+    pub remark: Option<String>,
+    /// Room, from the detail page (see `details`)
+    pub room: Option<String>,
+    /// Lecturers, from the detail page (see `details`)
+    pub lecturers: Vec<String>,
+    /// Link to the detail page of the session, relative to the course page
+    pub detail_url: Option<String>,
+    /// Exam (Klausur) instead of a regular session, exported with `CATEGORIES:EXAM` and alarms
+    pub exam: bool,
+    /// Exercise group of the session (e.g. "Gruppe B"), on pages listing several parallel groups
+    pub group: Option<String>,
+    /// Summary of the exported event, the course name if not set (see `summary`)
+    pub summary: Option<String>,
+    /// Lasts whole days (e.g. of a Blockseminar) and is exported without times, the timespan
+    /// then goes from midnight to midnight
+    #[serde(default)]
+    pub all_day: bool,
+}
+
+impl CourseEvent {
+    /// How long this session takes, whole days for all-day sessions.
+    pub fn duration(&self) -> chrono::Duration {
+        self.timespan.end - self.timespan.start
+    }
+
+    /// Whether this session and `other` take place at the same time, at least partially.
+    ///
+    /// Sessions that only touch, one ending when the other starts, don't overlap.
+    pub fn overlaps(&self, other: &CourseEvent) -> bool {
+        self.timespan.start < other.timespan.end && other.timespan.start < self.timespan.end
+    }
+
+    /// Whether this session and `other` start and end at exactly the same time.
+    pub fn same_slot(&self, other: &CourseEvent) -> bool {
+        self.timespan.start == other.timespan.start && self.timespan.end == other.timespan.end
+    }
+
+    /// Page showing this session: its detail page if the VV links one, otherwise the course page
+    /// at `course_url` scrolled to the session, or the course page itself for sessions listed
+    /// outside of the date table.
+    pub fn session_url(&self, course_url: Option<&str>) -> Option<String> {
+        // Links that only open the details in place, via script, don't lead anywhere on their own
+        let detail_url = self
+            .detail_url
+            .as_ref()
+            .filter(|url| !url.starts_with('#') && !url.starts_with("javascript:"));
+        let course_url = match course_url {
+            Some(course_url) => course_url,
+            None => return detail_url.filter(|url| url.contains("://")).cloned(),
+        };
+        if let Some(detail_url) = detail_url {
+            if let Ok(url) = url::Url::parse(course_url).and_then(|base| base.join(detail_url)) {
+                return Some(url.into_string());
+            }
+        }
+        // Sessions of the date table have numeric ids, taken from `link_to_details_{id}`
+        if !self.id.is_empty() && self.id.chars().all(|c| c.is_ascii_digit()) {
+            let page = course_url.split('#').next().unwrap_or(course_url);
+            return Some(format!("{}#link_to_details_{}", page, self.id));
+        }
+        Some(course_url.to_owned())
+    }
+
+    /// Turn this session into an all-day one over the days it takes place on, e.g. for block
+    /// courses whose times don't matter.
+    pub fn make_all_day(&mut self) -> Result<(), StdError> {
+        let first = self
+            .timespan
+            .start
+            .with_timezone(&Berlin)
+            .naive_local()
+            .date();
+        let mut last = self.timespan.end.with_timezone(&Berlin).naive_local();
+        if last.time() == NaiveTime::from_hms(0, 0, 0) {
+            // Ends at midnight, so the day before is the last one
+            last -= chrono::Duration::seconds(1);
+        }
+        self.timespan =
+            all_day_span(first, last.date().max(first)).ok_or_else(|| Error::InvalidDate {
+                text: self.timespan.start.to_string(),
+            })?;
+        self.all_day = true;
+        Ok(())
+    }
+
+    /// The sessions listed on a course page, adding the ones skipped if `lenient` to
+    /// `warnings`.
+    pub fn all_from_document(
+        document: &Document,
+        lenient: bool,
+        warnings: &mut Vec<String>,
+    ) -> Result<Vec<Self>, StdError> {
+        let mut events = vec![];
+        for node in document.find(Class("link_to_details")) {
+            let date_text = match node.find(Class("course_date_time")).next() {
+                Some(date_node) => date_node.text().trim().to_owned(),
+                None => {
+                    let row = node.text().split_whitespace().collect::<Vec<_>>().join(" ");
+                    let err = Error::MissingElement {
+                        element: "session date",
+                    };
+                    lenient::skip_session(&row, err.into(), lenient, warnings)?;
+                    continue;
+                }
+            };
+
+            let (date_span, all_day) = match parse_timespan(date_text.clone()) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    lenient::skip_session(&date_text, err, lenient, warnings)?;
+                    continue;
+                }
+            };
+
+            let id = match node.attr("id") {
+                Some(id) => id.replace("link_to_details_", ""),
+                None => fallback_session_id("", &date_span, &events),
+            };
+
+            let part = node
+                .find(Class("course_type"))
+                .next()
+                .map(|part_node| part_node.text().trim().to_owned())
+                .filter(|part| !part.is_empty());
+
+            let detail_url = node
+                .attr("href")
+                .or_else(|| {
+                    node.find(Name("a"))
+                        .next()
+                        .and_then(|link| link.attr("href"))
+                })
+                .map(str::to_owned);
+
+            let remark = node
+                .find(Class("course_remark"))
+                .next()
+                .map(|remark_node| {
+                    remark_node
+                        .text()
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .filter(|remark| !remark.is_empty());
+
+            events.push(CourseEvent {
+                id,
+                timespan: date_span,
+                sequence: 0,
+                week: None,
+                exam: is_exam(part.as_ref(), preceding_heading(node, |_| true).as_ref()),
+                part,
+                remark,
+                room: None,
+                lecturers: vec![],
+                detail_url,
+                group: preceding_heading(node, is_group_label),
+                summary: None,
+                all_day,
+            })
+        }
+
+        // Extra sessions are listed outside of the regular date table, as rows or list items
+        // with just the date and a note under a heading like "Zusätzliche Termine"
+        for node in document.find(Name("tr").or(Name("li"))) {
+            let is_regular = node.find(Class("link_to_details")).next().is_some()
+                || within_class(node, "link_to_details");
+            // Only the innermost rows, so nested lists don't yield a session twice
+            let is_nested = node.find(Name("tr").or(Name("li"))).next().is_some();
+            if is_regular || is_nested {
+                continue;
+            }
+            let heading = match preceding_heading(node, |_| true) {
+                Some(heading) if is_extra_sessions_label(&heading) => heading,
+                _ => continue,
+            };
+            let text = node.text().split_whitespace().collect::<Vec<_>>().join(" ");
+            if let Some(event) = extra_session(&text, &heading, &events) {
+                events.push(event);
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+/// Headings of sections with extra sessions, lowercase
+const EXTRA_SESSION_LABELS: &[&str] = &[
+    "zusätzliche termine",
+    "weitere termine",
+    "zusatztermin",
+    "sondertermin",
+    "klausurtermin",
+    "prüfungstermin",
+    "additional dates",
+    "additional sessions",
+    "further dates",
+    "exam dates",
+];
+
+/// Whether the heading `text` introduces a section of extra sessions.
+pub(crate) fn is_extra_sessions_label(text: &str) -> bool {
+    let text = text.to_lowercase();
+    EXTRA_SESSION_LABELS
+        .iter()
+        .any(|label| text.contains(label))
+}
+
+/// The extra session of the row `text` listed under `heading`, unless it has no date or is one of
+/// `events` already.
+pub(crate) fn extra_session(
+    text: &str,
+    heading: &str,
+    events: &[CourseEvent],
+) -> Option<CourseEvent> {
+    let (timespan, rest) = find_timespan(text)?;
+    let is_known = events
+        .iter()
+        .any(|event| event.timespan.start == timespan.start && event.timespan.end == timespan.end);
+    if is_known {
+        return None;
+    }
+
+    Some(CourseEvent {
+        id: format!("extra_{}", timespan.start.format("%Y%m%dT%H%M")),
+        timespan,
+        sequence: 0,
+        week: None,
+        exam: is_exam_label(heading) || is_exam_label(&rest),
+        part: None,
+        remark: Some(rest).filter(|rest| !rest.is_empty()),
+        room: None,
+        lecturers: vec![],
+        detail_url: None,
+        group: None,
+        summary: None,
+        all_day: false,
+    })
+}
+
+/// Start of the ids made up by `fallback_session_id`, which VV ids (all digits) never have
+const FALLBACK_ID_PREFIX: &str = "row_";
+
+/// Id of a session whose row lacks the `link_to_details_{id}` attribute, derived from the course
+/// id and the time of the session so it stays the same across runs, even if the course is
+/// renamed.
+///
+/// The course id isn't known while parsing, so the parsers pass an empty one and `Course::set_id`
+/// derives the ids again once it is. Rows without id in the same slot (e.g. of parallel groups)
+/// are told apart from the `events` before them by a counter.
+pub(crate) fn fallback_session_id(
+    course_id: &str,
+    timespan: &DateTimeSpan<chrono_tz::Tz>,
+    events: &[CourseEvent],
+) -> String {
+    let key = format!(
+        "{}|{}|{}",
+        course_id,
+        timespan.start.format("%Y%m%dT%H%M"),
+        timespan.end.format("%Y%m%dT%H%M")
+    );
+    let id = format!("{}{:016x}", FALLBACK_ID_PREFIX, stable_hash(&key));
+    let taken = |id: &str| events.iter().any(|event| event.id == id);
+    let mut unique = id.clone();
+    let mut n = 2;
+    while taken(&unique) {
+        unique = format!("{}_{}", id, n);
+        n += 1;
+    }
+    unique
+}
+
+/// FNV-1a hash of `text`, which unlike `DefaultHasher` is guaranteed not to change between Rust
+/// versions, for ids that have to stay the same across runs.
+pub(crate) fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Whether `node` or one of its ancestors has the class `class`.
+fn within_class(node: select::node::Node, class: &str) -> bool {
+    let mut current = Some(node);
+    while let Some(ancestor) = current {
+        let has_class = ancestor
+            .attr("class")
+            .map(|classes| classes.split_whitespace().any(|name| name == class))
+            .unwrap_or(false);
+        if has_class {
+            return true;
+        }
+        current = ancestor.parent();
+    }
+    false
+}
+
+/// Text of the nearest heading before `node` for which `matches` holds.
+fn preceding_heading(node: select::node::Node, matches: fn(&str) -> bool) -> Option<String> {
+    let mut current = Some(node);
+    while let Some(ancestor) = current {
+        let mut sibling = ancestor.prev();
+        while let Some(previous) = sibling {
+            let is_heading = previous
+                .name()
+                .map(|name| name.len() == 2 && name.starts_with('h'))
+                .unwrap_or(false);
+            if is_heading {
+                let text = previous.text();
+                if matches(&text) {
+                    return Some(text.split_whitespace().collect::<Vec<_>>().join(" "));
+                }
+            }
+            sibling = previous.prev();
+        }
+        current = ancestor.parent();
+    }
+    None
+}
+
+pub(crate) fn is_group_label(text: &str) -> bool {
+    let text = text.to_lowercase();
+    text.contains("gruppe") || text.contains("group")
+}
+
+pub(crate) fn is_exam_label(text: &str) -> bool {
+    let text = text.to_lowercase();
+    ["klausur", "prüfung", "exam"]
+        .iter()
+        .any(|keyword| text.contains(keyword))
+}
+
+/// Whether a session is an exam, judging by its part label or the heading it's listed under.
+pub(crate) fn is_exam(part: Option<&String>, heading: Option<&String>) -> bool {
+    part.into_iter()
+        .chain(heading)
+        .any(|label| is_exam_label(label))
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct RequestedCourse {
+    pub id: String,
+    pub semester: String,
+    /// Rules selecting which sessions of the course are kept
+    pub rules: Rules,
+    /// Where the course comes from, the FU VV by default
+    pub source: Arc<dyn CourseSource>,
+    /// Options of the calendar exported by `get_as_ical`
+    pub options: CalendarOptions,
+}
+
+#[cfg(feature = "native")]
+impl RequestedCourse {
+    pub fn new<S1: Into<String>, S2: Into<String>>(id: S1, semester: S2) -> Self {
+        Self {
+            id: id.into(),
+            semester: semester.into(),
+            rules: Rules::default(),
+            source: Arc::new(FuVv::new()),
+            options: CalendarOptions::default(),
+        }
+    }
+
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn with_source(mut self, source: Arc<dyn CourseSource>) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_options(mut self, options: CalendarOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub async fn get_course(&self) -> Result<Course, StdError> {
+        let mut course = self.source.get_course(&self.id, &self.semester).await?;
+        course.set_id(&self.id);
+        self.rules.apply(&self.id, &mut course)?;
+        course.url = Some(self.source.course_url(&self.id, &self.semester));
+
+        Ok(course)
+    }
+
+    pub async fn get_as_ical<'a>(&self) -> Result<ICalendar<'a>, StdError> {
+        self.get_course().await?.to_ical(&self.options)
+    }
+
+    pub async fn save_as_ical<'a, P: Into<std::path::PathBuf>>(
+        &self,
+        path: P,
+    ) -> Result<(), StdError> {
+        let path: std::path::PathBuf = path.into();
+        let calendar = self.get_as_ical().await?;
+        let content = sanitize::fold(&calendar.to_string());
+        update::write_if_changed(&path, &content)?;
+        Ok(())
+    }
+}
+
+/// `PRODID` of the generated calendars
+pub const PRODID: &str = concat!(
+    "-//fu-vv-cal//fu-vv-cal ",
+    env!("CARGO_PKG_VERSION"),
+    "//DE"
+);
+
+/// An empty calendar with the `PRODID` of fu-vv-cal.
+///
+/// CalDAV resources must not carry a `METHOD`, other calendars are built with
+/// `published_calendar`.
+pub fn new_calendar() -> ICalendar<'static> {
+    ICalendar::new("2.0", PRODID)
+}
+
+/// An empty calendar to be published as a file or feed, named `name` in calendar clients (which
+/// show it as "Untitled" otherwise) and with the hints of `refresh`.
+pub fn published_calendar(name: &str, options: &CalendarOptions) -> ICalendar<'static> {
+    let mut calendar = new_calendar();
+    calendar.push(Property::new("METHOD", "PUBLISH"));
+    calendar.push(Property::new("X-WR-CALNAME", sanitize::text(name)));
+    refresh::add_hints(&mut calendar, options);
+    timezone::add_hint(&mut calendar, options);
+    calendar
+}
+
+/// Several requested courses that are exported into one merged calendar.
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct Bundle {
+    pub courses: Vec<RequestedCourse>,
+    /// Name of the merged calendar in calendar clients
+    pub name: String,
+    /// Options of the merged calendar
+    pub options: CalendarOptions,
+    /// Print a status line per fetched course, see `progress`
+    pub progress: bool,
+}
+
+#[cfg(feature = "native")]
+impl Bundle {
+    pub fn new(courses: Vec<RequestedCourse>) -> Self {
+        Self {
+            courses,
+            name: config::DEFAULT_MERGED_NAME.to_owned(),
+            options: CalendarOptions::default(),
+            progress: false,
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_owned();
+        self
+    }
+
+    pub fn with_options(mut self, options: CalendarOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn with_progress(mut self) -> Self {
+        self.progress = true;
+        self
+    }
+
+    /// Fetch all courses and merge them into one calendar.
+    ///
+    /// UIDs that occur in more than one course are rewritten and returned as collisions.
+    pub async fn get_as_ical<'a>(&self) -> Result<(ICalendar<'a>, Vec<UidCollision>), StdError> {
+        let mut courses = vec![];
+        let mut progress = Progress::new(self.courses.len(), self.progress);
+        for requested in self.courses.iter() {
+            progress.start();
+            let course = match requested.get_course().await {
+                Ok(course) => course,
+                Err(err) => {
+                    progress.failed(&requested.id, &*err);
+                    return Err(err);
+                }
+            };
+            progress.fetched(&course);
+            courses.push((requested.id.clone(), course));
+        }
+
+        Ok(merge::merge_courses(&self.name, courses, &self.options))
+    }
+
+    pub async fn save_as_ical<P: Into<std::path::PathBuf>>(
+        &self,
+        path: P,
+    ) -> Result<Vec<UidCollision>, StdError> {
+        let path: std::path::PathBuf = path.into();
+        let (calendar, collisions) = self.get_as_ical().await?;
+        let content = sanitize::fold(&calendar.to_string());
+        update::write_if_changed(&path, &content)?;
+        Ok(collisions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(timespan: DateTimeSpan<chrono_tz::Tz>) -> CourseEvent {
+        CourseEvent {
+            id: "500001".to_owned(),
+            timespan,
+            sequence: 0,
+            week: None,
+            part: None,
+            remark: None,
+            room: None,
+            lecturers: vec![],
+            detail_url: None,
+            exam: false,
+            group: None,
+            summary: None,
+            all_day: false,
+        }
+    }
+
+    fn event(day: &str, start: &str, end: &str) -> CourseEvent {
+        session(timespan_from_parts(day, start, end).unwrap())
+    }
+
+    fn all_day_event(first: &str, last: &str) -> CourseEvent {
+        let span = all_day_span(parse_date(first).unwrap(), parse_date(last).unwrap());
+        let mut event = session(span.unwrap());
+        event.all_day = true;
+        event
+    }
+
+    fn course(events: Vec<CourseEvent>) -> Course {
+        Course {
+            id: Some("10101".to_owned()),
+            name: "Analysis 1".to_owned(),
+            kind: None,
+            events,
+            cancelled: vec![],
+            url: None,
+            color: None,
+            sws: None,
+            ects: None,
+            modules: vec![],
+            language: None,
+            registration: Registration::parse("Anmeldung über Campus Management bis 15.10.2019."),
+            warnings: vec![],
+        }
+    }
+
+    /// A course `id` named `name` with two parallel sessions without VV ids, as parsed.
+    fn course_without_session_ids(id: &str, name: &str) -> Course {
+        let mut events = vec![];
+        for _ in 0..2 {
+            let mut parallel = event("14.10.2019", "10:00", "12:00");
+            parallel.id = fallback_session_id("", &parallel.timespan, &events);
+            events.push(parallel);
+        }
+        events.push(event("21.10.2019", "10:00", "12:00"));
+        let mut parsed = course(events);
+        parsed.name = name.to_owned();
+        parsed.set_id(id);
+        parsed
+    }
+
+    fn ids(course: &Course) -> Vec<&str> {
+        course
+            .events
+            .iter()
+            .map(|event| event.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn fallback_ids_follow_the_course_id() {
+        let course = course_without_session_ids("524870", "Analysis 1");
+        let renamed = course_without_session_ids("524870", "Analysis I");
+        let other = course_without_session_ids("525101", "Analysis 1");
+
+        assert_eq!(course.id.as_ref().map(String::as_str), Some("524870"));
+        assert_eq!(ids(&course), ids(&renamed));
+        assert_ne!(ids(&course)[..2], ids(&other)[..2]);
+        assert!(ids(&course)[0].starts_with(FALLBACK_ID_PREFIX));
+        assert_eq!(ids(&course)[1], format!("{}_2", ids(&course)[0]));
+        // Sessions with VV ids keep them
+        assert_eq!(ids(&course)[2], "500001");
+    }
+
+    #[test]
+    fn duration_of_sessions() {
+        let session = event("14.10.2019", "10:00", "12:00");
+        assert_eq!(session.duration(), chrono::Duration::hours(2));
+        let block = all_day_event("14.10.2019", "16.10.2019");
+        assert_eq!(block.duration(), chrono::Duration::days(3));
+    }
+
+    #[test]
+    fn all_day_span_across_dst_change() {
+        // Clocks go back on 27.10.2019, so that day has 25 hours
+        let block = all_day_event("27.10.2019", "27.10.2019");
+        assert_eq!(block.duration(), chrono::Duration::hours(25));
+    }
+
+    #[test]
+    fn touching_sessions_neither_overlap_nor_share_a_slot() {
+        let first = event("14.10.2019", "10:00", "12:00");
+        let second = event("14.10.2019", "12:00", "14:00");
+        assert!(!first.overlaps(&second));
+        assert!(!second.overlaps(&first));
+        assert!(!first.same_slot(&second));
+    }
+
+    #[test]
+    fn partially_overlapping_sessions() {
+        let first = event("14.10.2019", "10:00", "12:00");
+        let second = event("14.10.2019", "11:00", "13:00");
+        assert!(first.overlaps(&second));
+        assert!(second.overlaps(&first));
+        assert!(!first.same_slot(&second));
+    }
+
+    #[test]
+    fn equal_sessions_overlap_and_share_a_slot() {
+        let first = event("14.10.2019", "10:00", "12:00");
+        let second = event("14.10.2019", "10:00", "12:00");
+        assert!(first.overlaps(&second));
+        assert!(first.same_slot(&second));
+    }
+
+    #[test]
+    fn all_day_sessions() {
+        let block = all_day_event("14.10.2019", "15.10.2019");
+        let next_day = all_day_event("16.10.2019", "16.10.2019");
+        assert!(!block.overlaps(&next_day));
+        assert!(block.overlaps(&event("15.10.2019", "23:00", "23:30")));
+        assert!(!block.overlaps(&event("16.10.2019", "00:00", "01:00")));
+        assert!(block.same_slot(&all_day_event("14.10.2019", "15.10.2019")));
+        assert!(!block.same_slot(&event("14.10.2019", "00:00", "23:59")));
+    }
+
+    #[test]
+    fn registration_reminder_only_if_enabled() {
+        let course = course(vec![event("14.10.2019", "10:00", "12:00")]);
+        let calendar = course.clone().to_ical(&CalendarOptions::default()).unwrap();
+        assert!(!calendar.to_string().contains("Anmeldeschluss"));
+
+        let options = CalendarOptions {
+            registration_reminders: true,
+            ..CalendarOptions::default()
+        };
+        let calendar = course.to_ical(&options).unwrap().to_string();
+        assert!(calendar.contains("SUMMARY:Anmeldeschluss: Analysis 1"));
+        assert!(calendar.contains("DTSTART;VALUE=DATE:20191015"));
+    }
+}
@@ -0,0 +1,63 @@
+//! Courses given by their official LV number (Lehrveranstaltungsnummer, e.g. "21720a"), which
+//! students know from the printed course catalogue, instead of the id in VV URLs.
+
+use crate::fetch::Locale;
+use crate::search;
+use crate::{Error, StdError};
+
+/// Prefix marking a course reference as LV number, for LV numbers that consist only of digits
+pub const LV_PREFIX: &str = "lv:";
+
+/// The LV number `reference` stands for, if it is one: prefixed with `lv:` or, unlike VV ids,
+/// not only digits.
+pub fn lv_number(reference: &str) -> Option<&str> {
+    if reference.starts_with(LV_PREFIX) {
+        return Some(reference[LV_PREFIX.len()..].trim());
+    }
+    if reference.is_empty()
+        || reference.contains("://")
+        || reference.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    Some(reference)
+}
+
+/// VV id of the course `id`, which may be an LV number, see `lv_number`.
+pub async fn resolve_id(id: &str, semester: &str, locale: Locale) -> Result<String, StdError> {
+    match lv_number(id) {
+        Some(number) => resolve(number, semester, locale).await,
+        None => Ok(id.to_owned()),
+    }
+}
+
+/// VV id of the course with the LV number `number` in `semester`, found with the VV search in
+/// `locale`.
+pub async fn resolve(number: &str, semester: &str, locale: Locale) -> Result<String, StdError> {
+    let mut ids = search::search(number, semester, locale)
+        .await?
+        .into_iter()
+        .filter(|result| lists_number(&result.context, number))
+        .map(|result| result.id)
+        .collect::<Vec<_>>();
+
+    match ids.len() {
+        0 => Err(Error::UnknownLvNumber {
+            number: number.to_owned(),
+            semester: semester.to_owned(),
+        }
+        .into()),
+        1 => Ok(ids.remove(0)),
+        _ => Err(Error::AmbiguousLvNumber {
+            number: number.to_owned(),
+            ids: ids.join(", "),
+        }
+        .into()),
+    }
+}
+
+/// Whether the text of a search result shows the LV number `number`.
+fn lists_number(text: &str, number: &str) -> bool {
+    text.split(|c: char| c.is_whitespace() || c == ',' || c == ':' || c == '(' || c == ')')
+        .any(|word| word.eq_ignore_ascii_case(number))
+}
@@ -1,215 +1,2331 @@
-use chrono::TimeZone;
-use chrono::{NaiveDate, NaiveTime};
+use chrono::{NaiveDate, Utc};
 use chrono_tz::Europe::Berlin;
-use hyper::Client;
-use hyper_tls::HttpsConnector;
-use ics::properties::{
-    Categories, Description, DtEnd, DtStart, Organizer, RelatedTo, Status, Summary,
+use clap::{App, AppSettings, Arg, ArgMatches, Shell, SubCommand};
+use fu_vv_cal::busy;
+use fu_vv_cal::cache::PageCache;
+use fu_vv_cal::changes::Snapshot;
+use fu_vv_cal::checks;
+use fu_vv_cal::coalesce;
+use fu_vv_cal::colors;
+use fu_vv_cal::completions;
+use fu_vv_cal::config::{self, Config};
+use fu_vv_cal::details;
+use fu_vv_cal::digest::Digest;
+use fu_vv_cal::duplicates;
+use fu_vv_cal::enrollment;
+use fu_vv_cal::failure::{self, CourseFailure, Failure};
+use fu_vv_cal::feed::Feed;
+use fu_vv_cal::fetch::Locale;
+use fu_vv_cal::first_last;
+use fu_vv_cal::holidays;
+use fu_vv_cal::lenient::ParseWarning;
+use fu_vv_cal::milestones;
+use fu_vv_cal::model::Courses;
+use fu_vv_cal::options::CalendarOptions;
+use fu_vv_cal::progress::Progress;
+use fu_vv_cal::related;
+use fu_vv_cal::reload::ConfigWatcher;
+use fu_vv_cal::robots;
+use fu_vv_cal::rules::Rules;
+use fu_vv_cal::sanitize;
+use fu_vv_cal::selection::{SelectedCourse, Selection};
+use fu_vv_cal::semester::Semester;
+use fu_vv_cal::server::auth::{self, Access};
+use fu_vv_cal::server::tls;
+use fu_vv_cal::server::{Served, ServedCourses};
+use fu_vv_cal::shutdown;
+use fu_vv_cal::source::FuVv;
+use fu_vv_cal::stats::Workload;
+use fu_vv_cal::store::Store;
+use fu_vv_cal::summary::SummaryTemplate;
+use fu_vv_cal::telemetry::{self, ParseStats};
+use fu_vv_cal::timezone;
+use fu_vv_cal::travel;
+use fu_vv_cal::update;
+use fu_vv_cal::usage::{self, Usage};
+use fu_vv_cal::validate;
+use fu_vv_cal::{
+    caldav, fetch, google, html, jscalendar, lecturer, lv, merge, offsets, org, outlook, picker,
+    server, webhook, weeks, xlsx, Bundle, Course, Error, RequestedCourse, StdError,
 };
-use ics::{escape_text, Event, ICalendar};
-use select::document::Document;
-use select::predicate::{Attr, Class, Name, Predicate};
-use snafu::{ensure, Backtrace, ErrorCompat, ResultExt, Snafu};
-use timespan::{DateTimeSpan, NaiveDateTimeSpan};
+use ics::ICalendar;
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::timer::Timeout;
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
 
-type StdError = Box<dyn std::error::Error>;
+#[tokio::main]
+async fn main() {
+    let matches = app().get_matches();
 
-#[derive(Debug, Snafu)]
-enum Error {
-    #[snafu(display("The HTTP request for the course page was not successful"))]
-    HttpRequestError,
+    if let Err(err) = run(&matches).await {
+        let failure = Failure::new(&*err);
+        failure.report(matches.value_of("error-format").unwrap());
+        std::process::exit(failure.exit_code);
+    }
 }
 
-// TODO: RELATED-TO to cancel all events of a series
+/// The command line interface, also used to generate shell completions.
+fn app() -> App<'static, 'static> {
+    App::new("fu-vv-cal")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Generates iCalendar files from the course pages of the FU Berlin VV")
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .default_value("fu-vv-cal.toml")
+                .env("FU_VV_CAL_CONFIG")
+                .help(
+                    "Configuration file with the courses to export, FU_VV_CAL_* environment \
+                     variables override it",
+                ),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .multiple(true)
+                .help("Logs more details, -v for progress and -vv for every request (or RUST_LOG)"),
+        )
+        .arg(
+            Arg::with_name("ignore-robots")
+                .long("ignore-robots")
+                .help("Requests pages of the VV even if its robots.txt disallows them"),
+        )
+        .arg(
+            Arg::with_name("lenient")
+                .long("lenient")
+                .help("Skips sessions that can't be parsed with a warning instead of failing"),
+        )
+        .arg(
+            Arg::with_name("connect-timeout")
+                .long("connect-timeout")
+                .value_name("SECONDS")
+                .help("Time allowed for connecting to the VV, overrides the config"),
+        )
+        .arg(
+            Arg::with_name("request-timeout")
+                .long("request-timeout")
+                .value_name("SECONDS")
+                .help("Time allowed for a whole request to the VV, overrides the config"),
+        )
+        .arg(
+            Arg::with_name("cache-dir")
+                .long("cache-dir")
+                .value_name("DIR")
+                .env("FU_VV_CAL_CACHE_DIR")
+                .help("Caches fetched course pages in this directory"),
+        )
+        .arg(
+            Arg::with_name("max-age")
+                .long("max-age")
+                .value_name("DURATION")
+                .default_value("1h")
+                .help("Age up to which cached course pages are reused, e.g. 1h or 1d"),
+        )
+        .arg(
+            Arg::with_name("locale")
+                .long("locale")
+                .possible_values(&["de", "en"])
+                .help("Language version of the VV, overrides the config"),
+        )
+        .arg(
+            Arg::with_name("uid-scheme")
+                .long("uid-scheme")
+                .possible_values(&["stable", "legacy"])
+                .help("UIDs of the events, legacy for subscriptions of older versions"),
+        )
+        .arg(Arg::with_name("migrate-uids").long("migrate-uids").help(
+            "Also exports every session as cancelled under its legacy UID, so clients \
+                     drop the copies of older versions",
+        ))
+        .arg(
+            Arg::with_name("busy-only")
+                .long("busy-only")
+                .help("Exports every session as just \"Busy\", without course names or rooms"),
+        )
+        .arg(
+            Arg::with_name("transparent")
+                .long("transparent")
+                .help("Marks the events as free time, so they don't block free/busy lookups"),
+        )
+        .arg(
+            Arg::with_name("visibility")
+                .long("visibility")
+                .possible_values(&["public", "private", "confidential"])
+                .help("Access classification (CLASS) of the events"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .possible_values(&["ics", "jscalendar", "org", "xlsx", "html", "json"])
+                .default_value("ics")
+                .help("Format of the exported calendars"),
+        )
+        .arg(
+            Arg::with_name("part")
+                .long("part")
+                .value_name("PART")
+                .help("Only exports sessions of this course part, e.g. vorlesung, übung or tut"),
+        )
+        .arg(
+            Arg::with_name("summary")
+                .long("summary")
+                .value_name("TEMPLATE")
+                .help("Summary of the events, e.g. \"{type}: {name} ({id})\", also {week}"),
+        )
+        .arg(
+            Arg::with_name("usage").long("usage").help(
+                "Prints downloads, cache hits, stage timings and peak memory after exporting",
+            ),
+        )
+        .arg(
+            Arg::with_name("import-selection")
+                .long("import-selection")
+                .value_name("FILE")
+                .help("Adds the courses of a shared selection (.fuvv file) to the config file"),
+        )
+        .arg(
+            Arg::with_name("from")
+                .long("from")
+                .value_name("DATE")
+                .help("Only exports sessions on or after this day, e.g. 2019-11-01"),
+        )
+        .arg(
+            Arg::with_name("until")
+                .long("until")
+                .value_name("DATE")
+                .help("Only exports sessions on or before this day, e.g. 2020-02-01"),
+        )
+        .arg(
+            Arg::with_name("group")
+                .long("group")
+                .value_name("GROUP")
+                .help("Only exports sessions of this exercise group, e.g. \"Gruppe B\""),
+        )
+        .arg(
+            Arg::with_name("weekday")
+                .long("weekday")
+                .value_name("DAYS")
+                .help("Only exports sessions on these weekdays, e.g. Mo,We or Mo,Mi"),
+        )
+        .arg(
+            Arg::with_name("language")
+                .long("language")
+                .value_name("LANG")
+                .help("Only exports courses taught in this language, e.g. en or Deutsch"),
+        )
+        .arg(
+            Arg::with_name("details")
+                .long("details")
+                .help("Fetches the detail page of every session for rooms, lecturers and comments"),
+        )
+        .arg(
+            Arg::with_name("fail-on-clash")
+                .long("fail-on-clash")
+                .help("Fails if sessions of different courses overlap"),
+        )
+        .arg(
+            Arg::with_name("keep-duplicates")
+                .long("keep-duplicates")
+                .help(
+                    "Keeps sessions listed by several courses twice in combined calendars \
+                     instead of merging them",
+                ),
+        )
+        .arg(
+            Arg::with_name("force").long("force").help(
+                "Rewrites all calendars, even those of courses unchanged since the last export",
+            ),
+        )
+        .arg(
+            Arg::with_name("keep-going")
+                .long("keep-going")
+                .help("Exports the other courses if some fail, exiting with code 5 afterwards"),
+        )
+        .arg(
+            Arg::with_name("error-format")
+                .long("error-format")
+                .value_name("FORMAT")
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("Prints errors as text or as JSON lines with course, phase and message"),
+        )
+        .arg(
+            Arg::with_name("progress")
+                .long("progress")
+                .help("Prints the status of every course while exporting"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .short("o")
+                .value_name("FILE")
+                .help(
+                    "Writes all courses into one calendar at FILE (- for stdout) instead of the \
+                     configured outputs",
+                ),
+        )
+        .arg(
+            Arg::with_name("merge-into")
+                .long("merge-into")
+                .value_name("FILE")
+                .conflicts_with("output")
+                .help(
+                    "Updates the sessions of the courses in the existing calendar FILE, keeping \
+                     all other events",
+                ),
+        )
+        .arg(
+            Arg::with_name("from-file")
+                .long("from-file")
+                .value_name("FILE")
+                .help("Prints the calendar of a saved course page instead of exporting"),
+        )
+        .arg(
+            Arg::with_name("coalesce-sessions")
+                .long("coalesce-sessions")
+                .help("Joins back-to-back sessions of a course into one event"),
+        )
+        .arg(
+            Arg::with_name("mark-first-last")
+                .long("mark-first-last")
+                .help("Marks the first and the last session of every course in its summary"),
+        )
+        .arg(
+            Arg::with_name("milestones")
+                .long("milestones")
+                .help("Adds the semester dates as all-day events to combined calendars"),
+        )
+        .arg(
+            Arg::with_name("timezone")
+                .long("timezone")
+                .value_name("ZONE")
+                .help("Zone of the exported local times, e.g. Europe/London"),
+        )
+        .arg(
+            Arg::with_name("floating")
+                .long("floating")
+                .help("Writes the times of .ics files as local times without a zone"),
+        )
+        .arg(
+            Arg::with_name("travel-buffer")
+                .long("travel-buffer")
+                .value_name("MINUTES")
+                .help("Adds events for the way before sessions in another building"),
+        )
+        .arg(
+            Arg::with_name("update-in-place")
+                .long("update-in-place")
+                .help("Updates existing .ics files event by event instead of overwriting them"),
+        )
+        .arg(
+            Arg::with_name("lecture-free")
+                .long("lecture-free")
+                .possible_values(&["keep", "annotate", "drop"])
+                .help("Marks or drops sessions on public holidays and in lecture breaks"),
+        )
+        .arg(Arg::with_name("all-semesters").long("all-semesters").help(
+            "Exports every course for all semesters with known VV ids, each into its own \
+                     semester directory",
+        ))
+        .arg(
+            Arg::with_name("ids-file")
+                .long("ids-file")
+                .value_name("FILE")
+                .help(
+                    "Exports the courses listed in FILE instead of the configured ones, one \
+                     id[,semester][,alias] per line",
+                ),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .long("stdin")
+                .conflicts_with_all(&["ids-file", "watch"])
+                .help("Reads the list of courses like --ids-file from stdin"),
+        )
+        .arg(
+            Arg::with_name("sign-in")
+                .long("sign-in")
+                .help("Signs in to the VV for course pages only shown to members of the FU"),
+        )
+        .arg(
+            Arg::with_name("enrolled")
+                .long("enrolled")
+                .help("Signs in to Campus Management and adds the courses you are enrolled in"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .help("Keeps running and exports again after every interval"),
+        )
+        .arg(
+            Arg::with_name("interval")
+                .long("interval")
+                .value_name("DURATION")
+                .default_value("6h")
+                .help("Time between exports in watch mode, e.g. 6h, 30m or 1d"),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about(
+                    "Serves course calendars at /course/{id}.ics?sm={semester} (or merged at \
+                     /ics?courses={id},{id}&sm={semester}), their changes \
+                     at /course/{id}/changes.atom?sm={semester}, the configured courses via \
+                     CalDAV at /caldav/, Prometheus metrics at /metrics and probes at /healthz \
+                     and /readyz",
+                )
+                .arg(
+                    Arg::with_name("bind")
+                        .long("bind")
+                        .value_name("ADDR")
+                        .default_value("127.0.0.1:8080")
+                        .env("FU_VV_CAL_BIND")
+                        .help("Address to listen on"),
+                )
+                .arg(
+                    Arg::with_name("token")
+                        .long("token")
+                        .value_name("TOKEN")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .env("FU_VV_CAL_TOKEN")
+                        .help("Only answers requests with this token, in addition to the config"),
+                )
+                .arg(
+                    Arg::with_name("signing-key")
+                        .long("signing-key")
+                        .value_name("SECRET")
+                        .env("FU_VV_CAL_SIGNING_KEY")
+                        .help("Also answers requests with URLs signed by sign-url with this key"),
+                )
+                .arg(
+                    Arg::with_name("tls-cert")
+                        .long("tls-cert")
+                        .value_name("FILE")
+                        .env("FU_VV_CAL_TLS_CERT")
+                        .requires("tls-key")
+                        .help("Serves HTTPS with this PEM certificate chain"),
+                )
+                .arg(
+                    Arg::with_name("tls-key")
+                        .long("tls-key")
+                        .value_name("FILE")
+                        .env("FU_VV_CAL_TLS_KEY")
+                        .requires("tls-cert")
+                        .help("Private key of the certificate given with --tls-cert"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sign-url")
+                .about("Signs a URL path of serve, e.g. a personal /ics?courses=… subscription")
+                .arg(
+                    Arg::with_name("path")
+                        .value_name("PATH")
+                        .required(true)
+                        .help("Path and query, e.g. \"/ics?courses=524870,525101&sm=498562\""),
+                )
+                .arg(
+                    Arg::with_name("signing-key")
+                        .long("signing-key")
+                        .value_name("SECRET")
+                        .env("FU_VV_CAL_SIGNING_KEY")
+                        .help("Defaults to the signing key of the [server] config section"),
+                )
+                .arg(
+                    Arg::with_name("valid-days")
+                        .long("valid-days")
+                        .value_name("DAYS")
+                        .help("Makes the URL expire after this many days"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sync")
+                .about("Syncs the configured courses into an online calendar")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("google")
+                        .about("Syncs into the Google Calendar from the [google] config section"),
+                )
+                .subcommand(
+                    SubCommand::with_name("caldav").about(
+                        "Uploads into the CalDAV collection from the [caldav] config section",
+                    ),
+                )
+                .subcommand(
+                    SubCommand::with_name("outlook")
+                        .about("Syncs into the Outlook calendar of the [outlook] config section"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("lecturer")
+                .about("Exports all courses linked from a lecturer's VV page into one calendar")
+                .arg(
+                    Arg::with_name("url")
+                        .required(true)
+                        .help("URL of the lecturer's VV page"),
+                )
+                .arg(
+                    Arg::with_name("semester")
+                        .long("semester")
+                        .value_name("SM")
+                        .required(true)
+                        .help("Semester ID as found in the `sm` parameter of VV URLs"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("FILE")
+                        .default_value("lecturer.ics"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("smoke")
+                .about("Fetches, parses and exports one course and prints a single OK/FAIL line")
+                .arg(
+                    Arg::with_name("course")
+                        .long("course")
+                        .value_name("ID|URL")
+                        .required(true)
+                        .help("ID of the course, the URL of its VV page or its LV number"),
+                )
+                .arg(
+                    Arg::with_name("semester")
+                        .long("semester")
+                        .value_name("SM")
+                        .help("Defaults to the semester of the config file"),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .value_name("SECONDS")
+                        .default_value("60")
+                        .help("Fails if the whole cycle takes longer"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Prints what is parsed from a course page, to check it before adding it")
+                .arg(
+                    Arg::with_name("course")
+                        .value_name("ID|URL")
+                        .required(true)
+                        .help("ID of the course, the URL of its VV page or its LV number"),
+                )
+                .arg(
+                    Arg::with_name("semester")
+                        .long("semester")
+                        .value_name("SM")
+                        .help("Defaults to the semester of the config file"),
+                )
+                .arg(
+                    Arg::with_name("no-details")
+                        .long("no-details")
+                        .help("Skips fetching the session pages, which list the lecturers"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("list-events")
+                .about("Prints the parsed sessions of a course as a table")
+                .arg(
+                    Arg::with_name("course")
+                        .value_name("ID|URL")
+                        .required(true)
+                        .help("ID of the course, the URL of its VV page or its LV number"),
+                )
+                .arg(
+                    Arg::with_name("semester")
+                        .long("semester")
+                        .value_name("SM")
+                        .help("Defaults to the semester of the config file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("related")
+                .about("Lists the courses linked from a course page, e.g. the rest of its module")
+                .arg(
+                    Arg::with_name("course")
+                        .value_name("ID|URL")
+                        .required(true)
+                        .help("ID of the course, the URL of its VV page or its LV number"),
+                )
+                .arg(
+                    Arg::with_name("semester")
+                        .long("semester")
+                        .value_name("SM")
+                        .help("Defaults to the semester of the config file"),
+                )
+                .arg(
+                    Arg::with_name("depth")
+                        .long("depth")
+                        .value_name("LINKS")
+                        .default_value("1")
+                        .help("Also follows the links of the courses found, up to this many hops"),
+                )
+                .arg(
+                    Arg::with_name("add")
+                        .long("add")
+                        .help("Adds the courses found to the config"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Prints how the schedule changed since the last export")
+                .arg(
+                    Arg::with_name("course")
+                        .long("course")
+                        .value_name("ID|URL")
+                        .help("Only compares this course, given by its ID, VV URL or LV number"),
+                )
+                .arg(
+                    Arg::with_name("state")
+                        .long("state")
+                        .value_name("FILE")
+                        .help("State file to compare against, defaults to `state` of the config"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("share")
+                .about("Prints the configured courses as a token to share with others")
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Writes the selection to a .fuvv file instead"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("digest")
+                .about("Prints upcoming sessions and the changes since the last digest")
+                .arg(
+                    Arg::with_name("weekly")
+                        .long("weekly")
+                        .help("Covers the upcoming week (default)"),
+                )
+                .arg(
+                    Arg::with_name("daily")
+                        .long("daily")
+                        .conflicts_with("weekly")
+                        .help("Covers the upcoming day"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .possible_values(&["text", "html"])
+                        .default_value("text"),
+                )
+                .arg(
+                    Arg::with_name("state")
+                        .long("state")
+                        .value_name("FILE")
+                        .default_value("digest-state.json")
+                        .help("Where the state of the last digest is kept"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stats").about(
+                "Prints contact hours per week and course, the busiest weekday and free slots",
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("next")
+                .about("Prints the next sessions of the configured courses")
+                .after_help(
+                    "Course pages are cached for --max-age, in the system's temporary directory \
+                     unless --cache-dir is given.",
+                )
+                .arg(
+                    Arg::with_name("count")
+                        .long("count")
+                        .short("n")
+                        .value_name("N")
+                        .default_value("5")
+                        .help("Number of sessions to print"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pick")
+                .about("Searches the VV interactively and adds the selected courses to the config")
+                .arg(
+                    Arg::with_name("semester")
+                        .long("semester")
+                        .value_name("SM")
+                        .help("Semester to search, defaults to the semester of the config file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("completions")
+                .about("Prints a completion script for the shell, completing configured courses")
+                .after_help(
+                    "Load it with e.g. `source <(fu-vv-cal completions bash)` in your .bashrc, or \
+                     `fu-vv-cal completions fish > ~/.config/fish/completions/fu-vv-cal.fish`.",
+                )
+                .arg(
+                    Arg::with_name("shell")
+                        .possible_values(&["bash", "zsh", "fish"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name(completions::COURSES_SUBCOMMAND)
+                .setting(AppSettings::Hidden)
+                .about("Prints the configured courses for shell completions"),
+        )
+}
 
-/// Parse timespan of "Mo, 21.10.2019 10:00 - 13:00"
-fn parse_timespan(date_text: String) -> Result<DateTimeSpan<chrono_tz::Tz>, StdError> {
-    let date_text = date_text[4..].to_owned();
+/// Directory in the temporary directory where `next` caches course pages by default
+const NEXT_CACHE_DIR: &str = "fu-vv-cal-cache";
 
-    let date_split = date_text.split(" ").collect::<Vec<_>>();
-    let date_day = date_split[0];
-    let date_start_time = date_split[1];
-    let date_end_time = date_split[3];
+/// Run the subcommand, or the export without one.
+async fn run(matches: &ArgMatches<'static>) -> Result<(), StdError> {
+    init_logging(matches.occurrences_of("verbose"))?;
+    apply_setting_args(matches)?;
+    if let Some(cache_dir) = matches.value_of("cache-dir") {
+        let max_age = parse_interval(matches.value_of("max-age").unwrap())?;
+        fetch::set_page_cache(PageCache::new(cache_dir, max_age));
+    }
 
-    let date_day = NaiveDate::parse_from_str(&date_day, "%d.%m.%Y")?;
-    let date_start_time = NaiveTime::parse_from_str(&date_start_time, "%R")?;
-    let date_end_time = NaiveTime::parse_from_str(&date_end_time, "%R")?;
+    match matches.subcommand() {
+        ("serve", Some(serve_matches)) => {
+            let addr = serve_matches.value_of("bind").unwrap().parse()?;
+            // The config is optional here, without it only the per-course routes are useful
+            let config_path = matches.value_of("config").unwrap();
+            let config = if Path::new(config_path).exists()
+                || config::configured_by_env()
+                || matches.is_present("ids-file")
+                || matches.is_present("stdin")
+            {
+                Some(load_config(matches).await?)
+            } else {
+                None
+            };
+            let served = match &config {
+                Some(config) => served_courses(config)?,
+                None => {
+                    let (locale, options) = cli_calendar(matches)?;
+                    Served {
+                        courses: vec![],
+                        name: config::DEFAULT_MERGED_NAME.to_owned(),
+                        locale,
+                        options,
+                        lenient: matches.is_present("lenient"),
+                    }
+                }
+            };
+            let courses = ServedCourses::new(served);
+            // Course lists from stdin can't be read again
+            if Path::new(config_path).exists() && !matches.is_present("stdin") {
+                let mut watcher = ConfigWatcher::new(config_path);
+                let matches = matches.clone();
+                let courses = courses.clone();
+                tokio::spawn(async move {
+                    loop {
+                        watcher.next_change().await;
+                        let reloaded = match load_config(&matches).await {
+                            Ok(config) => served_courses(&config),
+                            Err(err) => Err(err),
+                        };
+                        match reloaded {
+                            Ok(served) => {
+                                info!(courses = served.courses.len(), "reloaded config");
+                                courses.replace(served);
+                            }
+                            Err(err) => {
+                                error!(%err, "failed to reload config, keeping the old one")
+                            }
+                        }
+                    }
+                });
+            }
+            let server_config = config.and_then(|config| config.server);
+            let mut tokens = serve_matches
+                .values_of("token")
+                .map(|tokens| tokens.map(str::to_owned).collect::<Vec<_>>())
+                .unwrap_or_default();
+            let mut signing_key = serve_matches.value_of("signing-key").map(str::to_owned);
+            // clap makes sure both or neither are given
+            let mut tls_files = serve_matches.value_of("tls-cert").map(|cert| {
+                let key = serve_matches.value_of("tls-key").unwrap();
+                (cert.to_owned(), key.to_owned())
+            });
+            if let Some(server_config) = server_config {
+                tokens.extend(server_config.tokens);
+                signing_key = signing_key.or(server_config.signing_key);
+                if tls_files.is_none() {
+                    tls_files = match (server_config.tls_cert, server_config.tls_key) {
+                        (Some(cert), Some(key)) => Some((cert, key)),
+                        (None, None) => None,
+                        _ => return Err(Error::IncompleteTlsConfig.into()),
+                    };
+                }
+            }
+            let access = Access::new(tokens, signing_key);
+            if access.is_open() {
+                warn!("no tokens or signing key configured, anyone can use the server");
+            }
+            let tls = match tls_files {
+                Some((cert, key)) => Some(tls::acceptor(&cert, &key)?),
+                None => None,
+            };
+            shutdown::install()?;
+            server::serve(addr, courses, access, tls).await
+        }
+        ("sign-url", Some(sign_matches)) => {
+            let key = match sign_matches.value_of("signing-key") {
+                Some(key) => key.to_owned(),
+                None => load_config(matches)
+                    .await?
+                    .server
+                    .and_then(|server| server.signing_key)
+                    .ok_or_else(|| missing_section("server"))?,
+            };
+            let expires = match sign_matches.value_of("valid-days") {
+                Some(days) => {
+                    Some((Utc::now() + chrono::Duration::days(days.parse()?)).timestamp())
+                }
+                None => None,
+            };
+            println!(
+                "{}",
+                auth::sign(&key, sign_matches.value_of("path").unwrap(), expires)
+            );
+            Ok(())
+        }
+        ("sync", Some(sync_matches)) => {
+            let config = load_config(matches).await?;
+            let courses = configured_courses(&config)?;
+            match sync_matches.subcommand() {
+                ("google", Some(_)) => {
+                    let google = config
+                        .google
+                        .as_ref()
+                        .ok_or_else(|| missing_section("google"))?;
+                    google::sync(google, &courses).await
+                }
+                ("caldav", Some(_)) => {
+                    let caldav = config
+                        .caldav
+                        .as_ref()
+                        .ok_or_else(|| missing_section("caldav"))?;
+                    caldav::sync(caldav, &courses).await
+                }
+                ("outlook", Some(_)) => {
+                    let outlook = config
+                        .outlook
+                        .as_ref()
+                        .ok_or_else(|| missing_section("outlook"))?;
+                    outlook::sync(outlook, &courses).await
+                }
+                _ => unreachable!(),
+            }
+        }
+        ("lecturer", Some(lecturer_matches)) => {
+            let (mut locale, mut options) = cli_calendar(matches)?;
+            let mut lenient = matches.is_present("lenient");
+            let mut name = None;
+            if Path::new(matches.value_of("config").unwrap()).exists() {
+                let config = load_config(matches).await?;
+                locale = config.locale;
+                options = config.calendar;
+                lenient = config.lenient;
+                name = Some(config.merged_name);
+            }
+            let courses = lecturer::courses_of(
+                lecturer_matches.value_of("url").unwrap(),
+                lecturer_matches.value_of("semester").unwrap(),
+                locale,
+                lenient,
+            )
+            .await?;
+            println!("Found {} courses", courses.len());
 
-    let start_date = date_day.and_time(date_start_time);
-    let end_date = date_day.and_time(date_end_time);
+            let mut bundle = Bundle::new(courses).with_options(options);
+            if let Some(name) = name {
+                bundle = bundle.with_name(&name);
+            }
+            if matches.is_present("progress") {
+                bundle = bundle.with_progress();
+            }
+            let collisions = bundle
+                .save_as_ical(lecturer_matches.value_of("output").unwrap())
+                .await?;
+            for collision in collisions {
+                warn!(%collision, "UID collision");
+            }
+            Ok(())
+        }
+        ("smoke", Some(smoke_matches)) => {
+            let (locale, options) = cli_calendar(matches)?;
+            let (id, semester) = course_argument(matches, smoke_matches, locale).await?;
+            let id = id.as_str();
+            let timeout = Duration::from_secs(smoke_matches.value_of("timeout").unwrap().parse()?);
 
-    let date_span = DateTimeSpan::from_local_datetimespan(
-        &NaiveDateTimeSpan::new(start_date, end_date).unwrap(),
-        &Berlin,
-    );
+            let requested = RequestedCourse::new(id, semester)
+                .with_source(Arc::new(cli_source(matches, locale)))
+                .with_options(options);
+            let started = Instant::now();
+            let result = Timeout::new(smoke(requested), timeout).await;
+            let elapsed = started.elapsed().as_secs_f64();
+            match result {
+                Ok(Ok(events)) => {
+                    println!("OK course={} events={} time={:.2}s", id, events, elapsed);
+                    Ok(())
+                }
+                Ok(Err(err)) => {
+                    println!("FAIL course={} time={:.2}s error=\"{}\"", id, elapsed, err);
+                    std::process::exit(Failure::new(&*err).exit_code);
+                }
+                Err(_) => {
+                    println!(
+                        "FAIL course={} time={:.2}s error=\"timed out\"",
+                        id, elapsed
+                    );
+                    std::process::exit(failure::EXIT_NETWORK);
+                }
+            }
+        }
+        ("show", Some(show_matches)) => {
+            let (locale, options) = cli_calendar(matches)?;
+            let (id, semester) = course_argument(matches, show_matches, locale).await?;
+            let mut course = RequestedCourse::new(id, semester)
+                .with_source(Arc::new(cli_source(matches, locale)))
+                .get_course()
+                .await?;
+            timezone::convert(&mut course, &options)?;
+            if !show_matches.is_present("no-details") {
+                if let Err(err) = details::enrich(&mut course).await {
+                    warn!(%err, "the session pages could not be fetched");
+                }
+            }
+            print!("{}", course_overview(&course));
+            Ok(())
+        }
+        ("list-events", Some(list_matches)) => {
+            let (locale, options) = cli_calendar(matches)?;
+            let (id, semester) = course_argument(matches, list_matches, locale).await?;
+            let mut course = RequestedCourse::new(id, semester)
+                .with_source(Arc::new(cli_source(matches, locale)))
+                .get_course()
+                .await?;
+            timezone::convert(&mut course, &options)?;
+            print!("{}", event_table(&course));
+            Ok(())
+        }
+        ("related", Some(related_matches)) => {
+            let (locale, _) = cli_calendar(matches)?;
+            let (id, semester) = course_argument(matches, related_matches, locale).await?;
+            let depth = related_matches.value_of("depth").unwrap().parse()?;
+            let source = cli_source(matches, locale);
+            let courses = related::crawl(&source, &id, &semester, depth).await?;
+            if courses.is_empty() {
+                println!("No courses linked from {}", id);
+                return Ok(());
+            }
+            for course in courses.iter() {
+                let kind = course.kind.as_ref().map(String::as_str).unwrap_or("-");
+                println!(
+                    "{}\t{}\t{}\t(via {})",
+                    course.id, kind, course.name, course.linked_from
+                );
+            }
+            if !related_matches.is_present("add") {
+                println!("{} related courses, add them with --add", courses.len());
+                return Ok(());
+            }
+            let config_path = matches.value_of("config").unwrap();
+            let selection = Selection {
+                semester: None,
+                courses: courses
+                    .into_iter()
+                    .map(|course| SelectedCourse {
+                        id: course.id,
+                        semester: Some(semester.clone()),
+                    })
+                    .collect(),
+            };
+            let added = selection.append_to_config(config_path)?;
+            for course in added.iter() {
+                println!("Added course {}", course.id);
+            }
+            println!("{} courses added to {}", added.len(), config_path);
+            Ok(())
+        }
+        ("diff", Some(diff_matches)) => {
+            let config = load_config(matches).await?;
+            let state = diff_matches
+                .value_of("state")
+                .or_else(|| config.state.as_ref().map(String::as_str))
+                .ok_or(Error::MissingStateFile)?;
+            let mut courses = configured_courses(&config)?;
+            // The state tracks further semesters of a course separately, compare the first one
+            courses.dedup_by(|course, previous| course.id == previous.id);
+            if let Some(reference) = diff_matches.value_of("course") {
+                let (id, url_semester) = fetch::parse_course_reference(reference)?;
+                let semester = url_semester.or_else(|| config.semester.clone());
+                let id = match &semester {
+                    Some(semester) => lv::resolve_id(&id, semester, config.locale).await?,
+                    None => id,
+                };
+                courses.retain(|course| course.id == id);
+                if courses.is_empty() {
+                    let semester =
+                        semester.ok_or_else(|| Error::MissingSemester { id: id.clone() })?;
+                    courses.push(
+                        RequestedCourse::new(id, semester)
+                            .with_source(Arc::new(
+                                FuVv::new()
+                                    .with_locale(config.locale)
+                                    .with_lenient(config.lenient),
+                            ))
+                            .with_options(config.calendar.clone()),
+                    );
+                }
+            }
+
+            // The state is only read, so the next export still sees the changes
+            let current = Snapshot::fetch(&courses).await?;
+            let previous = Snapshot::load(state)?;
+            for (id, course) in current.courses.iter() {
+                if !previous.courses.contains_key(id) {
+                    println!("{}: not exported before, nothing to compare", course.name);
+                }
+            }
+            let changes = current.changes_since(&previous);
+            if changes.is_empty() {
+                println!("No changes.");
+            }
+            for change in changes {
+                println!("{}", change);
+            }
+            Ok(())
+        }
+        ("share", Some(share_matches)) => {
+            let selection = Selection::from_config(&load_config(matches).await?);
+            match share_matches.value_of("output") {
+                Some(output) => selection.save(output),
+                None => {
+                    println!("{}", selection.to_token()?);
+                    Ok(())
+                }
+            }
+        }
+        ("stats", Some(_)) => {
+            let courses = configured_course_data(&load_config(matches).await?).await?;
+            print!("{}", Workload::new(&courses).to_text());
+            Ok(())
+        }
+        ("next", Some(next_matches)) => {
+            if matches.value_of("cache-dir").is_none() {
+                let max_age = parse_interval(matches.value_of("max-age").unwrap())?;
+                let cache_dir = std::env::temp_dir().join(NEXT_CACHE_DIR);
+                fetch::set_page_cache(PageCache::new(cache_dir, max_age));
+            }
+            let count = next_matches.value_of("count").unwrap().parse::<usize>()?;
+            let courses = configured_course_data(&load_config(matches).await?).await?;
+            print!("{}", upcoming_sessions(&courses, count));
+            Ok(())
+        }
+        ("pick", Some(pick_matches)) => {
+            let config_path = matches.value_of("config").unwrap();
+            let semester = match pick_matches.value_of("semester") {
+                Some(semester) => semester.to_owned(),
+                None if Path::new(config_path).exists() => load_config(matches)
+                    .await?
+                    .semester
+                    .ok_or(Error::MissingDefaultSemester)?,
+                None => return Err(Error::MissingDefaultSemester.into()),
+            };
+
+            let (locale, _) = cli_calendar(matches)?;
+            let picked = picker::pick(&semester, locale, matches.is_present("lenient")).await?;
+            let selection = Selection {
+                semester: None,
+                courses: picked
+                    .into_iter()
+                    .map(|result| SelectedCourse {
+                        id: result.id,
+                        semester: Some(semester.clone()),
+                    })
+                    .collect(),
+            };
+            let added = selection.append_to_config(config_path)?;
+            for course in added.iter() {
+                println!("Added course {}", course.id);
+            }
+            println!("{} courses added to {}", added.len(), config_path);
+            Ok(())
+        }
+        ("completions", Some(completions_matches)) => {
+            let shell = completions_matches.value_of("shell").unwrap();
+            let mut script = vec![];
+            app().gen_completions_to("fu-vv-cal", shell.parse::<Shell>()?, &mut script);
+            let script = completions::with_courses(shell, &String::from_utf8(script)?);
+            print!("{}", script);
+            Ok(())
+        }
+        (completions::COURSES_SUBCOMMAND, _) => {
+            // Completion stays quiet without a usable config
+            if let Ok(config) = Config::load(matches.value_of("config").unwrap()) {
+                for line in completions::course_lines(&config) {
+                    println!("{}", line);
+                }
+            }
+            Ok(())
+        }
+        ("digest", Some(digest_matches)) => {
+            let config = load_config(matches).await?;
+            let state = digest_matches.value_of("state").unwrap();
+            let days = if digest_matches.is_present("daily") {
+                1
+            } else {
+                7
+            };
 
-    date_span.map_err(Into::into)
+            let current = Snapshot::fetch(&configured_courses(&config)?).await?;
+            let previous = Snapshot::load(state)?;
+            let digest = Digest::new(&current, &previous, Utc::now(), days);
+            match digest_matches.value_of("format").unwrap() {
+                "html" => print!("{}", digest.to_html()),
+                _ => print!("{}", digest.to_text()),
+            }
+            current.save(state)
+        }
+        _ => {
+            if let Some(file) = matches.value_of("import-selection") {
+                let config_path = matches.value_of("config").unwrap();
+                let added = Selection::load(file)?.append_to_config(config_path)?;
+                for course in added.iter() {
+                    println!("Added course {}", course.id);
+                }
+                println!("{} courses added to {}", added.len(), config_path);
+                return Ok(());
+            }
+            if let Some(file) = matches.value_of("from-file") {
+                let (_, options) = cli_calendar(matches)?;
+                let lenient = matches.is_present("lenient");
+                let calendar = Course::from_file(file, lenient)?.to_ical(&options)?;
+                print!("{}", sanitize::fold(&calendar.to_string()));
+                return Ok(());
+            }
+
+            let options = ExportOptions {
+                format: matches.value_of("format").unwrap(),
+                filters: export_filters(matches)?,
+                details: matches.is_present("details"),
+                fail_on_clash: matches.is_present("fail-on-clash"),
+                keep_going: matches.is_present("keep-going"),
+                force: matches.is_present("force"),
+                keep_duplicates: matches.is_present("keep-duplicates"),
+                error_format: matches.value_of("error-format").unwrap(),
+                output: matches.value_of("output"),
+                merge_into: matches.value_of("merge-into"),
+                progress: matches.is_present("progress"),
+                summary: matches
+                    .value_of("summary")
+                    .map(|template| SummaryTemplate(template.to_owned())),
+            };
+            let print_usage = || {
+                if matches.is_present("usage") {
+                    eprintln!("{}", Usage::current());
+                }
+            };
+            if !matches.is_present("watch") {
+                let result = export(&load_config(matches).await?, &options).await;
+                print_usage();
+                return result;
+            }
+
+            let default_interval = parse_interval(matches.value_of("interval").unwrap())?;
+            shutdown::install()?;
+            let mut watcher = ConfigWatcher::new(matches.value_of("config").unwrap());
+            loop {
+                // The config is reloaded, so changes apply without a restart
+                let mut interval = default_interval;
+                let result = match load_config(matches).await {
+                    Ok(config) => {
+                        let configured = config
+                            .watch_interval
+                            .as_ref()
+                            .filter(|_| matches.occurrences_of("interval") == 0);
+                        if let Some(configured) = configured {
+                            match parse_interval(configured) {
+                                Ok(configured) => interval = configured,
+                                Err(err) => Failure::new(&err).report(options.error_format),
+                            }
+                        }
+                        export(&config, &options).await
+                    }
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = result {
+                    Failure::new(&*err).report(options.error_format);
+                }
+                print_usage();
+                // A changed config is exported right away instead of after the interval
+                watcher.wait(interval).await;
+                if shutdown::requested() {
+                    return Ok(());
+                }
+            }
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-struct Course {
-    name: String,
-    events: Vec<CourseEvent>,
+/// Log to stderr, filtered by `RUST_LOG` if set and otherwise by the number of `-v` flags.
+fn init_logging(verbosity: u64) -> Result<(), StdError> {
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(_) => EnvFilter::from_default_env(),
+        Err(_) => {
+            let level = match verbosity {
+                0 => "warn",
+                1 => "info",
+                2 => "debug",
+                _ => "trace",
+            };
+            EnvFilter::new(format!("warn,fu_vv_cal={}", level))
+        }
+    };
+    let subscriber = tracing_subscriber::fmt::Subscriber::builder()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .finish();
+    tracing::subscriber::set_global_default(subscriber)?;
+    Ok(())
 }
 
-impl Course {
-    pub fn from_document(document: &Document) -> Result<Self, StdError> {
-        Ok(Self {
-            name: Self::name_from_document(&document)?,
-            events: CourseEvent::all_from_document(document)?,
-        })
+/// Load the config file, with the settings given on the command line taking precedence.
+async fn load_config(matches: &ArgMatches<'_>) -> Result<Config, StdError> {
+    let course_list = if let Some(file) = matches.value_of("ids-file") {
+        Some(std::fs::read_to_string(file)?)
+    } else if matches.is_present("stdin") {
+        let mut list = String::new();
+        std::io::stdin().read_to_string(&mut list)?;
+        Some(list)
+    } else {
+        None
+    };
+    let mut config = Config::load_with_course_list(
+        matches.value_of("config").unwrap(),
+        course_list.as_ref().map(String::as_str),
+    )?;
+    if matches.is_present("enrolled") {
+        let enrollment = config
+            .enrollment
+            .as_ref()
+            .ok_or_else(|| missing_section("enrollment"))?;
+        let ids = enrollment::enrolled_course_ids(enrollment).await?;
+        info!(courses = ids.len(), "read enrolled courses");
+        config.add_courses(ids);
+    }
+    if matches.is_present("sign-in") && config.session_cookie.is_none() {
+        let enrollment = config
+            .enrollment
+            .as_ref()
+            .ok_or_else(|| missing_section("enrollment"))?;
+        let cookie = enrollment::vv_session_cookie(enrollment).await?;
+        fetch::set_session_cookie(Some(cookie));
+    }
+    config.all_semesters |= matches.is_present("all-semesters");
+    config.milestones |= matches.is_present("milestones");
+    config.mark_first_last |= matches.is_present("mark-first-last");
+    config.coalesce_sessions |= matches.is_present("coalesce-sessions");
+    config.update_in_place |= matches.is_present("update-in-place");
+    config.lenient |= matches.is_present("lenient");
+    if let Some(minutes) = matches.value_of("travel-buffer") {
+        config.travel_buffer_mins = minutes.parse()?;
+    }
+    if let Some(mode) = matches.value_of("lecture-free") {
+        config.lecture_free = mode.parse()?;
     }
+    apply_calendar_args(matches, &mut config.locale, &mut config.calendar)?;
+    config.resolve_lv_numbers().await?;
+    config.expand_children().await?;
+    apply_setting_args(matches)?;
+    Ok(config)
+}
+
+/// Apply the settings of the command line to the calendar options and the VV locale.
+fn apply_calendar_args(
+    matches: &ArgMatches,
+    locale: &mut Locale,
+    options: &mut CalendarOptions,
+) -> Result<(), StdError> {
+    options.appearance.transparent |= matches.is_present("transparent");
+    if let Some(visibility) = matches.value_of("visibility") {
+        options.appearance.visibility = Some(visibility.parse()?);
+    }
+    if let Some(value) = matches.value_of("locale") {
+        *locale = value.parse()?;
+    }
+    if let Some(scheme) = matches.value_of("uid-scheme") {
+        options.uid_scheme = scheme.parse()?;
+    }
+    options.migrate_uids |= matches.is_present("migrate-uids");
+    if let Some(name) = matches.value_of("timezone") {
+        options.timezone = Some(timezone::parse(name)?);
+    }
+    options.floating |= matches.is_present("floating");
+    options.busy_only |= matches.is_present("busy-only");
+    Ok(())
+}
 
-    fn name_from_document(document: &Document) -> Result<String, StdError> {
-        let node = document
-            .find(Class("subc").descendant(Name("h1")))
-            .next()
-            .expect("Course has no name/title");
+/// Calendar options and VV locale of the commands that work without a config file, taken from
+/// the command line alone.
+fn cli_calendar(matches: &ArgMatches) -> Result<(Locale, CalendarOptions), StdError> {
+    let mut locale = Locale::default();
+    let mut options = CalendarOptions::default();
+    apply_calendar_args(matches, &mut locale, &mut options)?;
+    Ok((locale, options))
+}
 
-        Ok(node.text().trim().to_owned())
+fn apply_setting_args(matches: &ArgMatches) -> Result<(), StdError> {
+    if matches.is_present("ignore-robots") {
+        robots::set_respected(false);
     }
+    if matches.is_present("connect-timeout") || matches.is_present("request-timeout") {
+        let (connect, request) = fetch::timeouts();
+        let seconds = |name| -> Result<Option<Duration>, StdError> {
+            match matches.value_of(name) {
+                Some(value) => Ok(Some(Duration::from_secs(value.parse()?))),
+                None => Ok(None),
+            }
+        };
+        fetch::set_timeouts(
+            seconds("connect-timeout")?.unwrap_or(connect),
+            seconds("request-timeout")?.unwrap_or(request),
+        );
+    }
+    Ok(())
+}
 
-    pub fn to_ical(self) -> Result<ICalendar<'static>, StdError> {
-        let mut calendar = ICalendar::new("2.0", "ics-rs");
+fn configured_courses(config: &Config) -> Result<Vec<RequestedCourse>, StdError> {
+    Ok(config
+        .requested_courses()?
+        .into_iter()
+        .map(|(course, _)| course)
+        .collect())
+}
 
-        let first_id = self.events.iter().next().unwrap().id.clone();
-        for event in self.events.into_iter() {
-            let start_date = event
-                .timespan
-                .start
-                .naive_utc()
-                .format("%Y%m%dT%H%M%SZ")
-                .to_string();
-            let end_date = event
-                .timespan
-                .end
-                .naive_utc()
-                .format("%Y%m%dT%H%M%SZ")
-                .to_string();
-            let mut cal_event = Event::new(event.id, start_date.to_string());
-            cal_event.push(DtStart::new(start_date));
-            cal_event.push(DtEnd::new(end_date));
-            cal_event.push(Summary::new(self.name.clone()));
-            cal_event.push(RelatedTo::new(first_id.clone()));
-            cal_event.push(ics::components::Property::new("RELTYPE", "CHILD"));
+/// What `serve` serves with `config`.
+/// The course page source of the commands run without a config, with the VV in `locale`.
+fn cli_source(matches: &ArgMatches, locale: Locale) -> FuVv {
+    FuVv::new()
+        .with_locale(locale)
+        .with_lenient(matches.is_present("lenient"))
+}
 
-            calendar.add_event(cal_event);
+fn served_courses(config: &Config) -> Result<Served, StdError> {
+    Ok(Served {
+        courses: configured_courses(config)?,
+        name: config.merged_name.clone(),
+        locale: config.locale,
+        options: config.calendar.clone(),
+        lenient: config.lenient,
+    })
+}
+
+/// ID and semester of the `course` argument, from `--semester`, the VV URL or the config file.
+///
+/// LV numbers are looked up in the VV in `locale`.
+async fn course_argument(
+    matches: &ArgMatches<'_>,
+    sub_matches: &ArgMatches<'_>,
+    locale: Locale,
+) -> Result<(String, String), StdError> {
+    let (reference, url_semester) =
+        fetch::parse_course_reference(sub_matches.value_of("course").unwrap())?;
+    let semester = match sub_matches.value_of("semester").map(str::to_owned) {
+        Some(semester) => semester,
+        None => match url_semester {
+            Some(semester) => semester,
+            None => load_config(matches)
+                .await?
+                .semester
+                .ok_or_else(|| Error::MissingSemester {
+                    id: reference.clone(),
+                })?,
+        },
+    };
+    let id = lv::resolve_id(&reference, &semester, locale).await?;
+    Ok((id, semester))
+}
+
+/// What `show` prints about a course.
+fn course_overview(course: &Course) -> String {
+    let mut lecturers = Vec::<&str>::new();
+    for lecturer in course
+        .events
+        .iter()
+        .flat_map(|event| event.lecturers.iter())
+    {
+        if !lecturers.contains(&lecturer.as_str()) {
+            lecturers.push(lecturer);
         }
+    }
+    let number = |value: Option<f32>| {
+        value
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "-".to_owned())
+    };
+    let starts = course.events.iter().map(|event| event.timespan.start);
+    let dates = match (starts.clone().min(), starts.max()) {
+        (Some(first), Some(last)) => format!(
+            "{} to {}",
+            first.format("%a %d.%m.%Y"),
+            last.format("%a %d.%m.%Y")
+        ),
+        _ => "-".to_owned(),
+    };
+    let or_dash = |value: Option<&String>| value.map(String::as_str).unwrap_or("-").to_owned();
 
-        Ok(calendar)
+    let mut lines = vec![
+        course.name.clone(),
+        format!("Type:      {}", or_dash(course.kind.as_ref())),
+        format!(
+            "Lecturers: {}",
+            if lecturers.is_empty() {
+                "-".to_owned()
+            } else {
+                lecturers.join(", ")
+            }
+        ),
+        format!("SWS:       {}", number(course.sws)),
+        format!("ECTS:      {}", number(course.ects)),
+        format!("Language:  {}", or_dash(course.language.as_ref())),
+        format!("Sessions:  {}", course.events.len()),
+        format!("Dates:     {}", dates),
+    ];
+    if !course.modules.is_empty() {
+        lines.push(format!("Modules:   {}", course.modules.join(", ")));
+    }
+    if let Some(url) = &course.url {
+        lines.push(format!("Page:      {}", url));
     }
+    lines.push(String::new());
+    lines.join("\n")
 }
 
-#[derive(Debug, Clone)]
-struct CourseEvent {
-    id: String,
-    timespan: DateTimeSpan<chrono_tz::Tz>,
+/// Fetch the configured courses with their group rules and aliases applied, for the subcommands
+/// that only look at the sessions.
+async fn configured_course_data(config: &Config) -> Result<Vec<Course>, StdError> {
+    let mut courses = vec![];
+    for (requested, course_config) in config.requested_courses()? {
+        let mut course = requested.get_course().await?;
+        if let Some(group) = &course_config.group {
+            Rules::group(group).apply(&requested.id, &mut course)?;
+        }
+        if config.coalesce_sessions {
+            coalesce::coalesce(&mut course)?;
+        }
+        offsets::apply(
+            &mut course,
+            course_config.start_offset_mins,
+            course_config.end_offset_mins,
+        )?;
+        if course_config.all_day {
+            for event in course.events.iter_mut() {
+                event.make_all_day()?;
+            }
+        }
+        if let Some(alias) = &course_config.alias {
+            course.name = alias.clone();
+        }
+        timezone::convert(&mut course, &config.calendar)?;
+        courses.push(course);
+    }
+    Ok(courses)
 }
 
-impl CourseEvent {
-    pub fn all_from_document(document: &Document) -> Result<Vec<Self>, StdError> {
-        let mut events = vec![];
-        for node in document.find(Class("link_to_details")) {
-            let date_node = node.find(Class("course_date_time")).next().unwrap();
-            let date_text = date_node.text().trim().to_owned();
+/// What `next` prints: the `count` next sessions of `courses`, including the ones in progress.
+fn upcoming_sessions(courses: &[Course], count: usize) -> String {
+    let now = Utc::now().with_timezone(&Berlin);
+    let mut sessions = courses
+        .iter()
+        .flat_map(|course| course.events.iter().map(move |event| (course, event)))
+        .filter(|(_, event)| event.timespan.end > now)
+        .collect::<Vec<_>>();
+    sessions.sort_by_key(|(_, event)| event.timespan.start);
+    if sessions.is_empty() {
+        return "No upcoming sessions.\n".to_owned();
+    }
 
-            let date_span = parse_timespan(date_text)?;
+    let mut text = String::new();
+    for (course, event) in sessions.into_iter().take(count) {
+        let name = event.summary.as_ref().unwrap_or(&course.name);
+        let marker = if event.timespan.start <= now {
+            " (now)"
+        } else {
+            ""
+        };
+        let room = event
+            .room
+            .as_ref()
+            .map(|room| format!(", {}", room))
+            .unwrap_or_default();
+        text.push_str(&format!(
+            "{} {}-{}  {}{}{}\n",
+            event.timespan.start.format("%a %d.%m."),
+            event.timespan.start.format("%H:%M"),
+            event.timespan.end.format("%H:%M"),
+            name,
+            room,
+            marker
+        ));
+    }
+    text
+}
 
-            let id = node.attr("id").unwrap().replace("link_to_details_", "");
+/// What `list-events` prints: one row per session, ordered by time.
+fn event_table(course: &Course) -> String {
+    let mut events = course
+        .events
+        .iter()
+        .map(|event| (event, false))
+        .chain(course.cancelled.iter().map(|event| (event, true)))
+        .collect::<Vec<_>>();
+    events.sort_by_key(|(event, _)| event.timespan.start);
 
-            events.push(CourseEvent {
-                id,
-                timespan: date_span,
-            })
+    let header = ["Date", "Time", "Room", "Status", "Note"];
+    let rows = events
+        .into_iter()
+        .map(|(event, cancelled)| {
+            let status = if cancelled {
+                "cancelled"
+            } else if event.exam {
+                "exam"
+            } else {
+                "scheduled"
+            };
+            let note = event
+                .part
+                .iter()
+                .chain(event.group.iter())
+                .chain(event.remark.iter())
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ");
+            vec![
+                event.timespan.start.format("%a %d.%m.%Y").to_string(),
+                format!(
+                    "{}-{}",
+                    event.timespan.start.format("%H:%M"),
+                    event.timespan.end.format("%H:%M")
+                ),
+                event.room.clone().unwrap_or_else(|| "-".to_owned()),
+                status.to_owned(),
+                note,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = header.iter().map(|title| title.len()).collect::<Vec<_>>();
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
         }
+    }
+    let format_row = |cells: Vec<&str>| {
+        let line = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| {
+                let padding = width - cell.chars().count();
+                format!("{}{}", cell, " ".repeat(padding))
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        format!("{}\n", line.trim_end())
+    };
 
-        Ok(events)
+    let mut table = format!("{} ({} sessions)\n\n", course.name, course.events.len());
+    table.push_str(&format_row(header.to_vec()));
+    for row in rows.iter() {
+        table.push_str(&format_row(row.iter().map(String::as_str).collect()));
     }
+    table
 }
 
-#[derive(Debug)]
-struct RequestedCourse {
-    pub id: String,
-    pub semester: String,
+/// One fetch, parse and export cycle, returning the number of exported events.
+async fn smoke(course: RequestedCourse) -> Result<usize, StdError> {
+    let calendar = course.get_as_ical().await?.to_string();
+    Ok(calendar.matches("BEGIN:VEVENT").count())
 }
 
-impl RequestedCourse {
-    pub fn new<S1: Into<String>, S2: Into<String>>(id: S1, semester: S2) -> Self {
-        Self {
-            id: id.into(),
-            semester: semester.into(),
+/// Parse a duration like "6h", "30m", "45s" or "1d".
+fn parse_interval(text: &str) -> Result<Duration, Error> {
+    let invalid = || Error::InvalidInterval {
+        text: text.to_owned(),
+    };
+
+    let unit_start = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or_else(|| text.len());
+    let (number, unit) = text.split_at(unit_start);
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let number = number.parse::<u64>().map_err(|_| invalid())?;
+
+    Ok(Duration::from_secs(number * seconds))
+}
+
+fn missing_section(section: &str) -> Error {
+    Error::MissingConfigSection {
+        section: section.to_owned(),
+    }
+}
+
+/// Filters of the exported sessions given on the command line.
+fn export_filters(matches: &ArgMatches) -> Result<Vec<Rules>, StdError> {
+    let mut filters = vec![];
+    if let Some(part) = matches.value_of("part") {
+        filters.push(Rules::part(part));
+    }
+
+    if let Some(group) = matches.value_of("group") {
+        filters.push(Rules::group(group));
+    }
+    if let Some(language) = matches.value_of("language") {
+        filters.push(Rules::language(language));
+    }
+    if let Some(weekdays) = matches.value_of("weekday") {
+        filters.push(Rules::weekdays(&weekdays.split(',').collect::<Vec<_>>())?);
+    }
+
+    let date = |name| -> Result<Option<NaiveDate>, StdError> {
+        match matches.value_of(name) {
+            Some(value) => Ok(Some(NaiveDate::parse_from_str(value, "%Y-%m-%d")?)),
+            None => Ok(None),
         }
+    };
+    let (from, until) = (date("from")?, date("until")?);
+    if from.is_some() || until.is_some() {
+        filters.push(Rules::date_range(from, until));
     }
 
-    pub async fn get_as_ical<'a>(&self) -> Result<ICalendar<'a>, StdError> {
-        let body_str = self.request_course().await?;
+    Ok(filters)
+}
 
-        let document = Document::from(body_str.as_str());
-        let course = Course::from_document(&document)?;
+/// Command line settings of an export.
+struct ExportOptions<'a> {
+    /// Format of the calendars, "ics", "jscalendar", "org", "xlsx", "html" or "json" (see `model`)
+    format: &'a str,
+    /// Rules every exported session has to satisfy, in addition to the configured ones
+    filters: Vec<Rules>,
+    summary: Option<SummaryTemplate>,
+    /// Fetch the detail page of every session, see `details`
+    details: bool,
+    /// Fail if sessions of different courses overlap, see `Checks::fail_on_clash`
+    fail_on_clash: bool,
+    /// Single file all courses are written to instead of the configured outputs, "-" for stdout
+    output: Option<&'a str>,
+    /// Existing iCalendar file the sessions are merged into instead, see `merge::merge_into`
+    merge_into: Option<&'a str>,
+    /// Print a status line per course, see `progress`
+    progress: bool,
+    /// Skip courses that fail instead of aborting the export
+    keep_going: bool,
+    /// Rewrite calendars even if their courses didn't change since the last export
+    force: bool,
+    /// Keep sessions listed by several courses twice in combined calendars, see `duplicates`
+    keep_duplicates: bool,
+    /// Format of errors of skipped courses, see `failure`
+    error_format: &'a str,
+}
 
-        course.to_ical()
+/// Export the configured courses.
+async fn export(config: &Config, options: &ExportOptions<'_>) -> Result<(), StdError> {
+    let now = Utc::now();
+    let format = options.format;
+    usage::reset_stages();
+    let mut stage_start = Instant::now();
+    let mut courses = vec![];
+    let mut stats = ParseStats::new();
+    let calendar_options = &config.calendar;
+    let auto_colors = calendar_options.appearance.auto_colors;
+    let requested_courses = config.requested_courses()?;
+    let total = requested_courses.len();
+    // State keys of the courses skipped with --keep-going
+    let mut failed = vec![];
+    let mut progress = Progress::new(total, options.progress);
+    let mut store = match &config.store {
+        Some(path) => Some(Store::open(path)?),
+        None => None,
+    };
+    let mut warnings = vec![];
+    for (index, (requested, course_config)) in requested_courses.into_iter().enumerate() {
+        let started = Instant::now();
+        progress.start();
+        let mut course = match requested.get_course().await {
+            Ok(course) => course,
+            Err(err) => {
+                progress.failed(&requested.id, &*err);
+                stats.failed += 1;
+                let err: StdError = Box::new(CourseFailure {
+                    id: requested.id.clone(),
+                    error: err,
+                });
+                if options.keep_going {
+                    Failure::new(&*err).report(options.error_format);
+                    failed.push(config.state_key(
+                        &requested.id,
+                        &requested.semester,
+                        course_config,
+                    ));
+                    continue;
+                }
+                telemetry::report(config.telemetry.as_ref(), &stats).await;
+                return Err(err);
+            }
+        };
+        stats.parsed += 1;
+        progress.fetched(&course);
+        for message in std::mem::replace(&mut course.warnings, vec![]) {
+            let warning = ParseWarning {
+                course: Some(requested.id.clone()),
+                message,
+            };
+            warning.report(options.error_format);
+            warnings.push(warning);
+        }
+        if let Some(store) = &mut store {
+            store.record(&requested.id, &requested.semester, &course, now)?;
+        }
+        info!(
+            course = requested.id.as_str(),
+            events = course.events.len(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "fetched course"
+        );
+        if options.details {
+            details::enrich(&mut course).await?;
+        }
+        for filter in options.filters.iter() {
+            filter.apply(&requested.id, &mut course)?;
+        }
+        if let Some(group) = &course_config.group {
+            Rules::group(group).apply(&requested.id, &mut course)?;
+        }
+        if config.coalesce_sessions {
+            let merged = coalesce::coalesce(&mut course)?;
+            if merged > 0 {
+                info!(
+                    course = requested.id.as_str(),
+                    merged = merged as u64,
+                    "joined back-to-back sessions"
+                );
+            }
+        }
+        offsets::apply(
+            &mut course,
+            course_config.start_offset_mins,
+            course_config.end_offset_mins,
+        )?;
+        if course_config.all_day {
+            for event in course.events.iter_mut() {
+                event.make_all_day()?;
+            }
+        }
+        let lecture_free = holidays::apply(&mut course, config.lecture_free);
+        if lecture_free > 0 {
+            info!(
+                course = requested.id.as_str(),
+                sessions = lecture_free as u64,
+                "sessions on lecture-free days"
+            );
+        }
+        if course.events.is_empty() {
+            warn!(
+                course = requested.id.as_str(),
+                name = course.name.as_str(),
+                "no sessions published yet"
+            );
+        }
+        for implausible in config.checks.implausible_durations(&course) {
+            warn!(course = requested.id.as_str(), %implausible, "implausible duration");
+        }
+        if let Some(alias) = &course_config.alias {
+            course.name = alias.clone();
+        }
+        course.color = match &course_config.color {
+            Some(color) => Some(colors::parse(color)?),
+            None if auto_colors => Some(colors::auto(index)),
+            None => None,
+        };
+        courses.push((requested.id, requested.semester, course_config, course));
+    }
+    telemetry::report(config.telemetry.as_ref(), &stats).await;
+    stage_start = finish_stage("fetch", stage_start);
+
+    let clashes = checks::clashes(courses.iter().map(|(_, _, _, course)| course));
+    for clash in clashes.iter() {
+        warn!(%clash, "timetable clash");
+    }
+
+    let mut changes = vec![];
+    let mut tracked = None;
+    let mut dropped = vec![];
+    let mut newly_dropped = 0;
+    if let Some(state) = &config.state {
+        let mut snapshot = Snapshot::new();
+        for (id, semester, course_config, course) in courses.iter() {
+            snapshot.insert(&config.state_key(id, semester, course_config), course);
+        }
+        let previous = Snapshot::load(state)?;
+        changes = snapshot.changes_since(&previous);
+        snapshot.track(&previous);
+        // Skipped courses keep their last state, so they aren't taken as new next time
+        for key in failed.iter() {
+            if let Some(course) = previous.courses.get(key) {
+                snapshot.courses.insert(key.clone(), course.clone());
+            }
+        }
+        // Courses removed from the config are cancelled as a whole in the combined calendars
+        newly_dropped = snapshot.drop_missing(&previous);
+        dropped = snapshot.dropped_courses()?;
+        for (id, semester, course_config, course) in courses.iter_mut() {
+            snapshot.annotate(&config.state_key(id, semester, course_config), course)?;
+        }
+        tracked = Some((state, snapshot, previous));
+    }
+
+    let lecture_start = config
+        .lecture_start
+        .or_else(|| {
+            let semester = Semester::by_vv_id(config.semester.as_ref()?)?;
+            Some(semester.lecture_start)
+        })
+        .or_else(|| weeks::first_week_start(courses.iter().map(|(_, _, _, course)| course)));
+    if let Some(lecture_start) = lecture_start {
+        for (_, _, _, course) in courses.iter_mut() {
+            weeks::assign_weeks(course, lecture_start);
+        }
+    }
+    if let Some(summary) = &options.summary {
+        for (id, _, _, course) in courses.iter_mut() {
+            summary.apply(id, course);
+        }
+    }
+    if config.mark_first_last {
+        for (_, _, _, course) in courses.iter_mut() {
+            first_last::mark(course);
+        }
+    }
+    if let Some((_, snapshot, _)) = &tracked {
+        for (id, semester, course_config, course) in courses.iter_mut() {
+            snapshot.mark_room_changes(&config.state_key(id, semester, course_config), course);
+        }
+    }
+    for (_, _, _, course) in courses.iter_mut() {
+        timezone::convert(course, calendar_options)?;
+        busy::apply(course, calendar_options);
     }
+    for (_, course) in dropped.iter_mut() {
+        busy::apply(course, calendar_options);
+    }
+
+    let buffers = travel::buffers(
+        &courses
+            .iter()
+            .map(|(_, _, _, course)| course)
+            .collect::<Vec<_>>(),
+        config.travel_buffer_mins,
+        calendar_options,
+    );
 
-    pub async fn save_as_ical<'a, P: Into<std::path::PathBuf>>(
-        &self,
-        path: P,
-    ) -> Result<(), StdError> {
-        let calendar = self.get_as_ical().await?;
-        calendar.save_file(path.into())?;
-        Ok(())
+    // Calendars of courses exported exactly as last time are only rewritten with --force, so
+    // frequent runs don't touch files that subscribers or sync tools watch
+    let mut unchanged = BTreeSet::new();
+    if let Some((_, snapshot, previous)) = &mut tracked {
+        let settings = render_settings(config, options, format);
+        for (index, (id, semester, course_config, course)) in courses.iter().enumerate() {
+            let key = config.state_key(id, semester, course_config);
+            // The travel buffers of a course also depend on the other courses
+            let course_buffers = buffers
+                .iter()
+                .filter(|buffer| buffer.course == index)
+                .map(|buffer| buffer.event.to_string())
+                .collect::<String>();
+            snapshot.set_fingerprint(&key, course, &(settings.clone() + &course_buffers))?;
+            if !options.force && snapshot.unchanged(&key, previous) {
+                unchanged.insert(key);
+            }
+        }
     }
+    let all_unchanged =
+        !courses.is_empty() && unchanged.len() == courses.len() && newly_dropped == 0;
 
-    async fn request_course(&self) -> Result<String, StdError> {
-        let https = HttpsConnector::new().unwrap();
-        let client = Client::builder().build::<_, hyper::Body>(https);
+    stage_start = finish_stage("process", stage_start);
 
-        let url = format!(
-            "https://www.fu-berlin.de/vv/de/lv/{id}?sm={semester}",
-            id = self.id,
-            semester = self.semester
+    if let Some(output) = options.output {
+        let all_courses = combined(
+            courses
+                .iter()
+                .map(|(id, _, _, course)| (id.clone(), course.clone()))
+                .collect(),
+            &dropped,
+            options,
         );
-        let res = client.get(url.parse().unwrap()).await?;
-        let status = res.status();
-        let mut body = res.into_body();
-        let mut bytes = Vec::new();
-        while let Some(next) = body.next().await {
-            let chunk = next.unwrap();
-            bytes.extend(chunk);
+        let (content, collisions) = match format {
+            "jscalendar" => {
+                let (group, collisions) = jscalendar::merged_group(
+                    &config.merged_name,
+                    all_courses,
+                    now,
+                    calendar_options,
+                );
+                (jscalendar::to_string(&group)?.into_bytes(), collisions)
+            }
+            "org" => (
+                org::document(&config.merged_name, &without_ids(all_courses)).into_bytes(),
+                vec![],
+            ),
+            "xlsx" => (
+                xlsx::to_bytes(&config.merged_name, &without_ids(all_courses))?,
+                vec![],
+            ),
+            "html" => (
+                html::document(&config.merged_name, &without_ids(all_courses)).into_bytes(),
+                vec![],
+            ),
+            "json" => (
+                Courses::new(without_ids(all_courses))
+                    .to_json()?
+                    .into_bytes(),
+                vec![],
+            ),
+            _ => {
+                let (mut calendar, collisions) =
+                    merge::merge_courses(&config.merged_name, all_courses, calendar_options);
+                add_milestones(
+                    &mut calendar,
+                    courses.iter().map(|(_, semester, _, _)| semester),
+                    config,
+                );
+                add_travel_buffers(&mut calendar, &buffers, |_| true);
+                let in_place = config.update_in_place && output != "-";
+                let content = in_place_update(Path::new(output), calendar.to_string(), in_place)?;
+                (content.into_bytes(), collisions)
+            }
+        };
+        for collision in collisions {
+            warn!(%collision, "UID collision");
+        }
+        if let Ok(text) = std::str::from_utf8(&content) {
+            report_problems(output, text, format);
+        }
+        if output == "-" {
+            std::io::stdout().write_all(&content)?;
+        } else {
+            let path = Path::new(output);
+            create_parent_dir(path)?;
+            let written = match std::str::from_utf8(&content) {
+                Ok(text) => update::write_if_changed(path, text)?,
+                Err(_) => {
+                    std::fs::write(path, &content)?;
+                    true
+                }
+            };
+            if written {
+                progress.written("Calendar", output);
+                config.hooks.post_generate(output);
+            } else {
+                progress.unchanged("Calendar", output);
+            }
+        }
+    } else if let Some(target) = options.merge_into {
+        let sources = courses
+            .iter()
+            .map(|(id, _, _, _)| id.clone())
+            .collect::<Vec<_>>();
+        let all_courses = combined(
+            courses
+                .iter()
+                .map(|(id, _, _, course)| (id.clone(), course.clone()))
+                .collect(),
+            &dropped,
+            options,
+        );
+        let (mut calendar, collisions) =
+            merge::merge_courses(&config.merged_name, all_courses, calendar_options);
+        add_milestones(
+            &mut calendar,
+            courses.iter().map(|(_, semester, _, _)| semester),
+            config,
+        );
+        add_travel_buffers(&mut calendar, &buffers, |_| true);
+        for collision in collisions {
+            warn!(%collision, "UID collision");
+        }
+        let existing = match std::fs::read_to_string(target) {
+            Ok(existing) => existing,
+            Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let content = sanitize::fold(&merge::merge_into(
+            &existing,
+            &calendar.to_string(),
+            &sources,
+        )?);
+        let path = Path::new(target);
+        create_parent_dir(path)?;
+        report_problems(target, &content, "ics");
+        if update::write_if_changed(path, &content)? {
+            progress.written("Merged calendar", target);
+            config.hooks.post_generate(target);
+        } else {
+            progress.unchanged("Merged calendar", target);
+        }
+    } else {
+        // Courses of different semesters are written to one subdirectory per semester, so e.g.
+        // the WiSe and SoSe calendars of a course don't overwrite each other
+        let semesters = courses
+            .iter()
+            .map(|(_, semester, _, _)| semester.clone())
+            .collect::<BTreeSet<_>>();
+        let by_semester = semesters.len() > 1;
+        let output_path = |output: &str, semester: &str| {
+            if by_semester {
+                semester_path(output, semester)
+            } else {
+                PathBuf::from(output)
+            }
+        };
+        let calendar_name = |name: &str, semester: &str| {
+            if by_semester {
+                format!("{} ({})", name, Semester::label(semester))
+            } else {
+                name.to_owned()
+            }
+        };
+
+        let extension = match format {
+            "jscalendar" => "json",
+            "org" => "org",
+            "xlsx" => "xlsx",
+            "html" => "html",
+            "json" => "json",
+            _ => "ics",
+        };
+        for (index, (id, semester, course_config, course)) in courses.iter().enumerate() {
+            if let Some(output) = course_config.output(extension) {
+                let path = output_path(&output, semester);
+                let key = config.state_key(id, semester, course_config);
+                if unchanged.contains(&key) && path.exists() {
+                    info!(course = id.as_str(), "unchanged, not rewritten");
+                    progress.unchanged(&course.name, &path.to_string_lossy());
+                    continue;
+                }
+                create_parent_dir(&path)?;
+                let name = calendar_name(&course.name, semester);
+                let written = match format {
+                    "jscalendar" => {
+                        let mut group =
+                            jscalendar::course_group(course.clone(), now, calendar_options);
+                        group["title"] = name.into();
+                        jscalendar::save(&group, &path)?;
+                        true
+                    }
+                    "org" => {
+                        org::save(&org::document(&name, std::slice::from_ref(course)), &path)?;
+                        true
+                    }
+                    "xlsx" => {
+                        xlsx::save(&name, std::slice::from_ref(course), &path)?;
+                        true
+                    }
+                    "json" => {
+                        Courses::new(vec![course.clone()]).save(&path)?;
+                        true
+                    }
+                    "html" => {
+                        html::save(&html::document(&name, std::slice::from_ref(course)), &path)?;
+                        true
+                    }
+                    _ => {
+                        let mut calendar = course.clone().to_ical_named(&name, calendar_options)?;
+                        add_travel_buffers(&mut calendar, &buffers, |course| course == index);
+                        save_calendar(&calendar, &path, config.update_in_place)?
+                    }
+                };
+                if written {
+                    progress.written(&course.name, &path.to_string_lossy());
+                    config.hooks.post_generate(&path.to_string_lossy());
+                } else {
+                    progress.unchanged(&course.name, &path.to_string_lossy());
+                }
+            }
+        }
+
+        let skip_shared = |path: &Path| all_unchanged && path.exists();
+
+        if let Some(json_output) = config
+            .json_output
+            .as_ref()
+            .filter(|json_output| !skip_shared(Path::new(json_output)))
+        {
+            let mut feed = Feed::new(
+                courses
+                    .iter()
+                    .map(|(id, _, _, course)| (id.as_str(), course)),
+                &changes,
+                now,
+            );
+            feed.warnings = warnings;
+            feed.save(json_output)?;
+            config.hooks.post_generate(json_output);
         }
-        let body_str = String::from_utf8(bytes).unwrap();
 
-        if !status.is_success() {
-            dbg!(&self.id);
-            return Err(Error::HttpRequestError.into());
+        // Everything in one calendar, per semester
+        if let Some(merged_output) = &config.merged_output {
+            for semester in semesters.iter() {
+                let path = output_path(merged_output, semester);
+                if skip_shared(&path) {
+                    continue;
+                }
+                create_parent_dir(&path)?;
+                let name = calendar_name(&config.merged_name, semester);
+                let semester_courses = combined(
+                    courses
+                        .iter()
+                        .filter(|(_, course_semester, _, _)| course_semester == semester)
+                        .map(|(id, _, _, course)| (id.clone(), course.clone()))
+                        .collect(),
+                    &dropped,
+                    options,
+                );
+                let mut written = true;
+                let collisions = match format {
+                    "jscalendar" => {
+                        let (group, collisions) = jscalendar::merged_group(
+                            &name,
+                            semester_courses,
+                            now,
+                            calendar_options,
+                        );
+                        jscalendar::save(&group, &path)?;
+                        collisions
+                    }
+                    "org" => {
+                        org::save(&org::document(&name, &without_ids(semester_courses)), &path)?;
+                        vec![]
+                    }
+                    "xlsx" => {
+                        xlsx::save(&name, &without_ids(semester_courses), &path)?;
+                        vec![]
+                    }
+                    "html" => {
+                        let document = html::document(&name, &without_ids(semester_courses));
+                        html::save(&document, &path)?;
+                        vec![]
+                    }
+                    "json" => {
+                        Courses::new(without_ids(semester_courses)).save(&path)?;
+                        vec![]
+                    }
+                    _ => {
+                        let (mut calendar, collisions) =
+                            merge::merge_courses(&name, semester_courses, calendar_options);
+                        add_milestones(&mut calendar, Some(semester), config);
+                        add_travel_buffers(&mut calendar, &buffers, |course| {
+                            courses[course].1 == *semester
+                        });
+                        written = save_calendar(&calendar, &path, config.update_in_place)?;
+                        collisions
+                    }
+                };
+                if written {
+                    progress.written("Merged calendar", &path.to_string_lossy());
+                    config.hooks.post_generate(&path.to_string_lossy());
+                } else {
+                    progress.unchanged("Merged calendar", &path.to_string_lossy());
+                }
+                for collision in collisions {
+                    warn!(%collision, "UID collision");
+                }
+            }
         }
+    }
+
+    progress.summary();
+    // Only once everything is written, so a failed write isn't taken as exported next time
+    if let Some((state, snapshot, _)) = &tracked {
+        snapshot.save(state)?;
+    }
+    stage_start = finish_stage("write", stage_start);
 
-        Ok(body_str)
+    config.hooks.on_change(&changes);
+    webhook::notify(&config.webhooks, &changes).await?;
+    finish_stage("notify", stage_start);
+
+    // Only now, so the calendars are written anyway
+    if !clashes.is_empty() && (config.checks.fail_on_clash || options.fail_on_clash) {
+        return Err(Error::TimetableClash {
+            count: clashes.len(),
+        }
+        .into());
     }
+    if !failed.is_empty() {
+        return Err(Error::PartialExport {
+            failed: failed.len(),
+            total,
+        }
+        .into());
+    }
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), StdError> {
-    // OC 1 Vorlesung
-    let course = RequestedCourse::new("524870", "498562");
-    course.save_as_ical("oc1_vorlesung.ics").await?;
-    // OC1 Uebungen
-    let course = RequestedCourse::new("524871", "498562");
-    course.save_as_ical("oc1_uebung.ics").await?;
-    // BC 1 Vorlesung
-    let course = RequestedCourse::new("525101", "498562");
-    course.save_as_ical("bc1_vorlesung.ics").await?;
-    // BC1 Uebungen
-    let course = RequestedCourse::new("525102", "498562");
-    course.save_as_ical("bc1_uebung.ics").await?;
-    // Botanik Vorlesung
-    let course = RequestedCourse::new("503925", "498562");
-    course.save_as_ical("botanik_vorlesung.ics").await?;
-    // Botanik Seminar A
-    let course = RequestedCourse::new("503926", "498562");
-    course.save_as_ical("botanik_seminar_a.ics").await?;
-    // Botanik Seminar B
-    let course = RequestedCourse::new("503927", "498562");
-    course.save_as_ical("botanik_seminar_b.ics").await?;
+/// Everything besides the courses themselves that the exported calendars depend on, for the
+/// fingerprints of `changes`.
+fn render_settings(config: &Config, options: &ExportOptions<'_>, format: &str) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n{}\n",
+        format,
+        config.calendar.fingerprint(),
+        config.travel_buffer_mins,
+        config.milestones,
+        config.merged_name,
+        options.keep_duplicates,
+    )
+}
+
+/// `courses` for a combined calendar, with sessions listed by several of them merged into one
+/// unless `--keep-duplicates` is given, and the cancelled sessions of `dropped` courses.
+fn combined(
+    mut courses: Vec<(String, Course)>,
+    dropped: &[(String, Course)],
+    options: &ExportOptions<'_>,
+) -> Vec<(String, Course)> {
+    if !options.keep_duplicates {
+        let merged = duplicates::merge_duplicates(&mut courses);
+        if merged > 0 {
+            info!(merged, "merged sessions listed by several courses");
+        }
+    }
+    courses.extend(dropped.iter().cloned());
+    courses
+}
+
+/// Add the milestones of `semesters` to a combined `calendar` if configured, see `milestones`.
+fn add_milestones<'a, I: IntoIterator<Item = &'a String>>(
+    calendar: &mut ICalendar<'static>,
+    semesters: I,
+    config: &Config,
+) {
+    if !config.milestones {
+        return;
+    }
+    let semesters = semesters.into_iter().collect::<BTreeSet<_>>();
+    for semester in semesters
+        .into_iter()
+        .filter_map(|id| Semester::by_vv_id(id))
+    {
+        for event in milestones::events(&semester) {
+            calendar.add_event(event);
+        }
+    }
+}
+
+/// Add the travel buffers before sessions of the courses whose index satisfies `include` to
+/// `calendar`, see `travel`.
+fn add_travel_buffers<F: Fn(usize) -> bool>(
+    calendar: &mut ICalendar<'static>,
+    buffers: &[travel::Buffer],
+    include: F,
+) {
+    for buffer in buffers.iter().filter(|buffer| include(buffer.course)) {
+        calendar.add_event(buffer.event.clone());
+    }
+}
+
+/// Record the stage that started at `start` in the resource usage, returning the start of the
+/// next stage.
+fn finish_stage(name: &'static str, start: Instant) -> Instant {
+    usage::record_stage(name, start.elapsed());
+    Instant::now()
+}
 
+/// `output` within a subdirectory named after `semester`, next to where `output` would be.
+fn semester_path(output: &str, semester: &str) -> PathBuf {
+    let output = Path::new(output);
+    let directory = Semester::label(semester).replace('/', "-");
+    let file_name = output.file_name().unwrap_or_else(|| output.as_os_str());
+    output
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(directory)
+        .join(file_name)
+}
+
+/// Write `calendar` to `path`, as an update of the previous file if `in_place` (see `update`),
+/// warning about problems strict clients would reject.
+///
+/// The file is left as it is if only the `DTSTAMP`s would change, returns whether it was written.
+fn save_calendar(calendar: &ICalendar, path: &Path, in_place: bool) -> Result<bool, StdError> {
+    let content = in_place_update(path, calendar.to_string(), in_place)?;
+    report_problems(&path.to_string_lossy(), &content, "ics");
+    let written = update::write_if_changed(path, &content)?;
+    if written {
+        info!(path = path.to_string_lossy().as_ref(), "wrote calendar");
+    } else {
+        info!(
+            path = path.to_string_lossy().as_ref(),
+            "calendar unchanged, not rewritten"
+        );
+    }
+    Ok(written)
+}
+
+/// `calendar` as an update of the previous file at `path` if `in_place` and there is one, folded.
+fn in_place_update(path: &Path, calendar: String, in_place: bool) -> Result<String, StdError> {
+    let previous = match std::fs::read_to_string(path) {
+        Ok(previous) if in_place => previous,
+        Ok(_) => return Ok(sanitize::fold(&calendar)),
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(sanitize::fold(&calendar))
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let (content, stats) = update::update(&previous, &calendar);
+    info!(
+        path = path.to_string_lossy().as_ref(),
+        added = stats.added,
+        changed = stats.changed,
+        unchanged = stats.unchanged,
+        cancelled = stats.cancelled,
+        "updated calendar in place"
+    );
+    Ok(sanitize::fold(&content))
+}
+
+/// `courses` without the ids they are paired with, for formats without UIDs.
+fn without_ids(courses: Vec<(String, Course)>) -> Vec<Course> {
+    courses.into_iter().map(|(_, course)| course).collect()
+}
+
+fn report_problems(output: &str, content: &str, format: &str) {
+    if format != "ics" {
+        return;
+    }
+    for problem in validate::validate(content) {
+        warn!(output, %problem, "invalid calendar");
+    }
+}
+
+fn create_parent_dir(path: &Path) -> Result<(), StdError> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,330 @@
+use crate::options::CalendarOptions;
+use crate::{published_calendar, uid, Course, Error};
+use ics::components::Property;
+use ics::ICalendar;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// An event whose UID was already taken by an earlier event of a merged calendar.
+#[derive(Debug, Clone)]
+pub struct UidCollision {
+    pub uid: String,
+    /// Source of the event that kept the UID
+    pub first_source: String,
+    /// Source of the event whose UID was rewritten
+    pub source: String,
+    pub rewritten_uid: String,
+}
+
+impl fmt::Display for UidCollision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "UID {} of {} collides with {}, rewritten to {}",
+            self.uid, self.source, self.first_source, self.rewritten_uid
+        )
+    }
+}
+
+/// Hands out unique UIDs while merging events from multiple sources.
+#[derive(Debug, Default)]
+pub struct UidRegistry {
+    /// UID -> source that claimed it
+    taken: HashMap<String, String>,
+    collisions: Vec<UidCollision>,
+}
+
+impl UidRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Claim `uid` for `source` and return the UID that should be used.
+    ///
+    /// A conflicting UID is rewritten to `<uid>-<source>` (plus a counter if that is taken as
+    /// well), so merging the same inputs in the same order always yields the same UIDs.
+    pub fn claim(&mut self, uid: &str, source: &str) -> String {
+        let first_source = match self.taken.get(uid) {
+            Some(first_source) => first_source.clone(),
+            None => {
+                self.taken.insert(uid.to_owned(), source.to_owned());
+                return uid.to_owned();
+            }
+        };
+
+        let base = format!("{}-{}", uid, source);
+        let mut rewritten_uid = base.clone();
+        let mut counter = 2;
+        while self.taken.contains_key(&rewritten_uid) {
+            rewritten_uid = format!("{}-{}", base, counter);
+            counter += 1;
+        }
+        self.taken.insert(rewritten_uid.clone(), source.to_owned());
+
+        self.collisions.push(UidCollision {
+            uid: uid.to_owned(),
+            first_source,
+            source: source.to_owned(),
+            rewritten_uid: rewritten_uid.clone(),
+        });
+
+        rewritten_uid
+    }
+
+    /// Like `claim`, additionally returning whether `uid` had to be rewritten.
+    pub fn claim_checked(&mut self, uid: &str, source: &str) -> (String, bool) {
+        let claimed = self.claim(uid, source);
+        let rewritten = claimed != uid;
+        (claimed, rewritten)
+    }
+
+    pub fn into_collisions(self) -> Vec<UidCollision> {
+        self.collisions
+    }
+}
+
+/// Merge several courses into one calendar named `name`, with `options`.
+///
+/// `courses` pairs every course with a label of where it came from (e.g. the course id), which
+/// is recorded in `X-FU-VV-CAL-SOURCE` and used to rewrite colliding UIDs.
+pub fn merge_courses(
+    name: &str,
+    courses: Vec<(String, Course)>,
+    options: &CalendarOptions,
+) -> (ICalendar<'static>, Vec<UidCollision>) {
+    let mut calendar = published_calendar(name, options);
+    let mut registry = UidRegistry::new();
+
+    for (source, course) in courses {
+        let cal_events = course.into_ical_events(options, |event| {
+            registry.claim_checked(&uid::for_event(options, Some(&source), &event.id), &source)
+        });
+        for mut cal_event in cal_events {
+            cal_event.push(Property::new("X-FU-VV-CAL-SOURCE", source.clone()));
+            calendar.add_event(cal_event);
+        }
+    }
+
+    (calendar, registry.into_collisions())
+}
+
+/// Merge the events of the generated `calendar` into the existing iCalendar `existing`, e.g. a
+/// personal calendar file.
+///
+/// Events of `existing` that were generated for one of `sources` before (see
+/// `X-FU-VV-CAL-SOURCE`) or share a UID with an event of `calendar` are replaced, everything else
+/// (other events, todos, timezones and calendar properties) is kept as it is. Timezones of
+/// `calendar` that `existing` lacks are added, so the TZIDs of the events resolve.
+///
+/// An empty `existing` (e.g. a file that doesn't exist yet) is replaced by `calendar`, one without
+/// `END:VCALENDAR` is rejected, as its events past the cut would be lost.
+pub fn merge_into(existing: &str, calendar: &str, sources: &[String]) -> Result<String, Error> {
+    if existing.trim().is_empty() {
+        return Ok(calendar.to_owned());
+    }
+    let generated = unfold(calendar);
+    let existing = unfold(existing);
+    let existing_tzids = existing
+        .iter()
+        .filter_map(|line| property_value(line, "TZID"))
+        .collect::<HashSet<_>>();
+    let mut generated_components = vec![];
+    for component in components(&generated) {
+        if component[0].eq_ignore_ascii_case("BEGIN:VEVENT") {
+            generated_components.extend(component.iter().cloned());
+        } else if component[0].eq_ignore_ascii_case("BEGIN:VTIMEZONE") {
+            let tzid = component
+                .iter()
+                .find_map(|line| property_value(line, "TZID"));
+            if tzid.map_or(false, |tzid| !existing_tzids.contains(tzid)) {
+                generated_components.extend(component.iter().cloned());
+            }
+        }
+    }
+    let generated_uids = generated
+        .iter()
+        .filter_map(|line| property_value(line, "UID"))
+        .collect::<HashSet<_>>();
+    let is_owned = |event: &[String]| {
+        event.iter().any(|line| {
+            property_value(line, "UID").map_or(false, |uid| generated_uids.contains(&uid))
+                || property_value(line, "X-FU-VV-CAL-SOURCE")
+                    .map_or(false, |source| sources.iter().any(|own| own == source))
+        })
+    };
+
+    let mut merged = vec![];
+    let mut event: Option<Vec<String>> = None;
+    let mut inserted = false;
+    for line in existing.iter().cloned() {
+        if let Some(mut lines) = event.take() {
+            let end = line.eq_ignore_ascii_case("END:VEVENT");
+            lines.push(line);
+            if !end {
+                event = Some(lines);
+            } else if !is_owned(&lines) {
+                merged.extend(lines);
+            }
+        } else if line.eq_ignore_ascii_case("BEGIN:VEVENT") {
+            event = Some(vec![line]);
+        } else if line.eq_ignore_ascii_case("END:VCALENDAR") && !inserted {
+            merged.extend(generated_components.iter().cloned());
+            merged.push(line);
+            inserted = true;
+        } else {
+            merged.push(line);
+        }
+    }
+    if !inserted {
+        return Err(Error::IncompleteCalendar);
+    }
+
+    let mut content = merged.join("\r\n");
+    content.push_str("\r\n");
+    Ok(content)
+}
+
+/// The components directly inside the calendar `lines`, e.g. its events and timezones, with
+/// their nested components.
+fn components(lines: &[String]) -> Vec<&[String]> {
+    let mut components = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let upper = line.to_ascii_uppercase();
+        if upper.starts_with("BEGIN:") {
+            depth += 1;
+            if depth == 2 {
+                start = index;
+            }
+        } else if upper.starts_with("END:") {
+            if depth == 2 {
+                components.push(&lines[start..=index]);
+            }
+            depth -= 1;
+        }
+    }
+    components
+}
+
+/// Content lines of `content` with folded lines joined, accepting bare LF line endings as well.
+pub(crate) fn unfold(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in content.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&line[1..]);
+                continue;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line.to_owned());
+        }
+    }
+    lines
+}
+
+/// Value of `line` if it is the property `name`, ignoring parameters.
+pub(crate) fn property_value<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let colon = line.find(':')?;
+    let end = line[..colon].find(';').unwrap_or(colon);
+    if line[..end].eq_ignore_ascii_case(name) {
+        Some(&line[colon + 1..])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERSONAL: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Personal//EN\r\n\
+                            X-WR-CALNAME:Mein Kalender\r\n\
+                            BEGIN:VEVENT\r\nUID:dentist@example.com\r\n\
+                            DTSTART:20191015T070000Z\r\nSUMMARY:Zahnarzt\r\nEND:VEVENT\r\n\
+                            BEGIN:VEVENT\r\nUID:1-old@vv.fu-berlin.de\r\n\
+                            DTSTART:20191014T080000Z\r\nSUMMARY:Vorlesung (alt)\r\n\
+                            X-FU-VV-CAL-SOURCE:524870\r\nEND:VEVENT\r\n\
+                            BEGIN:VEVENT\r\nUID:1-a@vv.fu-berlin.de\r\n\
+                            DTSTART:20191014T080000Z\r\nSUMMARY:Übung (alt)\r\nEND:VEVENT\r\n\
+                            BEGIN:VTODO\r\nUID:todo@example.com\r\nSUMMARY:Hausaufgaben\r\n\
+                            END:VTODO\r\nEND:VCALENDAR\r\n";
+
+    const GENERATED: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:fu-vv-cal\r\n\
+                             BEGIN:VTIMEZONE\r\nTZID:Europe/London\r\nBEGIN:STANDARD\r\n\
+                             DTSTART:19701025T020000\r\nTZOFFSETFROM:+0100\r\n\
+                             TZOFFSETTO:+0000\r\nEND:STANDARD\r\nEND:VTIMEZONE\r\n\
+                             BEGIN:VEVENT\r\nUID:1-a@vv.fu-berlin.de\r\n\
+                             DTSTART;TZID=Europe/London:20191014T090000\r\nSUMMARY:Übung\r\n\
+                             X-FU-VV-CAL-SOURCE:524870\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+    fn sources() -> Vec<String> {
+        vec!["524870".to_owned()]
+    }
+
+    #[test]
+    fn unfolds_lines() {
+        let content = "BEGIN:VEVENT\r\nSUMMARY:Eine sehr lange\r\n  Zusammenfassung\r\n\
+                       DESCRIPTION:mit\n\tTab\nEND:VEVENT\r\n\r\n";
+        assert_eq!(
+            unfold(content),
+            vec![
+                "BEGIN:VEVENT",
+                "SUMMARY:Eine sehr lange Zusammenfassung",
+                "DESCRIPTION:mitTab",
+                "END:VEVENT",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_foreign_components() {
+        let merged = merge_into(PERSONAL, GENERATED, &sources()).unwrap();
+        assert!(merged.contains("X-WR-CALNAME:Mein Kalender\r\n"));
+        assert!(merged.contains("PRODID:-//Personal//EN\r\n"));
+        assert!(merged.contains("UID:dentist@example.com\r\nDTSTART:20191015T070000Z\r\n"));
+        assert!(merged.contains("BEGIN:VTODO\r\nUID:todo@example.com\r\n"));
+        assert!(merged.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn replaces_owned_events() {
+        let merged = merge_into(PERSONAL, GENERATED, &sources()).unwrap();
+        // Owned by source and by UID
+        assert!(!merged.contains("Vorlesung (alt)"));
+        assert!(!merged.contains("Übung (alt)"));
+        assert_eq!(merged.matches("UID:1-a@vv.fu-berlin.de").count(), 1);
+        assert!(merged.contains("SUMMARY:Übung\r\n"));
+
+        // Merging again changes nothing
+        assert_eq!(merge_into(&merged, GENERATED, &sources()).unwrap(), merged);
+    }
+
+    #[test]
+    fn carries_over_timezones() {
+        let merged = merge_into(PERSONAL, GENERATED, &sources()).unwrap();
+        assert_eq!(merged.matches("BEGIN:VTIMEZONE").count(), 1);
+        assert!(merged.contains("TZID:Europe/London\r\n"));
+        let again = merge_into(&merged, GENERATED, &sources()).unwrap();
+        assert_eq!(again.matches("BEGIN:VTIMEZONE").count(), 1);
+    }
+
+    #[test]
+    fn replaces_empty_calendars() {
+        assert_eq!(
+            merge_into(" \r\n", GENERATED, &sources()).unwrap(),
+            GENERATED
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_calendars() {
+        let truncated = &PERSONAL[..PERSONAL.find("BEGIN:VTODO").unwrap()];
+        match merge_into(truncated, GENERATED, &sources()) {
+            Err(Error::IncompleteCalendar) => {}
+            other => panic!("expected IncompleteCalendar, got {:?}", other),
+        }
+    }
+}
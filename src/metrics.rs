@@ -0,0 +1,175 @@
+//! Counters of the server, exposed in the Prometheus text format under `/metrics`.
+
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds of the request latency buckets, in seconds
+const LATENCY_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+static UPSTREAM_SUCCESSES: AtomicU64 = AtomicU64::new(0);
+static UPSTREAM_FAILURES: AtomicU64 = AtomicU64::new(0);
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+static PARSE_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    /// Latencies of the requests to the server, by route
+    static ref REQUESTS: Mutex<BTreeMap<&'static str, Histogram>> = Mutex::new(BTreeMap::new());
+    /// Responses of the server, by route and status code
+    static ref RESPONSES: Mutex<BTreeMap<(&'static str, u16), u64>> = Mutex::new(BTreeMap::new());
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Number of observations per bucket of `LATENCY_BUCKETS`, not cumulative
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// A request to the VV was answered, successfully or not.
+pub fn record_upstream_request(success: bool) {
+    let counter = if success {
+        &UPSTREAM_SUCCESSES
+    } else {
+        &UPSTREAM_FAILURES
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A course page was looked up in the cache, see `cache`.
+pub fn record_cache_lookup(hit: bool) {
+    let counter = if hit { &CACHE_HITS } else { &CACHE_MISSES };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A course page could not be parsed.
+pub fn record_parse_failure() {
+    PARSE_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The server answered a request for `route` with `status` after `duration`.
+pub fn record_request(route: &'static str, status: u16, duration: Duration) {
+    let seconds = duration.as_secs_f64();
+    {
+        let mut requests = REQUESTS.lock().unwrap();
+        let histogram = requests.entry(route).or_default();
+        histogram.buckets.resize(LATENCY_BUCKETS.len(), 0);
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            histogram.buckets[bucket] += 1;
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+    *RESPONSES
+        .lock()
+        .unwrap()
+        .entry((route, status))
+        .or_insert(0) += 1;
+}
+
+/// All metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+    header(
+        &mut out,
+        "fu_vv_cal_upstream_requests_total",
+        "counter",
+        "Requests to the VV, by result",
+    );
+    for (result, counter) in &[("ok", &UPSTREAM_SUCCESSES), ("error", &UPSTREAM_FAILURES)] {
+        let _ = writeln!(
+            out,
+            "fu_vv_cal_upstream_requests_total{{result=\"{}\"}} {}",
+            result,
+            load(*counter)
+        );
+    }
+
+    header(
+        &mut out,
+        "fu_vv_cal_cache_lookups_total",
+        "counter",
+        "Lookups of course pages in the page cache, by result",
+    );
+    for (result, counter) in &[("hit", &CACHE_HITS), ("miss", &CACHE_MISSES)] {
+        let _ = writeln!(
+            out,
+            "fu_vv_cal_cache_lookups_total{{result=\"{}\"}} {}",
+            result,
+            load(*counter)
+        );
+    }
+
+    header(
+        &mut out,
+        "fu_vv_cal_parse_failures_total",
+        "counter",
+        "Course pages that could not be parsed",
+    );
+    let _ = writeln!(
+        out,
+        "fu_vv_cal_parse_failures_total {}",
+        load(&PARSE_FAILURES)
+    );
+
+    header(
+        &mut out,
+        "fu_vv_cal_http_responses_total",
+        "counter",
+        "Responses of the server, by route and status code",
+    );
+    for ((route, status), count) in RESPONSES.lock().unwrap().iter() {
+        let _ = writeln!(
+            out,
+            "fu_vv_cal_http_responses_total{{route=\"{}\",status=\"{}\"}} {}",
+            route, status, count
+        );
+    }
+
+    header(
+        &mut out,
+        "fu_vv_cal_http_request_duration_seconds",
+        "histogram",
+        "Time taken to answer requests, by route",
+    );
+    for (route, histogram) in REQUESTS.lock().unwrap().iter() {
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets.iter()) {
+            cumulative += *count;
+            let _ = writeln!(
+                out,
+                "fu_vv_cal_http_request_duration_seconds_bucket{{route=\"{}\",le=\"{}\"}} {}",
+                route, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "fu_vv_cal_http_request_duration_seconds_bucket{{route=\"{}\",le=\"+Inf\"}} {}",
+            route, histogram.count
+        );
+        let _ = writeln!(
+            out,
+            "fu_vv_cal_http_request_duration_seconds_sum{{route=\"{}\"}} {}",
+            route, histogram.sum
+        );
+        let _ = writeln!(
+            out,
+            "fu_vv_cal_http_request_duration_seconds_count{{route=\"{}\"}} {}",
+            route, histogram.count
+        );
+    }
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
@@ -0,0 +1,72 @@
+//! All-day events framing the semester in combined calendars: its start and end, the lecture
+//! period and the re-registration deadline, from the dates of `semester`.
+
+use crate::semester::Semester;
+use crate::uid::UID_DOMAIN;
+use chrono::{Duration, NaiveDate};
+use ics::components::{Parameter, Property};
+use ics::properties::{Categories, Summary, Transp};
+use ics::Event;
+
+/// Milestones of `semester` as all-day events, which don't block time in free/busy lookups.
+pub fn events(semester: &Semester) -> Vec<Event<'static>> {
+    let mut milestones = vec![
+        (
+            semester.start,
+            semester.start,
+            "semester-start",
+            "Semesterbeginn",
+        ),
+        (semester.end, semester.end, "semester-end", "Semesterende"),
+        (
+            semester.lecture_start,
+            semester.lecture_start,
+            "lecture-start",
+            "Vorlesungsbeginn",
+        ),
+        (
+            semester.lecture_end,
+            semester.lecture_end,
+            "lecture-end",
+            "Vorlesungsende",
+        ),
+    ];
+    for (from, until) in semester.breaks.iter() {
+        milestones.push((*from, *until, "break", "Vorlesungsfreie Zeit"));
+    }
+    if let Some((from, until)) = semester.reregistration {
+        milestones.push((from, until, "reregistration", "Rückmeldung"));
+    }
+
+    milestones
+        .into_iter()
+        .map(|(from, until, key, title)| {
+            all_day_event(
+                format!("milestone-{}-{}@{}", key, from.format("%Y%m%d"), UID_DOMAIN),
+                from,
+                until,
+                format!("{} ({})", title, semester.name),
+            )
+        })
+        .collect()
+}
+
+/// All-day event from `from` to `until` (both inclusive), shown as free time.
+pub(crate) fn all_day_event(
+    uid: String,
+    from: NaiveDate,
+    until: NaiveDate,
+    title: String,
+) -> Event<'static> {
+    let mut event = Event::new(uid, format!("{}T000000Z", from.format("%Y%m%d")));
+    // DTEND of all-day events is exclusive
+    for (name, date) in &[("DTSTART", from), ("DTEND", until + Duration::days(1))] {
+        let mut property = Property::new(*name, date.format("%Y%m%d").to_string());
+        property.add(Parameter::new("VALUE", "DATE"));
+        event.push(property);
+    }
+    event.push(Summary::new(title));
+    event.push(Categories::new("Semester"));
+    event.push(Transp::transparent());
+    event
+}
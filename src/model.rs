@@ -0,0 +1,143 @@
+//! The parsed courses as JSON, for exchanging them with other programs instead of scraping the
+//! calendars again. Written by `--format json` and served by `serve` under
+//! `/course/{id}.json?sm={semester}`.
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "courses": [
+//!     {
+//!       "id": "524870",
+//!       "name": "Organische Chemie 1",
+//!       "kind": "Vorlesung",
+//!       "url": "https://www.fu-berlin.de/vv/de/lv/524870?sm=499474",
+//!       "color": null,
+//!       "sws": 4,
+//!       "ects": 5,
+//!       "modules": ["21601"],
+//!       "language": "Deutsch",
+//!       "registration": {
+//!         "capacity": 60,
+//!         "note": "Anmeldung über Campus Management bis 15.10.2019",
+//!         "deadline": "2019-10-15"
+//!       },
+//!       "events": [
+//!         {
+//!           "id": "5551234",
+//!           "start": "2019-10-21T10:00:00+02:00",
+//!           "end": "2019-10-21T13:00:00+02:00",
+//!           "sequence": 0,
+//!           "week": 1,
+//!           "part": null,
+//!           "remark": null,
+//!           "room": "Hörsaal A",
+//!           "lecturers": ["Prof. Dr. Beispiel"],
+//!           "detail_url": null,
+//!           "exam": false,
+//!           "group": null,
+//!           "summary": null
+//!         }
+//!       ],
+//!       "cancelled": []
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! - Timestamps are RFC 3339 in the local time of Berlin, with its offset
+//! - `registration.deadline` is a date without time
+//! - `cancelled` are sessions that disappeared from the VV since an earlier export, see
+//!   `changes::Snapshot::annotate`
+//! - Optional fields are `null` if the course page doesn't state them
+//!
+//! `SCHEMA` is the JSON Schema of the format. Like the feed (see `feed`), fields are only ever
+//! added within a version; removing or changing fields bumps `version`.
+
+use crate::{Course, Error, StdError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Version of the format, see the module documentation.
+pub const MODEL_VERSION: u32 = 1;
+
+/// JSON Schema (draft-07) of `Courses`
+pub const SCHEMA: &str = include_str!("../schema/courses.schema.json");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Courses {
+    pub version: u32,
+    pub courses: Vec<Course>,
+}
+
+impl Courses {
+    pub fn new(courses: Vec<Course>) -> Self {
+        Self {
+            version: MODEL_VERSION,
+            courses,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, StdError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Read courses written by this or an earlier version.
+    pub fn from_json(json: &str) -> Result<Self, StdError> {
+        let value = serde_json::from_str::<serde_json::Value>(json)?;
+        let version = value
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or(0);
+        if version == 0 || version > u64::from(MODEL_VERSION) {
+            return Err(Error::UnsupportedModelVersion { version }.into());
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StdError> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, StdError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// (De)serialization of `CourseEvent::timespan` as `start` and `end`.
+pub(crate) mod timespan {
+    use chrono::{DateTime, FixedOffset, Offset};
+    use chrono_tz::Europe::Berlin;
+    use chrono_tz::Tz;
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use timespan::DateTimeSpan;
+
+    #[derive(Serialize, Deserialize)]
+    struct Span {
+        start: DateTime<FixedOffset>,
+        end: DateTime<FixedOffset>,
+    }
+
+    pub fn serialize<S: Serializer>(
+        timespan: &DateTimeSpan<Tz>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Span {
+            start: timespan.start.with_timezone(&timespan.start.offset().fix()),
+            end: timespan.end.with_timezone(&timespan.end.offset().fix()),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTimeSpan<Tz>, D::Error> {
+        let span = Span::deserialize(deserializer)?;
+        DateTimeSpan::new(
+            span.start.with_timezone(&Berlin),
+            span.end.with_timezone(&Berlin),
+        )
+        .map_err(|err| D::Error::custom(err.to_string()))
+    }
+}
@@ -0,0 +1,173 @@
+use crate::fetch::{self, HttpRequest};
+use crate::{Error, StdError};
+use chrono::Utc;
+use http::header::CONTENT_TYPE;
+use http::Method;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::timer::delay;
+
+/// OAuth 2.0 device authorization grant, for signing in from a terminal.
+///
+/// Tokens are persisted in `token_file`, so the user only has to confirm access once.
+#[derive(Debug)]
+pub struct DeviceFlow<'a> {
+    /// What access is requested for, e.g. "your Google Calendar"
+    pub service: &'a str,
+    pub device_code_url: &'a str,
+    pub token_url: &'a str,
+    pub client_id: &'a str,
+    pub client_secret: Option<&'a str>,
+    pub scope: &'a str,
+    pub token_file: &'a str,
+}
+
+/// OAuth tokens persisted between runs.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredToken {
+    access_token: String,
+    refresh_token: String,
+    /// Unix timestamp at which the access token expires
+    expires_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    user_code: String,
+    // Google calls it `verification_url`, the spec `verification_uri`
+    #[serde(alias = "verification_uri")]
+    verification_url: String,
+    expires_in: u64,
+    interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+    error: Option<String>,
+}
+
+impl<'a> DeviceFlow<'a> {
+    /// A valid access token, taken from the token file, refreshed, or obtained via the device
+    /// flow.
+    pub async fn access_token(&self) -> Result<String, StdError> {
+        let stored = std::fs::read_to_string(self.token_file)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<StoredToken>(&contents).ok());
+
+        let token = match stored {
+            Some(stored) => {
+                if stored.expires_at > Utc::now().timestamp() + 60 {
+                    return Ok(stored.access_token);
+                }
+                self.refresh(stored.refresh_token).await?
+            }
+            None => self.authorize().await?,
+        };
+        std::fs::write(self.token_file, serde_json::to_string_pretty(&token)?)?;
+
+        Ok(token.access_token)
+    }
+
+    async fn refresh(&self, refresh_token: String) -> Result<StoredToken, StdError> {
+        let mut params = vec![
+            ("client_id", self.client_id),
+            ("refresh_token", refresh_token.as_str()),
+            ("grant_type", "refresh_token"),
+            ("scope", self.scope),
+        ];
+        if let Some(client_secret) = self.client_secret {
+            params.push(("client_secret", client_secret));
+        }
+        let response = post_form(self.token_url, &params).await?;
+        let response: TokenResponse = serde_json::from_str(&response)?;
+
+        // Refresh responses don't necessarily include a new refresh token
+        stored_token(response, Some(refresh_token))
+    }
+
+    /// Run the device flow, which asks the user to confirm access in a browser.
+    async fn authorize(&self) -> Result<StoredToken, StdError> {
+        let response = post_form(
+            self.device_code_url,
+            &[("client_id", self.client_id), ("scope", self.scope)],
+        )
+        .await?;
+        let device_code: DeviceCode = serde_json::from_str(&response)?;
+
+        eprintln!(
+            "To allow fu-vv-cal to access {}, visit {} and enter the code {}",
+            self.service, device_code.verification_url, device_code.user_code
+        );
+
+        let mut params = vec![
+            ("client_id", self.client_id),
+            ("device_code", device_code.device_code.as_str()),
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+        ];
+        if let Some(client_secret) = self.client_secret {
+            params.push(("client_secret", client_secret));
+        }
+
+        let expires_at = Instant::now() + Duration::from_secs(device_code.expires_in);
+        let mut interval = Duration::from_secs(device_code.interval);
+        while Instant::now() < expires_at {
+            delay(Instant::now() + interval).await;
+
+            let response = post_form(self.token_url, &params).await?;
+            let response: TokenResponse = serde_json::from_str(&response)?;
+
+            match response.error.as_ref().map(String::as_str) {
+                Some("authorization_pending") => continue,
+                Some("slow_down") => interval += Duration::from_secs(5),
+                _ => return stored_token(response, None),
+            }
+        }
+
+        Err(Error::AuthError {
+            message: "The device code expired before access was granted".to_owned(),
+        }
+        .into())
+    }
+}
+
+fn stored_token(
+    response: TokenResponse,
+    previous_refresh_token: Option<String>,
+) -> Result<StoredToken, StdError> {
+    if let Some(error) = response.error {
+        return Err(Error::AuthError { message: error }.into());
+    }
+
+    match (
+        response.access_token,
+        response.refresh_token.or(previous_refresh_token),
+    ) {
+        (Some(access_token), Some(refresh_token)) => Ok(StoredToken {
+            access_token,
+            refresh_token,
+            expires_at: Utc::now().timestamp() + response.expires_in.unwrap_or(3600),
+        }),
+        _ => Err(Error::AuthError {
+            message: "The token response is missing the access or refresh token".to_owned(),
+        }
+        .into()),
+    }
+}
+
+async fn post_form(url: &str, params: &[(&str, &str)]) -> Result<String, StdError> {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    let req = HttpRequest::new(Method::POST, url)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")?
+        .with_body(body);
+
+    // OAuth errors like `authorization_pending` come with a 4xx status, but are handled by the
+    // caller based on the response body
+    let (_, body) = fetch::send(req).await?;
+    Ok(body)
+}
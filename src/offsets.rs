@@ -0,0 +1,118 @@
+//! Corrections of the times the VV states for a course, e.g. for lectures that start at a quarter
+//! past the hour (akademisches Viertel) while the page gives the full hour.
+
+use crate::{Course, Error, StdError};
+use chrono::Duration;
+use timespan::DateTimeSpan;
+
+/// Move the start of every session of `course` by `start_mins` and its end by `end_mins`, both
+/// negative for earlier. Fails if a session would end before it starts.
+///
+/// All-day sessions have no times to correct and stay as they are.
+pub fn apply(course: &mut Course, start_mins: i64, end_mins: i64) -> Result<(), StdError> {
+    if start_mins == 0 && end_mins == 0 {
+        return Ok(());
+    }
+    let (start_offset, end_offset) = (Duration::minutes(start_mins), Duration::minutes(end_mins));
+    for event in course.events.iter_mut().chain(course.cancelled.iter_mut()) {
+        if event.all_day {
+            continue;
+        }
+        let start = event.timespan.start + start_offset;
+        let end = event.timespan.end + end_offset;
+        if end <= start {
+            return Err(Error::InvalidTimeOffsets {
+                course: course.name.clone(),
+            }
+            .into());
+        }
+        event.timespan = DateTimeSpan::new(start, end)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registration::Registration;
+    use crate::{timespan_from_parts, CourseEvent};
+
+    fn session(day: &str, start: &str, end: &str) -> CourseEvent {
+        CourseEvent {
+            id: "500001".to_owned(),
+            timespan: timespan_from_parts(day, start, end).unwrap(),
+            sequence: 0,
+            week: None,
+            part: None,
+            remark: None,
+            room: None,
+            lecturers: vec![],
+            detail_url: None,
+            exam: false,
+            group: None,
+            summary: None,
+            all_day: false,
+        }
+    }
+
+    fn course() -> Course {
+        let mut block = session("18.10.2019", "09:00", "17:00");
+        block.make_all_day().unwrap();
+        Course {
+            id: Some("10101".to_owned()),
+            name: "Analysis I".to_owned(),
+            kind: Some("Vorlesung".to_owned()),
+            events: vec![session("14.10.2019", "10:00", "12:00"), block],
+            cancelled: vec![session("21.10.2019", "10:00", "12:00")],
+            url: None,
+            color: None,
+            sws: None,
+            ects: None,
+            modules: vec![],
+            language: None,
+            registration: Registration::default(),
+            warnings: vec![],
+        }
+    }
+
+    fn times(events: &[CourseEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| {
+                format!(
+                    "{}-{}",
+                    event.timespan.start.format("%d.%m. %H:%M"),
+                    event.timespan.end.format("%d.%m. %H:%M")
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn moves_start_and_end() {
+        let mut shorter = course();
+        apply(&mut shorter, 15, -15).unwrap();
+        assert_eq!(
+            times(&shorter.events),
+            vec!["14.10. 10:15-14.10. 11:45", "18.10. 00:00-19.10. 00:00"]
+        );
+        assert_eq!(times(&shorter.cancelled), vec!["21.10. 10:15-21.10. 11:45"]);
+
+        let mut longer = course();
+        apply(&mut longer, -30, 30).unwrap();
+        assert_eq!(times(&longer.events)[0], "14.10. 09:30-14.10. 12:30");
+    }
+
+    #[test]
+    fn keeps_times_without_offsets() {
+        let mut unchanged = course();
+        apply(&mut unchanged, 0, 0).unwrap();
+        assert_eq!(times(&unchanged.events), times(&course().events));
+    }
+
+    #[test]
+    fn rejects_sessions_ending_before_they_start() {
+        let mut course = course();
+        assert!(apply(&mut course, 60, -60).is_err());
+    }
+}
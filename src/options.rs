@@ -0,0 +1,88 @@
+//! Options of the generated calendars.
+//!
+//! They are passed along with the courses (see `RequestedCourse::options`) instead of being set
+//! for the whole process, so exports with different settings can run side by side, e.g. the
+//! server next to a sync.
+
+use crate::appearance::Appearance;
+use crate::campus::Building;
+use crate::refresh::DEFAULT_REFRESH_INTERVAL;
+use crate::uid::UidScheme;
+use chrono_tz::Europe::Berlin;
+use chrono_tz::Tz;
+use serde_json::json;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct CalendarOptions {
+    /// How the UIDs of events are built, see `uid`
+    pub uid_scheme: UidScheme,
+    /// Also export events under their legacy UID as cancelled, see `uid`
+    pub migrate_uids: bool,
+    /// Transparency and visibility of all events, see `appearance`
+    pub appearance: Appearance,
+    /// Export all courses anonymized, see `busy`
+    pub busy_only: bool,
+    /// Zone the times are exported in, Berlin if unset, see `timezone`
+    pub timezone: Option<Tz>,
+    /// Write iCalendar times as floating local times, see `timezone`
+    pub floating: bool,
+    /// Add the registration deadline of every course as an all-day event, see
+    /// `Registration::reminder`
+    pub registration_reminders: bool,
+    /// How often subscribers should poll the calendars, left to them if zero, see `refresh`
+    pub refresh_interval: Duration,
+    /// Buildings rooms are located in before the built-in ones, see `campus`
+    pub buildings: Vec<Building>,
+}
+
+impl Default for CalendarOptions {
+    fn default() -> Self {
+        Self {
+            uid_scheme: UidScheme::default(),
+            migrate_uids: false,
+            appearance: Appearance::default(),
+            busy_only: false,
+            timezone: None,
+            floating: false,
+            registration_reminders: false,
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+            buildings: vec![],
+        }
+    }
+}
+
+impl CalendarOptions {
+    /// The configured zone, if it isn't Berlin.
+    pub fn output_timezone(&self) -> Option<Tz> {
+        self.timezone.filter(|timezone| *timezone != Berlin)
+    }
+
+    /// All options as a stable string, for the fingerprints of `changes`.
+    pub fn fingerprint(&self) -> String {
+        // Destructured, so a new option can't be left out here
+        let CalendarOptions {
+            uid_scheme,
+            migrate_uids,
+            appearance,
+            busy_only,
+            timezone,
+            floating,
+            registration_reminders,
+            refresh_interval,
+            buildings,
+        } = self;
+        json!({
+            "uid_scheme": uid_scheme,
+            "migrate_uids": migrate_uids,
+            "appearance": appearance,
+            "busy_only": busy_only,
+            "timezone": timezone.map(|timezone| timezone.name()),
+            "floating": floating,
+            "registration_reminders": registration_reminders,
+            "refresh_interval": refresh_interval.as_secs(),
+            "buildings": buildings,
+        })
+        .to_string()
+    }
+}
@@ -0,0 +1,103 @@
+//! Org-mode export for Emacs users: one heading per course with its sessions as subheadings,
+//! scheduled with active timestamps so they show up in the agenda.
+
+use crate::{Course, CourseEvent, StdError};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Org document titled `title` with the sessions of `courses`, the counterpart of
+/// `merge::merge_courses`.
+///
+/// Cancelled sessions are left out, unlike in calendars there is no client that has to be told
+/// to remove them.
+pub fn document(title: &str, courses: &[Course]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "#+TITLE: {}", one_line(title));
+    for course in courses {
+        let _ = writeln!(out, "* {}", one_line(&course.name));
+        let mut properties = vec![];
+        if let Some(kind) = &course.kind {
+            properties.push(("KIND", kind.clone()));
+        }
+        if let Some(url) = &course.url {
+            properties.push(("URL", format!("[[{}]]", url)));
+        }
+        drawer(&mut out, &properties);
+        for event in course.events.iter() {
+            session(&mut out, course, event);
+        }
+    }
+    out
+}
+
+pub fn save<P: AsRef<Path>>(content: &str, path: P) -> Result<(), StdError> {
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+fn session(out: &mut String, course: &Course, event: &CourseEvent) {
+    let title = event.summary.as_ref().unwrap_or(&course.name);
+    let tag = if event.exam { " :exam:" } else { "" };
+    let _ = writeln!(out, "** {}{}", one_line(title), tag);
+
+    let mut properties = vec![];
+    if let Some(room) = &event.room {
+        properties.push(("ROOM", room.clone()));
+    }
+    if !event.lecturers.is_empty() {
+        properties.push(("LECTURERS", event.lecturers.join(", ")));
+    }
+    if let Some(part) = &event.part {
+        properties.push(("PART", part.clone()));
+    }
+    if let Some(url) = event.session_url(course.url.as_ref().map(String::as_str)) {
+        properties.push(("LINK", format!("[[{}]]", url)));
+    }
+    drawer(out, &properties);
+
+    let _ = writeln!(out, "{}", timestamp(event));
+    if let Some(week) = event.week {
+        let _ = writeln!(out, "Woche {}", week);
+    }
+    if let Some(remark) = &event.remark {
+        for line in remark.lines() {
+            let _ = writeln!(out, "{}", line);
+        }
+    }
+}
+
+fn drawer(out: &mut String, properties: &[(&str, String)]) {
+    if properties.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, ":PROPERTIES:");
+    for (name, value) in properties {
+        let _ = writeln!(out, ":{}: {}", name, one_line(value));
+    }
+    let _ = writeln!(out, ":END:");
+}
+
+/// Active timestamp like `<2019-10-21 Mon 10:00-13:00>`, or a range for sessions spanning
+/// several days.
+fn timestamp(event: &CourseEvent) -> String {
+    let start = event.timespan.start.naive_local();
+    let end = event.timespan.end.naive_local();
+    if start.date() == end.date() {
+        format!(
+            "<{}-{}>",
+            start.format("%Y-%m-%d %a %H:%M"),
+            end.format("%H:%M")
+        )
+    } else {
+        format!(
+            "<{}>--<{}>",
+            start.format("%Y-%m-%d %a %H:%M"),
+            end.format("%Y-%m-%d %a %H:%M")
+        )
+    }
+}
+
+/// `text` on one line, as headings and properties can't span several.
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,216 @@
+use crate::appearance::Appearance;
+use crate::config::OutlookConfig;
+use crate::fetch::{self, HttpRequest};
+use crate::oauth::DeviceFlow;
+use crate::{Course, CourseEvent, Error, RequestedCourse, StdError};
+use chrono::Utc;
+use http::header::{AUTHORIZATION, CONTENT_TYPE};
+use http::{Method, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tracing::info;
+
+const GRAPH_API: &str = "https://graph.microsoft.com/v1.0";
+const SCOPE: &str = "offline_access Calendars.ReadWrite";
+/// Extended property holding the VV event id of events created by fu-vv-cal
+const EVENT_ID_PROPERTY: &str = "String {5c4b1f5e-8f0a-4d7e-9a55-3c2f0e6b9d41} Name fuVvCalEvent";
+
+/// Sync the events of `courses` into the Outlook calendar configured in `outlook`.
+///
+/// Outlook assigns its own event ids, so the VV event id is stored in an extended property and
+/// used to find the events of earlier syncs, which are updated in place. Events that are no
+/// longer part of any course are deleted.
+pub async fn sync(outlook: &OutlookConfig, courses: &[RequestedCourse]) -> Result<(), StdError> {
+    let device_code_url = format!(
+        "https://login.microsoftonline.com/{}/oauth2/v2.0/devicecode",
+        outlook.tenant
+    );
+    let token_url = format!(
+        "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
+        outlook.tenant
+    );
+    let flow = DeviceFlow {
+        service: "your Outlook calendar",
+        device_code_url: &device_code_url,
+        token_url: &token_url,
+        client_id: &outlook.client_id,
+        client_secret: None,
+        scope: SCOPE,
+        token_file: &outlook.token_file,
+    };
+    let api = GraphApi {
+        token: flow.access_token().await?,
+        calendar: outlook.calendar.clone(),
+    };
+
+    let mut existing = api.list_managed_events().await?;
+    for requested in courses.iter() {
+        let course = requested.get_course().await?;
+        for event in course.events.iter() {
+            let body = event_json(&course, event, &requested.options.appearance);
+            match existing.remove(&event.id) {
+                Some(graph_id) => api.update(&graph_id, &body).await?,
+                None => api.create(&body).await?,
+            }
+        }
+        info!(
+            events = course.events.len() as u64,
+            course = course.name.as_str(),
+            "synced to Outlook"
+        );
+    }
+
+    // Whatever is left over belongs to events that no longer exist
+    for graph_id in existing.values() {
+        api.delete(graph_id).await?;
+    }
+
+    Ok(())
+}
+
+fn event_json(course: &Course, event: &CourseEvent, appearance: &Appearance) -> Value {
+    let format = "%Y-%m-%dT%H:%M:%S";
+    let mut value = json!({
+        "subject": course.name,
+        "start": {
+            "dateTime": event.timespan.start.with_timezone(&Utc).format(format).to_string(),
+            "timeZone": "UTC",
+        },
+        "end": {
+            "dateTime": event.timespan.end.with_timezone(&Utc).format(format).to_string(),
+            "timeZone": "UTC",
+        },
+        "singleValueExtendedProperties": [
+            { "id": EVENT_ID_PROPERTY, "value": event.id },
+        ],
+    });
+    appearance.apply_outlook(&mut value);
+    value
+}
+
+struct GraphApi {
+    token: String,
+    /// Calendar id, or the default calendar if unset
+    calendar: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventList {
+    #[serde(default)]
+    value: Vec<EventListItem>,
+    #[serde(rename = "@odata.nextLink")]
+    next_link: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventListItem {
+    id: String,
+    #[serde(default)]
+    single_value_extended_properties: Vec<ExtendedProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtendedProperty {
+    value: String,
+}
+
+impl GraphApi {
+    /// Graph ids of all events in the calendar that were created by fu-vv-cal, keyed by VV id.
+    async fn list_managed_events(&self) -> Result<HashMap<String, String>, StdError> {
+        let filter = format!(
+            "singleValueExtendedProperties/any(ep: ep/id eq '{}')",
+            EVENT_ID_PROPERTY
+        );
+        let expand = format!(
+            "singleValueExtendedProperties($filter=id eq '{}')",
+            EVENT_ID_PROPERTY
+        );
+        let mut url = format!(
+            "{}/events?$select=id&$top=100&$filter={}&$expand={}",
+            self.calendar_url(),
+            encode(&filter),
+            encode(&expand)
+        );
+
+        let mut ids = HashMap::new();
+        loop {
+            let (status, body) = self.call(Method::GET, &url, None).await?;
+            check_response(status, &body)?;
+            let list: EventList = serde_json::from_str(&body)?;
+            for item in list.value {
+                if let Some(property) = item.single_value_extended_properties.into_iter().next() {
+                    ids.insert(property.value, item.id);
+                }
+            }
+
+            match list.next_link {
+                Some(next_link) => url = next_link,
+                None => return Ok(ids),
+            }
+        }
+    }
+
+    async fn create(&self, event: &Value) -> Result<(), StdError> {
+        let url = format!("{}/events", self.calendar_url());
+        let (status, body) = self.call(Method::POST, &url, Some(event)).await?;
+        check_response(status, &body)
+    }
+
+    async fn update(&self, graph_id: &str, event: &Value) -> Result<(), StdError> {
+        let url = format!("{}/me/events/{}", GRAPH_API, encode(graph_id));
+        let (status, body) = self.call(Method::PATCH, &url, Some(event)).await?;
+        check_response(status, &body)
+    }
+
+    async fn delete(&self, graph_id: &str) -> Result<(), StdError> {
+        let url = format!("{}/me/events/{}", GRAPH_API, encode(graph_id));
+        let (status, body) = self.call(Method::DELETE, &url, None).await?;
+        // Already gone
+        if status == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        check_response(status, &body)
+    }
+
+    fn calendar_url(&self) -> String {
+        match &self.calendar {
+            Some(calendar) => format!("{}/me/calendars/{}", GRAPH_API, encode(calendar)),
+            None => format!("{}/me/calendar", GRAPH_API),
+        }
+    }
+
+    async fn call(
+        &self,
+        method: Method,
+        url: &str,
+        event: Option<&Value>,
+    ) -> Result<(StatusCode, String), StdError> {
+        let body = event.map(Value::to_string).unwrap_or_default();
+        let req = HttpRequest::new(method, url)
+            .header(AUTHORIZATION, format!("Bearer {}", self.token))?
+            .header(CONTENT_TYPE, "application/json")?
+            .with_body(body);
+
+        fetch::send(req).await
+    }
+}
+
+fn check_response(status: StatusCode, body: &str) -> Result<(), StdError> {
+    if !status.is_success() {
+        return Err(Error::OutlookApiError {
+            status: status.as_u16(),
+            message: body.to_owned(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Percent-encode `value`, with spaces as `%20` since Graph doesn't decode `+` in OData queries.
+fn encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes())
+        .collect::<String>()
+        .replace('+', "%20")
+}
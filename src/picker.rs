@@ -0,0 +1,311 @@
+//! Interactive course picker for the start of the semester: search the VV, browse the results,
+//! preview the sessions of a course and select courses to add to the config.
+
+use crate::fetch::Locale;
+use crate::search::{self, SearchResult};
+use crate::source::FuVv;
+use crate::{Course, RequestedCourse, StdError};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::AlternateScreen;
+use tui::backend::{Backend, TermionBackend};
+use tui::layout::{Constraint, Direction, Layout};
+use tui::style::{Color, Style};
+use tui::terminal::Frame;
+use tui::widgets::{Block, Borders, Paragraph, SelectableList, Text, Widget};
+use tui::Terminal;
+
+const SEARCH_HELP: &str = "Enter: suchen  Esc: zu den Ergebnissen  Ctrl-C: abbrechen";
+const BROWSE_HELP: &str =
+    "↑/↓: auswählen  Leertaste: markieren  Enter: Vorschau  /: suchen  s: speichern  q: abbrechen";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    /// Typing the search query
+    Search,
+    /// Moving through the results
+    Browse,
+}
+
+/// What the key pressed last asks for.
+#[derive(Debug, PartialEq)]
+enum Action {
+    None,
+    Search,
+    Preview(String),
+    Save,
+    Quit,
+}
+
+#[derive(Debug)]
+struct Picker {
+    mode: Mode,
+    query: String,
+    results: Vec<SearchResult>,
+    cursor: usize,
+    /// Marked courses, kept across searches
+    selected: Vec<SearchResult>,
+    /// Sessions of previewed courses, or why they couldn't be fetched
+    previews: HashMap<String, Vec<String>>,
+    status: Option<String>,
+}
+
+/// Run the picker on the terminal, searching courses of `semester` in the VV in `locale`.
+///
+/// Previews parse the course pages `lenient`ly if set. Returns the selected courses, none if the
+/// picker was cancelled.
+pub async fn pick(
+    semester: &str,
+    locale: Locale,
+    lenient: bool,
+) -> Result<Vec<SearchResult>, StdError> {
+    let stdout = AlternateScreen::from(io::stdout().into_raw_mode()?);
+    let mut terminal = Terminal::new(TermionBackend::new(stdout))?;
+    terminal.hide_cursor()?;
+
+    let mut picker = Picker::new();
+    let mut keys = io::stdin().keys();
+    loop {
+        terminal.draw(|mut frame| picker.draw(&mut frame))?;
+        let key = match keys.next() {
+            Some(key) => key?,
+            None => return Ok(vec![]),
+        };
+        match picker.handle(key) {
+            Action::None => {}
+            Action::Search => {
+                picker.status = Some("Suche …".to_owned());
+                terminal.draw(|mut frame| picker.draw(&mut frame))?;
+                match search::search(&picker.query, semester, locale).await {
+                    Ok(results) => {
+                        picker.status = Some(format!("{} Veranstaltungen gefunden", results.len()));
+                        picker.results = results;
+                        picker.cursor = 0;
+                    }
+                    Err(err) => picker.status = Some(format!("Suche fehlgeschlagen: {}", err)),
+                }
+            }
+            Action::Preview(id) => {
+                picker.status = Some("Lade Termine …".to_owned());
+                terminal.draw(|mut frame| picker.draw(&mut frame))?;
+                let preview = match RequestedCourse::new(id.as_str(), semester)
+                    .with_source(Arc::new(
+                        FuVv::new().with_locale(locale).with_lenient(lenient),
+                    ))
+                    .get_course()
+                    .await
+                {
+                    Ok(course) => session_lines(&course),
+                    Err(err) => vec![format!("Termine nicht verfügbar: {}", err)],
+                };
+                picker.previews.insert(id, preview);
+                picker.status = None;
+            }
+            Action::Save => return Ok(picker.selected),
+            Action::Quit => return Ok(vec![]),
+        }
+    }
+}
+
+impl Picker {
+    fn new() -> Self {
+        Self {
+            mode: Mode::Search,
+            query: String::new(),
+            results: vec![],
+            cursor: 0,
+            selected: vec![],
+            previews: HashMap::new(),
+            status: None,
+        }
+    }
+
+    fn handle(&mut self, key: Key) -> Action {
+        if key == Key::Ctrl('c') {
+            return Action::Quit;
+        }
+        match self.mode {
+            Mode::Search => match key {
+                Key::Char('\n') if !self.query.trim().is_empty() => {
+                    self.mode = Mode::Browse;
+                    Action::Search
+                }
+                Key::Char('\n') => Action::None,
+                Key::Char(c) => {
+                    self.query.push(c);
+                    Action::None
+                }
+                Key::Backspace => {
+                    self.query.pop();
+                    Action::None
+                }
+                Key::Esc if self.results.is_empty() => Action::Quit,
+                Key::Esc => {
+                    self.mode = Mode::Browse;
+                    Action::None
+                }
+                _ => Action::None,
+            },
+            Mode::Browse => match key {
+                Key::Up | Key::Char('k') => {
+                    self.cursor = self.cursor.saturating_sub(1);
+                    Action::None
+                }
+                Key::Down | Key::Char('j') => {
+                    if self.cursor + 1 < self.results.len() {
+                        self.cursor += 1;
+                    }
+                    Action::None
+                }
+                Key::Char(' ') => {
+                    self.toggle();
+                    Action::None
+                }
+                Key::Char('\n') => match self.results.get(self.cursor) {
+                    Some(result) if !self.previews.contains_key(&result.id) => {
+                        Action::Preview(result.id.clone())
+                    }
+                    _ => Action::None,
+                },
+                Key::Char('/') => {
+                    self.mode = Mode::Search;
+                    Action::None
+                }
+                Key::Char('s') => Action::Save,
+                Key::Char('q') | Key::Esc => Action::Quit,
+                _ => Action::None,
+            },
+        }
+    }
+
+    /// Mark the course under the cursor, or unmark it if it is marked.
+    fn toggle(&mut self) {
+        let result = match self.results.get(self.cursor) {
+            Some(result) => result,
+            None => return,
+        };
+        match self
+            .selected
+            .iter()
+            .position(|selected| selected.id == result.id)
+        {
+            Some(index) => {
+                self.selected.remove(index);
+            }
+            None => self.selected.push(result.clone()),
+        }
+    }
+
+    fn draw<B: Backend>(&self, frame: &mut Frame<B>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Length(3),
+                    Constraint::Min(5),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
+            .split(frame.size());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(rows[1]);
+        let active = Style::default().fg(Color::Yellow);
+
+        let query = [Text::raw(self.query.clone())];
+        Paragraph::new(query.iter())
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Suche im VV")
+                    .border_style(if self.mode == Mode::Search {
+                        active
+                    } else {
+                        Style::default()
+                    }),
+            )
+            .render(frame, rows[0]);
+
+        let items = self
+            .results
+            .iter()
+            .map(|result| {
+                let marked = self
+                    .selected
+                    .iter()
+                    .any(|selected| selected.id == result.id);
+                format!("[{}] {}", if marked { "x" } else { " " }, result.title)
+            })
+            .collect::<Vec<_>>();
+        let title = format!("Ergebnisse ({} markiert)", self.selected.len());
+        SelectableList::default()
+            .block(Block::default().borders(Borders::ALL).title(&title))
+            .items(&items)
+            .select(if items.is_empty() {
+                None
+            } else {
+                Some(self.cursor)
+            })
+            .highlight_style(active)
+            .highlight_symbol(">")
+            .render(frame, columns[0]);
+
+        let preview = match self.results.get(self.cursor) {
+            Some(result) => {
+                let mut lines = vec![Text::raw(format!("{}\n\n", result.context))];
+                match self.previews.get(&result.id) {
+                    Some(sessions) if sessions.is_empty() => {
+                        lines.push(Text::raw("Noch keine Termine veröffentlicht\n"))
+                    }
+                    Some(sessions) => lines.extend(
+                        sessions
+                            .iter()
+                            .map(|session| Text::raw(format!("{}\n", session))),
+                    ),
+                    None => lines.push(Text::raw("Enter zeigt die Termine\n")),
+                }
+                lines
+            }
+            None => vec![],
+        };
+        Paragraph::new(preview.iter())
+            .block(Block::default().borders(Borders::ALL).title("Vorschau"))
+            .wrap(true)
+            .render(frame, columns[1]);
+
+        let help = match (&self.status, self.mode) {
+            (Some(status), _) => status.as_str(),
+            (None, Mode::Search) => SEARCH_HELP,
+            (None, Mode::Browse) => BROWSE_HELP,
+        };
+        let help = [Text::raw(help)];
+        Paragraph::new(help.iter()).render(frame, rows[2]);
+    }
+}
+
+/// The sessions of `course` as lines of the preview.
+fn session_lines(course: &Course) -> Vec<String> {
+    course
+        .events
+        .iter()
+        .map(|event| {
+            let start = event.timespan.start.naive_local();
+            let end = event.timespan.end.naive_local();
+            let mut line = format!(
+                "{}–{}",
+                start.format("%a %d.%m.%Y %H:%M"),
+                end.format("%H:%M")
+            );
+            if let Some(room) = &event.room {
+                line = format!("{}, {}", line, room);
+            }
+            line
+        })
+        .collect()
+}
@@ -0,0 +1,88 @@
+//! Status lines for exports of many courses, so long runs show where they are.
+//!
+//! Written to stderr, so they don't mix with calendars written to stdout.
+
+use crate::Course;
+use std::time::Instant;
+
+#[derive(Debug)]
+pub struct Progress {
+    enabled: bool,
+    total: usize,
+    current: usize,
+    started: Instant,
+    /// Files written, and files left as they were since their content didn't change
+    written: usize,
+    unchanged: usize,
+}
+
+impl Progress {
+    /// Progress of `total` courses, silent unless `enabled`.
+    pub fn new(total: usize, enabled: bool) -> Self {
+        Self {
+            enabled,
+            total,
+            current: 0,
+            started: Instant::now(),
+            written: 0,
+            unchanged: 0,
+        }
+    }
+
+    /// Start fetching the next course.
+    pub fn start(&mut self) {
+        self.current += 1;
+        self.started = Instant::now();
+    }
+
+    pub fn fetched(&self, course: &Course) {
+        self.line(
+            &course.name,
+            &format!(
+                "fetched, {} sessions ({:.1}s)",
+                course.events.len(),
+                self.started.elapsed().as_secs_f64()
+            ),
+        );
+    }
+
+    pub fn failed(&self, id: &str, err: &dyn std::error::Error) {
+        self.line(id, &format!("failed: {}", err));
+    }
+
+    pub fn written(&mut self, name: &str, path: &str) {
+        self.written += 1;
+        if self.enabled {
+            eprintln!("{} written to {}", name, path);
+        }
+    }
+
+    /// The file at `path` already had the content of `name`, so it wasn't rewritten.
+    pub fn unchanged(&mut self, name: &str, path: &str) {
+        self.unchanged += 1;
+        if self.enabled {
+            eprintln!("{} unchanged at {}", name, path);
+        }
+    }
+
+    /// Print how many files were written and how many were unchanged.
+    pub fn summary(&self) {
+        if self.enabled {
+            eprintln!("{} written, {} unchanged", self.written, self.unchanged);
+        }
+    }
+
+    fn line(&self, name: &str, status: &str) {
+        if self.enabled {
+            let width = self.total.to_string().len();
+            eprintln!(
+                "[{:>width$}/{}] {}: {}",
+                self.current,
+                self.total,
+                name,
+                status,
+                width = width
+            );
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! How often clients subscribing to the generated calendars should poll them, announced with
+//! `REFRESH-INTERVAL` (RFC 7986) and `X-PUBLISHED-TTL`, which Outlook and older clients read.
+
+use crate::options::CalendarOptions;
+use ics::components::{Parameter, Property};
+use ics::ICalendar;
+use std::time::Duration;
+
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Add the refresh hints of `CalendarOptions::refresh_interval` to a published `calendar`.
+pub fn add_hints(calendar: &mut ICalendar, options: &CalendarOptions) {
+    let interval = options.refresh_interval;
+    if interval.as_secs() == 0 {
+        return;
+    }
+    let duration = iso_duration(interval);
+    let mut refresh = Property::new("REFRESH-INTERVAL", duration.clone());
+    refresh.add(Parameter::new("VALUE", "DURATION"));
+    calendar.push(refresh);
+    calendar.push(Property::new("X-PUBLISHED-TTL", duration));
+}
+
+/// `interval` as an iCalendar duration, e.g. "PT12H" or "PT1H30M".
+fn iso_duration(interval: Duration) -> String {
+    let seconds = interval.as_secs();
+    let mut text = String::from("PT");
+    for (amount, unit) in &[
+        (seconds / 3600, 'H'),
+        (seconds / 60 % 60, 'M'),
+        (seconds % 60, 'S'),
+    ] {
+        if *amount > 0 {
+            text.push_str(&format!("{}{}", amount, unit));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::new_calendar;
+
+    fn hints(interval: Duration) -> String {
+        let options = CalendarOptions {
+            refresh_interval: interval,
+            ..CalendarOptions::default()
+        };
+        let mut calendar = new_calendar();
+        add_hints(&mut calendar, &options);
+        calendar.to_string()
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(iso_duration(DEFAULT_REFRESH_INTERVAL), "PT12H");
+        assert_eq!(iso_duration(Duration::from_secs(90 * 60)), "PT1H30M");
+        assert_eq!(iso_duration(Duration::from_secs(45)), "PT45S");
+    }
+
+    #[test]
+    fn hints_of_the_options() {
+        let calendar = hints(Duration::from_secs(60 * 60));
+        assert!(calendar.contains("REFRESH-INTERVAL;VALUE=DURATION:PT1H\r\n"));
+        assert!(calendar.contains("X-PUBLISHED-TTL:PT1H\r\n"));
+        assert!(!hints(Duration::from_secs(0)).contains("REFRESH-INTERVAL"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Participation details of course pages: the number of places and how and until when to
+//! register, e.g. "Anmeldung über Campus Management bis 15.10.2019".
+
+use crate::milestones;
+use chrono::NaiveDate;
+use ics::properties::Categories;
+use ics::Event;
+use serde::{Deserialize, Serialize};
+
+/// Labels next to the number of places on course pages
+const CAPACITY_LABELS: &[&str] = &["Teilnehmerzahl", "Teilnehmer", "Teilnehmende", "Plätze"];
+/// Word starting the registration note
+const NOTE_LABEL: &str = "Anmeldung";
+/// Maximum length of the registration note in characters
+const NOTE_CHARS: usize = 200;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Registration {
+    /// Maximum number of participants
+    pub capacity: Option<u32>,
+    /// The sentence about registering, e.g. "Anmeldung über Campus Management bis 15.10.2019"
+    pub note: Option<String>,
+    /// Last day to register, from the note
+    pub deadline: Option<NaiveDate>,
+}
+
+impl Registration {
+    /// Registration details mentioned in the text of a course page.
+    pub(crate) fn parse(text: &str) -> Self {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let note = note(&text);
+        let deadline = note.as_ref().and_then(|note| deadline(note));
+        Self {
+            capacity: capacity(&text),
+            note,
+            deadline,
+        }
+    }
+
+    /// Lines for the description of the course, see `Course::description`.
+    pub fn description_lines(&self) -> Vec<String> {
+        self.capacity
+            .map(|capacity| format!("Max. {} Teilnehmende", capacity))
+            .into_iter()
+            .chain(self.note.clone())
+            .collect()
+    }
+
+    /// All-day event on the registration deadline of `course_name`, with a UID derived from
+    /// `course_uid`.
+    ///
+    /// Only exported with `CalendarOptions::registration_reminders`.
+    pub fn reminder(&self, course_name: &str, course_uid: &str) -> Option<Event<'static>> {
+        let deadline = self.deadline?;
+        let mut event = milestones::all_day_event(
+            format!("registration-{}", course_uid),
+            deadline,
+            deadline,
+            format!("Anmeldeschluss: {}", course_name),
+        );
+        event.push(Categories::new("Anmeldung"));
+        Some(event)
+    }
+}
+
+/// The text from `NOTE_LABEL` up to the end of its sentence.
+fn note(text: &str) -> Option<String> {
+    let start = text
+        .match_indices(NOTE_LABEL)
+        .map(|(index, _)| index)
+        // "Anmeldung" without anything after it is just a heading
+        .find(|index| text[*index + NOTE_LABEL.len()..].starts_with(' '))?;
+    let note = &text[start..];
+    let note = match note.find(". ") {
+        Some(end) => &note[..end],
+        None => note,
+    };
+    let note = note.chars().take(NOTE_CHARS).collect::<String>();
+    Some(note.trim_end_matches('.').to_owned())
+}
+
+/// The date after "bis" in `note`, e.g. "bis 15.10.2019".
+fn deadline(note: &str) -> Option<NaiveDate> {
+    let words = note.split(' ').collect::<Vec<_>>();
+    words.windows(2).find_map(|pair| {
+        if pair[0] != "bis" {
+            return None;
+        }
+        let date = pair[1].trim_end_matches(|c: char| !c.is_ascii_digit());
+        NaiveDate::parse_from_str(date, "%d.%m.%Y").ok()
+    })
+}
+
+/// The number next to one of `CAPACITY_LABELS`, e.g. "Teilnehmerzahl: 30" or "max. 30 Plätze".
+fn capacity(text: &str) -> Option<u32> {
+    let words = text
+        .split(' ')
+        .map(|word| word.trim_matches(|c: char| ":;,.()[]".contains(c)))
+        .collect::<Vec<_>>();
+    let number = |index: Option<usize>| words.get(index?)?.parse::<u32>().ok();
+    words
+        .iter()
+        .enumerate()
+        .filter(|(_, word)| CAPACITY_LABELS.contains(*word))
+        .filter_map(|(index, _)| number(index.checked_sub(1)).or_else(|| number(Some(index + 1))))
+        .next()
+}
@@ -0,0 +1,84 @@
+//! Courses related to a course, found by following the links between course pages, e.g. from a
+//! lecture to the other courses of its module ("gehört zu Modul …", "siehe auch").
+
+use crate::hierarchy;
+use crate::source::CourseSource;
+use crate::StdError;
+use std::collections::VecDeque;
+use tracing::{debug, warn};
+
+/// Maximum number of courses a crawl discovers, as a few hops can reach much of the VV
+pub const MAX_COURSES: usize = 50;
+
+/// A course found by `crawl`.
+#[derive(Debug, Clone)]
+pub struct RelatedCourse {
+    pub id: String,
+    pub name: String,
+    pub kind: Option<String>,
+    /// Number of links between the course the crawl started at and this one
+    pub depth: usize,
+    /// Course whose page links this one
+    pub linked_from: String,
+}
+
+/// Courses reachable from course `id` via at most `depth` links, in the order they were found.
+///
+/// Pages that can't be fetched or parsed are skipped with a warning.
+pub async fn crawl(
+    source: &dyn CourseSource,
+    id: &str,
+    semester: &str,
+    depth: usize,
+) -> Result<Vec<RelatedCourse>, StdError> {
+    let mut seen = vec![id.to_owned()];
+    let mut found: Vec<RelatedCourse> = vec![];
+    let mut queue = VecDeque::new();
+    queue.push_back((id.to_owned(), 0));
+    while let Some((current, distance)) = queue.pop_front() {
+        if distance >= depth {
+            continue;
+        }
+        let linked = match hierarchy::child_ids(source, &current, semester).await {
+            Ok(linked) => linked,
+            Err(err) => {
+                warn!(course = current.as_str(), "skipping page: {}", err);
+                continue;
+            }
+        };
+        for linked_id in linked {
+            if seen.contains(&linked_id) {
+                continue;
+            }
+            seen.push(linked_id.clone());
+            if found.len() >= MAX_COURSES {
+                warn!(
+                    "more than {} related courses, the rest is skipped",
+                    MAX_COURSES
+                );
+                return Ok(found);
+            }
+            let course = match source.get_course(&linked_id, semester).await {
+                Ok(course) => course,
+                Err(err) => {
+                    warn!(course = linked_id.as_str(), "skipping course: {}", err);
+                    continue;
+                }
+            };
+            debug!(
+                course = linked_id.as_str(),
+                linked_from = current.as_str(),
+                "found related course"
+            );
+            found.push(RelatedCourse {
+                id: linked_id.clone(),
+                name: course.name,
+                kind: course.kind,
+                depth: distance + 1,
+                linked_from: current.clone(),
+            });
+            queue.push_back((linked_id, distance + 1));
+        }
+    }
+    Ok(found)
+}
@@ -0,0 +1,69 @@
+//! Changes of the config file while running for long (`serve`, `--watch`), so added and removed
+//! courses, aliases and intervals apply without a restart.
+//!
+//! The modification time of the file is polled, which also works on mounts (e.g. a ConfigMap of
+//! a container) that don't deliver change notifications.
+
+use crate::shutdown;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::timer::delay;
+use tracing::info;
+
+/// Time between two checks of the config file
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// Watch the file at `path` for changes from now on.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        let modified = modified(&path);
+        Self { path, modified }
+    }
+
+    /// Whether the file was changed, created or removed since the last check.
+    pub fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        info!(path = %self.path.display(), "config file changed");
+        true
+    }
+
+    /// Wait until the file changes.
+    pub async fn next_change(&mut self) {
+        while !self.changed() {
+            delay(Instant::now() + POLL_INTERVAL).await;
+        }
+    }
+
+    /// Wait until the file changes, `timeout` has passed or shutdown is requested (see
+    /// `shutdown`), returning whether it changed.
+    pub async fn wait(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.changed() {
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline || shutdown::requested() {
+                return false;
+            }
+            delay((now + POLL_INTERVAL).min(deadline)).await;
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
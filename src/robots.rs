@@ -0,0 +1,281 @@
+//! The robots.txt of the VV host, fetched before the first request to the VV and honored by all
+//! of them: disallowed pages fail with `Error::DisallowedByRobots` instead of being requested, and
+//! a `Crawl-delay` longer than the configured request delay spaces out the requests instead.
+//!
+//! Only the group for `fu-vv-cal` or, if there is none, the one for all robots (`*`) applies.
+//! Rules are matched like search engines do: `*` matches any characters, a trailing `$` the end of
+//! the path, and the longest matching rule wins, `Allow` on ties.
+
+use crate::fetch;
+use crate::{Error, StdError};
+use lazy_static::lazy_static;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Name of fu-vv-cal in robots.txt groups
+pub const ROBOT_NAME: &str = "fu-vv-cal";
+
+lazy_static! {
+    /// Whether robots.txt is honored, see `set_respected`.
+    static ref RESPECTED: Mutex<bool> = Mutex::new(true);
+
+    /// robots.txt of the VV host, once fetched.
+    static ref ROBOTS: Mutex<Option<Arc<Robots>>> = Mutex::new(None);
+}
+
+/// Request pages regardless of robots.txt if `respected` is false.
+pub fn set_respected(respected: bool) {
+    *RESPECTED.lock().unwrap() = respected;
+}
+
+/// Rules of the group of a robots.txt that applies to fu-vv-cal.
+#[derive(Debug, Clone, Default)]
+pub struct Robots {
+    /// Path patterns, with whether they allow access
+    rules: Vec<(String, bool)>,
+    pub crawl_delay: Option<Duration>,
+}
+
+impl Robots {
+    /// The group of `text` for `ROBOT_NAME`, or the one for all robots.
+    pub fn parse(text: &str) -> Self {
+        let mut groups: Vec<(Vec<String>, Robots)> = vec![];
+        let mut in_agents = false;
+        for line in text.lines() {
+            let line = line.splitn(2, '#').next().unwrap_or("").trim();
+            let mut pair = line.splitn(2, ':');
+            let key = pair.next().unwrap_or("").trim().to_lowercase();
+            let value = match pair.next() {
+                Some(value) => value.trim(),
+                None => continue,
+            };
+            if key == "user-agent" {
+                if !in_agents {
+                    groups.push((vec![], Robots::default()));
+                }
+                in_agents = true;
+                if let Some((agents, _)) = groups.last_mut() {
+                    agents.push(value.to_lowercase());
+                }
+                continue;
+            }
+            in_agents = false;
+            let robots = match groups.last_mut() {
+                Some((_, robots)) => robots,
+                // Rules before the first group apply to no one
+                None => continue,
+            };
+            match key.as_str() {
+                "allow" | "disallow" if !value.is_empty() => {
+                    robots.rules.push((value.to_owned(), key == "allow"))
+                }
+                "crawl-delay" => {
+                    if let Ok(seconds) = value.parse::<f64>() {
+                        robots.crawl_delay = Some(Duration::from_millis((seconds * 1000.0) as u64));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let group = |name: &str| {
+            groups
+                .iter()
+                .find(|(agents, _)| agents.iter().any(|agent| agent == name))
+                .map(|(_, robots)| robots.clone())
+        };
+        group(ROBOT_NAME).or_else(|| group("*")).unwrap_or_default()
+    }
+
+    /// Whether the page at `path` (with its query) may be requested.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(pattern, _)| matches(pattern, path))
+            .max_by_key(|(pattern, allow)| (pattern.len(), *allow))
+            .map_or(true, |(_, allow)| *allow)
+    }
+}
+
+/// Whether the robots.txt `pattern` matches `path`.
+fn matches(pattern: &str, path: &str) -> bool {
+    let anchored = pattern.ends_with('$');
+    let mut parts = pattern.trim_end_matches('$').split('*');
+    let first = parts.next().unwrap_or("");
+    if !path.starts_with(first) {
+        return false;
+    }
+    let mut rest = &path[first.len()..];
+    let parts = parts.collect::<Vec<_>>();
+    for (index, part) in parts.iter().enumerate() {
+        if anchored && index + 1 == parts.len() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(found) => rest = &rest[found + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+/// Fail with `Error::DisallowedByRobots` if the robots.txt of the VV disallows `url`, fetching it
+/// on the first call. Other hosts aren't checked.
+pub async fn check(url: &str) -> Result<(), StdError> {
+    if !*RESPECTED.lock().unwrap() {
+        return Ok(());
+    }
+    let parsed = match url::Url::parse(url) {
+        Ok(parsed) if parsed.host_str() == Some(fetch::VV_HOST) => parsed,
+        _ => return Ok(()),
+    };
+    let robots = match robots() {
+        Some(robots) => robots,
+        None => {
+            let robots = Arc::new(fetch_robots().await);
+            *ROBOTS.lock().unwrap() = Some(robots.clone());
+            robots
+        }
+    };
+    let path = match parsed.query() {
+        Some(query) => format!("{}?{}", parsed.path(), query),
+        None => parsed.path().to_owned(),
+    };
+    if robots.is_allowed(&path) {
+        Ok(())
+    } else {
+        Err(Error::DisallowedByRobots {
+            url: url.to_owned(),
+        }
+        .into())
+    }
+}
+
+/// `Crawl-delay` of the robots.txt of the VV, if it was fetched and is honored.
+pub fn crawl_delay() -> Option<Duration> {
+    if !*RESPECTED.lock().unwrap() {
+        return None;
+    }
+    robots()?.crawl_delay
+}
+
+fn robots() -> Option<Arc<Robots>> {
+    ROBOTS.lock().unwrap().clone()
+}
+
+/// The robots.txt of the VV, without any rules if there is none or it can't be fetched.
+async fn fetch_robots() -> Robots {
+    let url = format!("https://{}/robots.txt", fetch::VV_HOST);
+    match fetch::get_unchecked(&url).await {
+        Ok((status, body)) if status.is_success() => {
+            let robots = Robots::parse(&body);
+            debug!(
+                rules = robots.rules.len(),
+                crawl_delay_ms = robots
+                    .crawl_delay
+                    .map_or(0, |delay| delay.as_millis() as u64),
+                "read robots.txt"
+            );
+            robots
+        }
+        Ok((status, _)) => {
+            debug!(status = status.as_u16(), "no robots.txt");
+            Robots::default()
+        }
+        Err(err) => {
+            warn!(%err, "failed to fetch robots.txt, continuing without it");
+            Robots::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_patterns() {
+        let cases = [
+            ("/", "/", true),
+            ("/", "/vv/de/lv/524870", true),
+            ("/vv/de/lv", "/vv/de/lv/524870", true),
+            ("/vv/de/lv", "/vv/en/lv/524870", false),
+            ("/vv/*/lv", "/vv/en/lv/524870", true),
+            ("/vv/*/lv", "/vv/lv", false),
+            ("/*.php", "/index.php?sm=498562", true),
+            ("/*.php", "/php", false),
+            ("/*.php$", "/index.php", true),
+            ("/*.php$", "/index.php?sm=498562", false),
+            ("/*.php$", "/index.php.php", true),
+            ("/search$", "/search", true),
+            ("/search$", "/search?q=analysis", false),
+            ("/*?*sm=", "/vv/de/lv/524870?sm=498562", true),
+            ("/*?*sm=", "/vv/de/lv/524870", false),
+            ("/vv*$", "/vv/de/search", true),
+        ];
+        for (pattern, path, expected) in cases.iter() {
+            assert_eq!(
+                matches(pattern, path),
+                *expected,
+                "{} matching {}",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn longest_rule_wins() {
+        let robots = Robots::parse(
+            "User-agent: *\n\
+             Disallow: /vv/\n\
+             Allow: /vv/de/lv/\n\
+             Disallow: /vv/de/lv/*/print\n\
+             Disallow: /vv/de/search\n\
+             Allow: /vv/de/search\n",
+        );
+        let cases = [
+            ("/", true),
+            ("/vv/en/lv/524870", false),
+            ("/vv/de/lv/524870", true),
+            ("/vv/de/lv/524870/print", false),
+            // Allow wins ties
+            ("/vv/de/search?q=analysis", true),
+        ];
+        for (path, expected) in cases.iter() {
+            assert_eq!(robots.is_allowed(path), *expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn selects_the_group_of_fu_vv_cal() {
+        let text = "Disallow: /ignored\n\
+                    \n\
+                    User-agent: *\n\
+                    Disallow: /\n\
+                    Crawl-delay: 10\n\
+                    \n\
+                    User-agent: Googlebot\n\
+                    User-agent: FU-VV-CAL # two agents, one group\n\
+                    Disallow: /vv/de/search\n\
+                    Crawl-delay: 2.5\n";
+        let robots = Robots::parse(text);
+        assert!(robots.is_allowed("/vv/de/lv/524870"));
+        assert!(!robots.is_allowed("/vv/de/search?q=analysis"));
+        assert!(robots.is_allowed("/ignored"));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_millis(2500)));
+
+        let robots = Robots::parse(&text.replace("FU-VV-CAL", "otherbot"));
+        assert!(!robots.is_allowed("/vv/de/lv/524870"));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn allows_everything_without_a_group() {
+        let robots = Robots::parse("User-agent: Googlebot\nDisallow: /\nDisallow:\n");
+        assert!(robots.is_allowed("/vv/de/lv/524870"));
+        assert_eq!(robots.crawl_delay, None);
+        assert!(Robots::parse("").is_allowed("/"));
+    }
+}
@@ -0,0 +1,483 @@
+use crate::{language_code, Course, CourseEvent, Error, StdError};
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike, Weekday};
+use serde::Deserialize;
+
+/// Include and exclude rules deciding which sessions end up in the calendar.
+///
+/// If there are include rules, a session has to match at least one of them. Sessions matching any
+/// exclude rule are always dropped.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Rules {
+    #[serde(default)]
+    pub include: Vec<Rule>,
+    #[serde(default)]
+    pub exclude: Vec<Rule>,
+}
+
+/// A rule matching sessions by all of the given fields.
+///
+/// ```toml
+/// exclude = [{ course = "*Übung*", weekday = "Fri" }]
+/// include = [{ part = "VL" }]
+/// exclude = [{ from = "2020-02-10", until = "2020-02-15" }]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Rule {
+    /// Pattern for the course name, where `*` matches any text and `?` any single character.
+    /// Matched case-insensitively.
+    pub course: Option<String>,
+    /// Course id
+    pub id: Option<String>,
+    /// Weekday, e.g. "Fri", "Friday" or "Fr"
+    pub weekday: Option<String>,
+    /// Start time ("10:00") or range of start times ("08:00-12:00", end exclusive)
+    pub time: Option<String>,
+    /// Part of the course the session belongs to, e.g. "Vorlesung"/"VL", "Übung"/"Ü" or
+    /// "Tutorium"/"Tut". Sessions without a part label don't match.
+    pub part: Option<String>,
+    /// Exercise group, e.g. "Gruppe B" or just "B". Sessions that don't belong to any group
+    /// always match.
+    pub group: Option<String>,
+    /// First day of sessions (inclusive)
+    pub from: Option<NaiveDate>,
+    /// Last day of sessions (inclusive)
+    pub until: Option<NaiveDate>,
+    /// Language of instruction, e.g. "en", "English" or "Englisch". Courses whose page doesn't
+    /// state a language don't match.
+    pub language: Option<String>,
+}
+
+impl Rules {
+    /// Rules keeping only the sessions of the given course `part`.
+    pub fn part(part: &str) -> Self {
+        Self {
+            include: vec![Rule {
+                part: Some(part.to_owned()),
+                ..Rule::default()
+            }],
+            exclude: vec![],
+        }
+    }
+
+    /// Rules keeping only the sessions of `group`, and those that aren't part of a group.
+    pub fn group(group: &str) -> Self {
+        Self {
+            include: vec![Rule {
+                group: Some(group.to_owned()),
+                ..Rule::default()
+            }],
+            exclude: vec![],
+        }
+    }
+
+    /// Rules keeping only the sessions between `from` and `until` (both inclusive).
+    pub fn date_range(from: Option<NaiveDate>, until: Option<NaiveDate>) -> Self {
+        Self {
+            include: vec![Rule {
+                from,
+                until,
+                ..Rule::default()
+            }],
+            exclude: vec![],
+        }
+    }
+
+    /// Rules keeping only the sessions of courses taught in `language`.
+    pub fn language(language: &str) -> Self {
+        Self {
+            include: vec![Rule {
+                language: Some(language.to_owned()),
+                ..Rule::default()
+            }],
+            exclude: vec![],
+        }
+    }
+
+    /// Rules keeping only the sessions on one of `weekdays`.
+    pub fn weekdays(weekdays: &[&str]) -> Result<Self, StdError> {
+        let include = weekdays
+            .iter()
+            .map(|weekday| {
+                // Fail early instead of on the first session
+                parse_weekday(weekday)?;
+                Ok(Rule {
+                    weekday: Some(weekday.trim().to_owned()),
+                    ..Rule::default()
+                })
+            })
+            .collect::<Result<_, StdError>>()?;
+
+        Ok(Self {
+            include,
+            exclude: vec![],
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Remove all sessions of `course` that aren't selected by the rules.
+    pub fn apply(&self, course_id: &str, course: &mut Course) -> Result<(), StdError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let languages = course.language_codes();
+        let mut events = Vec::with_capacity(course.events.len());
+        for event in course.events.drain(..) {
+            if self.selects(course_id, &course.name, &languages, &event)? {
+                events.push(event);
+            }
+        }
+        course.events = events;
+
+        Ok(())
+    }
+
+    fn selects(
+        &self,
+        course_id: &str,
+        course_name: &str,
+        languages: &[&str],
+        event: &CourseEvent,
+    ) -> Result<bool, StdError> {
+        if !self.include.is_empty() {
+            let mut included = false;
+            for rule in self.include.iter() {
+                if rule.matches(course_id, course_name, languages, event)? {
+                    included = true;
+                    break;
+                }
+            }
+            if !included {
+                return Ok(false);
+            }
+        }
+        for rule in self.exclude.iter() {
+            if rule.matches(course_id, course_name, languages, event)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl Rule {
+    /// Whether `event` of a course taught in `languages` (ISO 639-1 codes) matches the rule.
+    pub fn matches(
+        &self,
+        course_id: &str,
+        course_name: &str,
+        languages: &[&str],
+        event: &CourseEvent,
+    ) -> Result<bool, StdError> {
+        if let Some(pattern) = &self.course {
+            if !glob_matches(&pattern.to_lowercase(), &course_name.to_lowercase()) {
+                return Ok(false);
+            }
+        }
+        if let Some(id) = &self.id {
+            if id != course_id {
+                return Ok(false);
+            }
+        }
+        if let Some(language) = &self.language {
+            let code = language_code(language).unwrap_or(language.as_str());
+            if !languages.contains(&code) {
+                return Ok(false);
+            }
+        }
+        if let Some(part) = &self.part {
+            let matches = event
+                .part
+                .as_ref()
+                .map(|label| normalize_part(label) == normalize_part(part))
+                .unwrap_or(false);
+            if !matches {
+                return Ok(false);
+            }
+        }
+
+        if let (Some(group), Some(label)) = (&self.group, &event.group) {
+            let (group, label) = (group.trim().to_lowercase(), label.to_lowercase());
+            if label != group && !label.ends_with(&format!(" {}", group)) {
+                return Ok(false);
+            }
+        }
+
+        let start = event.timespan.start.naive_local();
+        if let Some(from) = self.from {
+            if start.date() < from {
+                return Ok(false);
+            }
+        }
+        if let Some(until) = self.until {
+            if start.date() > until {
+                return Ok(false);
+            }
+        }
+        if let Some(weekday) = &self.weekday {
+            if parse_weekday(weekday)? != start.weekday() {
+                return Ok(false);
+            }
+        }
+        if let Some(time) = &self.time {
+            let (from, until) = parse_time_range(time)?;
+            let start_time = start.time();
+            let matches = match until {
+                Some(until) => start_time >= from && start_time < until,
+                None => start_time.hour() == from.hour() && start_time.minute() == from.minute(),
+            };
+            if !matches {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Parse an English or German weekday name or abbreviation, e.g. "Wed", "We" or "Mi".
+pub fn parse_weekday(text: &str) -> Result<Weekday, StdError> {
+    let weekday = match text.trim().to_lowercase().as_str() {
+        "mo" | "montag" => Weekday::Mon,
+        "di" | "tu" | "dienstag" => Weekday::Tue,
+        "mi" | "we" | "mittwoch" => Weekday::Wed,
+        "do" | "th" | "donnerstag" => Weekday::Thu,
+        "fr" | "freitag" => Weekday::Fri,
+        "sa" | "samstag" => Weekday::Sat,
+        "so" | "su" | "sonntag" => Weekday::Sun,
+        other => other.parse::<Weekday>().map_err(|_| Error::InvalidRule {
+            message: format!("unknown weekday \"{}\"", text),
+        })?,
+    };
+
+    Ok(weekday)
+}
+
+/// Canonical name of a course part, so abbreviations match the labels of the VV.
+fn normalize_part(text: &str) -> String {
+    let text = text.trim().to_lowercase();
+    match text.trim_end_matches('.') {
+        "vl" | "v" | "vorlesung" | "lecture" => "vorlesung".to_owned(),
+        "ü" | "ue" | "übung" | "uebung" | "exercise" => "übung".to_owned(),
+        "tut" | "tutorium" | "tutorial" => "tutorium".to_owned(),
+        "s" | "sem" | "seminar" => "seminar".to_owned(),
+        "p" | "pr" | "praktikum" | "practical" => "praktikum".to_owned(),
+        _ => text,
+    }
+}
+
+fn parse_time_range(text: &str) -> Result<(NaiveTime, Option<NaiveTime>), StdError> {
+    let parse = |time: &str| {
+        NaiveTime::parse_from_str(time.trim(), "%R").map_err(|_| Error::InvalidRule {
+            message: format!("invalid time \"{}\"", text),
+        })
+    };
+
+    let mut split = text.splitn(2, '-');
+    let from = parse(split.next().unwrap_or(""))?;
+    let until = match split.next() {
+        Some(until) => Some(parse(until)?),
+        None => None,
+    };
+
+    Ok((from, until))
+}
+
+/// Match `text` against a pattern with `*` and `?` wildcards.
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    // Position in the pattern after the last `*`, and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p + 1, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last `*` swallow one more character
+            backtrack = Some((star_p, star_t + 1));
+            p = star_p;
+            t = star_t + 1;
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registration::Registration;
+    use crate::timespan_from_parts;
+
+    /// Session of the "Analysis I" lecture on Monday, 14.10.2019.
+    fn session(id: &str, day: &str, start: &str, end: &str) -> CourseEvent {
+        CourseEvent {
+            id: id.to_owned(),
+            timespan: timespan_from_parts(day, start, end).unwrap(),
+            sequence: 0,
+            week: None,
+            part: Some("Vorlesung".to_owned()),
+            remark: None,
+            room: None,
+            lecturers: vec![],
+            detail_url: None,
+            exam: false,
+            group: None,
+            summary: None,
+            all_day: false,
+        }
+    }
+
+    fn course() -> Course {
+        let mut exercise = session("500002", "16.10.2019", "14:00", "16:00");
+        exercise.part = Some("Übung".to_owned());
+        exercise.group = Some("Gruppe B".to_owned());
+        let mut other_group = session("500003", "18.10.2019", "08:00", "10:00");
+        other_group.part = Some("Übung".to_owned());
+        other_group.group = Some("Gruppe C".to_owned());
+        Course {
+            id: Some("10101".to_owned()),
+            name: "Analysis I".to_owned(),
+            kind: Some("Vorlesung".to_owned()),
+            events: vec![
+                session("500001", "14.10.2019", "10:00", "12:00"),
+                exercise,
+                other_group,
+                session("500004", "10.02.2020", "10:00", "12:00"),
+            ],
+            cancelled: vec![],
+            url: None,
+            color: None,
+            sws: None,
+            ects: None,
+            modules: vec![],
+            language: Some("Deutsch/Englisch".to_owned()),
+            registration: Registration::default(),
+            warnings: vec![],
+        }
+    }
+
+    /// Ids of the sessions of `course` left by `rules`.
+    fn selected(rules: &Rules) -> Vec<String> {
+        let mut course = course();
+        rules.apply("10101", &mut course).unwrap();
+        course.events.into_iter().map(|event| event.id).collect()
+    }
+
+    fn rule(toml: &str) -> Rule {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_matches("*übung*", "analysis i übung"));
+        assert!(glob_matches("analysis ?", "analysis i"));
+        assert!(glob_matches("a*s*s", "analysis"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("analysis ?", "analysis ii"));
+        assert!(!glob_matches("*übung", "übung 1"));
+    }
+
+    #[test]
+    fn parses_weekdays() {
+        for text in &["Mi", "mittwoch", "We", "Wed", " wednesday "] {
+            assert_eq!(parse_weekday(text).unwrap(), Weekday::Wed, "{}", text);
+        }
+        assert!(parse_weekday("Mittwo").is_err());
+    }
+
+    #[test]
+    fn keeps_everything_without_rules() {
+        assert_eq!(
+            selected(&Rules::default()),
+            vec!["500001", "500002", "500003", "500004"]
+        );
+    }
+
+    #[test]
+    fn includes_and_excludes() {
+        let rules = Rules {
+            include: vec![rule("part = \"Ü\""), rule("weekday = \"Mo\"")],
+            exclude: vec![rule("time = \"08:00-10:00\"")],
+        };
+        assert_eq!(selected(&rules), vec!["500001", "500002", "500004"]);
+    }
+
+    #[test]
+    fn matches_fields() {
+        let cases = [
+            (
+                "course = \"analysis*\"",
+                vec!["500001", "500002", "500003", "500004"],
+            ),
+            ("course = \"*Übung*\"", vec![]),
+            (
+                "id = \"10101\"",
+                vec!["500001", "500002", "500003", "500004"],
+            ),
+            ("id = \"10102\"", vec![]),
+            ("part = \"VL\"", vec!["500001", "500004"]),
+            ("group = \"b\"", vec!["500001", "500002", "500004"]),
+            ("time = \"14:00\"", vec!["500002"]),
+            ("time = \"08:00-10:00\"", vec!["500003"]),
+            (
+                "from = \"2019-10-15\"\nuntil = \"2019-10-18\"",
+                vec!["500002", "500003"],
+            ),
+            (
+                "language = \"English\"",
+                vec!["500001", "500002", "500003", "500004"],
+            ),
+            ("language = \"fr\"", vec![]),
+        ];
+        for (toml, expected) in cases.iter() {
+            let rules = Rules {
+                include: vec![rule(toml)],
+                exclude: vec![],
+            };
+            assert_eq!(&selected(&rules), expected, "{}", toml);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let mut course = course();
+        let rules = Rules {
+            include: vec![rule("time = \"10 Uhr\"")],
+            exclude: vec![],
+        };
+        assert!(rules.apply("10101", &mut course).is_err());
+        assert!(Rules::weekdays(&["Mo", "Someday"]).is_err());
+    }
+
+    #[test]
+    fn shortcuts() {
+        assert_eq!(selected(&Rules::part("Übung")), vec!["500002", "500003"]);
+        assert_eq!(
+            selected(&Rules::group("Gruppe C")),
+            vec!["500001", "500003", "500004"]
+        );
+        assert_eq!(
+            selected(&Rules::weekdays(&["Mi", "Fr"]).unwrap()),
+            vec!["500002", "500003"]
+        );
+        let until = NaiveDate::from_ymd(2019, 12, 31);
+        assert_eq!(
+            selected(&Rules::date_range(None, Some(until))),
+            vec!["500001", "500002", "500003"]
+        );
+        assert!(selected(&Rules::language("Französisch")).is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+//! Free text from the VV is arbitrary, so everything that ends up in a text property of a
+//! generated calendar goes through `text`, and written calendars through `fold`.
+
+use ics::escape_text;
+
+/// Maximum length of a content line in octets, without the line break
+pub(crate) const MAX_LINE_OCTETS: usize = 75;
+
+/// `value` cleaned up and escaped for a TEXT property value.
+///
+/// Line breaks are normalized, other control characters dropped and runs of spaces collapsed,
+/// then backslashes, commas, semicolons and line breaks are escaped.
+pub fn text(value: &str) -> String {
+    escape_text(clean(value)).into_owned()
+}
+
+fn clean(value: &str) -> String {
+    value
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .split('\n')
+        .map(|line| {
+            line.chars()
+                .map(|c| if c == '\t' { ' ' } else { c })
+                .filter(|c| !c.is_control())
+                .collect::<String>()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+/// Fold all content lines of `content` longer than 75 octets, without splitting characters.
+///
+/// Lines that are already short enough are kept as they are, so folding twice is harmless.
+pub fn fold(content: &str) -> String {
+    let mut folded = String::with_capacity(content.len());
+    for line in content.split_terminator("\r\n") {
+        let mut octets = 0;
+        for c in line.chars() {
+            if octets + c.len_utf8() > MAX_LINE_OCTETS {
+                folded.push_str("\r\n ");
+                // The space starting the continuation line counts towards its length
+                octets = 1;
+            }
+            folded.push(c);
+            octets += c.len_utf8();
+        }
+        folded.push_str("\r\n");
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleans_and_escapes_text() {
+        assert_eq!(
+            text("  Raum 1.2; Gebäude A,\tFlügel\\B \r\nSprechstunde\rnach\u{7} Vereinbarung  "),
+            r"Raum 1.2\; Gebäude A\, Flügel\\B\nSprechstunde\nnach Vereinbarung"
+        );
+        assert_eq!(text("\r\n \t"), "");
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let long = format!("DESCRIPTION:{}", "x".repeat(100));
+        let folded = fold(&format!("BEGIN:VEVENT\r\n{}\r\nEND:VEVENT\r\n", long));
+        let lines = folded.split_terminator("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1].len(), MAX_LINE_OCTETS);
+        assert!(lines[2].starts_with(' '));
+        assert_eq!(format!("{}{}", lines[1], &lines[2][1..]), long);
+    }
+
+    #[test]
+    fn folds_without_splitting_characters() {
+        let long = format!("SUMMARY:{}", "ä".repeat(60));
+        let folded = fold(&format!("{}\r\n", long));
+        let lines = folded.split_terminator("\r\n").collect::<Vec<_>>();
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_OCTETS));
+        let continued = lines[1..].iter().map(|line| &line[1..]).collect::<String>();
+        assert_eq!(lines[0].to_owned() + &continued, long);
+    }
+
+    #[test]
+    fn folding_twice_is_harmless() {
+        let content = format!(
+            "SUMMARY:{}\r\nUID:1@vv.fu-berlin.de\r\n",
+            "Übung ".repeat(30)
+        );
+        let folded = fold(&content);
+        assert_ne!(folded, content);
+        assert_eq!(fold(&folded), folded);
+    }
+}
@@ -0,0 +1,59 @@
+//! Landmarks of the course page layout the parsers rely on.
+//!
+//! When the FU changes its HTML, parsing fails somewhere deep down or yields no sessions at all.
+//! `check` tells which of the expected landmarks is gone, with an excerpt of the page to compare.
+
+use crate::{find_timespan, Error};
+use select::document::Document;
+use select::node::Node;
+use select::predicate::{Class, Name, Predicate};
+
+/// Maximum length of page excerpts in error messages
+const SNIPPET_CHARS: usize = 400;
+
+/// Verify that `html` still has the structure of a VV course page.
+pub fn check(html: &str) -> Result<(), Error> {
+    let document = Document::from(html);
+    let page = document
+        .find(Name("body"))
+        .next()
+        .or_else(|| document.nth(0));
+
+    if document
+        .find(Class("subc").descendant(Name("h1")))
+        .next()
+        .is_none()
+    {
+        let context = document.find(Class("subc")).next().or(page);
+        return Err(drift(".subc h1", context));
+    }
+
+    let sessions = document.find(Class("link_to_details")).collect::<Vec<_>>();
+    if sessions.is_empty() {
+        // Courses without published dates have no sessions either, but also no dates
+        let text = page.map(|page| page.text()).unwrap_or_default();
+        if find_timespan(&text).is_some() {
+            return Err(drift(".link_to_details", page));
+        }
+        return Ok(());
+    }
+
+    if let Some(session) = sessions
+        .iter()
+        .find(|session| session.find(Class("course_date_time")).next().is_none())
+    {
+        return Err(drift(".link_to_details .course_date_time", Some(*session)));
+    }
+
+    Ok(())
+}
+
+fn drift(selector: &'static str, context: Option<Node>) -> Error {
+    let html = context.map(|node| node.html()).unwrap_or_default();
+    let html = html.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut snippet = html.chars().take(SNIPPET_CHARS).collect::<String>();
+    if html.chars().count() > SNIPPET_CHARS {
+        snippet.push('…');
+    }
+    Error::PageStructureChanged { selector, snippet }
+}
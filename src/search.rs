@@ -0,0 +1,58 @@
+//! The course search of the VV.
+
+use crate::fetch::{self, Locale};
+use crate::lecturer::course_id_from_link;
+use crate::{Error, StdError};
+use select::document::Document;
+use select::predicate::Name;
+
+/// A course found by the VV search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub id: String,
+    /// Text of the link to the course, usually its LV number and name
+    pub title: String,
+    /// Text of the search result around the link, e.g. with the LV number and lecturers
+    pub context: String,
+}
+
+/// Courses of `semester` the VV search finds for `query` in the VV in `locale`, in the order of
+/// the results.
+pub async fn search(
+    query: &str,
+    semester: &str,
+    locale: Locale,
+) -> Result<Vec<SearchResult>, StdError> {
+    let url = fetch::search_url(query, semester, locale);
+    let (status, body) = fetch::get(&url).await?;
+    if !status.is_success() {
+        return Err(Error::HttpRequestError {
+            url,
+            status: status.as_u16(),
+        }
+        .into());
+    }
+
+    let document = Document::from(body.as_str());
+    let mut results: Vec<SearchResult> = vec![];
+    for link in document.find(Name("a")) {
+        let id = match link.attr("href").and_then(course_id_from_link) {
+            Some(id) => id,
+            None => continue,
+        };
+        if results.iter().any(|result| result.id == id) {
+            continue;
+        }
+        let title = normalize_whitespace(&link.text());
+        let context = match link.parent() {
+            Some(parent) => normalize_whitespace(&parent.text()),
+            None => title.clone(),
+        };
+        results.push(SearchResult { id, title, context });
+    }
+    Ok(results)
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,129 @@
+//! Shareable course selections, so e.g. a study group can pass around its timetable setup.
+//!
+//! A selection is the compact JSON of the selected courses, base64-encoded behind a version
+//! prefix (`fuvv1:…`). `.fuvv` files contain just that token.
+
+use crate::config::Config;
+use crate::{Error, StdError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const TOKEN_PREFIX: &str = "fuvv1:";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Selection {
+    /// Default semester of the courses
+    #[serde(rename = "s", default, skip_serializing_if = "Option::is_none")]
+    pub semester: Option<String>,
+    #[serde(rename = "c")]
+    pub courses: Vec<SelectedCourse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectedCourse {
+    pub id: String,
+    #[serde(rename = "s", default, skip_serializing_if = "Option::is_none")]
+    pub semester: Option<String>,
+}
+
+impl Selection {
+    /// The courses configured in `config`.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            semester: config.semester.clone(),
+            courses: config
+                .courses
+                .iter()
+                .map(|course| SelectedCourse {
+                    id: course.id.clone(),
+                    semester: course.semester.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_token(&self) -> Result<String, StdError> {
+        let json = serde_json::to_vec(self)?;
+        Ok(format!(
+            "{}{}",
+            TOKEN_PREFIX,
+            base64::encode_config(&json, base64::URL_SAFE_NO_PAD)
+        ))
+    }
+
+    pub fn from_token(token: &str) -> Result<Self, StdError> {
+        let invalid = |message: &str| Error::InvalidSelection {
+            message: message.to_owned(),
+        };
+
+        let token = token.trim();
+        if !token.starts_with(TOKEN_PREFIX) {
+            return Err(invalid("not a fu-vv-cal selection").into());
+        }
+        let json = base64::decode_config(&token[TOKEN_PREFIX.len()..], base64::URL_SAFE_NO_PAD)
+            .map_err(|err| invalid(&err.to_string()))?;
+        Ok(serde_json::from_slice(&json).map_err(|err| invalid(&err.to_string()))?)
+    }
+
+    /// Read a selection from a `.fuvv` file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, StdError> {
+        Self::from_token(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), StdError> {
+        std::fs::write(path, format!("{}\n", self.to_token()?))?;
+        Ok(())
+    }
+
+    /// Append the courses of this selection that aren't configured yet to the config file at
+    /// `config_path`, returning the added courses.
+    ///
+    /// The file is extended rather than rewritten, so comments and formatting are kept.
+    pub fn append_to_config<P: AsRef<Path>>(
+        &self,
+        config_path: P,
+    ) -> Result<Vec<SelectedCourse>, StdError> {
+        let config_path = config_path.as_ref();
+        let (mut contents, existing) = if config_path.exists() {
+            let contents = std::fs::read_to_string(config_path)?;
+            let existing = Self::from_config(&toml::from_str(&contents)?);
+            (contents, existing)
+        } else {
+            (String::new(), Selection::default())
+        };
+
+        let mut added = vec![];
+        for course in self.courses.iter() {
+            // Resolve the default semesters, so courses keep theirs in the other config
+            let semester = course.semester.clone().or_else(|| self.semester.clone());
+            let already_configured = existing.courses.iter().any(|configured| {
+                configured.id == course.id
+                    && configured
+                        .semester
+                        .as_ref()
+                        .or_else(|| existing.semester.as_ref())
+                        == semester.as_ref()
+            });
+            if already_configured {
+                continue;
+            }
+
+            if !contents.is_empty() && !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+            contents.push_str(&format!("\n[[course]]\nid = {:?}\n", course.id));
+            if let Some(semester) = &semester {
+                contents.push_str(&format!("semester = {:?}\n", semester));
+            }
+            added.push(SelectedCourse {
+                id: course.id.clone(),
+                semester,
+            });
+        }
+
+        if !added.is_empty() {
+            std::fs::write(config_path, contents)?;
+        }
+        Ok(added)
+    }
+}
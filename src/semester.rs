@@ -0,0 +1,115 @@
+use crate::weeks::week_start;
+use chrono::{Duration, NaiveDate};
+
+/// A semester of the FU academic calendar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Semester {
+    /// e.g. "WiSe 2019/20"
+    pub name: &'static str,
+    /// `sm` parameter of the VV, if known
+    pub vv_id: Option<&'static str>,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub lecture_start: NaiveDate,
+    pub lecture_end: NaiveDate,
+    /// Lecture-free periods within the lecture period (inclusive)
+    pub breaks: Vec<(NaiveDate, NaiveDate)>,
+    /// Period for re-registering (Rückmeldung) for the following semester (inclusive)
+    pub reregistration: Option<(NaiveDate, NaiveDate)>,
+}
+
+impl Semester {
+    /// All semesters with known dates, oldest first.
+    pub fn all() -> Vec<Semester> {
+        vec![
+            Semester {
+                name: "SoSe 2019",
+                vv_id: None,
+                start: date(2019, 4, 1),
+                end: date(2019, 9, 30),
+                lecture_start: date(2019, 4, 15),
+                lecture_end: date(2019, 7, 20),
+                breaks: vec![],
+                reregistration: Some((date(2019, 6, 17), date(2019, 7, 15))),
+            },
+            Semester {
+                name: "WiSe 2019/20",
+                vv_id: Some("498562"),
+                start: date(2019, 10, 1),
+                end: date(2020, 3, 31),
+                lecture_start: date(2019, 10, 14),
+                lecture_end: date(2020, 2, 15),
+                breaks: vec![(date(2019, 12, 23), date(2020, 1, 4))],
+                reregistration: Some((date(2020, 1, 20), date(2020, 2, 14))),
+            },
+        ]
+    }
+
+    /// The semester with the given VV `sm` parameter.
+    pub fn by_vv_id(vv_id: &str) -> Option<Semester> {
+        Self::all()
+            .into_iter()
+            .find(|semester| semester.vv_id == Some(vv_id))
+    }
+
+    /// Human-readable label of the semester with the given VV `sm` parameter, its name if the
+    /// semester is known and the parameter itself otherwise.
+    pub fn label(vv_id: &str) -> String {
+        Self::by_vv_id(vv_id)
+            .map(|semester| semester.name.to_owned())
+            .unwrap_or_else(|| vv_id.to_owned())
+    }
+
+    /// The semester that `date` belongs to.
+    pub fn containing(date: NaiveDate) -> Option<Semester> {
+        Self::all()
+            .into_iter()
+            .find(|semester| semester.contains(date))
+    }
+
+    /// First and last day of lectures.
+    pub fn lecture_period(&self) -> (NaiveDate, NaiveDate) {
+        (self.lecture_start, self.lecture_end)
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        date >= self.start && date <= self.end
+    }
+
+    /// Whether lectures take place on `date`, i.e. it is in the lecture period but not in a break.
+    pub fn is_lecture_day(&self, date: NaiveDate) -> bool {
+        date >= self.lecture_start
+            && date <= self.lecture_end
+            && !self
+                .breaks
+                .iter()
+                .any(|(from, until)| date >= *from && date <= *until)
+    }
+
+    /// Lecture week of `date`, counted from 1 and skipping weeks that are entirely lecture-free.
+    ///
+    /// Dates outside of lectures have no week.
+    pub fn week_of(&self, date: NaiveDate) -> Option<u32> {
+        if !self.is_lecture_day(date) {
+            return None;
+        }
+
+        let mut week = 0;
+        let mut monday = week_start(self.lecture_start);
+        while monday <= date {
+            let has_lectures = (0..7)
+                .map(|offset| monday + Duration::days(offset))
+                .any(|day| self.is_lecture_day(day));
+            if has_lectures {
+                week += 1;
+            }
+            monday = monday + Duration::weeks(1);
+        }
+
+        Some(week)
+    }
+}
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd(year, month, day)
+}
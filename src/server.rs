@@ -0,0 +1,686 @@
+use crate::atom;
+use crate::busy;
+use crate::changes::{Change, Snapshot};
+use crate::fetch::{self, Locale};
+use crate::merge;
+use crate::metrics;
+use crate::model::Courses;
+use crate::options::CalendarOptions;
+use crate::sanitize;
+use crate::shutdown;
+use crate::source::FuVv;
+use crate::{stable_hash, Course, RequestedCourse, StdError};
+use chrono::{DateTime, Utc};
+use chrono_tz::Europe::Berlin;
+use hyper::header::{HeaderValue, CONTENT_TYPE, ETAG, IF_NONE_MATCH, WWW_AUTHENTICATE};
+use hyper::server::conn::Http;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use ics::components::{Parameter, Property};
+use ics::properties::{Description, Summary};
+use ics::Event;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::timer::{delay, Timeout};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, error, info};
+
+pub mod auth;
+mod caldav;
+pub mod tls;
+
+use auth::Access;
+
+/// Number of changes kept per course for the Atom feed
+const MAX_CHANGES: usize = 100;
+/// Number of courses whose history is kept, the least recently fetched ones are dropped first
+const MAX_HISTORIES: usize = 500;
+/// Maximum length of course ids and semesters in requests
+const MAX_PARAM_LEN: usize = 32;
+/// Maximum number of courses of a calendar under `/ics`, so one request can't crawl the VV
+const MAX_MERGED_COURSES: usize = 20;
+/// Time open HTTPS connections get to finish their requests on shutdown
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
+/// How long the result of a readiness check is reused, so probes don't hammer the VV
+const READINESS_TTL: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    /// Change history of the courses served so far, keyed by `{id}?sm={semester}`. Only courses
+    /// that could be fetched get an entry, at most `MAX_HISTORIES`.
+    static ref HISTORY: Mutex<HashMap<String, CourseHistory>> = Mutex::new(HashMap::new());
+    /// Time and error of the last readiness check, see `READINESS_TTL`
+    static ref READINESS: Mutex<Option<(Instant, Option<String>)>> = Mutex::new(None);
+}
+
+/// What is served, replaced while serving when the config changes (see `reload`).
+#[derive(Debug, Clone, Default)]
+pub struct ServedCourses(Arc<Mutex<Arc<Served>>>);
+
+#[derive(Debug, Default)]
+pub struct Served {
+    /// The configured courses
+    pub courses: Vec<RequestedCourse>,
+    /// `merged_name` of the config
+    pub name: String,
+    /// Language of the VV pages of the courses requested by URL
+    pub locale: Locale,
+    /// Options of the calendars of the courses requested by URL
+    pub options: CalendarOptions,
+    /// Parse the pages of the courses requested by URL leniently, see `lenient`
+    pub lenient: bool,
+}
+
+impl ServedCourses {
+    pub fn new(served: Served) -> Self {
+        Self(Arc::new(Mutex::new(Arc::new(served))))
+    }
+
+    /// Serve `served` from the next request on.
+    pub fn replace(&self, served: Served) {
+        *self.0.lock().unwrap() = Arc::new(served);
+    }
+
+    fn current(&self) -> Arc<Served> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[derive(Debug)]
+struct CourseHistory {
+    /// Time of the last successful fetch, to drop the least recently fetched courses first
+    fetched: Instant,
+    snapshot: Option<Snapshot>,
+    /// Changes with the time they were detected, oldest first
+    changes: Vec<(DateTime<Utc>, Change)>,
+    /// Last successfully fetched version of the course, served while the VV is unreachable
+    course: Option<Course>,
+    /// Time of the first failed fetch since the last successful one
+    stale_since: Option<DateTime<Utc>>,
+}
+
+enum Route {
+    /// `/course/{id}.ics?sm={semester}`
+    Calendar(RequestedCourse),
+    /// `/course/{id}/changes.atom?sm={semester}`
+    Changes(RequestedCourse),
+    /// `/course/{id}.json?sm={semester}`
+    Data(RequestedCourse),
+    /// `/ics?courses={id},{id}&sm={semester}`
+    Merged(Vec<RequestedCourse>),
+}
+
+/// Serve course calendars under `/course/{id}.ics?sm={semester}`, Atom feeds of their changes
+/// under `/course/{id}/changes.atom?sm={semester}` and the parsed courses (see `model`) under
+/// `/course/{id}.json?sm={semester}`. `/ics?courses={id},{id}&sm={semester}` merges any set of
+/// courses into one calendar, e.g. for personal subscriptions against a shared instance.
+///
+/// Changes are detected between consecutive requests for a course and only kept in memory. If a
+/// course can't be fetched, its last good version is served with an `X-Feed-Stale-Since` header
+/// and a note event.
+///
+/// `courses` (the configured ones, see `ServedCourses`) are additionally served as a read-only
+/// CalDAV calendar under `/caldav/`, and
+/// Prometheus metrics under `/metrics`. `/healthz` answers as long as the process runs, `/readyz`
+/// only while the VV can be reached.
+///
+/// Requests not granted by `access` are answered with `401 Unauthorized`, see `auth`. With `tls`
+/// (see `tls::acceptor`) the server speaks HTTPS instead of HTTP.
+///
+/// Returns once shutdown is requested (see `shutdown`) and the requests received until then are
+/// answered.
+pub async fn serve(
+    addr: SocketAddr,
+    courses: ServedCourses,
+    access: Access,
+    tls: Option<TlsAcceptor>,
+) -> Result<(), StdError> {
+    let access = Arc::new(access);
+    if let Some(acceptor) = tls {
+        return serve_tls(addr, acceptor, courses, access).await;
+    }
+    let make_service = make_service_fn(move |_| {
+        let courses = courses.clone();
+        let access = access.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
+                handle(req, courses.clone(), access.clone())
+            }))
+        }
+    });
+
+    let server = Server::bind(&addr)
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown::signal());
+    info!(%addr, "listening on http");
+    server.await?;
+
+    Ok(())
+}
+
+/// Accept connections on `addr` and serve each over TLS, like `serve` does over plain HTTP.
+async fn serve_tls(
+    addr: SocketAddr,
+    acceptor: TlsAcceptor,
+    courses: ServedCourses,
+    access: Arc<Access>,
+) -> Result<(), StdError> {
+    let mut listener = TcpListener::bind(&addr)?;
+    info!(%addr, "listening on https");
+    let open_connections = Arc::new(AtomicUsize::new(0));
+    while !shutdown::requested() {
+        // Accepting is interrupted regularly to notice a requested shutdown
+        let (stream, peer) = match Timeout::new(listener.accept(), shutdown::POLL_INTERVAL).await {
+            Ok(accepted) => accepted?,
+            Err(_) => continue,
+        };
+        let acceptor = acceptor.clone();
+        let courses = courses.clone();
+        let access = access.clone();
+        let open_connections = open_connections.clone();
+        open_connections.fetch_add(1, Ordering::SeqCst);
+        tokio::spawn(async move {
+            serve_connection(stream, peer, acceptor, courses, access).await;
+            open_connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+
+    drop(listener);
+    let deadline = Instant::now() + SHUTDOWN_GRACE;
+    while open_connections.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+        delay(Instant::now() + shutdown::POLL_INTERVAL).await;
+    }
+    Ok(())
+}
+
+async fn serve_connection(
+    stream: TcpStream,
+    peer: SocketAddr,
+    acceptor: TlsAcceptor,
+    courses: ServedCourses,
+    access: Arc<Access>,
+) {
+    // Failed handshakes are common (scanners, clients rejecting the certificate) and only
+    // affect their own connection
+    let stream = match acceptor.accept(stream).await {
+        Ok(stream) => stream,
+        Err(err) => {
+            debug!(%peer, %err, "TLS handshake failed");
+            return;
+        }
+    };
+    let service = service_fn(move |req| handle(req, courses.clone(), access.clone()));
+    if let Err(err) = Http::new().serve_connection(stream, service).await {
+        debug!(%peer, %err, "connection failed");
+    }
+}
+
+async fn handle(
+    req: Request<Body>,
+    courses: ServedCourses,
+    access: Arc<Access>,
+) -> Result<Response<Body>, hyper::Error> {
+    let started = Instant::now();
+    let kind = route_kind(req.uri().path());
+    let response = if access.allows(&req) {
+        respond(req, courses.current()).await?
+    } else {
+        unauthorized(req.uri().path())
+    };
+    metrics::record_request(kind, response.status().as_u16(), started.elapsed());
+    Ok(response)
+}
+
+async fn respond(req: Request<Body>, served: Arc<Served>) -> Result<Response<Body>, hyper::Error> {
+    let path = req.uri().path();
+    if path.starts_with(caldav::PREFIX) || (path == "/" && req.method() != Method::GET) {
+        return Ok(caldav::handle(req, &served.courses).await);
+    }
+    if req.method() != Method::GET {
+        return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    if path == "/metrics" {
+        let mut response = Response::new(Body::from(metrics::render()));
+        response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4"),
+        );
+        return Ok(response);
+    }
+    if path == "/healthz" {
+        return Ok(text_response(StatusCode::OK, "ok".to_owned()));
+    }
+    if path == "/readyz" {
+        return Ok(match readiness().await {
+            None => text_response(StatusCode::OK, "ok".to_owned()),
+            Some(err) => text_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                format!("VV unreachable: {}", err),
+            ),
+        });
+    }
+    info!(method = req.method().as_str(), path, "request");
+    let route = match route(&req, &served) {
+        Some(route) => route,
+        None => return Ok(status_response(StatusCode::NOT_FOUND)),
+    };
+    let requested = match &route {
+        Route::Calendar(requested) | Route::Changes(requested) | Route::Data(requested) => {
+            requested
+        }
+        Route::Merged(courses) => {
+            return Ok(
+                match merged_calendar(&served.name, courses, &served.options).await {
+                    Ok((body, stale_since)) => {
+                        let mut response =
+                            cached_response(&req, "text/calendar; charset=utf-8", body);
+                        mark_stale(&mut response, stale_since);
+                        response
+                    }
+                    Err(status) => status_response(status),
+                },
+            );
+        }
+    };
+
+    let (mut course, changes, stale_since) = match requested.get_course().await {
+        Ok(course) => {
+            let changes = record_changes(requested, &course);
+            (course, changes, None)
+        }
+        Err(err) => {
+            error!(course = requested.id.as_str(), %err, "failed to fetch course");
+            match last_good(requested) {
+                Some(stale) => stale,
+                None => return Ok(status_response(StatusCode::BAD_GATEWAY)),
+            }
+        }
+    };
+    busy::apply(&mut course, &requested.options);
+
+    let (content_type, body) = match &route {
+        Route::Calendar(_) => match course.to_ical(&requested.options) {
+            Ok(mut calendar) => {
+                if let Some(since) = stale_since {
+                    calendar.add_event(stale_note(requested, since));
+                }
+                (
+                    "text/calendar; charset=utf-8",
+                    sanitize::fold(&calendar.to_string()),
+                )
+            }
+            Err(err) => {
+                error!(course = requested.id.as_str(), %err, "failed to generate calendar");
+                return Ok(status_response(StatusCode::INTERNAL_SERVER_ERROR));
+            }
+        },
+        Route::Changes(_) => {
+            let feed_id = format!(
+                "urn:fu-vv-cal:course:{}:{}",
+                requested.id, requested.semester
+            );
+            let title = format!("Changes of {}", course.name);
+            (
+                "application/atom+xml; charset=utf-8",
+                atom::changes_feed(&title, &feed_id, &changes),
+            )
+        }
+        Route::Data(_) => match Courses::new(vec![course]).to_json() {
+            Ok(json) => ("application/json; charset=utf-8", json),
+            Err(err) => {
+                error!(course = requested.id.as_str(), %err, "failed to serialize course");
+                return Ok(status_response(StatusCode::INTERNAL_SERVER_ERROR));
+            }
+        },
+        Route::Merged(_) => unreachable!("merged calendars are served by merged_calendar"),
+    };
+
+    let mut response = cached_response(&req, content_type, body);
+    mark_stale(&mut response, stale_since);
+    Ok(response)
+}
+
+/// One calendar named `name` of `requested_courses` with `options`, each course falling back to
+/// its last good version like `/course/{id}.ics`, and the time since which it is stale.
+async fn merged_calendar(
+    name: &str,
+    requested_courses: &[RequestedCourse],
+    options: &CalendarOptions,
+) -> Result<(String, Option<DateTime<Utc>>), StatusCode> {
+    let mut courses = vec![];
+    let mut notes = vec![];
+    let mut stale_since: Option<DateTime<Utc>> = None;
+    for requested in requested_courses {
+        let mut course = match requested.get_course().await {
+            Ok(course) => {
+                record_changes(requested, &course);
+                course
+            }
+            Err(err) => {
+                error!(course = requested.id.as_str(), %err, "failed to fetch course");
+                let (course, _, since) = match last_good(requested) {
+                    Some(stale) => stale,
+                    None => return Err(StatusCode::BAD_GATEWAY),
+                };
+                if let Some(since) = since {
+                    notes.push(stale_note(requested, since));
+                    stale_since = Some(stale_since.map_or(since, |earlier| earlier.min(since)));
+                }
+                course
+            }
+        };
+        busy::apply(&mut course, options);
+        courses.push((requested.id.clone(), course));
+    }
+
+    let (mut calendar, _) = merge::merge_courses(name, courses, options);
+    for note in notes {
+        calendar.add_event(note);
+    }
+    Ok((sanitize::fold(&calendar.to_string()), stale_since))
+}
+
+/// Add the `X-Feed-Stale-Since` header if the response is made of outdated data.
+fn mark_stale(response: &mut Response<Body>, stale_since: Option<DateTime<Utc>>) {
+    if let Some(since) = stale_since {
+        let header = since.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        if let Ok(value) = HeaderValue::from_str(&header) {
+            response.headers_mut().insert("X-Feed-Stale-Since", value);
+        }
+    }
+}
+
+/// Record the changes of `course` since the previous request for it, returning all recorded
+/// changes.
+fn record_changes(requested: &RequestedCourse, course: &Course) -> Vec<(DateTime<Utc>, Change)> {
+    let mut snapshot = Snapshot::new();
+    snapshot.insert(&requested.id, course);
+
+    let key = format!("{}?sm={}", requested.id, requested.semester);
+    let mut histories = HISTORY.lock().unwrap();
+    if !histories.contains_key(&key) && histories.len() >= MAX_HISTORIES {
+        let oldest = histories
+            .iter()
+            .min_by_key(|(_, history)| history.fetched)
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            histories.remove(&oldest);
+        }
+    }
+    let history = histories.entry(key).or_insert_with(|| CourseHistory {
+        fetched: Instant::now(),
+        snapshot: None,
+        changes: vec![],
+        course: None,
+        stale_since: None,
+    });
+    history.fetched = Instant::now();
+    if let Some(previous) = &history.snapshot {
+        let now = Utc::now();
+        history.changes.extend(
+            snapshot
+                .changes_since(previous)
+                .into_iter()
+                .map(|change| (now, change)),
+        );
+        if history.changes.len() > MAX_CHANGES {
+            let excess = history.changes.len() - MAX_CHANGES;
+            history.changes.drain(..excess);
+        }
+    }
+    history.snapshot = Some(snapshot);
+    history.course = Some(course.clone());
+    history.stale_since = None;
+
+    history.changes.clone()
+}
+
+/// The last successfully fetched version of a course with its recorded changes, and the time
+/// since which it is stale.
+fn last_good(
+    requested: &RequestedCourse,
+) -> Option<(Course, Vec<(DateTime<Utc>, Change)>, Option<DateTime<Utc>>)> {
+    let key = format!("{}?sm={}", requested.id, requested.semester);
+    let mut histories = HISTORY.lock().unwrap();
+    let history = histories.get_mut(&key)?;
+    let course = history.course.clone()?;
+    let stale_since = *history.stale_since.get_or_insert_with(Utc::now);
+
+    Some((course, history.changes.clone(), Some(stale_since)))
+}
+
+/// All-day event on the current day telling subscribers that the calendar is outdated.
+fn stale_note(requested: &RequestedCourse, since: DateTime<Utc>) -> Event<'static> {
+    let now = Utc::now();
+    let since = since
+        .with_timezone(&Berlin)
+        .format("%d.%m.%Y %H:%M")
+        .to_string();
+    let today = now.with_timezone(&Berlin).date();
+
+    let uid = format!("fu-vv-cal-stale-{}-{}", requested.id, requested.semester);
+    let mut event = Event::new(uid, now.format("%Y%m%dT%H%M%SZ").to_string());
+    let mut start = Property::new("DTSTART", today.format("%Y%m%d").to_string());
+    start.add(Parameter::new("VALUE", "DATE"));
+    event.push(start);
+    event.push(Summary::new(sanitize::text(&format!(
+        "Data may be outdated since {}",
+        since
+    ))));
+    event.push(Description::new(sanitize::text(
+        "The course page could not be fetched from the VV, this calendar shows the last known \
+         sessions.",
+    )));
+    event
+}
+
+fn route(req: &Request<Body>, served: &Served) -> Option<Route> {
+    let path = req.uri().path();
+    if path == "/ics" {
+        return merged_route(req, served);
+    }
+    if !path.starts_with("/course/") {
+        return None;
+    }
+    let rest = &path["/course/".len()..];
+
+    let (id, to_route): (_, fn(RequestedCourse) -> Route) = if rest.ends_with(".ics") {
+        (&rest[..rest.len() - ".ics".len()], Route::Calendar)
+    } else if rest.ends_with("/changes.atom") {
+        (&rest[..rest.len() - "/changes.atom".len()], Route::Changes)
+    } else if rest.ends_with(".json") {
+        (&rest[..rest.len() - ".json".len()], Route::Data)
+    } else {
+        return None;
+    };
+    if !is_valid_id(id) {
+        return None;
+    }
+
+    let semester = query_param(req, "sm").filter(|semester| is_valid_semester(semester))?;
+    Some(to_route(requested_course(id, &semester, served)))
+}
+
+/// Course `id` requested by URL, with the locale and the options of `served`.
+fn requested_course(id: &str, semester: &str, served: &Served) -> RequestedCourse {
+    let source = FuVv::new()
+        .with_locale(served.locale)
+        .with_lenient(served.lenient);
+    RequestedCourse::new(id, semester)
+        .with_source(Arc::new(source))
+        .with_options(served.options.clone())
+}
+
+/// `/ics?courses={id},{id}&sm={semester}`, with up to `MAX_MERGED_COURSES` distinct ids.
+fn merged_route(req: &Request<Body>, served: &Served) -> Option<Route> {
+    let semester = query_param(req, "sm").filter(|semester| is_valid_semester(semester))?;
+    let ids = query_param(req, "courses")?
+        .replace("%2C", ",")
+        .replace("%2c", ",");
+    let mut courses: Vec<RequestedCourse> = vec![];
+    for id in ids.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+        if !is_valid_id(id) {
+            return None;
+        }
+        if !courses.iter().any(|course| course.id == id) {
+            courses.push(requested_course(id, &semester, served));
+        }
+    }
+    if courses.is_empty() || courses.len() > MAX_MERGED_COURSES {
+        return None;
+    }
+
+    Some(Route::Merged(courses))
+}
+
+/// Why the VV can't be reached, or `None` if it can.
+async fn readiness() -> Option<String> {
+    if let Some(err) = recent_readiness() {
+        return err;
+    }
+    let err = match fetch::check_reachable().await {
+        Ok(()) => None,
+        Err(err) => {
+            error!(%err, "readiness check failed");
+            Some(err.to_string())
+        }
+    };
+    *READINESS.lock().unwrap() = Some((Instant::now(), err.clone()));
+    err
+}
+
+/// Result of the last readiness check, unless it is older than `READINESS_TTL`.
+fn recent_readiness() -> Option<Option<String>> {
+    match &*READINESS.lock().unwrap() {
+        Some((checked, err)) if checked.elapsed() < READINESS_TTL => Some(err.clone()),
+        _ => None,
+    }
+}
+
+/// Route label of `path` in the metrics.
+fn route_kind(path: &str) -> &'static str {
+    if path.starts_with(caldav::PREFIX) || path == "/" {
+        "caldav"
+    } else if path == "/metrics" {
+        "metrics"
+    } else if path == "/healthz" || path == "/readyz" {
+        "probe"
+    } else if path.starts_with("/course/") && path.ends_with(".ics") {
+        "calendar"
+    } else if path.starts_with("/course/") && path.ends_with("/changes.atom") {
+        "changes"
+    } else if path.starts_with("/course/") && path.ends_with(".json") {
+        "data"
+    } else if path == "/ics" {
+        "merged"
+    } else {
+        "other"
+    }
+}
+
+/// Whether `id` looks like a VV course id, so requests can't fill the history with arbitrary keys.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_PARAM_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Whether `semester` looks like the `sm` parameter of the VV, which is numeric.
+fn is_valid_semester(semester: &str) -> bool {
+    !semester.is_empty()
+        && semester.len() <= MAX_PARAM_LEN
+        && semester.chars().all(|c| c.is_ascii_digit())
+}
+
+fn query_param(req: &Request<Body>, name: &str) -> Option<String> {
+    req.uri()
+        .query()?
+        .split('&')
+        .filter_map(|pair| {
+            let mut split = pair.splitn(2, '=');
+            Some((split.next()?, split.next().unwrap_or("")))
+        })
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_owned())
+}
+
+/// Respond with `body`, or with `304 Not Modified` if the client already has this version.
+fn cached_response(
+    req: &Request<Body>,
+    content_type: &'static str,
+    body: String,
+) -> Response<Body> {
+    let etag = etag_for(&body);
+    let etag_header = HeaderValue::from_str(&etag).unwrap();
+
+    let not_modified = req
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| if_none_match_matches(value, &etag))
+        .unwrap_or(false);
+
+    let mut response = if not_modified {
+        status_response(StatusCode::NOT_MODIFIED)
+    } else {
+        let mut response = Response::new(Body::from(body));
+        response
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        response
+    };
+    response.headers_mut().insert(ETAG, etag_header);
+
+    response
+}
+
+/// Strong ETag derived from the generated content, stable across restarts and Rust versions.
+fn etag_for(content: &str) -> String {
+    format!("\"{:016x}\"", stable_hash(content))
+}
+
+/// Whether an `If-None-Match` header value matches `etag`.
+///
+/// Uses the weak comparison mandated for `If-None-Match`, i.e. `W/` prefixes are ignored.
+fn if_none_match_matches(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+fn text_response(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response
+}
+
+/// `401 Unauthorized`, asking CalDAV clients for a password and everyone else for a token.
+fn unauthorized(path: &str) -> Response<Body> {
+    let mut response = status_response(StatusCode::UNAUTHORIZED);
+    let challenge = if path.starts_with(caldav::PREFIX) || path == "/" {
+        "Basic realm=\"fu-vv-cal\""
+    } else {
+        "Bearer realm=\"fu-vv-cal\""
+    };
+    response
+        .headers_mut()
+        .insert(WWW_AUTHENTICATE, HeaderValue::from_static(challenge));
+    response
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
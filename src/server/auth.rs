@@ -0,0 +1,256 @@
+//! Access control of `serve`, for instances reachable from the internet.
+//!
+//! Without tokens and signing key the server is open, as before. Otherwise a request needs one of
+//! - a token, as `Authorization: Bearer {token}`, as the password of Basic auth (for CalDAV
+//!   clients) or as `token` query parameter (for calendar clients, which can't send headers)
+//! - a URL signed with the signing key (see `sign`), optionally expiring, so personal
+//!   subscription URLs can be handed out without sharing a token
+//!
+//! The probes `/healthz` and `/readyz` stay open.
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use hyper::header::AUTHORIZATION;
+use hyper::{Body, Request};
+use sha2::Sha256;
+
+/// Query parameter carrying the signature of a signed URL
+const SIGNATURE_PARAM: &str = "sig";
+/// Query parameter with the Unix time after which a signed URL is rejected
+const EXPIRES_PARAM: &str = "expires";
+/// Query parameter carrying a token
+const TOKEN_PARAM: &str = "token";
+
+/// Who may use the server, see the module documentation.
+#[derive(Debug, Clone, Default)]
+pub struct Access {
+    tokens: Vec<String>,
+    signing_key: Option<String>,
+}
+
+impl Access {
+    pub fn new(tokens: Vec<String>, signing_key: Option<String>) -> Self {
+        Self {
+            tokens: tokens
+                .into_iter()
+                .filter(|token| !token.trim().is_empty())
+                .collect(),
+            signing_key: signing_key.filter(|key| !key.is_empty()),
+        }
+    }
+
+    /// Whether every request is allowed.
+    pub fn is_open(&self) -> bool {
+        self.tokens.is_empty() && self.signing_key.is_none()
+    }
+
+    /// Whether `req` may be answered.
+    pub fn allows(&self, req: &Request<Body>) -> bool {
+        let path = req.uri().path();
+        if self.is_open() || path == "/healthz" || path == "/readyz" {
+            return true;
+        }
+        let query = req.uri().query().unwrap_or("");
+        let token = presented_token(req).or_else(|| query_value(query, TOKEN_PARAM));
+        if let Some(token) = token {
+            if self.tokens.iter().any(|known| same(known, &token)) {
+                return true;
+            }
+        }
+        match &self.signing_key {
+            Some(key) => is_signed(key, path, query),
+            None => false,
+        }
+    }
+}
+
+/// `path_and_query` (e.g. `/ics?courses=524870,525101&sm=498562`) signed with `key`, valid until
+/// the Unix time `expires` if given.
+pub fn sign(key: &str, path_and_query: &str, expires: Option<i64>) -> String {
+    let mut url = path_and_query.to_owned();
+    if let Some(expires) = expires {
+        url.push(if url.contains('?') { '&' } else { '?' });
+        url.push_str(&format!("{}={}", EXPIRES_PARAM, expires));
+    }
+    let signature = base64::encode_config(&mac(key, &url).result().code(), base64::URL_SAFE_NO_PAD);
+    url.push(if url.contains('?') { '&' } else { '?' });
+    url.push_str(&format!("{}={}", SIGNATURE_PARAM, signature));
+    url
+}
+
+/// Whether the URL made of `path` and `query` carries a valid, unexpired signature by `key`.
+fn is_signed(key: &str, path: &str, query: &str) -> bool {
+    let signature = match query_value(query, SIGNATURE_PARAM)
+        .and_then(|signature| base64::decode_config(&signature, base64::URL_SAFE_NO_PAD).ok())
+    {
+        Some(signature) => signature,
+        None => return false,
+    };
+    if let Some(expires) = query_value(query, EXPIRES_PARAM) {
+        match expires.parse::<i64>() {
+            Ok(expires) if expires >= Utc::now().timestamp() => {}
+            _ => return false,
+        }
+    }
+    // The signature covers everything before it
+    let signed_query = query
+        .split('&')
+        .filter(|pair| !pair.starts_with(&format!("{}=", SIGNATURE_PARAM)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let url = if signed_query.is_empty() {
+        path.to_owned()
+    } else {
+        format!("{}?{}", path, signed_query)
+    };
+    mac(key, &url).verify(&signature).is_ok()
+}
+
+fn mac(key: &str, message: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_varkey(key.as_bytes()).expect("HMAC takes keys of any size");
+    mac.input(message.as_bytes());
+    mac
+}
+
+/// Token of a Bearer or Basic `Authorization` header.
+fn presented_token(req: &Request<Body>) -> Option<String> {
+    let header = req.headers().get(AUTHORIZATION)?.to_str().ok()?.trim();
+    let mut parts = header.splitn(2, ' ');
+    let scheme = parts.next()?;
+    let credentials = parts.next()?.trim();
+    if scheme.eq_ignore_ascii_case("bearer") {
+        Some(credentials.to_owned())
+    } else if scheme.eq_ignore_ascii_case("basic") {
+        let decoded = String::from_utf8(base64::decode(credentials).ok()?).ok()?;
+        // The user name is ignored, the password is the token
+        decoded.splitn(2, ':').nth(1).map(str::to_owned)
+    } else {
+        None
+    }
+}
+
+fn query_value(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| {
+            let mut split = pair.splitn(2, '=');
+            Some((split.next()?, split.next().unwrap_or("")))
+        })
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value.to_owned())
+}
+
+/// Compare secrets in constant time, so response times don't reveal how much of a guess matched.
+fn same(known: &str, presented: &str) -> bool {
+    known.len() == presented.len()
+        && known
+            .bytes()
+            .zip(presented.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "signing key";
+
+    fn request(uri: &str, authorization: Option<&str>) -> Request<Body> {
+        let mut req = Request::builder();
+        req.uri(uri);
+        if let Some(authorization) = authorization {
+            req.header(AUTHORIZATION, authorization);
+        }
+        req.body(Body::empty()).unwrap()
+    }
+
+    fn signed(path_and_query: &str, expires: Option<i64>) -> Request<Body> {
+        request(&sign(KEY, path_and_query, expires), None)
+    }
+
+    #[test]
+    fn open_without_tokens_and_key() {
+        let access = Access::new(vec![" ".to_owned()], Some(String::new()));
+        assert!(access.is_open());
+        assert!(access.allows(&request("/course/524870.ics", None)));
+    }
+
+    #[test]
+    fn probes_stay_open() {
+        let access = Access::new(vec!["secret".to_owned()], None);
+        assert!(access.allows(&request("/healthz", None)));
+        assert!(access.allows(&request("/readyz", None)));
+        assert!(!access.allows(&request("/metrics", None)));
+    }
+
+    #[test]
+    fn accepts_valid_signatures() {
+        let access = Access::new(vec![], Some(KEY.to_owned()));
+        assert!(access.allows(&signed("/ics?courses=524870,525101&sm=498562", None)));
+        assert!(access.allows(&signed("/course/524870.ics", None)));
+        let tomorrow = Utc::now().timestamp() + 24 * 60 * 60;
+        assert!(access.allows(&signed("/ics?courses=524870", Some(tomorrow))));
+    }
+
+    #[test]
+    fn rejects_expired_signatures() {
+        let access = Access::new(vec![], Some(KEY.to_owned()));
+        let yesterday = Utc::now().timestamp() - 24 * 60 * 60;
+        assert!(!access.allows(&signed("/ics?courses=524870", Some(yesterday))));
+    }
+
+    #[test]
+    fn rejects_tampered_urls() {
+        let access = Access::new(vec![], Some(KEY.to_owned()));
+        let url = sign(KEY, "/ics?courses=524870&sm=498562", None);
+
+        let other_path = url.replace("/ics", "/course/524870.ics");
+        assert!(!access.allows(&request(&other_path, None)));
+        let other_query = url.replace("courses=524870", "courses=524870,525101");
+        assert!(!access.allows(&request(&other_query, None)));
+        let tomorrow = Utc::now().timestamp() + 24 * 60 * 60;
+        let expiring = sign(KEY, "/ics?courses=524870", Some(tomorrow));
+        let extended = expiring.replace(
+            &format!("expires={}", tomorrow),
+            &format!("expires={}", tomorrow + 365 * 24 * 60 * 60),
+        );
+        assert!(!access.allows(&request(&extended, None)));
+    }
+
+    #[test]
+    fn rejects_signatures_of_other_keys() {
+        let access = Access::new(vec![], Some(KEY.to_owned()));
+        let url = sign("another key", "/ics?courses=524870", None);
+        assert!(!access.allows(&request(&url, None)));
+    }
+
+    #[test]
+    fn rejects_missing_credentials() {
+        let access = Access::new(vec!["secret".to_owned()], Some(KEY.to_owned()));
+        assert!(!access.allows(&request("/ics?courses=524870", None)));
+        assert!(!access.allows(&request("/ics?courses=524870&sig=", None)));
+        assert!(!access.allows(&request("/ics?courses=524870&token=", None)));
+    }
+
+    #[test]
+    fn accepts_tokens_as_bearer_basic_or_query() {
+        let access = Access::new(vec!["secret".to_owned()], None);
+        let basic = format!("Basic {}", base64::encode("anyone:secret"));
+        assert!(access.allows(&request("/caldav/", Some("Bearer secret"))));
+        assert!(access.allows(&request("/caldav/", Some("bearer  secret "))));
+        assert!(access.allows(&request("/caldav/", Some(&basic))));
+        assert!(access.allows(&request("/course/524870.ics?token=secret", None)));
+    }
+
+    #[test]
+    fn rejects_wrong_tokens() {
+        let access = Access::new(vec!["secret".to_owned()], None);
+        let basic = format!("Basic {}", base64::encode("secret:wrong"));
+        assert!(!access.allows(&request("/caldav/", Some("Bearer wrong"))));
+        assert!(!access.allows(&request("/caldav/", Some("Bearer secre"))));
+        assert!(!access.allows(&request("/caldav/", Some(&basic))));
+        assert!(!access.allows(&request("/caldav/", Some("Digest secret"))));
+        assert!(!access.allows(&request("/course/524870.ics?token=wrong", None)));
+    }
+}
@@ -0,0 +1,285 @@
+//! Minimal read-only CalDAV interface for clients that can't subscribe to plain `.ics` feeds.
+//!
+//! The configured courses are exposed as one calendar collection at `/caldav/calendar/`, with
+//! one resource per event. `/caldav/` doubles as principal and calendar home, so clients can
+//! discover the calendar from the server root.
+
+use super::{etag_for, status_response};
+use crate::busy;
+use crate::caldav::resource_name;
+use crate::digest::escape_html;
+use crate::merge::UidRegistry;
+use crate::sanitize;
+use crate::uid;
+use crate::{new_calendar, RequestedCourse, StdError};
+use hyper::header::{HeaderValue, CONTENT_TYPE, ETAG};
+use hyper::{Body, Request, Response, StatusCode};
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+pub const PREFIX: &str = "/caldav/";
+const CALENDAR_PATH: &str = "/caldav/calendar/";
+/// How long fetched events are reused before the courses are fetched again
+const CACHE_TTL: Duration = Duration::from_secs(300);
+
+lazy_static! {
+    static ref CACHE: Mutex<Option<(Instant, Vec<Resource>)>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone)]
+struct Resource {
+    name: String,
+    etag: String,
+    calendar: String,
+}
+
+/// Handle a request below `PREFIX` (or a discovery request to `/`).
+pub async fn handle(req: Request<Body>, courses: &[RequestedCourse]) -> Response<Body> {
+    let path = req.uri().path().to_owned();
+    let depth = req
+        .headers()
+        .get("Depth")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("0")
+        .to_owned();
+
+    match req.method().as_str() {
+        "OPTIONS" => {
+            let mut response = status_response(StatusCode::OK);
+            response
+                .headers_mut()
+                .insert("DAV", HeaderValue::from_static("1, calendar-access"));
+            response.headers_mut().insert(
+                "Allow",
+                HeaderValue::from_static("OPTIONS, GET, PROPFIND, REPORT"),
+            );
+            response
+        }
+        "PROPFIND" => {
+            if path == "/" || path == PREFIX {
+                return multistatus(&home_responses(depth != "0"));
+            }
+            if path != CALENDAR_PATH {
+                return status_response(StatusCode::NOT_FOUND);
+            }
+            let resources = match resources(courses).await {
+                Ok(resources) => resources,
+                Err(err) => return upstream_error(err),
+            };
+            let mut responses = vec![calendar_response(&resources)];
+            if depth != "0" {
+                responses.extend(
+                    resources
+                        .iter()
+                        .map(|resource| resource_response(resource, false)),
+                );
+            }
+            multistatus(&responses)
+        }
+        "REPORT" => {
+            if path != CALENDAR_PATH {
+                return status_response(StatusCode::NOT_FOUND);
+            }
+            let body = match read_body(req).await {
+                Ok(body) => body,
+                Err(_) => return status_response(StatusCode::BAD_REQUEST),
+            };
+            let resources = match resources(courses).await {
+                Ok(resources) => resources,
+                Err(err) => return upstream_error(err),
+            };
+
+            // calendar-multiget asks for specific resources, calendar-query filters are ignored
+            // and answered with all events
+            let hrefs = multiget_hrefs(&body);
+            let responses = resources
+                .iter()
+                .filter(|resource| {
+                    hrefs.is_empty() || hrefs.iter().any(|href| href.ends_with(&resource.name))
+                })
+                .map(|resource| resource_response(resource, true))
+                .collect::<Vec<_>>();
+            multistatus(&responses)
+        }
+        "GET" => {
+            if !path.starts_with(CALENDAR_PATH) {
+                return status_response(StatusCode::NOT_FOUND);
+            }
+            let name = &path[CALENDAR_PATH.len()..];
+            let resources = match resources(courses).await {
+                Ok(resources) => resources,
+                Err(err) => return upstream_error(err),
+            };
+            match resources.into_iter().find(|resource| resource.name == name) {
+                Some(resource) => {
+                    let mut response = Response::new(Body::from(resource.calendar));
+                    response.headers_mut().insert(
+                        CONTENT_TYPE,
+                        HeaderValue::from_static("text/calendar; charset=utf-8"),
+                    );
+                    if let Ok(etag) = HeaderValue::from_str(&resource.etag) {
+                        response.headers_mut().insert(ETAG, etag);
+                    }
+                    response
+                }
+                None => status_response(StatusCode::NOT_FOUND),
+            }
+        }
+        // Read-only
+        _ => status_response(StatusCode::FORBIDDEN),
+    }
+}
+
+/// One resource per event of all courses, cached for `CACHE_TTL`.
+async fn resources(courses: &[RequestedCourse]) -> Result<Vec<Resource>, StdError> {
+    let cached = CACHE.lock().unwrap().clone();
+    if let Some((fetched, resources)) = cached {
+        if fetched.elapsed() < CACHE_TTL {
+            return Ok(resources);
+        }
+    }
+
+    let mut resources = vec![];
+    let mut registry = UidRegistry::new();
+    for requested in courses.iter() {
+        let mut course = requested.get_course().await?;
+        busy::apply(&mut course, &requested.options);
+        let mut uids = vec![];
+        let options = &requested.options;
+        let cal_events = course.into_ical_events(options, |event| {
+            let (uid, rewritten) = registry.claim_checked(
+                &uid::for_event(options, Some(&requested.id), &event.id),
+                &requested.id,
+            );
+            uids.push(uid.clone());
+            (uid, rewritten)
+        });
+        for (uid, cal_event) in uids.into_iter().zip(cal_events) {
+            let mut calendar = new_calendar();
+            calendar.add_event(cal_event);
+            let calendar = sanitize::fold(&calendar.to_string());
+            resources.push(Resource {
+                name: resource_name(&uid),
+                etag: etag_for(&calendar),
+                calendar,
+            });
+        }
+    }
+
+    *CACHE.lock().unwrap() = Some((Instant::now(), resources.clone()));
+    Ok(resources)
+}
+
+fn home_responses(with_children: bool) -> Vec<String> {
+    let mut responses = vec![format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>\
+         <d:resourcetype><d:collection/></d:resourcetype>\
+         <d:current-user-principal><d:href>{}</d:href></d:current-user-principal>\
+         <c:calendar-home-set><d:href>{}</d:href></c:calendar-home-set>\
+         </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        PREFIX, PREFIX, PREFIX
+    )];
+    if with_children {
+        responses.push(format!(
+            "<d:response><d:href>{}</d:href><d:propstat><d:prop>\
+             <d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+             </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+            CALENDAR_PATH
+        ));
+    }
+    responses
+}
+
+fn calendar_response(resources: &[Resource]) -> String {
+    // The collection changes whenever any of its resources does
+    let ctag = etag_for(
+        &resources
+            .iter()
+            .map(|resource| resource.etag.as_str())
+            .collect::<String>(),
+    );
+    format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>\
+         <d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+         <d:displayname>fu-vv-cal</d:displayname>\
+         <c:supported-calendar-component-set><c:comp name=\"VEVENT\"/></c:supported-calendar-component-set>\
+         <cs:getctag>{}</cs:getctag>\
+         </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        CALENDAR_PATH,
+        escape_html(&ctag)
+    )
+}
+
+fn resource_response(resource: &Resource, with_data: bool) -> String {
+    let data = if with_data {
+        format!(
+            "<c:calendar-data>{}</c:calendar-data>",
+            escape_html(&resource.calendar)
+        )
+    } else {
+        String::new()
+    };
+    format!(
+        "<d:response><d:href>{}{}</d:href><d:propstat><d:prop>\
+         <d:getetag>{}</d:getetag>\
+         <d:getcontenttype>text/calendar; charset=utf-8</d:getcontenttype>{}\
+         </d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        CALENDAR_PATH,
+        resource.name,
+        escape_html(&resource.etag),
+        data
+    )
+}
+
+fn multistatus(responses: &[String]) -> Response<Body> {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\" \
+         xmlns:cs=\"http://calendarserver.org/ns/\">{}</d:multistatus>",
+        responses.concat()
+    );
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = StatusCode::MULTI_STATUS;
+    response.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/xml; charset=utf-8"),
+    );
+    response
+}
+
+/// Hrefs requested by a `calendar-multiget` report, empty for other reports.
+fn multiget_hrefs(body: &str) -> Vec<String> {
+    let document = match roxmltree::Document::parse(body) {
+        Ok(document) => document,
+        Err(_) => return vec![],
+    };
+    if !document
+        .root_element()
+        .has_tag_name(("urn:ietf:params:xml:ns:caldav", "calendar-multiget"))
+    {
+        return vec![];
+    }
+
+    document
+        .descendants()
+        .filter(|node| node.has_tag_name(("DAV:", "href")))
+        .filter_map(|node| node.text())
+        .map(|href| href.trim().to_owned())
+        .collect()
+}
+
+fn upstream_error(err: StdError) -> Response<Body> {
+    error!(%err, "failed to fetch courses for CalDAV");
+    status_response(StatusCode::BAD_GATEWAY)
+}
+
+async fn read_body(req: Request<Body>) -> Result<String, StdError> {
+    let mut body = req.into_body();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.next().await {
+        bytes.extend(chunk?);
+    }
+    Ok(String::from_utf8(bytes)?)
+}
@@ -0,0 +1,44 @@
+//! HTTPS for `serve` with rustls, since many calendar clients refuse `webcal://` subscriptions
+//! over plain HTTP and not every instance sits behind a reverse proxy.
+
+use crate::{Error, StdError};
+use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use rustls::{NoClientAuth, ServerConfig};
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use tokio_rustls::TlsAcceptor;
+
+/// Acceptor for TLS connections with the PEM certificate chain at `cert_path` and the PKCS#8 or
+/// RSA private key at `key_path`, e.g. as issued by Let's Encrypt.
+pub fn acceptor(cert_path: &str, key_path: &str) -> Result<TlsAcceptor, StdError> {
+    let invalid = |path: &str, message: &str| Error::InvalidTlsFile {
+        path: path.to_owned(),
+        message: message.to_owned(),
+    };
+
+    let chain = certs(&mut BufReader::new(File::open(cert_path)?))
+        .map_err(|_| invalid(cert_path, "not a PEM certificate"))?;
+    if chain.is_empty() {
+        return Err(invalid(cert_path, "no certificate found").into());
+    }
+
+    let mut keys = pkcs8_private_keys(&mut BufReader::new(File::open(key_path)?))
+        .map_err(|_| invalid(key_path, "not a PEM private key"))?;
+    if keys.is_empty() {
+        keys = rsa_private_keys(&mut BufReader::new(File::open(key_path)?))
+            .map_err(|_| invalid(key_path, "not a PEM private key"))?;
+    }
+    let key = keys
+        .into_iter()
+        .next()
+        .ok_or_else(|| invalid(key_path, "no PKCS#8 or RSA private key found"))?;
+
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config
+        .set_single_cert(chain, key)
+        .map_err(|err| invalid(cert_path, &err.to_string()))?;
+    config.set_protocols(&[b"http/1.1".to_vec()]);
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
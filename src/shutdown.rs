@@ -0,0 +1,42 @@
+//! Graceful shutdown of the long-running modes (`serve`, `--watch`) on SIGINT and SIGTERM, e.g.
+//! when a container is redeployed.
+//!
+//! A signal only requests the shutdown: an export that is running completes (including its
+//! upstream fetches, the store and the state file) before `--watch` exits, and `serve` stops
+//! accepting connections but answers the requests it already received.
+
+use crate::StdError;
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::timer::delay;
+use tracing::info;
+
+/// Time between two checks whether shutdown was requested
+pub const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+lazy_static! {
+    static ref REQUESTED: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+}
+
+/// Request shutdown on SIGINT and SIGTERM instead of terminating right away.
+pub fn install() -> Result<(), StdError> {
+    for signal in &[signal_hook::SIGINT, signal_hook::SIGTERM] {
+        signal_hook::flag::register(*signal, REQUESTED.clone())?;
+    }
+    Ok(())
+}
+
+/// Whether a signal requested the shutdown.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Wait until shutdown is requested.
+pub async fn signal() {
+    while !requested() {
+        delay(Instant::now() + POLL_INTERVAL).await;
+    }
+    info!("shutting down");
+}
@@ -0,0 +1,324 @@
+//! Where courses come from.
+//!
+//! Everything past `CourseSource::get_course` (rules, merging, iCal generation) only sees the
+//! parsed `Course`, so another university only needs another implementation of `CourseSource`.
+
+use crate::fast::{self, has_token, NEXT_PAGE_LABELS, PAGINATION_CLASSES};
+use crate::fetch::{self, CoursePageFetcher, LiveFetcher, Locale};
+use crate::{metrics, schema, Course, Error, StdError};
+use select::document::Document;
+use select::predicate::{Class, Name, Predicate};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, warn};
+
+/// Maximum number of pages of a paginated date list that are fetched
+const MAX_PAGES: usize = 20;
+
+/// Future returned by `CourseSource::get_course`.
+pub type CourseFuture<'a> = Pin<Box<dyn Future<Output = Result<Course, StdError>> + Send + 'a>>;
+
+/// Backend fetching and parsing the courses of one course catalogue.
+pub trait CourseSource: fmt::Debug + Send + Sync {
+    /// Name of the source in the config, e.g. `fu`.
+    fn name(&self) -> &'static str;
+
+    /// URL of the page of a course, linked from its events.
+    fn course_url(&self, id: &str, semester: &str) -> String;
+
+    /// Fetch and parse a course.
+    fn get_course<'a>(&'a self, id: &'a str, semester: &'a str) -> CourseFuture<'a>;
+}
+
+/// The source with the given config name, fetching its pages in `locale` and parsing them
+/// `lenient`ly if set.
+pub fn by_name(
+    name: &str,
+    locale: Locale,
+    lenient: bool,
+) -> Result<Arc<dyn CourseSource>, StdError> {
+    match name {
+        "fu" => Ok(Arc::new(
+            FuVv::new().with_locale(locale).with_lenient(lenient),
+        )),
+        _ => Err(Error::UnknownSource {
+            name: name.to_owned(),
+        }
+        .into()),
+    }
+}
+
+/// Course catalogue (Vorlesungsverzeichnis) of the FU Berlin.
+#[derive(Debug)]
+pub struct FuVv {
+    /// Where the course page comes from, the live VV by default
+    pub fetcher: Arc<dyn CoursePageFetcher>,
+    /// Use the fast-path parser of `fast`, for bulk crawls
+    pub fast_parser: bool,
+    /// Language of the fetched pages and of the linked course URLs
+    pub locale: Locale,
+    /// Skip sessions that can't be parsed with a warning instead of failing, see `lenient`
+    pub lenient: bool,
+}
+
+impl Default for FuVv {
+    fn default() -> Self {
+        Self {
+            fetcher: Arc::new(LiveFetcher),
+            fast_parser: false,
+            locale: Locale::default(),
+            lenient: false,
+        }
+    }
+}
+
+impl FuVv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fetcher(mut self, fetcher: Arc<dyn CoursePageFetcher>) -> Self {
+        self.fetcher = fetcher;
+        self
+    }
+
+    pub fn with_fast_parser(mut self) -> Self {
+        self.fast_parser = true;
+        self
+    }
+
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    pub fn with_lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    fn parse(&self, id: &str, body_str: &str) -> Result<Course, StdError> {
+        let started = Instant::now();
+        let course = if self.fast_parser {
+            fast::course_from_html(body_str, self.lenient)
+        } else {
+            Course::from_document(&Document::from(body_str), self.lenient)
+        };
+        if let Ok(course) = &course {
+            debug!(
+                course = id,
+                events = course.events.len(),
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "parsed page"
+            );
+        }
+        // The landmark check explains failures, and pages that silently stopped yielding sessions
+        let course = match course {
+            Ok(course) if !course.events.is_empty() => Ok(course),
+            course => match schema::check(body_str) {
+                Ok(()) => course,
+                Err(err) => Err(err.into()),
+            },
+        };
+        course.map_err(|err| {
+            metrics::record_parse_failure();
+            Error::CourseParseError {
+                id: id.to_owned(),
+                message: err.to_string(),
+            }
+            .into()
+        })
+    }
+
+    /// Link to the next page of sessions, found with the parser `parse` uses.
+    fn next_page(&self, html: &str) -> Option<String> {
+        if self.fast_parser {
+            fast::next_page(html)
+        } else {
+            next_page(html)
+        }
+    }
+}
+
+impl CourseSource for FuVv {
+    fn name(&self) -> &'static str {
+        "fu"
+    }
+
+    fn course_url(&self, id: &str, semester: &str) -> String {
+        fetch::course_url(id, semester, self.locale)
+    }
+
+    fn get_course<'a>(&'a self, id: &'a str, semester: &'a str) -> CourseFuture<'a> {
+        Box::pin(async move {
+            let body_str = self.fetcher.fetch_page(id, semester, self.locale).await?;
+            let mut course = self.parse(id, &body_str)?;
+
+            // Courses with very many sessions have their date list split into pages
+            let mut page_url = fetch::course_url(id, semester, self.locale);
+            let mut visited = vec![page_url.clone()];
+            let mut next = self.next_page(&body_str);
+            while let Some(target) = next {
+                page_url = fetch::resolve_url(&page_url, &target)?;
+                if visited.contains(&page_url) {
+                    break;
+                }
+                if visited.len() >= MAX_PAGES {
+                    warn!(
+                        course = id,
+                        "more than {} pages of sessions, the rest is skipped", MAX_PAGES
+                    );
+                    break;
+                }
+                visited.push(page_url.clone());
+
+                debug!(
+                    course = id,
+                    url = page_url.as_str(),
+                    "fetching next page of sessions"
+                );
+                let body_str = self.fetcher.fetch_url(&page_url).await?;
+                let page = match self.parse(id, &body_str) {
+                    Ok(page) => page,
+                    Err(err) => {
+                        warn!(
+                            course = id,
+                            url = page_url.as_str(),
+                            %err,
+                            "next page of sessions could not be parsed, the rest is skipped"
+                        );
+                        break;
+                    }
+                };
+                course.warnings.extend(page.warnings);
+                for event in page.events {
+                    if !course.events.iter().any(|known| known.id == event.id) {
+                        course.events.push(event);
+                    }
+                }
+                next = self.next_page(&body_str);
+            }
+
+            Ok(course)
+        })
+    }
+}
+
+/// Link to the next page of a paginated date list.
+///
+/// Only the pagination element next to the sessions is considered, so "next" links elsewhere on
+/// the page (e.g. of the navigation or a search) aren't mistaken for further pages of sessions.
+fn next_page(html: &str) -> Option<String> {
+    let document = Document::from(html);
+    let date_list = document.find(Class("link_to_details")).next()?.parent()?;
+    let pagination = date_list
+        .find(Name("nav").or(Name("div")).or(Name("ul")))
+        .find(|node| {
+            PAGINATION_CLASSES
+                .iter()
+                .any(|class| has_token(node.attr("class"), class))
+        })?;
+    let link = pagination.find(Name("a")).find(|link| {
+        let label = link.text().trim().to_lowercase();
+        has_token(link.attr("rel"), "next")
+            || has_token(link.attr("class"), "next")
+            || NEXT_PAGE_LABELS.contains(&label.as_str())
+    })?;
+    link.attr("href").map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::FixtureFetcher;
+    use crate::options::CalendarOptions;
+    use crate::RequestedCourse;
+
+    const ID: &str = "524870";
+    const SEMESTER: &str = "498562";
+
+    /// Course page with a session, an exam and a link to a second page of sessions
+    const PAGE: &str = "<html><body>\
+        <div class=\"subc\"><h1>Organische Chemie 1</h1><p>Vorlesung</p></div>\
+        <div class=\"dates\">\
+        <div id=\"link_to_details_500001\" class=\"link_to_details\">\
+        <span class=\"course_date_time\">Mo, 14.10.2019 10:00 - 12:00</span>\
+        <span class=\"course_remark\">Hörsaal A</span></div>\
+        <h3>Klausur</h3>\
+        <div id=\"link_to_details_500002\" class=\"link_to_details\">\
+        <span class=\"course_date_time\">Mo, 10.02.2020 10:00 - 12:00</span></div>\
+        <ul class=\"pagination\"><li><a href=\"/vv/de/lv/524870?sm=498562&amp;page=2\">\
+        weiter</a></li></ul>\
+        </div></body></html>";
+
+    /// Second page of the sessions of `PAGE`
+    const SECOND_PAGE: &str = "<html><body>\
+        <div class=\"subc\"><h1>Organische Chemie 1</h1><p>Vorlesung</p></div>\
+        <div class=\"dates\">\
+        <div id=\"link_to_details_500003\" class=\"link_to_details\">\
+        <span class=\"course_date_time\">Mo, 21.10.2019 10:00 - 12:00</span></div>\
+        </div></body></html>";
+
+    fn source() -> FuVv {
+        let fetcher = FixtureFetcher::new().with_page(ID, PAGE).with_page(
+            "https://www.fu-berlin.de/vv/de/lv/524870?sm=498562&page=2",
+            SECOND_PAGE,
+        );
+        FuVv::new().with_fetcher(Arc::new(fetcher))
+    }
+
+    #[tokio::test]
+    async fn parses_saved_pages() {
+        let course = source().get_course(ID, SEMESTER).await.unwrap();
+        assert_eq!(course.name, "Organische Chemie 1");
+        assert_eq!(course.kind.as_ref().map(String::as_str), Some("Vorlesung"));
+        let ids = course
+            .events
+            .iter()
+            .map(|event| event.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["500001", "500002", "500003"]);
+        assert_eq!(
+            course.events[0].timespan.start.to_rfc3339(),
+            "2019-10-14T10:00:00+02:00"
+        );
+        assert_eq!(
+            course.events[0].remark.as_ref().map(String::as_str),
+            Some("Hörsaal A")
+        );
+        let exams = course
+            .events
+            .iter()
+            .map(|event| event.exam)
+            .collect::<Vec<_>>();
+        assert_eq!(exams, vec![false, true, false]);
+    }
+
+    #[tokio::test]
+    async fn exports_saved_pages_as_ical() {
+        let requested = RequestedCourse::new(ID, SEMESTER).with_source(Arc::new(source()));
+        let course = requested.get_course().await.unwrap();
+        let ics = course
+            .to_ical(&CalendarOptions::default())
+            .unwrap()
+            .to_string();
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 3);
+        assert!(ics.contains("X-WR-CALNAME:Organische Chemie 1\r\n"));
+        assert!(ics.contains("UID:524870-500001@vv.fu-berlin.de\r\n"));
+        assert!(ics.contains("DTSTART:20191014T080000Z\r\n"));
+        assert!(ics.contains("DTEND:20191014T100000Z\r\n"));
+        // Winter time
+        assert!(ics.contains("DTSTART:20200210T090000Z\r\n"));
+        assert!(ics.contains("SUMMARY:Organische Chemie 1\r\n"));
+        assert!(ics.contains("CATEGORIES:EXAM\r\n"));
+        assert!(ics.contains("RELATED-TO;RELTYPE=PARENT:524870-500001@vv.fu-berlin.de\r\n"));
+    }
+
+    #[tokio::test]
+    async fn fails_on_missing_pages() {
+        assert!(source().get_course("1", SEMESTER).await.is_err());
+    }
+}
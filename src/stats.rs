@@ -0,0 +1,208 @@
+//! Workload of the configured courses: contact hours per week and per course, the busiest
+//! weekday and the slots left free, e.g. to decide whether one more seminar fits in.
+
+use crate::weeks::{self, WEEKDAYS};
+use crate::Course;
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime};
+use std::collections::BTreeMap;
+
+/// Start of the day considered for free slots
+pub const DAY_START: (u32, u32) = (8, 0);
+/// End of the day considered for free slots
+pub const DAY_END: (u32, u32) = (20, 0);
+/// Shortest gap reported as a free slot, in minutes
+pub const MIN_FREE_MINUTES: i64 = 90;
+
+#[derive(Debug, Clone)]
+pub struct Workload {
+    /// Contact time and credits per course, in the order of the config
+    pub courses: Vec<CourseLoad>,
+    /// Contact time per week, by the Monday of the week
+    pub weeks: BTreeMap<NaiveDate, Duration>,
+    /// Contact time over the semester per day of `WEEKDAYS`
+    pub weekdays: Vec<Duration>,
+    /// Gaps between `DAY_START` and `DAY_END` without any session in the week grid, per day of
+    /// `WEEKDAYS` from Monday to Friday
+    pub free_slots: Vec<Vec<(NaiveTime, NaiveTime)>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CourseLoad {
+    pub name: String,
+    pub contact: Duration,
+    /// Weekly hours as stated on the course page
+    pub sws: Option<f32>,
+    /// ECTS credits as stated on the course page
+    pub ects: Option<f32>,
+}
+
+impl Workload {
+    /// Workload of the sessions of `courses`, cancelled ones left out.
+    pub fn new(courses: &[Course]) -> Self {
+        let mut weeks = BTreeMap::new();
+        let mut weekdays = vec![Duration::zero(); WEEKDAYS.len()];
+        let mut per_course = vec![];
+        for course in courses {
+            let mut total = Duration::zero();
+            for event in course.events.iter() {
+                let duration = event.duration();
+                let start = event.timespan.start.naive_local();
+                total = total + duration;
+                let week = weeks
+                    .entry(weeks::week_start(start.date()))
+                    .or_insert_with(Duration::zero);
+                *week = *week + duration;
+                if let Some(day) = WEEKDAYS
+                    .iter()
+                    .position(|(weekday, _)| *weekday == start.weekday())
+                {
+                    weekdays[day] = weekdays[day] + duration;
+                }
+            }
+            per_course.push(CourseLoad {
+                name: course.name.clone(),
+                contact: total,
+                sws: course.sws,
+                ects: course.ects,
+            });
+        }
+
+        Self {
+            courses: per_course,
+            weeks,
+            weekdays,
+            free_slots: free_slots(courses),
+        }
+    }
+
+    /// Average contact time of the weeks with at least one session.
+    pub fn average_week(&self) -> Duration {
+        if self.weeks.is_empty() {
+            return Duration::zero();
+        }
+        let total = self
+            .weeks
+            .values()
+            .fold(Duration::zero(), |sum, week| sum + *week);
+        total / self.weeks.len() as i32
+    }
+
+    /// Day of `WEEKDAYS` with the most contact time, if there are any sessions.
+    pub fn busiest_weekday(&self) -> Option<(&'static str, Duration)> {
+        self.weekdays
+            .iter()
+            .enumerate()
+            .filter(|(_, duration)| **duration > Duration::zero())
+            .max_by_key(|(_, duration)| **duration)
+            .map(|(day, duration)| (WEEKDAYS[day].1, *duration))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("Contact hours per course\n\n");
+        for course in self.courses.iter() {
+            let credits = course
+                .sws
+                .map(|sws| format!("{} SWS", sws))
+                .into_iter()
+                .chain(course.ects.map(|ects| format!("{} ECTS", ects)))
+                .collect::<Vec<_>>();
+            let credits = if credits.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", credits.join(", "))
+            };
+            text.push_str(&format!(
+                "- {}: {}{}\n",
+                course.name,
+                hours(course.contact),
+                credits
+            ));
+        }
+        let total = self
+            .courses
+            .iter()
+            .fold(Duration::zero(), |sum, course| sum + course.contact);
+        text.push_str(&format!("Total: {}\n", hours(total)));
+        let ects = self
+            .courses
+            .iter()
+            .filter_map(|course| course.ects)
+            .sum::<f32>();
+        if ects > 0.0 {
+            text.push_str(&format!("ECTS: {}\n", ects));
+        }
+
+        text.push_str("\nContact hours per week\n\n");
+        if self.weeks.is_empty() {
+            text.push_str("No sessions.\n");
+        }
+        for (monday, duration) in self.weeks.iter() {
+            text.push_str(&format!(
+                "- Week of {}: {}\n",
+                monday.format("%d.%m.%Y"),
+                hours(*duration)
+            ));
+        }
+        if !self.weeks.is_empty() {
+            text.push_str(&format!("Average: {}\n", hours(self.average_week())));
+        }
+
+        if let Some((weekday, duration)) = self.busiest_weekday() {
+            text.push_str(&format!(
+                "\nBusiest weekday: {} ({} over the semester)\n",
+                weekday,
+                hours(duration)
+            ));
+        }
+
+        text.push_str(&format!(
+            "\nFree slots of at least {} minutes\n\n",
+            MIN_FREE_MINUTES
+        ));
+        for (day, slots) in self.free_slots.iter().enumerate() {
+            let slots = slots
+                .iter()
+                .map(|(start, end)| format!("{}–{}", start.format("%H:%M"), end.format("%H:%M")))
+                .collect::<Vec<_>>();
+            let slots = if slots.is_empty() {
+                "none".to_owned()
+            } else {
+                slots.join(", ")
+            };
+            text.push_str(&format!("- {}: {}\n", WEEKDAYS[day].1, slots));
+        }
+        text
+    }
+}
+
+/// Gaps of at least `MIN_FREE_MINUTES` between the slots of the week grid, Monday to Friday.
+fn free_slots(courses: &[Course]) -> Vec<Vec<(NaiveTime, NaiveTime)>> {
+    let day_start = NaiveTime::from_hms(DAY_START.0, DAY_START.1, 0);
+    let day_end = NaiveTime::from_hms(DAY_END.0, DAY_END.1, 0);
+    let grid = weeks::week_grid(courses);
+
+    // Saturdays are not part of the usual week
+    (0..WEEKDAYS.len() - 1)
+        .map(|day| {
+            let mut free = vec![];
+            let mut free_from = day_start;
+            // Slots are ordered by start, so only the end reached so far matters
+            for slot in grid.iter().filter(|slot| !slot.days[day].is_empty()) {
+                if slot.start > free_from {
+                    free.push((free_from, slot.start.min(day_end)));
+                }
+                free_from = free_from.max(slot.end);
+            }
+            free.push((free_from, day_end));
+            free.retain(|(start, end)| {
+                end.signed_duration_since(*start) >= Duration::minutes(MIN_FREE_MINUTES)
+            });
+            free
+        })
+        .collect()
+}
+
+/// `duration` in hours with one decimal, e.g. "4.5 h".
+fn hours(duration: Duration) -> String {
+    format!("{:.1} h", duration.num_minutes() as f64 / 60.0)
+}
@@ -0,0 +1,261 @@
+//! Persistent record of every fetched course in SQLite, see `store` in the config.
+//!
+//! Unlike the state file, which only holds the last export, the store keeps every fetch with
+//! its time, so courses can be compared across arbitrary points in time and the last good
+//! version of a course is available while the VV is down.
+
+use crate::changes::Snapshot;
+use crate::registration::Registration;
+use crate::{Course, CourseEvent, StdError};
+use chrono::{DateTime, NaiveTime, SecondsFormat, Utc};
+use chrono_tz::Europe::Berlin;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use timespan::DateTimeSpan;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS fetches (
+        id INTEGER PRIMARY KEY,
+        course_id TEXT NOT NULL,
+        semester TEXT NOT NULL,
+        fetched_at TEXT NOT NULL,
+        name TEXT NOT NULL,
+        kind TEXT,
+        url TEXT
+    );
+    CREATE INDEX IF NOT EXISTS fetches_course ON fetches (course_id, semester, fetched_at);
+    CREATE TABLE IF NOT EXISTS events (
+        fetch_id INTEGER NOT NULL REFERENCES fetches (id),
+        event_id TEXT NOT NULL,
+        start TEXT NOT NULL,
+        end TEXT NOT NULL,
+        part TEXT,
+        remark TEXT,
+        room TEXT,
+        lecturers TEXT NOT NULL,
+        detail_url TEXT,
+        exam INTEGER NOT NULL,
+        group_label TEXT
+    );
+    CREATE INDEX IF NOT EXISTS events_fetch ON events (fetch_id);
+";
+
+/// A course as it was fetched at some point.
+#[derive(Debug, Clone)]
+pub struct StoredCourse {
+    pub id: String,
+    pub semester: String,
+    pub fetched_at: DateTime<Utc>,
+    pub course: Course,
+}
+
+#[derive(Debug)]
+pub struct Store {
+    connection: Connection,
+}
+
+impl Store {
+    /// Open the store at `path`, creating it if it doesn't exist yet.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StdError> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { connection })
+    }
+
+    /// Record `course` as fetched at `fetched_at`.
+    pub fn record(
+        &mut self,
+        id: &str,
+        semester: &str,
+        course: &Course,
+        fetched_at: DateTime<Utc>,
+    ) -> Result<(), StdError> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO fetches (course_id, semester, fetched_at, name, kind, url)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id,
+                semester,
+                timestamp(fetched_at),
+                course.name,
+                course.kind,
+                course.url
+            ],
+        )?;
+        let fetch_id = transaction.last_insert_rowid();
+        for event in course.events.iter() {
+            transaction.execute(
+                "INSERT INTO events (fetch_id, event_id, start, end, part, remark, room, \
+                 lecturers, detail_url, exam, group_label)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    fetch_id,
+                    event.id,
+                    timestamp(event.timespan.start.with_timezone(&Utc)),
+                    timestamp(event.timespan.end.with_timezone(&Utc)),
+                    event.part,
+                    event.remark,
+                    event.room,
+                    event.lecturers.join("\n"),
+                    event.detail_url,
+                    event.exam,
+                    event.group
+                ],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// The most recent version of a course, if it was ever fetched.
+    pub fn latest(&self, id: &str, semester: &str) -> Result<Option<StoredCourse>, StdError> {
+        self.at(id, semester, Utc::now())
+    }
+
+    /// The version of a course that was current at `time`, i.e. the last one fetched before.
+    pub fn at(
+        &self,
+        id: &str,
+        semester: &str,
+        time: DateTime<Utc>,
+    ) -> Result<Option<StoredCourse>, StdError> {
+        let fetch = self
+            .connection
+            .query_row(
+                "SELECT id, fetched_at, name, kind, url FROM fetches
+                 WHERE course_id = ?1 AND semester = ?2 AND fetched_at <= ?3
+                 ORDER BY fetched_at DESC LIMIT 1",
+                params![id, semester, timestamp(time)],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let (fetch_id, fetched_at, name, kind, url) = match fetch {
+            Some(fetch) => fetch,
+            None => return Ok(None),
+        };
+
+        Ok(Some(StoredCourse {
+            id: id.to_owned(),
+            semester: semester.to_owned(),
+            fetched_at: DateTime::parse_from_rfc3339(&fetched_at)?.with_timezone(&Utc),
+            course: Course {
+                id: Some(id.to_owned()),
+                name,
+                kind,
+                events: self.events(fetch_id)?,
+                cancelled: vec![],
+                url,
+                color: None,
+                sws: None,
+                ects: None,
+                modules: vec![],
+                language: None,
+                registration: Registration::default(),
+                warnings: vec![],
+            },
+        }))
+    }
+
+    /// Snapshot of all courses in the store as they were at `time`, to compare with `changes`.
+    pub fn snapshot_at(&self, time: DateTime<Utc>) -> Result<Snapshot, StdError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT DISTINCT course_id, semester FROM fetches")?;
+        let courses = statement
+            .query_map(params![], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut snapshot = Snapshot::new();
+        for (id, semester) in courses {
+            if let Some(stored) = self.at(&id, &semester, time)? {
+                snapshot.insert(&id, &stored.course);
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Times at which a course was fetched, oldest first.
+    pub fn fetch_times(&self, id: &str, semester: &str) -> Result<Vec<DateTime<Utc>>, StdError> {
+        let mut statement = self.connection.prepare(
+            "SELECT fetched_at FROM fetches WHERE course_id = ?1 AND semester = ?2
+             ORDER BY fetched_at",
+        )?;
+        let times = statement
+            .query_map(params![id, semester], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        times
+            .iter()
+            .map(|time| Ok(DateTime::parse_from_rfc3339(time)?.with_timezone(&Utc)))
+            .collect()
+    }
+
+    fn events(&self, fetch_id: i64) -> Result<Vec<CourseEvent>, StdError> {
+        let mut statement = self.connection.prepare(
+            "SELECT event_id, start, end, part, remark, room, lecturers, detail_url, exam, \
+             group_label FROM events WHERE fetch_id = ?1 ORDER BY start",
+        )?;
+        let rows = statement
+            .query_map(params![fetch_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, String>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                    row.get::<_, bool>(8)?,
+                    row.get::<_, Option<String>>(9)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(
+                |(id, start, end, part, remark, room, lecturers, detail_url, exam, group)| {
+                    let start = DateTime::parse_from_rfc3339(&start)?.with_timezone(&Berlin);
+                    let end = DateTime::parse_from_rfc3339(&end)?.with_timezone(&Berlin);
+                    // Not stored, but all-day sessions are the only ones from midnight to midnight
+                    let midnight = NaiveTime::from_hms(0, 0, 0);
+                    let all_day = start.time() == midnight && end.time() == midnight;
+                    Ok(CourseEvent {
+                        id,
+                        timespan: DateTimeSpan::new(start, end)?,
+                        sequence: 0,
+                        week: None,
+                        part,
+                        remark,
+                        room,
+                        lecturers: lecturers
+                            .lines()
+                            .filter(|lecturer| !lecturer.is_empty())
+                            .map(str::to_owned)
+                            .collect(),
+                        detail_url,
+                        exam,
+                        group,
+                        summary: None,
+                        all_day,
+                    })
+                },
+            )
+            .collect()
+    }
+}
+
+/// `time` in a fixed format, so timestamps in the store can be compared as text.
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
@@ -0,0 +1,44 @@
+//! Templates for the summaries of exported events, e.g. `{type}: {name} ({id})`.
+//!
+//! Placeholders:
+//! - `{name}`: course name
+//! - `{id}`: course id
+//! - `{type}`: part of the session ("Übung") or, if the page doesn't say, course type
+//! - `{week}`: lecture week, see `weeks`
+//!
+//! Placeholders without a value render as empty text.
+
+use crate::{Course, CourseEvent};
+
+#[derive(Debug, Clone)]
+pub struct SummaryTemplate(pub String);
+
+impl SummaryTemplate {
+    /// Set the summary of every session of `course` from the template.
+    pub fn apply(&self, course_id: &str, course: &mut Course) {
+        let name = course.name.clone();
+        let kind = course.kind.clone();
+        for event in course.events.iter_mut().chain(course.cancelled.iter_mut()) {
+            event.summary = Some(self.render(course_id, &name, kind.as_ref(), event));
+        }
+    }
+
+    fn render(
+        &self,
+        course_id: &str,
+        name: &str,
+        kind: Option<&String>,
+        event: &CourseEvent,
+    ) -> String {
+        let kind = event.part.as_ref().or(kind).map(String::as_str);
+        let week = event.week.map(|week| week.to_string());
+
+        self.0
+            .replace("{name}", name)
+            .replace("{id}", course_id)
+            .replace("{type}", kind.unwrap_or(""))
+            .replace("{week}", week.as_ref().map(String::as_str).unwrap_or(""))
+            .trim()
+            .to_owned()
+    }
+}
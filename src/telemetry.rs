@@ -0,0 +1,59 @@
+use crate::config::TelemetryConfig;
+use crate::fetch::{self, HttpRequest};
+use crate::LAYOUT_VERSION;
+use http::header::CONTENT_TYPE;
+use http::Method;
+use serde::Serialize;
+use tracing::warn;
+
+/// Anonymous counts of parsed and failed course pages of one run.
+///
+/// Contains no course ids or other data identifying the user, only what maintainers need to
+/// notice that the VV markup changed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseStats {
+    pub layout_version: &'static str,
+    pub crate_version: &'static str,
+    pub parsed: u32,
+    pub failed: u32,
+}
+
+impl ParseStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for ParseStats {
+    fn default() -> Self {
+        Self {
+            layout_version: LAYOUT_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION"),
+            parsed: 0,
+            failed: 0,
+        }
+    }
+}
+
+/// Submit `stats` to the configured endpoint, but only if the user explicitly opted in.
+///
+/// Failures only produce a warning, reporting never affects the export itself.
+pub async fn report(telemetry: Option<&TelemetryConfig>, stats: &ParseStats) {
+    let telemetry = match telemetry {
+        Some(telemetry) if telemetry.opt_in => telemetry,
+        _ => return,
+    };
+
+    let req = HttpRequest::new(Method::POST, telemetry.endpoint.as_str())
+        .header(CONTENT_TYPE, "application/json")
+        .map(|req| req.with_body(serde_json::to_string(stats).unwrap()));
+    let result = match req {
+        Ok(req) => fetch::send(req).await,
+        Err(err) => Err(err.into()),
+    };
+    match result {
+        Ok((status, _)) if status.is_success() => {}
+        Ok((status, _)) => warn!(%status, "reporting parse statistics failed"),
+        Err(err) => warn!(%err, "reporting parse statistics failed"),
+    }
+}
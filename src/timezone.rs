@@ -0,0 +1,62 @@
+//! Time zone of the exported times, Berlin unless configured otherwise, e.g. for students following
+//! FU courses from abroad.
+//!
+//! The sessions are converted at the end of processing (see `convert`), so formats with local
+//! times (org, xlsx, HTML, JSCalendar) show them in the configured zone. iCalendar files keep
+//! their times in UTC, which clients show in their own zone anyway, and name the configured zone
+//! in `X-WR-TIMEZONE` for clients that display the calendar's zone.
+//!
+//! With `floating` times, iCalendar files give the local times without a zone instead, so every
+//! client shows them at the same wall-clock time wherever it is (see `ical_time`).
+
+use crate::options::CalendarOptions;
+use crate::{Course, Error, StdError};
+use chrono::DateTime;
+use chrono_tz::Tz;
+use ics::components::Property;
+use ics::ICalendar;
+use timespan::DateTimeSpan;
+
+/// Parse an IANA zone name like "Europe/London".
+pub fn parse(name: &str) -> Result<Tz, Error> {
+    name.parse().map_err(|_| Error::InvalidTimezone {
+        name: name.to_owned(),
+    })
+}
+
+/// Move the sessions of `course` into the zone of `options`, if any.
+pub fn convert(course: &mut Course, options: &CalendarOptions) -> Result<(), StdError> {
+    let timezone = match options.output_timezone() {
+        Some(timezone) => timezone,
+        None => return Ok(()),
+    };
+    for event in course.events.iter_mut().chain(course.cancelled.iter_mut()) {
+        event.timespan = DateTimeSpan::new(
+            event.timespan.start.with_timezone(&timezone),
+            event.timespan.end.with_timezone(&timezone),
+        )?;
+    }
+    Ok(())
+}
+
+/// DATE-TIME value of `time` for `DTSTART` and `DTEND`: UTC, or the local time without a zone if
+/// `options` ask for floating times.
+pub fn ical_time(time: &DateTime<Tz>, options: &CalendarOptions) -> String {
+    if options.floating {
+        time.naive_local().format("%Y%m%dT%H%M%S").to_string()
+    } else {
+        utc_time(time)
+    }
+}
+
+/// UTC DATE-TIME value of `time`, e.g. for `DTSTAMP`, which is never floating.
+pub fn utc_time(time: &DateTime<Tz>) -> String {
+    time.naive_utc().format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Name the zone of `options` in `calendar`, if any.
+pub fn add_hint(calendar: &mut ICalendar, options: &CalendarOptions) {
+    if let Some(timezone) = options.output_timezone() {
+        calendar.push(Property::new("X-WR-TIMEZONE", timezone.name()));
+    }
+}
@@ -0,0 +1,97 @@
+//! Buffer events for the way between buildings, e.g. from Takustraße to the Henry-Ford-Bau,
+//! before sessions in another building than the session right before them on the same day.
+//!
+//! Only rooms `campus` can locate count, sessions in unknown rooms never get a buffer.
+
+use crate::campus::{self, Building};
+use crate::options::CalendarOptions;
+use crate::{sanitize, timezone, uid, Course, CourseEvent};
+use chrono::{DateTime, Duration};
+use chrono_tz::Tz;
+use ics::properties::{Description, DtEnd, DtStart, Location, Summary};
+use ics::Event;
+
+/// Buffer event before a session of the course with the index `course`.
+#[derive(Debug, Clone)]
+pub struct Buffer {
+    pub course: usize,
+    pub event: Event<'static>,
+}
+
+/// Buffers of up to `minutes` before the sessions of `courses` that are in another building than
+/// the previous session. A buffer never starts before the previous session ends.
+pub fn buffers(courses: &[&Course], minutes: u32, options: &CalendarOptions) -> Vec<Buffer> {
+    if minutes == 0 {
+        return vec![];
+    }
+    let mut sessions = courses
+        .iter()
+        .enumerate()
+        .flat_map(|(index, course)| {
+            course
+                .events
+                .iter()
+                // All-day sessions have no time to travel before
+                .filter(|event| !event.all_day)
+                .map(move |event| (index, *course, event))
+        })
+        .collect::<Vec<_>>();
+    sessions.sort_by_key(|(_, _, event)| event.timespan.start);
+
+    let mut buffers = vec![];
+    for pair in sessions.windows(2) {
+        let (_, _, previous) = pair[0];
+        let (index, course, event) = pair[1];
+        if previous.timespan.start.date() != event.timespan.start.date() {
+            continue;
+        }
+        let (from, to) = match (building(previous, options), building(event, options)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+        if from.street == to.street {
+            continue;
+        }
+        let start =
+            (event.timespan.start - Duration::minutes(minutes.into())).max(previous.timespan.end);
+        if start >= event.timespan.start {
+            continue;
+        }
+        buffers.push(Buffer {
+            course: index,
+            event: buffer_event(course, event, previous, start, &to, options),
+        });
+    }
+    buffers
+}
+
+fn building(event: &CourseEvent, options: &CalendarOptions) -> Option<Building> {
+    campus::building_of(event.room.as_ref()?, &options.buildings)
+}
+
+fn buffer_event(
+    course: &Course,
+    event: &CourseEvent,
+    previous: &CourseEvent,
+    start: DateTime<Tz>,
+    to: &Building,
+    options: &CalendarOptions,
+) -> Event<'static> {
+    let room = event.room.clone().unwrap_or_default();
+    let uid = format!(
+        "travel-{}",
+        uid::for_event(options, course.id.as_ref().map(String::as_str), &event.id)
+    );
+    let mut buffer = Event::new(uid, timezone::utc_time(&start));
+    let end = event.timespan.start;
+    buffer.push(DtStart::new(timezone::ical_time(&start, options)));
+    buffer.push(DtEnd::new(timezone::ical_time(&end, options)));
+    buffer.push(Summary::new(sanitize::text(&format!("Weg zu {}", room))));
+    buffer.push(Location::new(sanitize::text(&to.address(&room))));
+    buffer.push(Description::new(sanitize::text(&format!(
+        "Von {} zu {}",
+        previous.room.as_ref().map(String::as_str).unwrap_or(""),
+        event.summary.as_ref().unwrap_or(&course.name)
+    ))));
+    buffer
+}
@@ -0,0 +1,136 @@
+//! UIDs of the generated events.
+//!
+//! Events get the UID `{course id}-{event id}@vv.fu-berlin.de`, which stays the same across
+//! runs, versions and output formats as long as the VV keeps the session. Versions before used
+//! the bare VV event id, which `UidScheme::Legacy` keeps for existing subscriptions. With
+//! `migrate_uids`, events are additionally exported under their legacy UID as cancelled, so
+//! clients drop the old copies instead of showing every session twice.
+
+use crate::options::CalendarOptions;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Right-hand side of generated UIDs
+pub const UID_DOMAIN: &str = "vv.fu-berlin.de";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UidScheme {
+    /// `{course id}-{event id}@vv.fu-berlin.de`
+    Stable,
+    /// The bare VV event id, as generated by versions before the stable scheme
+    Legacy,
+}
+
+impl Default for UidScheme {
+    fn default() -> Self {
+        UidScheme::Stable
+    }
+}
+
+impl FromStr for UidScheme {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "stable" => Ok(UidScheme::Stable),
+            "legacy" => Ok(UidScheme::Legacy),
+            _ => Err(Error::InvalidUidScheme {
+                text: text.to_owned(),
+            }),
+        }
+    }
+}
+
+/// UID of the event `event_id` of the course `course_id`.
+///
+/// Without a course id, e.g. for saved course pages, the legacy UID is used. In busy-only mode
+/// the course id is left out, as the anonymized event id is derived from it (see `busy`).
+pub fn for_event(options: &CalendarOptions, course_id: Option<&str>, event_id: &str) -> String {
+    if options.busy_only {
+        return format!("{}@{}", event_id, UID_DOMAIN);
+    }
+    match (options.uid_scheme, course_id) {
+        (UidScheme::Stable, Some(course_id)) => {
+            format!("{}-{}@{}", course_id, event_id, UID_DOMAIN)
+        }
+        _ => event_id.to_owned(),
+    }
+}
+
+/// The legacy UID an event was exported with before, if it has to be cancelled for migration.
+pub fn migrated_from(options: &CalendarOptions, uid: &str, event_id: &str) -> Option<String> {
+    if options.migrate_uids && !options.busy_only && uid != event_id {
+        Some(event_id.to_owned())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(uid_scheme: UidScheme, migrate_uids: bool, busy_only: bool) -> CalendarOptions {
+        CalendarOptions {
+            uid_scheme,
+            migrate_uids,
+            busy_only,
+            ..CalendarOptions::default()
+        }
+    }
+
+    #[test]
+    fn parses_schemes() {
+        assert_eq!("stable".parse::<UidScheme>().unwrap(), UidScheme::Stable);
+        assert_eq!("legacy".parse::<UidScheme>().unwrap(), UidScheme::Legacy);
+        assert!("Stable".parse::<UidScheme>().is_err());
+        assert_eq!(UidScheme::default(), UidScheme::Stable);
+    }
+
+    #[test]
+    fn stable_uids_contain_the_course() {
+        let options = options(UidScheme::Stable, false, false);
+        assert_eq!(
+            for_event(&options, Some("524870"), "500001"),
+            "524870-500001@vv.fu-berlin.de"
+        );
+        // Saved course pages have no course id
+        assert_eq!(for_event(&options, None, "500001"), "500001");
+    }
+
+    #[test]
+    fn legacy_uids_are_event_ids() {
+        let options = options(UidScheme::Legacy, false, false);
+        assert_eq!(for_event(&options, Some("524870"), "500001"), "500001");
+    }
+
+    #[test]
+    fn busy_only_uids_leave_out_the_course() {
+        for scheme in &[UidScheme::Stable, UidScheme::Legacy] {
+            let options = options(*scheme, true, true);
+            assert_eq!(
+                for_event(&options, Some("524870"), "busy_1a2b"),
+                "busy_1a2b@vv.fu-berlin.de"
+            );
+            assert_eq!(
+                migrated_from(&options, "busy_1a2b@vv.fu-berlin.de", "busy_1a2b"),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn migrates_changed_uids_only_if_asked() {
+        let uid = "524870-500001@vv.fu-berlin.de";
+        let migrating = options(UidScheme::Stable, true, false);
+        assert_eq!(
+            migrated_from(&migrating, uid, "500001"),
+            Some("500001".to_owned())
+        );
+        assert_eq!(migrated_from(&migrating, "500001", "500001"), None);
+        let options = options(UidScheme::Stable, false, false);
+        assert_eq!(migrated_from(&options, uid, "500001"), None);
+    }
+}
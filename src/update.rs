@@ -0,0 +1,382 @@
+//! In-place update of calendar files written by an earlier export, for clients that import files
+//! instead of subscribing to them.
+//!
+//! Importing clients match events by UID and only take over changes with a higher `SEQUENCE`.
+//! So instead of writing the calendar from scratch, every event of the previous file is compared
+//! with its freshly generated version: unchanged events are kept as they were, changed ones get a
+//! `SEQUENCE` above the previous one, and events that are gone are kept with `STATUS:CANCELLED`.
+//! Events that weren't generated by an export (e.g. added to the file by hand) are left alone.
+
+use crate::merge::{property_value, unfold};
+use crate::uid::UID_DOMAIN;
+use crate::StdError;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// Properties that differ between two exports even if the event didn't change
+const VOLATILE_PROPERTIES: &[&str] = &["DTSTAMP", "SEQUENCE"];
+
+/// What `update` did to the events of the previous file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UpdateStats {
+    pub added: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    pub cancelled: usize,
+}
+
+/// Update the calendar `previous` to the generated `calendar`, see the module documentation.
+///
+/// The calendar properties are taken from `calendar`. Returns the unfolded content, to be folded
+/// with `sanitize::fold`.
+pub fn update(previous: &str, calendar: &str) -> (String, UpdateStats) {
+    let mut stats = UpdateStats::default();
+    let (head, generated, tail) = split(&unfold(calendar));
+    let (_, previous_events, _) = split(&unfold(previous));
+
+    let mut previous_by_key = HashMap::new();
+    for (index, event) in previous_events.iter().enumerate() {
+        previous_by_key.insert(key(event), index);
+    }
+
+    let mut kept = vec![false; previous_events.len()];
+    let mut events = vec![];
+    for event in generated {
+        let index = match previous_by_key.get(&key(&event)) {
+            Some(&index) => index,
+            None => {
+                stats.added += 1;
+                events.push(event);
+                continue;
+            }
+        };
+        kept[index] = true;
+        let old = &previous_events[index];
+        if stable_lines(old) == stable_lines(&event) {
+            stats.unchanged += 1;
+            events.push(old.clone());
+        } else {
+            stats.changed += 1;
+            let sequence = sequence(&event).max(sequence(old) + 1);
+            events.push(with_property(event, "SEQUENCE", &sequence.to_string()));
+        }
+    }
+    for (event, _) in previous_events
+        .into_iter()
+        .zip(kept)
+        .filter(|(_, kept)| !kept)
+    {
+        let cancelled = event.iter().any(|line| {
+            property_value(line, "STATUS")
+                .map_or(false, |status| status.eq_ignore_ascii_case("CANCELLED"))
+        });
+        if cancelled || !is_generated(&event) {
+            events.push(event);
+            continue;
+        }
+        stats.cancelled += 1;
+        let sequence = (sequence(&event) + 1).to_string();
+        let event = with_property(event, "SEQUENCE", &sequence);
+        events.push(with_property(event, "STATUS", "CANCELLED"));
+    }
+
+    let mut content = head
+        .into_iter()
+        .chain(events.into_iter().flatten())
+        .chain(tail)
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    content.push_str("\r\n");
+    (content, stats)
+}
+
+/// Lines before the first event, the events and the lines after the last one.
+fn split(lines: &[String]) -> (Vec<String>, Vec<Vec<String>>, Vec<String>) {
+    let mut head = vec![];
+    let mut events = vec![];
+    let mut tail = vec![];
+    let mut event: Option<Vec<String>> = None;
+    for line in lines.iter().cloned() {
+        if let Some(mut current) = event.take() {
+            let end = line.eq_ignore_ascii_case("END:VEVENT");
+            current.push(line);
+            if end {
+                events.push(current);
+            } else {
+                event = Some(current);
+            }
+        } else if line.eq_ignore_ascii_case("BEGIN:VEVENT") {
+            event = Some(vec![line]);
+        } else if events.is_empty() && !line.eq_ignore_ascii_case("END:VCALENDAR") {
+            head.push(line);
+        } else {
+            // Other components between or after the events are moved behind them
+            tail.push(line);
+        }
+    }
+    (head, events, tail)
+}
+
+/// Whether the calendars `previous` and `calendar` only differ in their `DTSTAMP`s, which change
+/// with every export.
+pub fn same_content(previous: &str, calendar: &str) -> bool {
+    let lines = |content: &str| {
+        unfold(content)
+            .into_iter()
+            .filter(|line| property_value(line, "DTSTAMP").is_none())
+            .collect::<Vec<_>>()
+    };
+    lines(previous) == lines(calendar)
+}
+
+/// Write the calendar `content` to `path` unless the file there already has the same content
+/// (see `same_content`), so its modification time only changes along with the calendar and sync
+/// tools don't upload it again. Returns whether the file was written.
+pub fn write_if_changed(path: &Path, content: &str) -> Result<bool, StdError> {
+    match std::fs::read_to_string(path) {
+        Ok(previous) if same_content(&previous, content) => return Ok(false),
+        Ok(_) => {}
+        Err(ref err)
+            if err.kind() == ErrorKind::NotFound || err.kind() == ErrorKind::InvalidData => {}
+        Err(err) => return Err(err.into()),
+    }
+    std::fs::write(path, content)?;
+    Ok(true)
+}
+
+/// UID and `RECURRENCE-ID` of `event`, which identify it for calendar clients.
+fn key(event: &[String]) -> (String, Option<String>) {
+    let uid = event
+        .iter()
+        .find_map(|line| property_value(line, "UID"))
+        .unwrap_or("");
+    let recurrence_id = event
+        .iter()
+        .find_map(|line| property_value(line, "RECURRENCE-ID"));
+    (uid.to_owned(), recurrence_id.map(str::to_owned))
+}
+
+/// Whether `event` was generated by an export, judged by its `X-FU-VV-CAL-SOURCE` or its UID:
+/// generated UIDs end in `UID_DOMAIN` (which merged calendars keep when rewriting colliding UIDs)
+/// or, with the legacy scheme, have no domain at all, unlike the UIDs of calendar clients.
+fn is_generated(event: &[String]) -> bool {
+    let domain = format!("@{}", UID_DOMAIN);
+    event.iter().any(|line| {
+        property_value(line, "UID").map_or(false, |uid| uid.contains(&domain) || !uid.contains('@'))
+            || property_value(line, "X-FU-VV-CAL-SOURCE").is_some()
+    })
+}
+
+fn stable_lines(event: &[String]) -> Vec<&String> {
+    event
+        .iter()
+        .filter(|line| {
+            !VOLATILE_PROPERTIES
+                .iter()
+                .any(|name| property_value(line, name).is_some())
+        })
+        .collect()
+}
+
+fn sequence(event: &[String]) -> u32 {
+    event
+        .iter()
+        .find_map(|line| property_value(line, "SEQUENCE"))
+        .and_then(|sequence| sequence.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// `event` with the property `name` set to `value`, replacing its previous value. New properties
+/// go before the alarms, which must come after the properties of the event.
+fn with_property(mut event: Vec<String>, name: &str, value: &str) -> Vec<String> {
+    let line = format!("{}:{}", name, value);
+    let nested = event
+        .iter()
+        .skip(1)
+        .position(|existing| existing.to_ascii_uppercase().starts_with("BEGIN:"))
+        .map(|index| index + 1)
+        .unwrap_or(event.len() - 1);
+    match event[..nested]
+        .iter()
+        .position(|existing| property_value(existing, name).is_some())
+    {
+        Some(index) => event[index] = line,
+        None => event.insert(nested, line),
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:fu-vv-cal\r\n\
+                            BEGIN:VEVENT\r\nUID:1-a@vv.fu-berlin.de\r\n\
+                            DTSTAMP:20191014T080000Z\r\nDTSTART:20191014T100000\r\n\
+                            SUMMARY:Vorlesung\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+    /// A path in the temporary directory, unique to `name` and this test run.
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "fu-vv-cal-update-{}-{}.ics",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn event(uid: &str, start: &str, extra: &str) -> String {
+        format!(
+            "BEGIN:VEVENT\r\nUID:{}\r\nDTSTAMP:20191014T080000Z\r\nDTSTART:{}\r\n{}\
+             SUMMARY:Vorlesung\r\nEND:VEVENT\r\n",
+            uid, start, extra
+        )
+    }
+
+    fn calendar(events: &[String]) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:fu-vv-cal\r\n{}END:VCALENDAR\r\n",
+            events.concat()
+        )
+    }
+
+    /// The unfolded event with `uid` in `content`.
+    fn find<'a>(content: &'a str, uid: &str) -> Vec<&'a str> {
+        let lines = content.split("\r\n").collect::<Vec<_>>();
+        let start = lines
+            .iter()
+            .position(|line| *line == format!("UID:{}", uid))
+            .unwrap()
+            - 1;
+        let len = lines[start..]
+            .iter()
+            .position(|line| *line == "END:VEVENT")
+            .unwrap();
+        lines[start..=start + len].to_vec()
+    }
+
+    #[test]
+    fn keeps_unchanged_events() {
+        let previous = calendar(&[event(
+            "1-a@vv.fu-berlin.de",
+            "20191014T100000",
+            "SEQUENCE:2\r\n",
+        )]);
+        let generated = calendar(&[event("1-a@vv.fu-berlin.de", "20191014T100000", "")])
+            .replace("DTSTAMP:20191014T080000Z", "DTSTAMP:20191021T080000Z");
+
+        let (content, stats) = update(&previous, &generated);
+        assert_eq!(content, previous);
+        assert_eq!(stats.unchanged, 1);
+    }
+
+    #[test]
+    fn replaces_changed_events_with_a_higher_sequence() {
+        let previous = calendar(&[event(
+            "1-a@vv.fu-berlin.de",
+            "20191014T100000",
+            "SEQUENCE:2\r\n",
+        )]);
+        let generated = calendar(&[
+            event("1-a@vv.fu-berlin.de", "20191014T120000", ""),
+            event("1-b@vv.fu-berlin.de", "20191021T100000", ""),
+        ]);
+
+        let (content, stats) = update(&previous, &generated);
+        let changed = find(&content, "1-a@vv.fu-berlin.de");
+        assert!(changed.contains(&"DTSTART:20191014T120000"));
+        assert!(changed.contains(&"SEQUENCE:3"));
+        assert!(!changed.contains(&"DTSTART:20191014T100000"));
+        assert!(content.contains("UID:1-b@vv.fu-berlin.de"));
+        assert_eq!((stats.changed, stats.added), (1, 1));
+    }
+
+    #[test]
+    fn cancels_events_that_are_gone() {
+        let previous = calendar(&[
+            event("1-a@vv.fu-berlin.de", "20191014T100000", ""),
+            event("1-b@vv.fu-berlin.de", "20191021T100000", ""),
+        ]);
+        let generated = calendar(&[event("1-a@vv.fu-berlin.de", "20191014T100000", "")]);
+
+        let (content, stats) = update(&previous, &generated);
+        let cancelled = find(&content, "1-b@vv.fu-berlin.de");
+        assert!(cancelled.contains(&"STATUS:CANCELLED"));
+        assert!(cancelled.contains(&"SEQUENCE:1"));
+        assert_eq!(stats.cancelled, 1);
+
+        // Cancelled events stay as they are
+        let (again, stats) = update(&content, &generated);
+        assert_eq!(find(&again, "1-b@vv.fu-berlin.de"), cancelled);
+        assert_eq!(stats.cancelled, 0);
+    }
+
+    #[test]
+    fn cancels_events_with_legacy_uids() {
+        let previous = calendar(&[event("500001", "20191014T100000", "")]);
+        let generated = calendar(&[event("1-a@vv.fu-berlin.de", "20191014T100000", "")]);
+
+        let (content, stats) = update(&previous, &generated);
+        assert!(find(&content, "500001").contains(&"STATUS:CANCELLED"));
+        assert_eq!((stats.added, stats.cancelled), (1, 1));
+    }
+
+    #[test]
+    fn preserves_foreign_events() {
+        let foreign = event("dentist@example.com", "20191015T090000", "");
+        let previous = calendar(&[
+            event("1-a@vv.fu-berlin.de", "20191014T100000", ""),
+            foreign.clone(),
+        ]);
+        let generated = calendar(&[event("1-a@vv.fu-berlin.de", "20191014T120000", "")]);
+
+        let (content, stats) = update(&previous, &generated);
+        assert!(content.contains(&foreign));
+        assert_eq!((stats.changed, stats.cancelled), (1, 0));
+    }
+
+    #[test]
+    fn same_content_ignores_dtstamp() {
+        let later = CALENDAR.replace("DTSTAMP:20191014T080000Z", "DTSTAMP:20191021T080000Z");
+        assert!(same_content(CALENDAR, &later));
+        assert!(same_content(CALENDAR, &CALENDAR.replace("\r\n", "\n")));
+        let moved = CALENDAR.replace("DTSTART:20191014T100000", "DTSTART:20191014T120000");
+        assert!(!same_content(CALENDAR, &moved));
+    }
+
+    #[test]
+    fn keeps_files_differing_in_dtstamp_only() {
+        let path = temp_path("dtstamp");
+        std::fs::write(&path, CALENDAR).unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+        let later = CALENDAR.replace("DTSTAMP:20191014T080000Z", "DTSTAMP:20191021T080000Z");
+        assert!(!write_if_changed(&path, &later).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), CALENDAR);
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.modified().unwrap(), modified);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rewrites_changed_files() {
+        let path = temp_path("changed");
+        std::fs::write(&path, CALENDAR).unwrap();
+
+        let renamed = CALENDAR.replace("SUMMARY:Vorlesung", "SUMMARY:Übung");
+        assert!(write_if_changed(&path, &renamed).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), renamed);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn creates_missing_files() {
+        let path = temp_path("missing");
+        assert!(write_if_changed(&path, CALENDAR).unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), CALENDAR);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+//! Resource usage of a run, to help tuning request delays, caching and large bundles.
+
+use lazy_static::lazy_static;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+static BYTES_DOWNLOADED: AtomicU64 = AtomicU64::new(0);
+static PAGES_FETCHED: AtomicU64 = AtomicU64::new(0);
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    /// Wall-clock time of the stages of the run, in order
+    static ref STAGES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(vec![]);
+}
+
+/// A response body of `bytes` was downloaded.
+pub fn record_download(bytes: usize) {
+    BYTES_DOWNLOADED.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// A course page was fetched from the VV.
+pub fn record_page() {
+    PAGES_FETCHED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A course page was taken from the cache, see `cache`.
+pub fn record_cache_hit() {
+    CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Forget the stages of the previous run, e.g. between the exports of watch mode.
+pub fn reset_stages() {
+    STAGES.lock().unwrap().clear();
+}
+
+/// The stage `name` of the run took `duration`.
+pub fn record_stage(name: &'static str, duration: Duration) {
+    STAGES.lock().unwrap().push((name, duration));
+}
+
+#[derive(Debug, Clone)]
+pub struct Usage {
+    pub bytes_downloaded: u64,
+    pub pages_fetched: u64,
+    pub cache_hits: u64,
+    pub stages: Vec<(&'static str, Duration)>,
+    /// Peak resident memory in bytes, where the platform reports it
+    pub peak_memory: Option<u64>,
+}
+
+impl Usage {
+    /// Usage of the process so far.
+    pub fn current() -> Self {
+        Self {
+            bytes_downloaded: BYTES_DOWNLOADED.load(Ordering::Relaxed),
+            pages_fetched: PAGES_FETCHED.load(Ordering::Relaxed),
+            cache_hits: CACHE_HITS.load(Ordering::Relaxed),
+            stages: STAGES.lock().unwrap().clone(),
+            peak_memory: peak_memory(),
+        }
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Downloaded: {:.1} KiB",
+            self.bytes_downloaded as f64 / 1024.0
+        )?;
+        writeln!(f, "Pages fetched: {}", self.pages_fetched)?;
+        writeln!(f, "Cache hits: {}", self.cache_hits)?;
+        for (name, duration) in self.stages.iter() {
+            writeln!(f, "Stage {}: {:.2}s", name, duration.as_secs_f64())?;
+        }
+        match self.peak_memory {
+            Some(bytes) => write!(f, "Peak memory: {:.1} MiB", bytes as f64 / 1024.0 / 1024.0),
+            None => write!(f, "Peak memory: unknown"),
+        }
+    }
+}
+
+/// `VmHWM` of `/proc/self/status`, only available on Linux.
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes = line["VmHWM:".len()..]
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
@@ -0,0 +1,329 @@
+//! Checks of generated iCalendar output against the parts of RFC 5545 strict clients enforce.
+
+use crate::sanitize::MAX_LINE_OCTETS;
+use chrono::{NaiveDate, NaiveDateTime};
+use std::fmt;
+
+/// Properties every calendar needs
+const CALENDAR_PROPERTIES: &[&str] = &["VERSION", "PRODID"];
+/// Properties every event needs
+const EVENT_PROPERTIES: &[&str] = &["UID", "DTSTAMP", "DTSTART"];
+/// Properties whose value is a single TEXT, which has to be escaped
+const TEXT_PROPERTIES: &[&str] = &["SUMMARY", "DESCRIPTION", "LOCATION", "COMMENT", "CONTACT"];
+
+/// A problem of a generated calendar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// Line of the (folded) calendar the problem was found in, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Properties of the component that is currently being read.
+#[derive(Debug)]
+struct Component {
+    name: String,
+    line: usize,
+    properties: Vec<(String, String)>,
+}
+
+/// All problems of the iCalendar `content`.
+pub fn validate(content: &str) -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut problem = |line: usize, message: String| problems.push(Problem { line, message });
+
+    let mut stack: Vec<Component> = vec![];
+    let mut found_calendar = false;
+    for (line, content_line) in unfold(content, &mut problem) {
+        let (name, value) = match split_content_line(&content_line) {
+            Some(split) => split,
+            None => {
+                problem(line, "content line without a property name".to_owned());
+                continue;
+            }
+        };
+
+        match name.as_str() {
+            "BEGIN" => stack.push(Component {
+                name: value.to_uppercase(),
+                line,
+                properties: vec![],
+            }),
+            "END" => match stack.pop() {
+                Some(component) if component.name == value.to_uppercase() => {
+                    found_calendar |= component.name == "VCALENDAR";
+                    check_component(&component, &mut problem);
+                }
+                Some(component) => problem(
+                    line,
+                    format!("END:{} closes BEGIN:{}", value, component.name),
+                ),
+                None => problem(line, format!("END:{} without BEGIN", value)),
+            },
+            _ => {
+                if TEXT_PROPERTIES.contains(&name.as_str()) {
+                    if let Some(message) = escaping_problem(&value) {
+                        problem(line, format!("{} {}", name, message));
+                    }
+                }
+                match stack.last_mut() {
+                    Some(component) => component.properties.push((name, value)),
+                    None => problem(line, format!("{} outside of a component", name)),
+                }
+            }
+        }
+    }
+
+    for component in stack {
+        problem(
+            component.line,
+            format!("BEGIN:{} is never closed", component.name),
+        );
+    }
+    if !found_calendar {
+        problem(1, "no VCALENDAR component".to_owned());
+    }
+
+    problems
+}
+
+/// The unfolded content lines with the number of their first line, reporting overlong lines.
+fn unfold<F: FnMut(usize, String)>(content: &str, problem: &mut F) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = vec![];
+    for (index, line) in content.split('\n').enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.len() > MAX_LINE_OCTETS {
+            problem(
+                index + 1,
+                format!(
+                    "line is {} octets long, lines have to be folded after {}",
+                    line.len(),
+                    MAX_LINE_OCTETS
+                ),
+            );
+        }
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, previous)) = lines.last_mut() {
+                previous.push_str(&line[1..]);
+                continue;
+            }
+        }
+        if !line.is_empty() {
+            lines.push((index + 1, line.to_owned()));
+        }
+    }
+    lines
+}
+
+/// Property name (without parameters) and value of a content line.
+fn split_content_line(line: &str) -> Option<(String, String)> {
+    let colon = value_start(line)?;
+    let name_end = line[..colon].find(';').unwrap_or(colon);
+    let name = line[..name_end].trim();
+    if name.is_empty() {
+        return None;
+    }
+    Some((name.to_uppercase(), line[colon + 1..].to_owned()))
+}
+
+/// Position of the colon separating name and parameters from the value, skipping quoted
+/// parameter values.
+fn value_start(line: &str) -> Option<usize> {
+    let mut quoted = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Why a TEXT value isn't escaped correctly, if it isn't.
+fn escaping_problem(value: &str) -> Option<String> {
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\\') | Some(';') | Some(',') | Some('n') | Some('N') => {}
+                Some(other) => return Some(format!("contains the invalid escape \\{}", other)),
+                None => return Some("ends with an unescaped backslash".to_owned()),
+            },
+            ';' | ',' => return Some(format!("contains an unescaped \"{}\"", c)),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn check_component<F: FnMut(usize, String)>(component: &Component, problem: &mut F) {
+    let property = |name: &str| {
+        component
+            .properties
+            .iter()
+            .find(|(property, _)| property == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let required = match component.name.as_str() {
+        "VCALENDAR" => CALENDAR_PROPERTIES,
+        "VEVENT" => EVENT_PROPERTIES,
+        _ => return,
+    };
+    for name in required {
+        if property(name).is_none() {
+            problem(
+                component.line,
+                format!("{} without {}", component.name, name),
+            );
+        }
+    }
+
+    if component.name == "VEVENT" {
+        if let (Some(start), Some(end)) = (property("DTSTART"), property("DTEND")) {
+            if let (Some(start), Some(end)) = (parse_date_time(start), parse_date_time(end)) {
+                if end < start {
+                    problem(
+                        component.line,
+                        format!(
+                            "VEVENT {} ends before it starts",
+                            property("UID").unwrap_or("")
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// DATE-TIME or DATE value, ignoring the time zone (start and end of generated events share it).
+fn parse_date_time(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .ok()
+                .map(|date| date.and_hms(0, 0, 0))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT: &str = "BEGIN:VEVENT\r\nUID:1-500001@vv.fu-berlin.de\r\n\
+                         DTSTAMP:20191014T080000Z\r\nDTSTART:20191014T080000Z\r\n\
+                         DTEND:20191014T100000Z\r\nSUMMARY:Analysis I\\, Vorlesung\r\n\
+                         END:VEVENT\r\n";
+
+    fn calendar(events: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:fu-vv-cal\r\n{}END:VCALENDAR\r\n",
+            events
+        )
+    }
+
+    fn messages(content: &str) -> Vec<String> {
+        validate(content)
+            .into_iter()
+            .map(|problem| problem.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn accepts_valid_calendars() {
+        assert!(validate(&calendar(EVENT)).is_empty());
+        let folded = calendar(&EVENT.replace("Analysis I", "Analysis I\r\n  "));
+        assert!(validate(&folded).is_empty());
+    }
+
+    #[test]
+    fn reports_missing_properties() {
+        let event = EVENT.replace("DTSTAMP:20191014T080000Z\r\n", "");
+        assert_eq!(
+            messages(&calendar(&event).replace("PRODID:fu-vv-cal\r\n", "")),
+            vec![
+                "line 3: VEVENT without DTSTAMP",
+                "line 1: VCALENDAR without PRODID"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unbalanced_components() {
+        assert_eq!(
+            messages(&calendar(&EVENT.replace("END:VEVENT", "END:VTODO"))),
+            vec!["line 10: END:VTODO closes BEGIN:VEVENT"]
+        );
+        assert_eq!(
+            messages(&calendar("BEGIN:VEVENT\r\n")),
+            vec![
+                "line 5: END:VCALENDAR closes BEGIN:VEVENT",
+                "line 1: BEGIN:VCALENDAR is never closed",
+                "line 1: no VCALENDAR component",
+            ]
+        );
+        assert_eq!(
+            messages("VERSION:2.0\r\n"),
+            vec![
+                "line 1: VERSION outside of a component",
+                "line 1: no VCALENDAR component"
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unescaped_text() {
+        let event = EVENT.replace("Analysis I\\, Vorlesung", "Analysis I, Vorlesung\\");
+        assert_eq!(
+            messages(&calendar(&event)),
+            vec!["line 9: SUMMARY contains an unescaped \",\""]
+        );
+        assert_eq!(
+            escaping_problem("C:\\Temp"),
+            Some("contains the invalid escape \\T".to_owned())
+        );
+        assert_eq!(
+            escaping_problem("Raum 1\\"),
+            Some("ends with an unescaped backslash".to_owned())
+        );
+    }
+
+    #[test]
+    fn reports_long_lines() {
+        let event = EVENT.replace("Analysis I", &"x".repeat(80));
+        assert_eq!(
+            messages(&calendar(&event)),
+            vec!["line 9: line is 100 octets long, lines have to be folded after 75"]
+        );
+    }
+
+    #[test]
+    fn reports_events_ending_before_they_start() {
+        let event = EVENT.replace("DTEND:20191014T100000Z", "DTEND:20191014T070000Z");
+        assert_eq!(
+            messages(&calendar(&event)),
+            vec!["line 4: VEVENT 1-500001@vv.fu-berlin.de ends before it starts"]
+        );
+        let all_day = EVENT
+            .replace("DTSTART:20191014T080000Z", "DTSTART;VALUE=DATE:20191014")
+            .replace("DTEND:20191014T100000Z", "DTEND;VALUE=DATE:20191015");
+        assert!(validate(&calendar(&all_day)).is_empty());
+    }
+
+    #[test]
+    fn skips_quoted_colons() {
+        let event = EVENT.replace(
+            "SUMMARY:",
+            "ORGANIZER;CN=\"Prof. Dr. Müller: Analysis\":mailto:mueller@fu-berlin.de\r\nSUMMARY:",
+        );
+        assert!(validate(&calendar(&event)).is_empty());
+    }
+}
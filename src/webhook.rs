@@ -0,0 +1,44 @@
+use crate::changes::Change;
+use crate::config::WebhookConfig;
+use crate::fetch::{self, HttpRequest};
+use crate::StdError;
+use http::header::CONTENT_TYPE;
+use http::Method;
+use serde_json::json;
+use tracing::warn;
+
+/// POST `changes` as JSON to all configured webhooks.
+///
+/// Besides the structured `changes`, the payload contains a human readable summary as `text`
+/// (Slack, Matrix) and `content` (Discord), so chat integrations work without an adapter. Failing
+/// webhooks only produce a warning.
+pub async fn notify(webhooks: &[WebhookConfig], changes: &[Change]) -> Result<(), StdError> {
+    if webhooks.is_empty() || changes.is_empty() {
+        return Ok(());
+    }
+
+    let summary = changes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let payload = json!({
+        "text": summary,
+        "content": summary,
+        "changes": changes,
+    })
+    .to_string();
+
+    for webhook in webhooks.iter() {
+        let req = HttpRequest::new(Method::POST, webhook.url.as_str())
+            .header(CONTENT_TYPE, "application/json")?
+            .with_body(payload.clone());
+        match fetch::send(req).await {
+            Ok((status, _)) if status.is_success() => {}
+            Ok((status, _)) => warn!(url = webhook.url.as_str(), %status, "webhook failed"),
+            Err(err) => warn!(url = webhook.url.as_str(), %err, "webhook failed"),
+        }
+    }
+
+    Ok(())
+}
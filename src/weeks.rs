@@ -0,0 +1,101 @@
+use crate::Course;
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Weekday};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Columns of the week grid, see `week_grid`
+pub const WEEKDAYS: &[(Weekday, &str)] = &[
+    (Weekday::Mon, "Montag"),
+    (Weekday::Tue, "Dienstag"),
+    (Weekday::Wed, "Mittwoch"),
+    (Weekday::Thu, "Donnerstag"),
+    (Weekday::Fri, "Freitag"),
+    (Weekday::Sat, "Samstag"),
+];
+
+/// Row of the week grid: the courses taking place at a time of day, per weekday.
+#[derive(Debug, Clone)]
+pub struct GridSlot {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// Entries per day of `WEEKDAYS`
+    pub days: Vec<BTreeSet<GridEntry>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GridEntry {
+    pub title: String,
+    pub room: Option<String>,
+    /// Page of the course in the VV
+    pub url: Option<String>,
+}
+
+/// Monday of the week of the earliest session in `courses`, used as the start of the lecture
+/// period if none is configured.
+pub fn first_week_start<'a, I: IntoIterator<Item = &'a Course>>(courses: I) -> Option<NaiveDate> {
+    courses
+        .into_iter()
+        .flat_map(|course| course.events.iter())
+        .map(|event| event.timespan.start.naive_local().date())
+        .min()
+        .map(week_start)
+}
+
+/// Lecture week of `date`, counted from 1 for the week `lecture_start` is in.
+///
+/// Weeks are counted continuously (including breaks), independent of calendar weeks. Dates before
+/// the lecture period have no week.
+pub fn week_number(lecture_start: NaiveDate, date: NaiveDate) -> Option<u32> {
+    let days = date
+        .signed_duration_since(week_start(lecture_start))
+        .num_days();
+    if days < 0 {
+        return None;
+    }
+    Some(days as u32 / 7 + 1)
+}
+
+/// Set the lecture week of every session of `course`.
+pub fn assign_weeks(course: &mut Course, lecture_start: NaiveDate) {
+    for event in course.events.iter_mut().chain(course.cancelled.iter_mut()) {
+        event.week = week_number(lecture_start, event.timespan.start.naive_local().date());
+    }
+}
+
+/// Monday of the week of `date`.
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+}
+
+/// The sessions of `courses` as a typical week, one slot per time of day.
+///
+/// A course shows up in a slot if any of its sessions does, so irregular sessions are listed
+/// like weekly ones. Sessions on Sundays are left out.
+pub fn week_grid(courses: &[Course]) -> Vec<GridSlot> {
+    let mut slots = BTreeMap::<(NaiveTime, NaiveTime), Vec<BTreeSet<GridEntry>>>::new();
+    for course in courses {
+        for event in course.events.iter() {
+            let start = event.timespan.start.naive_local();
+            let end = event.timespan.end.naive_local();
+            let day = match WEEKDAYS
+                .iter()
+                .position(|(weekday, _)| *weekday == start.weekday())
+            {
+                Some(day) => day,
+                None => continue,
+            };
+            slots
+                .entry((start.time(), end.time()))
+                .or_insert_with(|| vec![BTreeSet::new(); WEEKDAYS.len()])[day]
+                .insert(GridEntry {
+                    title: event.summary.clone().unwrap_or_else(|| course.name.clone()),
+                    room: event.room.clone(),
+                    url: course.url.clone(),
+                });
+        }
+    }
+
+    slots
+        .into_iter()
+        .map(|((start, end), days)| GridSlot { start, end, days })
+        .collect()
+}
@@ -0,0 +1,140 @@
+//! Excel export for departments that still circulate schedules as spreadsheets: one sheet per
+//! course with its sessions, and a week grid of all courses.
+
+use crate::weeks::{self, WEEKDAYS};
+use crate::{Course, CourseEvent, StdError};
+use simple_excel_writer::{Column, Row, Workbook};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Longest sheet name Excel accepts
+const MAX_SHEET_NAME: usize = 31;
+
+/// Write the sessions of `courses` to the workbook at `path`, see `to_bytes`.
+pub fn save<P: AsRef<Path>>(title: &str, courses: &[Course], path: P) -> Result<(), StdError> {
+    std::fs::write(path, to_bytes(title, courses)?)?;
+    Ok(())
+}
+
+/// Workbook with the sessions of `courses`, the week grid first under the name `title`.
+pub fn to_bytes(title: &str, courses: &[Course]) -> Result<Vec<u8>, StdError> {
+    let mut workbook = Workbook::create_in_memory();
+    let mut names = BTreeSet::new();
+
+    let mut grid = workbook.create_sheet(&sheet_name(title, &mut names));
+    grid.add_column(Column { width: 14.0 });
+    for _ in WEEKDAYS {
+        grid.add_column(Column { width: 30.0 });
+    }
+    let rows = week_grid(courses);
+    workbook.write_sheet(&mut grid, |writer| {
+        let mut header = Row::new();
+        header.add_cell("Zeit");
+        for (_, name) in WEEKDAYS {
+            header.add_cell(*name);
+        }
+        writer.append_row(header)?;
+        for row in rows {
+            writer.append_row(row)?;
+        }
+        Ok(())
+    })?;
+
+    for course in courses {
+        let mut sheet = workbook.create_sheet(&sheet_name(&course.name, &mut names));
+        for width in &[12.0, 8.0, 8.0, 30.0, 20.0, 30.0, 8.0, 40.0] {
+            sheet.add_column(Column { width: *width });
+        }
+        workbook.write_sheet(&mut sheet, |writer| {
+            let mut header = Row::new();
+            for title in &[
+                "Datum",
+                "Beginn",
+                "Ende",
+                "Titel",
+                "Raum",
+                "Lehrende",
+                "Woche",
+                "Bemerkung",
+            ] {
+                header.add_cell(*title);
+            }
+            writer.append_row(header)?;
+            for event in course.events.iter() {
+                writer.append_row(session_row(course, event))?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(workbook.close()?.unwrap_or_default())
+}
+
+fn session_row(course: &Course, event: &CourseEvent) -> Row {
+    let start = event.timespan.start.naive_local();
+    let end = event.timespan.end.naive_local();
+    let mut row = Row::new();
+    row.add_cell(start.format("%d.%m.%Y").to_string());
+    row.add_cell(start.format("%H:%M").to_string());
+    row.add_cell(end.format("%H:%M").to_string());
+    row.add_cell(event.summary.clone().unwrap_or_else(|| course.name.clone()));
+    row.add_cell(event.room.clone().unwrap_or_default());
+    row.add_cell(event.lecturers.join(", "));
+    match event.week {
+        Some(week) => row.add_cell(f64::from(week)),
+        None => row.add_cell(()),
+    }
+    row.add_cell(event.remark.clone().unwrap_or_default());
+    row
+}
+
+/// Rows of the week grid, see `weeks::week_grid`.
+fn week_grid(courses: &[Course]) -> Vec<Row> {
+    weeks::week_grid(courses)
+        .into_iter()
+        .map(|slot| {
+            let mut row = Row::new();
+            row.add_cell(format!(
+                "{}–{}",
+                slot.start.format("%H:%M"),
+                slot.end.format("%H:%M")
+            ));
+            for entries in slot.days {
+                let entries = entries
+                    .into_iter()
+                    .map(|entry| match entry.room {
+                        Some(room) => format!("{} ({})", entry.title, room),
+                        None => entry.title,
+                    })
+                    .collect::<Vec<_>>();
+                row.add_cell(entries.join("\n"));
+            }
+            row
+        })
+        .collect()
+}
+
+/// `name` as a sheet name Excel accepts, distinct from the `taken` ones.
+fn sheet_name(name: &str, taken: &mut BTreeSet<String>) -> String {
+    let base = name
+        .chars()
+        .map(|c| match c {
+            '[' | ']' | ':' | '*' | '?' | '/' | '\\' => ' ',
+            c => c,
+        })
+        .take(MAX_SHEET_NAME)
+        .collect::<String>();
+    let mut candidate = base.clone();
+    let mut number = 2;
+    while taken.contains(&candidate.to_lowercase()) {
+        let suffix = format!(" ({})", number);
+        candidate = base
+            .chars()
+            .take(MAX_SHEET_NAME - suffix.chars().count())
+            .collect::<String>()
+            + &suffix;
+        number += 1;
+    }
+    taken.insert(candidate.to_lowercase());
+    candidate
+}
@@ -0,0 +1,30 @@
+//! WebAssembly interface to the parser and calendar generator of fu-vv-cal, for converting course
+//! pages in the browser.
+//!
+//! Fetching isn't part of it (the VV doesn't allow cross-origin requests), the page calling
+//! `courseToIcs` passes in HTML it got by other means. Build with
+//! `wasm-pack build --target web wasm`.
+
+use fu_vv_cal::options::CalendarOptions;
+use fu_vv_cal::{sanitize, Course};
+use wasm_bindgen::prelude::*;
+
+/// Parse the HTML of a course page of the VV and return its calendar as ICS text.
+///
+/// `id` is the course id from the URL of the page (the segment after `/lv/`), it keeps the UIDs
+/// the same as in calendars exported by the CLI. `url` is linked in the descriptions.
+#[wasm_bindgen(js_name = courseToIcs)]
+pub fn course_to_ics(
+    html: &str,
+    id: Option<String>,
+    url: Option<String>,
+) -> Result<String, JsValue> {
+    let mut course = Course::from_html(html).map_err(|err| JsValue::from_str(&err.to_string()))?;
+    course.id = id;
+    course.url = url;
+    let calendar = course
+        .to_ical(&CalendarOptions::default())
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+
+    Ok(sanitize::fold(&calendar.to_string()))
+}